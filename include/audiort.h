@@ -0,0 +1,105 @@
+/*
+ * C interface to the audiort capture engine. Build the library with
+ * `cargo build --release --no-default-features --features capi` and link
+ * against libaudiort (.so/.dylib/.dll or the static archive).
+ *
+ * Kept in sync with src/capi.rs by hand.
+ */
+
+#ifndef AUDIORT_H
+#define AUDIORT_H
+
+#include <stdint.h>
+
+#ifdef __cplusplus
+extern "C" {
+#endif
+
+#define AUDIORT_OK 0
+#define AUDIORT_INVALID_ARGUMENT -1
+/* The stream failed after starting, e.g. the device was unplugged */
+#define AUDIORT_STREAM_ERROR -2
+
+/* What audiort_recorder_state returns */
+#define AUDIORT_STATE_PLAYING 0
+#define AUDIORT_STATE_PAUSED 1
+#define AUDIORT_STATE_STOPPED 2
+#define AUDIORT_STATE_ERRORED 3
+
+/* Positive codes mirror the library's errors */
+#define AUDIORT_DEFAULT_INPUT_DEVICE_ERROR 1
+#define AUDIORT_DEFAULT_OUTPUT_DEVICE_ERROR 2
+#define AUDIORT_DEFAULT_CONFIG_ERROR 3
+#define AUDIORT_STREAM_CONFIG_FORMAT_ERROR 4
+#define AUDIORT_STREAM_CREATION_ERROR 5
+#define AUDIORT_OUTPUT_LOCK_ERROR 6
+#define AUDIORT_WRITE_ERROR 7
+#define AUDIORT_PLAY_ERROR 8
+#define AUDIORT_METADATA_ERROR 9
+#define AUDIORT_PAUSE_ERROR 10
+#define AUDIORT_DEVICE_NOT_FOUND_ERROR 11
+#define AUDIORT_READ_ERROR 12
+#define AUDIORT_AMBIGUOUS_DEVICE_ERROR 13
+#define AUDIORT_PLUGIN_NOT_FOUND_ERROR 14
+#define AUDIORT_PLUGIN_LOAD_ERROR 15
+#define AUDIORT_TRANSCRIBE_ERROR 16
+#define AUDIORT_CONFIG_ERROR 17
+#define AUDIORT_ENCODE_ERROR 18
+
+typedef struct AudiortRecorder AudiortRecorder;
+
+typedef struct AudiortLevels {
+    uint64_t frames;
+    float peak_dbfs;
+    float rms_dbfs;
+    /* Levels of the most recent buffer */
+    float current_peak_dbfs;
+    float current_rms_dbfs;
+    uint64_t clipped;
+    uint64_t dropouts;
+    /* Time spent in recent callbacks against the audio they carried */
+    float load;
+} AudiortLevels;
+
+/*
+ * Open `device` and create the WAV file `path`, ready to record once started.
+ * `device` is a device name or part of one, an index, "file:PATH" to record
+ * from a WAV file, or NULL for the default input. On success `*out` holds the new recorder.
+ */
+int32_t audiort_recorder_new(const char *device, const char *path, AudiortRecorder **out);
+
+/* Start or resume recording. */
+int32_t audiort_recorder_start(AudiortRecorder *recorder);
+
+/* Pause recording; audiort_recorder_start resumes into the same file. */
+int32_t audiort_recorder_stop(AudiortRecorder *recorder);
+
+/*
+ * Whether the recorder is playing, paused, stopped or has failed, as one of
+ * the AUDIORT_STATE_ values.
+ */
+int32_t audiort_recorder_state(const AudiortRecorder *recorder);
+
+/*
+ * Fill `levels` with the recording so far. Returns AUDIORT_STREAM_ERROR once
+ * the stream has failed.
+ */
+int32_t audiort_recorder_levels(const AudiortRecorder *recorder, AudiortLevels *levels);
+
+/*
+ * Stop recording and finalize the file, filling `levels` (which may be NULL)
+ * with its final stats. The recorder must still be freed.
+ */
+int32_t audiort_recorder_finish(AudiortRecorder *recorder, AudiortLevels *levels);
+
+/* Free a recorder, finalizing its file if it wasn't finished. NULL is ignored. */
+void audiort_recorder_free(AudiortRecorder *recorder);
+
+/* A static description of a return code. */
+const char *audiort_error_message(int32_t code);
+
+#ifdef __cplusplus
+}
+#endif
+
+#endif /* AUDIORT_H */
@@ -0,0 +1,37 @@
+export interface Levels {
+  frames: number
+  peakDbfs: number
+  rmsDbfs: number
+  clipped: number
+  dropouts: number
+  load: number
+}
+
+/**
+ * Records an input device to a WAV file, or only to chunks when no path is
+ * given. Iterating yields interleaved PCM chunks until `finish()`.
+ */
+export class Recorder implements AsyncIterable<Float32Array> {
+  /**
+   * `device` is a device name or part of one, an index or `file:PATH`; the
+   * default input otherwise.
+   */
+  constructor(path?: string | null, device?: string | null)
+  get sampleRate(): number
+  get channels(): number
+  /** Frames per buffer the device settled on, or `null` before it starts. */
+  get bufferFrames(): number | null
+  get state(): 'playing' | 'paused' | 'stopped' | 'errored'
+  start(): void
+  /** Pause recording; `start()` resumes into the same file. */
+  stop(): void
+  levels(): Levels
+  /** Stop recording, finalize the file and end the chunk iterator. */
+  finish(): Levels
+  /** The next chunk, or `null` once the recorder is finished. */
+  nextChunk(): Promise<Float32Array | null>
+  [Symbol.asyncIterator](): AsyncIterator<Float32Array>
+}
+
+export function inputDevices(): string[]
+export function outputDevices(): string[]
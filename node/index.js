@@ -0,0 +1,17 @@
+// Loads the native addon built with `cargo build --release --no-default-features
+// --features node` and copied here as `audiort.node`.
+const native = require('./audiort.node')
+
+native.Recorder.prototype[Symbol.asyncIterator] = async function* () {
+  for (;;) {
+    const chunk = await this.nextChunk()
+
+    if (chunk === null) {
+      return
+    }
+
+    yield chunk
+  }
+}
+
+module.exports = native
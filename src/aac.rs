@@ -0,0 +1,397 @@
+//! AAC-LC in an M4A file, encoded with the Fraunhofer FDK AAC library, for
+//! sharing and upload targets that prefer it to WAV.
+
+use crate::metadata::Tags;
+use crate::resample::Resampler;
+use crate::Error;
+use fdk_aac::enc::AudioObjectType;
+use fdk_aac::enc::BitRate;
+use fdk_aac::enc::ChannelMode;
+use fdk_aac::enc::Encoder;
+use fdk_aac::enc::EncoderParams;
+use fdk_aac::enc::Transport;
+use std::path::Path;
+
+/// Frames in each AAC-LC access unit.
+const FRAME_LEN: usize = 1024;
+
+/// Bitrate for each channel; plenty for speech and fine for music.
+const BITRATE_PER_CHANNEL: u32 = 64_000;
+
+/// Rates AAC has an index for. Others are resampled to 48 kHz.
+const SAMPLE_RATES: [u32; 12] = [
+    96000, 88200, 64000, 48000, 44100, 32000, 24000, 22050, 16000, 12000, 11025, 8000,
+];
+
+/// Convert the WAV file at `from` to an M4A file at `to`, with its tags as
+/// iTunes-style metadata. Mono and stereo only.
+pub fn encode_m4a<P, Q>(from: P, to: Q) -> Result<(), Error>
+where
+    P: AsRef<Path>,
+    Q: AsRef<Path>,
+{
+    let tags = Tags::read(&from)?;
+    let (spec, samples) = crate::read_samples_f64(from)?;
+
+    let channel_mode = match spec.channels {
+        1 => ChannelMode::Mono,
+        2 => ChannelMode::Stereo,
+        _ => return Err(Error::StreamConfigFormatError),
+    };
+    let channels = usize::from(spec.channels);
+
+    let sample_rate = match SAMPLE_RATES.contains(&spec.sample_rate) {
+        true => spec.sample_rate,
+        false => 48000,
+    };
+
+    let encoder = Encoder::new(EncoderParams {
+        bit_rate: BitRate::Cbr(BITRATE_PER_CHANNEL * u32::from(spec.channels)),
+        sample_rate,
+        transport: Transport::Raw,
+        channels: channel_mode,
+        audio_object_type: AudioObjectType::Mpeg4LowComplexity,
+    })
+    .or(Err(Error::EncodeError))?;
+
+    let info = encoder.info().or(Err(Error::EncodeError))?;
+    let config = info.confBuf[..info.confSize as usize].to_vec();
+
+    let mut resampler = Resampler::new(spec.sample_rate, sample_rate, channels);
+    let mut samples = samples.peekable();
+    let mut buffer = Vec::new();
+    let mut resampled = Vec::new();
+    let mut pending = Vec::new();
+    let mut units = Units::default();
+
+    while samples.peek().is_some() {
+        buffer.clear();
+
+        for sample in samples.by_ref().take(4096 * channels) {
+            buffer.push(sample? as f32);
+        }
+
+        resampled.clear();
+        resampler.process(&buffer, &mut resampled);
+        pending.extend(resampled.iter().map(|value| to_i16(*value)));
+
+        let whole = pending.len() - pending.len() % (FRAME_LEN * channels);
+        units.encode(&encoder, &pending[..whole])?;
+        pending.drain(..whole);
+    }
+
+    resampled.clear();
+    resampler.finish(&mut resampled);
+    pending.extend(resampled.iter().map(|value| to_i16(*value)));
+
+    let frames = units.consumed / channels as u64 + (pending.len() / channels) as u64;
+
+    // Silence after the end pushes out what the encoder is holding back
+    let delay = info.nDelay as u64;
+    let flush = (delay as usize / FRAME_LEN + 2) * FRAME_LEN * channels;
+    pending.resize(pending.len() + flush, 0);
+
+    let whole = pending.len() - pending.len() % (FRAME_LEN * channels);
+    units.encode(&encoder, &pending[..whole])?;
+
+    let m4a = Mp4 {
+        sample_rate,
+        channels: spec.channels,
+        bitrate: BITRATE_PER_CHANNEL * u32::from(spec.channels),
+        config,
+        delay,
+        frames,
+        tags,
+    };
+
+    std::fs::write(to, m4a.file(&units)?).or(Err(Error::WriteError))
+}
+
+fn to_i16(value: f32) -> i16 {
+    (value.clamp(-1.0, 1.0) * f32::from(i16::MAX)) as i16
+}
+
+/// The encoded access units, back to back.
+#[derive(Default)]
+struct Units {
+    data: Vec<u8>,
+    sizes: Vec<u32>,
+    /// Samples the encoder has taken, across channels
+    consumed: u64,
+}
+
+impl Units {
+    fn encode(&mut self, encoder: &Encoder, mut input: &[i16]) -> Result<(), Error> {
+        let mut output = [0u8; 8192];
+
+        while !input.is_empty() {
+            let info = encoder
+                .encode(input, &mut output)
+                .or(Err(Error::EncodeError))?;
+
+            if info.output_size > 0 {
+                self.data.extend_from_slice(&output[..info.output_size]);
+                self.sizes.push(info.output_size as u32);
+            }
+
+            if info.input_consumed == 0 && info.output_size == 0 {
+                return Err(Error::EncodeError);
+            }
+
+            self.consumed += info.input_consumed as u64;
+            input = &input[info.input_consumed..];
+        }
+
+        Ok(())
+    }
+}
+
+/// What the M4A boxes describe.
+struct Mp4 {
+    sample_rate: u32,
+    channels: u16,
+    bitrate: u32,
+    /// The AudioSpecificConfig from the encoder
+    config: Vec<u8>,
+    /// Frames of priming at the start, which the edit list skips
+    delay: u64,
+    /// Frames of the original audio
+    frames: u64,
+    tags: Tags,
+}
+
+impl Mp4 {
+    /// `ftyp`, then the audio in `mdat`, then `moov` describing it.
+    fn file(&self, units: &Units) -> Result<Vec<u8>, Error> {
+        let mut ftyp = b"M4A ".to_vec();
+        ftyp.extend_from_slice(&0u32.to_be_bytes());
+        ftyp.extend_from_slice(b"M4A isommp42");
+        let ftyp = boxed(b"ftyp", &ftyp);
+
+        let mdat_len = u32::try_from(8 + units.data.len()).or(Err(Error::WriteError))?;
+        let offset = ftyp.len() as u32 + 8;
+
+        let mut file = ftyp;
+        file.extend_from_slice(&mdat_len.to_be_bytes());
+        file.extend_from_slice(b"mdat");
+        file.extend_from_slice(&units.data);
+        file.extend_from_slice(&self.moov(&units.sizes, offset)?);
+
+        Ok(file)
+    }
+
+    fn moov(&self, sizes: &[u32], offset: u32) -> Result<Vec<u8>, Error> {
+        let duration = u32::try_from(self.frames).or(Err(Error::WriteError))?;
+        let media_duration = u32::try_from(sizes.len() * FRAME_LEN).or(Err(Error::WriteError))?;
+
+        let mut mvhd = Vec::new();
+        mvhd.extend_from_slice(&[0; 8]);
+        mvhd.extend_from_slice(&self.sample_rate.to_be_bytes());
+        mvhd.extend_from_slice(&duration.to_be_bytes());
+        mvhd.extend_from_slice(&0x0001_0000u32.to_be_bytes());
+        mvhd.extend_from_slice(&0x0100u16.to_be_bytes());
+        mvhd.extend_from_slice(&[0; 10]);
+        mvhd.extend_from_slice(&MATRIX);
+        mvhd.extend_from_slice(&[0; 24]);
+        mvhd.extend_from_slice(&2u32.to_be_bytes());
+
+        let mut tkhd = Vec::new();
+        tkhd.extend_from_slice(&[0; 8]);
+        tkhd.extend_from_slice(&1u32.to_be_bytes());
+        tkhd.extend_from_slice(&[0; 4]);
+        tkhd.extend_from_slice(&duration.to_be_bytes());
+        tkhd.extend_from_slice(&[0; 12]);
+        tkhd.extend_from_slice(&0x0100u16.to_be_bytes());
+        tkhd.extend_from_slice(&[0; 2]);
+        tkhd.extend_from_slice(&MATRIX);
+        tkhd.extend_from_slice(&[0; 8]);
+
+        let mut elst = 1u32.to_be_bytes().to_vec();
+        elst.extend_from_slice(&duration.to_be_bytes());
+        elst.extend_from_slice(&(self.delay as u32).to_be_bytes());
+        elst.extend_from_slice(&0x0001_0000u32.to_be_bytes());
+
+        let mut mdhd = Vec::new();
+        mdhd.extend_from_slice(&[0; 8]);
+        mdhd.extend_from_slice(&self.sample_rate.to_be_bytes());
+        mdhd.extend_from_slice(&media_duration.to_be_bytes());
+        // `und`, packed into 5 bits a letter
+        mdhd.extend_from_slice(&0x55c4u16.to_be_bytes());
+        mdhd.extend_from_slice(&[0; 2]);
+
+        let mut hdlr = vec![0; 4];
+        hdlr.extend_from_slice(b"soun");
+        hdlr.extend_from_slice(&[0; 12]);
+        hdlr.extend_from_slice(b"SoundHandler\0");
+
+        let mut dref = 1u32.to_be_bytes().to_vec();
+        dref.extend_from_slice(&full(b"url ", 1, &[]));
+
+        let mut stts = 1u32.to_be_bytes().to_vec();
+        stts.extend_from_slice(&(sizes.len() as u32).to_be_bytes());
+        stts.extend_from_slice(&(FRAME_LEN as u32).to_be_bytes());
+
+        let mut stsz = 0u32.to_be_bytes().to_vec();
+        stsz.extend_from_slice(&(sizes.len() as u32).to_be_bytes());
+
+        for size in sizes {
+            stsz.extend_from_slice(&size.to_be_bytes());
+        }
+
+        // Every unit in one chunk
+        let mut stsc = 1u32.to_be_bytes().to_vec();
+        stsc.extend_from_slice(&1u32.to_be_bytes());
+        stsc.extend_from_slice(&(sizes.len() as u32).to_be_bytes());
+        stsc.extend_from_slice(&1u32.to_be_bytes());
+
+        let mut stco = 1u32.to_be_bytes().to_vec();
+        stco.extend_from_slice(&offset.to_be_bytes());
+
+        let stbl = [
+            full(
+                b"stsd",
+                0,
+                &[&1u32.to_be_bytes()[..], &self.mp4a()].concat(),
+            ),
+            full(b"stts", 0, &stts),
+            full(b"stsc", 0, &stsc),
+            full(b"stsz", 0, &stsz),
+            full(b"stco", 0, &stco),
+        ]
+        .concat();
+
+        let minf = [
+            full(b"smhd", 0, &[0; 4]),
+            boxed(b"dinf", &full(b"dref", 0, &dref)),
+            boxed(b"stbl", &stbl),
+        ]
+        .concat();
+
+        let mdia = [
+            full(b"mdhd", 0, &mdhd),
+            full(b"hdlr", 0, &hdlr),
+            boxed(b"minf", &minf),
+        ]
+        .concat();
+
+        let trak = [
+            full(b"tkhd", 7, &tkhd),
+            boxed(b"edts", &full(b"elst", 0, &elst)),
+            boxed(b"mdia", &mdia),
+        ]
+        .concat();
+
+        let mut moov = [full(b"mvhd", 0, &mvhd), boxed(b"trak", &trak)].concat();
+
+        if !self.tags.is_empty() {
+            moov.extend_from_slice(&self.udta());
+        }
+
+        Ok(boxed(b"moov", &moov))
+    }
+
+    /// The tags, in the `ilst` list players read.
+    fn udta(&self) -> Vec<u8> {
+        let mut hdlr = vec![0; 4];
+        hdlr.extend_from_slice(b"mdirappl");
+        hdlr.extend_from_slice(&[0; 9]);
+
+        let mut ilst = Vec::new();
+
+        for (key, value) in self.tags.iter() {
+            let item = match key {
+                "title" => Some(b"\xa9nam"),
+                "artist" => Some(b"\xa9ART"),
+                "album" => Some(b"\xa9alb"),
+                "comment" => Some(b"\xa9cmt"),
+                "genre" => Some(b"\xa9gen"),
+                "date" => Some(b"\xa9day"),
+                "copyright" => Some(b"cprt"),
+                "software" => Some(b"\xa9too"),
+                _ => None,
+            };
+
+            // Text, with a locale of 0
+            let data = full(b"data", 1, &[&[0; 4], value.as_bytes()].concat());
+
+            let entry = match (item, value.parse::<u16>()) {
+                (Some(item), _) => boxed(item, &data),
+                // Track numbers are binary: padding, number, total, padding
+                (None, Ok(track)) if key == "track" => {
+                    let number = [&[0; 6], &track.to_be_bytes()[..], &[0; 4]].concat();
+                    boxed(b"trkn", &full(b"data", 0, &number))
+                }
+                // Others by name, as iTunes keeps its own extras
+                _ => boxed(
+                    b"----",
+                    &[
+                        full(b"mean", 0, b"com.apple.iTunes"),
+                        full(b"name", 0, key.as_bytes()),
+                        data,
+                    ]
+                    .concat(),
+                ),
+            };
+
+            ilst.extend_from_slice(&entry);
+        }
+
+        let meta = full(
+            b"meta",
+            0,
+            &[full(b"hdlr", 0, &hdlr), boxed(b"ilst", &ilst)].concat(),
+        );
+
+        boxed(b"udta", &meta)
+    }
+
+    /// The sample entry, with the decoder's config in an `esds`.
+    fn mp4a(&self) -> Vec<u8> {
+        let mut decoder = vec![0x40, 0x15, 0, 0x18, 0];
+        decoder.extend_from_slice(&self.bitrate.to_be_bytes());
+        decoder.extend_from_slice(&self.bitrate.to_be_bytes());
+        decoder.extend_from_slice(&descriptor(5, &self.config));
+
+        let mut es = vec![0, 0, 0];
+        es.extend_from_slice(&descriptor(4, &decoder));
+        es.extend_from_slice(&descriptor(6, &[2]));
+
+        let mut mp4a = vec![0; 6];
+        mp4a.extend_from_slice(&1u16.to_be_bytes());
+        mp4a.extend_from_slice(&[0; 8]);
+        mp4a.extend_from_slice(&self.channels.to_be_bytes());
+        mp4a.extend_from_slice(&16u16.to_be_bytes());
+        mp4a.extend_from_slice(&[0; 4]);
+        mp4a.extend_from_slice(&(self.sample_rate.min(0xffff) << 16).to_be_bytes());
+        mp4a.extend_from_slice(&full(b"esds", 0, &descriptor(3, &es)));
+
+        boxed(b"mp4a", &mp4a)
+    }
+}
+
+/// The identity transform, in 16.16 and 2.30 fixed point.
+const MATRIX: [u8; 36] = [
+    0, 1, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, //
+    0, 0, 0, 0, 0, 1, 0, 0, 0, 0, 0, 0, //
+    0, 0, 0, 0, 0, 0, 0, 0, 0x40, 0, 0, 0,
+];
+
+fn boxed(kind: &[u8; 4], body: &[u8]) -> Vec<u8> {
+    let mut out = ((8 + body.len()) as u32).to_be_bytes().to_vec();
+    out.extend_from_slice(kind);
+    out.extend_from_slice(body);
+    out
+}
+
+/// A box with a version of 0 and `flags`.
+fn full(kind: &[u8; 4], flags: u32, body: &[u8]) -> Vec<u8> {
+    boxed(kind, &[&flags.to_be_bytes()[..], body].concat())
+}
+
+/// An MPEG-4 descriptor; everything here is short enough for a one-byte
+/// length.
+fn descriptor(tag: u8, body: &[u8]) -> Vec<u8> {
+    let mut out = vec![tag, body.len() as u8];
+    out.extend_from_slice(body);
+    out
+}
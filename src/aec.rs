@@ -0,0 +1,148 @@
+//! Acoustic echo cancellation: removes what was played on the speakers (the
+//! far end) from what the microphone picks up.
+//!
+//! `EchoCanceller` runs on the capture stream and its `reference()` wherever
+//! the far-end signal passes, e.g. `player.effect(canceller.reference())`
+//! before `stream.effect(canceller)`. Both sides must run at the same sample
+//! rate.
+
+use crate::effects::Effect;
+use std::collections::VecDeque;
+use std::sync::Arc;
+use std::sync::Mutex;
+use std::time::Duration;
+
+/// How fast the filter adapts; higher converges faster but is noisier.
+const STEP: f32 = 0.3;
+
+/// Near-end louder than this share of the recent far-end peak counts as
+/// double talk, when adapting would make the filter diverge.
+const DOUBLE_TALK: f32 = 0.5;
+
+/// Keeps silence from dividing by zero.
+const REGULARIZATION: f32 = 1e-3;
+
+type Queue = Arc<Mutex<VecDeque<f32>>>;
+
+/// Feeds the far-end signal, mixed to mono, to an `EchoCanceller`.
+#[derive(Clone)]
+pub struct Reference {
+    queue: Queue,
+    max_queued: usize,
+}
+
+impl Effect for Reference {
+    fn process(&mut self, frames: &mut [f32], channels: usize) {
+        let channels = channels.max(1);
+
+        if let Ok(mut queue) = self.queue.lock() {
+            queue.extend(
+                frames
+                    .chunks(channels)
+                    .map(|frame| frame.iter().sum::<f32>() / frame.len() as f32),
+            );
+
+            // The capture side stopped; don't grow forever
+            let excess = queue.len().saturating_sub(self.max_queued);
+            queue.drain(..excess);
+        }
+    }
+}
+
+/// Normalized LMS echo canceller. `tail` is the longest echo it removes,
+/// including the device latency between playing and capturing.
+pub struct EchoCanceller {
+    queue: Queue,
+    sample_rate: u32,
+    /// Far-end history, written twice so the last `taps` samples are always
+    /// contiguous: `history[position + 1..][..taps]`, oldest first
+    history: Vec<f32>,
+    position: usize,
+    energy: f32,
+    taps: usize,
+    /// Filter weights per capture channel
+    weights: Vec<Vec<f32>>,
+}
+
+impl EchoCanceller {
+    pub fn new(sample_rate: u32, tail: Duration) -> EchoCanceller {
+        let taps = ((tail.as_secs_f64() * f64::from(sample_rate)) as usize).max(1);
+
+        EchoCanceller {
+            queue: Queue::default(),
+            sample_rate,
+            history: vec![0.0; taps * 2],
+            position: 0,
+            energy: 0.0,
+            taps,
+            weights: Vec::new(),
+        }
+    }
+
+    /// The effect to run on the far-end signal.
+    pub fn reference(&self) -> Reference {
+        Reference {
+            queue: Arc::clone(&self.queue),
+            max_queued: self.sample_rate as usize,
+        }
+    }
+}
+
+impl Effect for EchoCanceller {
+    fn process(&mut self, frames: &mut [f32], channels: usize) {
+        let channels = channels.max(1);
+        self.weights.resize_with(channels, || vec![0.0; self.taps]);
+
+        let mut queue = self.queue.lock().ok();
+
+        // A far end running ahead, e.g. from starting first, would put its
+        // echo beyond the tail
+        if let Some(queue) = queue.as_mut() {
+            let excess = queue
+                .len()
+                .saturating_sub(frames.len() / channels + self.taps);
+            queue.drain(..excess);
+        }
+
+        for frame in frames.chunks_mut(channels) {
+            // Nothing playing reads as silence
+            let far = queue
+                .as_mut()
+                .and_then(|queue| queue.pop_front())
+                .unwrap_or(0.0);
+
+            self.position = (self.position + 1) % self.taps;
+
+            let oldest = self.history[self.position];
+            self.energy = (self.energy - oldest * oldest + far * far).max(0.0);
+
+            self.history[self.position] = far;
+            self.history[self.position + self.taps] = far;
+
+            let history = &self.history[self.position + 1..][..self.taps];
+            let peak = history
+                .iter()
+                .fold(0f32, |peak, value| peak.max(value.abs()));
+
+            for (value, weights) in frame.iter_mut().zip(self.weights.iter_mut()) {
+                let estimate: f32 = weights
+                    .iter()
+                    .zip(history.iter())
+                    .map(|(weight, far)| weight * far)
+                    .sum();
+
+                let error = *value - estimate;
+
+                if value.abs() < DOUBLE_TALK * peak {
+                    let step = STEP * error / (self.energy + REGULARIZATION);
+
+                    for (weight, far) in weights.iter_mut().zip(history.iter()) {
+                        *weight += step * far;
+                    }
+                }
+
+                *value = error;
+            }
+        }
+    }
+}
@@ -0,0 +1,201 @@
+//! A single application's audio rather than the whole output mix, e.g. a
+//! browser or a call. On Linux it is taken from PipeWire with `pw-record`,
+//! following the application's playback stream; other systems don't have
+//! it yet.
+
+use crate::merge::DriftReader;
+#[cfg(target_os = "linux")]
+use crate::merge::Queue;
+use crate::DeviceBuilder;
+use crate::Error;
+#[cfg(target_os = "linux")]
+use std::io::Read;
+#[cfg(target_os = "linux")]
+use std::process::Child;
+#[cfg(target_os = "linux")]
+use std::process::Command;
+#[cfg(target_os = "linux")]
+use std::process::Stdio;
+
+/// Captures an application's audio until dropped; record from its `device`.
+pub struct AppCapture {
+    readers: Vec<DriftReader>,
+    name: String,
+    sample_rate: u32,
+    #[cfg(target_os = "linux")]
+    recorder: Child,
+}
+
+impl AppCapture {
+    /// The playback of the application whose name or program contains `app`,
+    /// ignoring case, at `sample_rate` with `channels`.
+    #[cfg(target_os = "linux")]
+    pub fn new(app: &str, sample_rate: u32, channels: u16) -> Result<AppCapture, Error> {
+        let (target, name) = find_stream(app)?;
+        let channels = usize::from(channels.max(1));
+
+        let mut recorder = Command::new("pw-record")
+            .arg("--target")
+            .arg(&target)
+            .args(["--rate", &sample_rate.to_string()])
+            .args(["--channels", &channels.to_string()])
+            .args(["--format", "f32", "--raw", "-"])
+            .stdin(Stdio::null())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::null())
+            .spawn()
+            .or(Err(Error::StreamCreationError))?;
+
+        let mut stdout = recorder.stdout.take().ok_or(Error::StreamCreationError)?;
+
+        let target_len =
+            (crate::merge::TARGET_LATENCY.as_secs_f64() * f64::from(sample_rate)) as usize;
+        let queues: Vec<Queue> = (0..channels).map(|_| Queue::default()).collect();
+        let readers = queues
+            .iter()
+            .map(|queue| DriftReader::new(queue.clone(), sample_rate, sample_rate, target_len))
+            .collect();
+
+        // Ends with `pw-record`, after which the device is silent
+        std::thread::spawn(move || {
+            let mut buffer = vec![0u8; 4096 * 4 * channels];
+            let mut pending = Vec::new();
+            let max_queued = target_len * 8;
+
+            while let Ok(read) = stdout.read(&mut buffer) {
+                if read == 0 {
+                    break;
+                }
+
+                pending.extend_from_slice(&buffer[..read]);
+
+                let whole = pending.len() / (4 * channels) * (4 * channels);
+                let samples = pending[..whole]
+                    .chunks_exact(4)
+                    .map(|bytes| f32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]));
+
+                for (index, sample) in samples.enumerate() {
+                    if let Ok(mut queue) = queues[index % channels].lock() {
+                        queue.push_back(sample);
+                    }
+                }
+
+                pending.drain(..whole);
+
+                for queue in &queues {
+                    if let Ok(mut queue) = queue.lock() {
+                        let excess = queue.len().saturating_sub(max_queued);
+                        queue.drain(..excess);
+                    }
+                }
+            }
+        });
+
+        Ok(AppCapture {
+            readers,
+            name,
+            sample_rate,
+            recorder,
+        })
+    }
+
+    #[cfg(not(target_os = "linux"))]
+    pub fn new(_app: &str, _sample_rate: u32, _channels: u16) -> Result<AppCapture, Error> {
+        Err(Error::DeviceNotFoundError)
+    }
+
+    /// The application's audio as a device, to record from while this is
+    /// kept.
+    pub fn device(&self) -> DeviceBuilder {
+        crate::mock::merged_device(self.readers.clone(), &self.name, self.sample_rate)
+    }
+}
+
+#[cfg(target_os = "linux")]
+impl Drop for AppCapture {
+    fn drop(&mut self) {
+        let _ = self.recorder.kill();
+        let _ = self.recorder.wait();
+    }
+}
+
+/// Names of the applications playing whose name or program contains `app`,
+/// ignoring case, as `AppCapture::new` would choose from.
+#[cfg(target_os = "linux")]
+pub fn matching(app: &str) -> Result<Vec<String>, Error> {
+    let mut names: Vec<String> = streams(app)?.into_iter().map(|(_, name)| name).collect();
+    names.sort();
+    names.dedup();
+
+    Ok(names)
+}
+
+#[cfg(not(target_os = "linux"))]
+pub fn matching(_app: &str) -> Result<Vec<String>, Error> {
+    Err(Error::DeviceNotFoundError)
+}
+
+/// The PipeWire node to record for `app`, and the application's name.
+#[cfg(target_os = "linux")]
+fn find_stream(app: &str) -> Result<(String, String), Error> {
+    let streams = streams(app)?;
+
+    let mut names: Vec<&str> = streams.iter().map(|(_, name)| name.as_str()).collect();
+    names.sort();
+    names.dedup();
+
+    // An application may play several streams, e.g. a browser's tabs; the
+    // first is taken
+    match names.len() {
+        0 => Err(Error::DeviceNotFoundError),
+        1 => Ok(streams.into_iter().next().expect("a stream matched")),
+        _ => Err(Error::AmbiguousDeviceError),
+    }
+}
+
+/// The PipeWire nodes of playback streams matching `app`, and the names of
+/// their applications.
+#[cfg(target_os = "linux")]
+fn streams(app: &str) -> Result<Vec<(String, String)>, Error> {
+    let output = Command::new("pw-dump")
+        .stderr(Stdio::null())
+        .output()
+        .or(Err(Error::DeviceNotFoundError))?;
+    let objects: serde_json::Value =
+        serde_json::from_slice(&output.stdout).or(Err(Error::DeviceNotFoundError))?;
+
+    let query = app.to_lowercase();
+
+    let streams = objects
+        .as_array()
+        .into_iter()
+        .flatten()
+        .filter(|object| object["type"] == "PipeWire:Interface:Node")
+        .filter_map(|object| {
+            let props = &object["info"]["props"];
+
+            if props["media.class"] != "Stream/Output/Audio" {
+                return None;
+            }
+
+            let name = props["application.name"]
+                .as_str()
+                .or(props["node.name"].as_str())?;
+            let binary = props["application.process.binary"].as_str().unwrap_or("");
+
+            if !name.to_lowercase().contains(&query) && !binary.to_lowercase().contains(&query) {
+                return None;
+            }
+
+            // Newer PipeWire targets by serial, older by id
+            let target = props["object.serial"]
+                .as_u64()
+                .or(object["id"].as_u64())?
+                .to_string();
+
+            Some((target, name.to_owned()))
+        })
+        .collect();
+
+    Ok(streams)
+}
@@ -0,0 +1,308 @@
+//! A C ABI over the capture engine, declared in `include/audiort.h`.
+//!
+//! Every function returns `AUDIORT_OK` (0) or an error code, which
+//! `audiort_error_message` describes. Recorders are only safe to use from one
+//! thread at a time.
+
+use crate::Device;
+use crate::DeviceBuilder;
+use crate::Error;
+use crate::Stats;
+use crate::StreamBuilder;
+use crate::StreamState;
+use std::ffi::c_char;
+use std::ffi::CStr;
+use std::sync::Arc;
+use std::sync::Mutex;
+
+pub const AUDIORT_OK: i32 = 0;
+pub const AUDIORT_INVALID_ARGUMENT: i32 = -1;
+/// The stream failed after starting, e.g. the device was unplugged
+pub const AUDIORT_STREAM_ERROR: i32 = -2;
+
+/// What `audiort_recorder_state` returns
+pub const AUDIORT_STATE_PLAYING: i32 = 0;
+pub const AUDIORT_STATE_PAUSED: i32 = 1;
+pub const AUDIORT_STATE_STOPPED: i32 = 2;
+pub const AUDIORT_STATE_ERRORED: i32 = 3;
+
+pub struct AudiortRecorder {
+    stream: StreamBuilder,
+    failed: Arc<Mutex<bool>>,
+    finished: bool,
+}
+
+#[repr(C)]
+#[derive(Debug, Clone, Copy, Default)]
+pub struct AudiortLevels {
+    pub frames: u64,
+    pub peak_dbfs: f32,
+    pub rms_dbfs: f32,
+    /// Levels of the most recent buffer
+    pub current_peak_dbfs: f32,
+    pub current_rms_dbfs: f32,
+    pub clipped: u64,
+    pub dropouts: u64,
+    /// Time spent in recent callbacks against the audio they carried
+    pub load: f32,
+}
+
+impl Error {
+    /// Codes are part of the C ABI, so variants keep their number.
+    fn code(self) -> i32 {
+        match self {
+            Error::DefaultInputDeviceError => 1,
+            Error::DefaultOutputDeviceError => 2,
+            Error::DefaultConfigError => 3,
+            Error::StreamConfigFormatError => 4,
+            Error::StreamCreationError => 5,
+            Error::OutputLockError => 6,
+            Error::WriteError => 7,
+            Error::PlayError => 8,
+            Error::MetadataError => 9,
+            Error::PauseError => 10,
+            Error::DeviceNotFoundError => 11,
+            Error::ReadError => 12,
+            Error::AmbiguousDeviceError => 13,
+            Error::PluginNotFoundError => 14,
+            Error::PluginLoadError => 15,
+            Error::TranscribeError => 16,
+            Error::ConfigError => 17,
+            Error::EncodeError => 18,
+        }
+    }
+}
+
+/// Open `device` and create `path`, ready to record once started.
+///
+/// `device` is a device name or part of one, an index, `file:PATH` to record
+/// from a WAV file, or `NULL` for the default input.
+///
+/// # Safety
+///
+/// `device` must be `NULL` or a valid C string, `path` a valid C string and
+/// `out` a valid pointer.
+#[no_mangle]
+pub unsafe extern "C" fn audiort_recorder_new(
+    device: *const c_char,
+    path: *const c_char,
+    out: *mut *mut AudiortRecorder,
+) -> i32 {
+    if path.is_null() || out.is_null() {
+        return AUDIORT_INVALID_ARGUMENT;
+    }
+
+    let Ok(path) = CStr::from_ptr(path).to_str() else {
+        return AUDIORT_INVALID_ARGUMENT;
+    };
+
+    let device = match device.is_null() {
+        true => None,
+        false => match CStr::from_ptr(device).to_str() {
+            Ok(device) => Some(device),
+            Err(_) => return AUDIORT_INVALID_ARGUMENT,
+        },
+    };
+
+    match recorder(device, path) {
+        Ok(recorder) => {
+            *out = Box::into_raw(Box::new(recorder));
+            AUDIORT_OK
+        }
+        Err(err) => err.code(),
+    }
+}
+
+fn recorder(device: Option<&str>, path: &str) -> Result<AudiortRecorder, Error> {
+    let device = match device {
+        Some(spec) => DeviceBuilder::open(Device::Input, spec)?,
+        None => DeviceBuilder::new_default_input()?,
+    };
+
+    let mut stream = StreamBuilder::new(device)?;
+    let failed = Arc::new(Mutex::new(false));
+    let on_error = Arc::clone(&failed);
+
+    // Report errors through the API instead of exiting the host process
+    stream.on_error(move |_| {
+        if let Ok(mut failed) = on_error.lock() {
+            *failed = true;
+        }
+    });
+
+    stream.write_wav(path)?;
+
+    Ok(AudiortRecorder {
+        stream,
+        failed,
+        finished: false,
+    })
+}
+
+/// Start or resume recording.
+///
+/// # Safety
+///
+/// `recorder` must come from `audiort_recorder_new` and not yet be freed.
+#[no_mangle]
+pub unsafe extern "C" fn audiort_recorder_start(recorder: *mut AudiortRecorder) -> i32 {
+    match recorder.as_mut() {
+        Some(recorder) if !recorder.finished => result(recorder.stream.play()),
+        _ => AUDIORT_INVALID_ARGUMENT,
+    }
+}
+
+/// Pause recording; `audiort_recorder_start` resumes into the same file.
+///
+/// # Safety
+///
+/// `recorder` must come from `audiort_recorder_new` and not yet be freed.
+#[no_mangle]
+pub unsafe extern "C" fn audiort_recorder_stop(recorder: *mut AudiortRecorder) -> i32 {
+    match recorder.as_mut() {
+        Some(recorder) if !recorder.finished => result(recorder.stream.pause()),
+        _ => AUDIORT_INVALID_ARGUMENT,
+    }
+}
+
+/// Whether the recorder is playing, paused, stopped or has failed, as one of
+/// the `AUDIORT_STATE_` values.
+///
+/// # Safety
+///
+/// `recorder` must come from `audiort_recorder_new` and not yet be freed.
+#[no_mangle]
+pub unsafe extern "C" fn audiort_recorder_state(recorder: *const AudiortRecorder) -> i32 {
+    let Some(recorder) = recorder.as_ref() else {
+        return AUDIORT_INVALID_ARGUMENT;
+    };
+
+    match recorder.stream.state() {
+        StreamState::Playing => AUDIORT_STATE_PLAYING,
+        StreamState::Paused => AUDIORT_STATE_PAUSED,
+        StreamState::Stopped => AUDIORT_STATE_STOPPED,
+        StreamState::Errored => AUDIORT_STATE_ERRORED,
+    }
+}
+
+/// Fill `levels` with the recording so far. Returns `AUDIORT_STREAM_ERROR`
+/// once the stream has failed.
+///
+/// # Safety
+///
+/// `recorder` must come from `audiort_recorder_new` and not yet be freed, and
+/// `levels` must be a valid pointer.
+#[no_mangle]
+pub unsafe extern "C" fn audiort_recorder_levels(
+    recorder: *const AudiortRecorder,
+    levels: *mut AudiortLevels,
+) -> i32 {
+    let (Some(recorder), false) = (recorder.as_ref(), levels.is_null()) else {
+        return AUDIORT_INVALID_ARGUMENT;
+    };
+
+    *levels = recorder.levels(recorder.stream.stats());
+
+    match recorder.failed.lock().map(|failed| *failed) {
+        Ok(false) => AUDIORT_OK,
+        _ => AUDIORT_STREAM_ERROR,
+    }
+}
+
+/// Stop recording and finalize the file, filling `levels` (which may be
+/// `NULL`) with its final stats. The recorder must still be freed.
+///
+/// # Safety
+///
+/// `recorder` must come from `audiort_recorder_new` and not yet be freed, and
+/// `levels` must be `NULL` or a valid pointer.
+#[no_mangle]
+pub unsafe extern "C" fn audiort_recorder_finish(
+    recorder: *mut AudiortRecorder,
+    levels: *mut AudiortLevels,
+) -> i32 {
+    let Some(recorder) = recorder.as_mut().filter(|recorder| !recorder.finished) else {
+        return AUDIORT_INVALID_ARGUMENT;
+    };
+
+    recorder.finished = true;
+
+    match recorder.stream.finish() {
+        Ok(stats) => {
+            if let Some(levels) = levels.as_mut() {
+                *levels = recorder.levels(stats);
+            }
+
+            AUDIORT_OK
+        }
+        Err(err) => err.code(),
+    }
+}
+
+/// Free a recorder, finalizing its file if `audiort_recorder_finish` wasn't
+/// called. `NULL` is ignored.
+///
+/// # Safety
+///
+/// `recorder` must be `NULL` or come from `audiort_recorder_new`, and not be
+/// used again.
+#[no_mangle]
+pub unsafe extern "C" fn audiort_recorder_free(recorder: *mut AudiortRecorder) {
+    if recorder.is_null() {
+        return;
+    }
+
+    let mut recorder = Box::from_raw(recorder);
+
+    if !recorder.finished {
+        let _ = recorder.stream.finish();
+    }
+}
+
+/// A static, NUL-terminated description of a return code.
+#[no_mangle]
+pub extern "C" fn audiort_error_message(code: i32) -> *const c_char {
+    let message: &'static CStr = match code {
+        AUDIORT_OK => c"Success",
+        AUDIORT_INVALID_ARGUMENT => c"Invalid argument",
+        AUDIORT_STREAM_ERROR => c"The stream failed",
+        1 => c"Error getting default input device",
+        2 => c"Error getting default output device",
+        3 => c"Error getting default device config",
+        4 => c"Bad stream config format",
+        5 => c"Error creating stream",
+        6 => c"Error getting output lock",
+        7 => c"Error writing data",
+        8 => c"Error recording data",
+        9 => c"Error writing metadata",
+        10 => c"Error pausing stream",
+        11 => c"No device with that name",
+        12 => c"Error reading audio file",
+        13 => c"More than one device matches",
+        _ => c"Unknown error",
+    };
+
+    message.as_ptr()
+}
+
+impl AudiortRecorder {
+    fn levels(&self, stats: Stats) -> AudiortLevels {
+        AudiortLevels {
+            frames: stats.frames(self.stream.config().channels()),
+            peak_dbfs: crate::to_dbfs(stats.peak),
+            rms_dbfs: crate::to_dbfs(stats.rms()),
+            current_peak_dbfs: crate::to_dbfs(stats.current_peak),
+            current_rms_dbfs: crate::to_dbfs(stats.current_rms),
+            clipped: stats.clipped,
+            dropouts: stats.dropouts,
+            load: stats.load,
+        }
+    }
+}
+
+fn result(result: Result<(), Error>) -> i32 {
+    match result {
+        Ok(()) => AUDIORT_OK,
+        Err(err) => err.code(),
+    }
+}
@@ -0,0 +1,139 @@
+//! Checksums of a recording's audio data, hashed as it is written and kept
+//! in its sidecar so the file can be checked later.
+
+// Files are only written outside the browser
+#![cfg_attr(target_arch = "wasm32", allow(dead_code, unused_imports))]
+
+use crate::Error;
+use sha2::Digest;
+use std::fs::File;
+use std::io::BufReader;
+use std::io::Read;
+use std::io::Seek;
+use std::io::SeekFrom;
+use std::path::Path;
+use std::str::FromStr;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Algorithm {
+    Sha256,
+    Blake3,
+}
+
+impl Algorithm {
+    pub fn name(&self) -> &'static str {
+        match self {
+            Algorithm::Sha256 => "sha256",
+            Algorithm::Blake3 => "blake3",
+        }
+    }
+}
+
+impl FromStr for Algorithm {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().replace('-', "").as_str() {
+            "sha256" => Ok(Algorithm::Sha256),
+            "blake3" => Ok(Algorithm::Blake3),
+            _ => Err(format!("unknown checksum `{s}`, expected sha256 or blake3")),
+        }
+    }
+}
+
+/// The hash of a file's audio data: the bytes of its `data` chunk.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Checksum {
+    pub algorithm: Algorithm,
+    pub bytes: [u8; 32],
+}
+
+impl Checksum {
+    pub fn hex(&self) -> String {
+        self.bytes
+            .iter()
+            .map(|byte| format!("{byte:02x}"))
+            .collect()
+    }
+
+    /// Hash the audio data of the WAV file at `path`.
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fn of_file<P>(path: P, algorithm: Algorithm) -> Result<Checksum, Error>
+    where
+        P: AsRef<Path>,
+    {
+        let mut hasher = Hasher::new(algorithm);
+        hash_file(path, &mut hasher)?;
+
+        Ok(hasher.checksum())
+    }
+}
+
+/// A checksum being computed.
+#[derive(Clone)]
+pub(crate) enum Hasher {
+    Sha256(sha2::Sha256),
+    Blake3(Box<blake3::Hasher>),
+}
+
+impl Hasher {
+    pub(crate) fn new(algorithm: Algorithm) -> Hasher {
+        match algorithm {
+            Algorithm::Sha256 => Hasher::Sha256(sha2::Sha256::new()),
+            Algorithm::Blake3 => Hasher::Blake3(Box::default()),
+        }
+    }
+
+    pub(crate) fn update(&mut self, data: &[u8]) {
+        match self {
+            Hasher::Sha256(hasher) => hasher.update(data),
+            Hasher::Blake3(hasher) => {
+                hasher.update(data);
+            }
+        }
+    }
+
+    /// The checksum so far; hashing can carry on after.
+    pub(crate) fn checksum(&self) -> Checksum {
+        match self {
+            Hasher::Sha256(hasher) => Checksum {
+                algorithm: Algorithm::Sha256,
+                bytes: hasher.clone().finalize().into(),
+            },
+            Hasher::Blake3(hasher) => Checksum {
+                algorithm: Algorithm::Blake3,
+                bytes: *hasher.finalize().as_bytes(),
+            },
+        }
+    }
+
+    pub(crate) fn algorithm(&self) -> Algorithm {
+        match self {
+            Hasher::Sha256(_) => Algorithm::Sha256,
+            Hasher::Blake3(_) => Algorithm::Blake3,
+        }
+    }
+}
+
+/// Feed the audio data of the WAV file at `path` to `hasher`.
+#[cfg(not(target_arch = "wasm32"))]
+pub(crate) fn hash_file<P>(path: P, hasher: &mut Hasher) -> Result<(), Error>
+where
+    P: AsRef<Path>,
+{
+    let mut file = File::open(path).or(Err(Error::ReadError))?;
+    let (offset, len) = crate::metadata::find_data(&mut file).or(Err(Error::ReadError))?;
+
+    file.seek(SeekFrom::Start(offset))
+        .or(Err(Error::ReadError))?;
+
+    let mut data = BufReader::new(file).take(len);
+    let mut buffer = vec![0; 64 * 1024];
+
+    loop {
+        match data.read(&mut buffer).or(Err(Error::ReadError))? {
+            0 => return Ok(()),
+            read => hasher.update(&buffer[..read]),
+        }
+    }
+}
@@ -0,0 +1,201 @@
+use crate::cli::Listen;
+use anyhow::Result;
+use clap::Args;
+use std::collections::BTreeMap;
+use std::sync::Arc;
+use std::sync::Mutex;
+use std::time::Duration;
+use std::time::Instant;
+
+#[derive(Args)]
+pub struct BenchOpts {
+    /// Device to capture from
+    #[clap(short, long, default_value = "in")]
+    listen: Listen,
+    /// Seconds to run for
+    #[clap(short, long, default_value = "10")]
+    duration: u64,
+}
+
+/// What one callback looked like.
+struct Sample {
+    at: Instant,
+    frames: usize,
+    /// CPU time the audio thread used since the previous callback
+    cpu: Option<Duration>,
+}
+
+pub fn run(options: BenchOpts) -> Result<()> {
+    let device = match options.listen {
+        Listen::In => audiort::DeviceBuilder::new_default_input()?,
+        Listen::Out => audiort::DeviceBuilder::new_default_output()?,
+    };
+
+    if let Ok(name) = device.name() {
+        eprintln!("Benchmarking {name} for {}s", options.duration);
+    }
+
+    let mut stream = audiort::StreamBuilder::new(device)?;
+    let sample_rate = stream.config().sample_rate().0;
+    let channels = usize::from(stream.config().channels().max(1));
+
+    // Generous room so the callback never reallocates
+    let samples = Arc::new(Mutex::new(Vec::with_capacity(
+        options.duration as usize * 1000,
+    )));
+    let recorder = Arc::clone(&samples);
+    let mut last_cpu = None;
+
+    stream.read(move |data| {
+        let at = Instant::now();
+        let cpu_now = thread_cpu_time();
+        let cpu = cpu_now
+            .zip(last_cpu)
+            .map(|(now, last)| now.saturating_sub(last));
+        last_cpu = cpu_now;
+
+        if let Ok(mut samples) = recorder.lock() {
+            samples.push(Sample {
+                at,
+                frames: data.len() / channels,
+                cpu,
+            });
+        }
+    })?;
+
+    stream.play()?;
+    std::thread::sleep(Duration::from_secs(options.duration));
+
+    let stats = stream.stats();
+    stream.stop();
+
+    let samples = std::mem::take(&mut *samples.lock().unwrap_or_else(|err| err.into_inner()));
+
+    if samples.len() < 2 {
+        anyhow::bail!("the stream delivered too few callbacks to measure");
+    }
+
+    println!("Callbacks: {}", samples.len());
+    println!("Dropouts:  {}", stats.dropouts);
+
+    let mut sizes = BTreeMap::new();
+
+    for sample in &samples {
+        *sizes.entry(sample.frames).or_insert(0usize) += 1;
+    }
+
+    println!("Buffer sizes (frames):");
+
+    for (frames, count) in &sizes {
+        println!(
+            "  {frames:>6}  {:.2} ms  x{count}",
+            *frames as f64 * 1000.0 / f64::from(sample_rate)
+        );
+    }
+
+    let intervals: Vec<f64> = samples
+        .windows(2)
+        .map(|pair| (pair[1].at - pair[0].at).as_secs_f64() * 1000.0)
+        .collect();
+
+    // Jitter is measured against the duration of the buffer that arrived
+    let jitter: Vec<f64> = samples
+        .windows(2)
+        .zip(&intervals)
+        .map(|(pair, interval)| {
+            let expected = pair[1].frames as f64 * 1000.0 / f64::from(sample_rate);
+            (interval - expected).abs()
+        })
+        .collect();
+
+    let summary = Summary::new(&intervals);
+    println!(
+        "Interval:  mean {:.3} ms, min {:.3} ms, max {:.3} ms, stddev {:.3} ms",
+        summary.mean, summary.min, summary.max, summary.stddev
+    );
+
+    let summary = Summary::new(&jitter);
+    println!(
+        "Jitter:    mean {:.3} ms, p99 {:.3} ms, max {:.3} ms",
+        summary.mean, summary.p99, summary.max
+    );
+
+    let load: Vec<f64> = samples
+        .iter()
+        .filter_map(|sample| {
+            let budget = sample.frames as f64 / f64::from(sample_rate);
+            sample.cpu.map(|cpu| cpu.as_secs_f64() / budget * 100.0)
+        })
+        .collect();
+
+    if load.is_empty() {
+        println!("CPU:       not available on this platform");
+    } else {
+        let cpu: Vec<f64> = samples
+            .iter()
+            .filter_map(|sample| sample.cpu)
+            .map(|cpu| cpu.as_secs_f64() * 1_000_000.0)
+            .collect();
+
+        let summary = Summary::new(&cpu);
+        let load = Summary::new(&load);
+
+        println!(
+            "CPU:       mean {:.1} us, max {:.1} us per callback ({:.1}% of the buffer on average, {:.1}% at worst)",
+            summary.mean, summary.max, load.mean, load.max
+        );
+    }
+
+    Ok(())
+}
+
+struct Summary {
+    mean: f64,
+    min: f64,
+    max: f64,
+    stddev: f64,
+    p99: f64,
+}
+
+impl Summary {
+    fn new(values: &[f64]) -> Summary {
+        let count = values.len().max(1) as f64;
+        let mean = values.iter().sum::<f64>() / count;
+        let variance = values.iter().map(|v| (v - mean).powi(2)).sum::<f64>() / count;
+
+        let mut sorted = values.to_vec();
+        sorted.sort_by(f64::total_cmp);
+
+        let p99 = sorted
+            .get(((sorted.len() as f64 * 0.99) as usize).min(sorted.len().saturating_sub(1)))
+            .copied()
+            .unwrap_or_default();
+
+        Summary {
+            mean,
+            min: sorted.first().copied().unwrap_or_default(),
+            max: sorted.last().copied().unwrap_or_default(),
+            stddev: variance.sqrt(),
+            p99,
+        }
+    }
+}
+
+/// CPU time consumed by the calling thread.
+#[cfg(unix)]
+fn thread_cpu_time() -> Option<Duration> {
+    let mut time = libc::timespec {
+        tv_sec: 0,
+        tv_nsec: 0,
+    };
+
+    // SAFETY: `time` is a valid timespec for the call to fill in
+    let result = unsafe { libc::clock_gettime(libc::CLOCK_THREAD_CPUTIME_ID, &mut time) };
+
+    (result == 0).then(|| Duration::new(time.tv_sec as u64, time.tv_nsec as u32))
+}
+
+#[cfg(not(unix))]
+fn thread_cpu_time() -> Option<Duration> {
+    None
+}
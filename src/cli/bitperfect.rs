@@ -0,0 +1,226 @@
+use crate::cli::devices;
+use anyhow::Result;
+use audiort::playback::Player;
+use audiort::Device;
+use clap::Args;
+use clap::ValueEnum;
+use std::sync::Arc;
+use std::sync::Mutex;
+use std::time::Duration;
+
+/// Frames of the signal that must come back unchanged in a row to find
+/// where it starts in the capture.
+const ALIGN_FRAMES: usize = 256;
+
+/// Silence before and after the signal, for the devices to settle and the
+/// end of it to arrive.
+const SETTLE: Duration = Duration::from_millis(500);
+
+#[derive(Args)]
+pub struct BitPerfectOpts {
+    /// Device to play the signal to, as `record --device` takes it
+    /// [default: the default output]
+    #[clap(long)]
+    output: Option<String>,
+    /// Device to capture it on [default: the default input]
+    #[clap(long)]
+    input: Option<String>,
+    /// Signal to play
+    #[clap(long, value_enum, default_value = "prbs")]
+    signal: Signal,
+    /// Bits of each sample that have to come back unchanged
+    #[clap(long, default_value = "16", value_parser = clap::value_parser!(u32).range(8..=24))]
+    bits: u32,
+    /// Seconds of signal
+    #[clap(short, long, default_value = "2")]
+    duration: f64,
+}
+
+#[derive(Clone, Copy, ValueEnum)]
+enum Signal {
+    /// Pseudo-random noise, exercising every bit
+    Prbs,
+    /// Counting up through every sample value
+    Ramp,
+}
+
+pub fn run(options: BitPerfectOpts) -> Result<()> {
+    let output = match &options.output {
+        Some(spec) => devices::open(Device::Output, spec)?,
+        None => audiort::DeviceBuilder::new_default_output()?,
+    };
+
+    let input = match &options.input {
+        Some(spec) => devices::open(Device::Input, spec)?,
+        None => audiort::DeviceBuilder::new_default_input()?,
+    };
+
+    if let (Ok(output), Ok(input)) = (output.name(), input.name()) {
+        println!("Playing to {output}, capturing on {input}");
+    }
+
+    let sample_rate = output.config().sample_rate().0;
+    let in_rate = input.config().sample_rate().0;
+
+    if in_rate != sample_rate {
+        anyhow::bail!(
+            "the output runs at {sample_rate} Hz and the input at {in_rate} Hz; \
+             resampled audio can't match"
+        );
+    }
+
+    let out_channels = output.config().channels();
+    let in_channels = usize::from(input.config().channels().max(1));
+    let channels = usize::from(out_channels).min(in_channels);
+
+    let frames = (options.duration.max(0.1) * f64::from(sample_rate)) as usize;
+    let scale = (1u32 << (options.bits - 1)) as f32;
+
+    let sent = (0..usize::from(out_channels))
+        .map(|channel| signal(options.signal, options.bits, channel, frames))
+        .collect::<Vec<_>>();
+
+    let samples = (0..frames)
+        .flat_map(|frame| {
+            sent.iter()
+                .map(move |channel| channel[frame] as f32 / scale)
+        })
+        .collect::<Vec<_>>();
+
+    let captured = Arc::new(Mutex::new(Vec::new()));
+    let recorder = Arc::clone(&captured);
+
+    let player = Player::new(&output, sample_rate, out_channels)?;
+    let mut stream = audiort::StreamBuilder::new(input)?;
+
+    stream.read(move |data| {
+        if let Ok(mut captured) = recorder.lock() {
+            captured.extend_from_slice(data);
+        }
+    })?;
+
+    player.play()?;
+    stream.play()?;
+
+    std::thread::sleep(SETTLE);
+
+    // A little at a time, as the player drops what's queued past a second
+    let quarter = sample_rate as usize / 4;
+
+    for part in samples.chunks(quarter / 2 * usize::from(out_channels).max(1)) {
+        while player.queued() > quarter {
+            std::thread::sleep(Duration::from_millis(10));
+        }
+
+        player.push(part);
+    }
+
+    while player.queued() > 0 {
+        std::thread::sleep(Duration::from_millis(10));
+    }
+
+    std::thread::sleep(SETTLE);
+    stream.stop();
+
+    let captured = match captured.lock() {
+        Ok(captured) => captured.clone(),
+        Err(_) => anyhow::bail!("capture failed"),
+    };
+
+    let received = (0..channels)
+        .map(|channel| {
+            captured
+                .iter()
+                .skip(channel)
+                .step_by(in_channels)
+                .map(|value| (value * scale).round() as i32)
+                .collect::<Vec<_>>()
+        })
+        .collect::<Vec<_>>();
+
+    let start = &sent[0][..ALIGN_FRAMES.min(frames)];
+
+    let Some(offset) = received[0]
+        .windows(start.len())
+        .position(|window| window == start)
+    else {
+        anyhow::bail!(
+            "the signal never came back intact; check the output is routed to the input, \
+             and that nothing changes the volume, resamples or adds effects on the way"
+        );
+    };
+
+    let mut perfect = true;
+
+    for (channel, (sent, received)) in sent.iter().zip(&received).enumerate() {
+        let received = &received[offset..];
+        let missing = frames.saturating_sub(received.len());
+
+        let mut differ = sent
+            .iter()
+            .zip(received)
+            .enumerate()
+            .filter(|(_, (a, b))| a != b);
+        let first = differ.next();
+        let count = first.map_or(0, |_| 1 + differ.count());
+
+        match first {
+            None if missing == 0 => println!("Channel {}: bit-perfect", channel + 1),
+            None => {
+                perfect = false;
+                println!(
+                    "Channel {}: the last {missing} frames never arrived",
+                    channel + 1
+                );
+            }
+            Some((frame, (expected, got))) => {
+                perfect = false;
+                println!(
+                    "Channel {}: {count} of {frames} samples differ, first at frame {frame} \
+                     (sent {expected}, got {got})",
+                    channel + 1
+                );
+            }
+        }
+    }
+
+    if !perfect {
+        anyhow::bail!("the chain isn't bit-perfect at {} bits", options.bits);
+    }
+
+    println!(
+        "Bit-perfect at {} bits: {frames} frames on {channels} channels",
+        options.bits
+    );
+
+    Ok(())
+}
+
+/// `frames` sample values of `bits` bits for `channel`, each channel's
+/// different so swapped channels are caught.
+fn signal(signal: Signal, bits: u32, channel: usize, frames: usize) -> Vec<i32> {
+    let half = 1i64 << (bits - 1);
+
+    match signal {
+        Signal::Prbs => {
+            // xorshift32, which never yields 0 from a non-zero seed
+            let mut state = 0x9e37_79b9_u32 ^ (channel as u32 + 1).wrapping_mul(0x85eb_ca6b);
+
+            (0..frames)
+                .map(|_| {
+                    state ^= state << 13;
+                    state ^= state >> 17;
+                    state ^= state << 5;
+
+                    ((state >> (32 - bits)) as i64 - half) as i32
+                })
+                .collect()
+        }
+        Signal::Ramp => (0..frames)
+            .map(|frame| {
+                let step = frame as i64 + channel as i64 * (half / 8);
+                (step.rem_euclid(half * 2) - half) as i32
+            })
+            .collect(),
+    }
+}
@@ -0,0 +1,74 @@
+use anyhow::Result;
+use audiort::generator::Metronome;
+use audiort::playback::Player;
+use clap::Args;
+use std::sync::atomic::AtomicBool;
+use std::sync::atomic::Ordering;
+use std::sync::Arc;
+use std::time::Duration;
+use std::time::Instant;
+
+#[derive(Args)]
+pub struct ClickOpts {
+    /// Tempo in beats per minute
+    #[clap(long, default_value = "120")]
+    bpm: f64,
+    /// Beats per bar; the first is accented
+    #[clap(long, default_value = "4")]
+    beats: u32,
+    /// Peak level in dBFS
+    #[clap(long, default_value = "-12", allow_negative_numbers = true)]
+    level: f32,
+    /// Seconds to play for [default: until interrupted]
+    #[clap(short, long)]
+    duration: Option<f64>,
+}
+
+pub fn run(options: ClickOpts) -> Result<()> {
+    let _player = start(options.bpm, options.beats, options.level)?;
+
+    let interrupted = Arc::new(AtomicBool::new(false));
+    let interrupt = Arc::clone(&interrupted);
+
+    ctrlc::set_handler(move || interrupt.store(true, Ordering::Relaxed))?;
+
+    let started = Instant::now();
+    let duration = options.duration.map(Duration::from_secs_f64);
+
+    while !interrupted.load(Ordering::Relaxed)
+        && duration.is_none_or(|duration| started.elapsed() < duration)
+    {
+        std::thread::sleep(Duration::from_millis(10));
+    }
+
+    Ok(())
+}
+
+/// Play a click on the default output until the player is dropped.
+pub fn start(bpm: f64, beats: u32, level: f32) -> Result<Player> {
+    if bpm <= 0.0 {
+        anyhow::bail!("--bpm must be above 0");
+    }
+
+    if level > 0.0 {
+        anyhow::bail!("--level must be at most 0 dBFS");
+    }
+
+    let device = audiort::DeviceBuilder::new_default_output()?;
+
+    if let Ok(name) = device.name() {
+        eprintln!("Clicking on {name}");
+    }
+
+    let sample_rate = device.config().sample_rate().0;
+    let player = Player::new(&device, sample_rate, device.config().channels())?;
+
+    let mut metronome = Metronome::new(bpm, sample_rate);
+    metronome.beats(beats).level(level);
+
+    // Nothing is queued, so the player only plays the click
+    player.effect(metronome);
+    player.play()?;
+
+    Ok(player)
+}
@@ -0,0 +1,50 @@
+use anyhow::Result;
+use clap::Args;
+use std::path::PathBuf;
+
+#[derive(Args)]
+pub struct ConcatOpts {
+    /// WAV files to join, in order
+    #[clap(required = true)]
+    inputs: Vec<PathBuf>,
+    /// Where to write them; it takes the format of the first
+    #[clap(short, long)]
+    output: PathBuf,
+}
+
+pub fn run(options: ConcatOpts) -> Result<()> {
+    let mut spec = None;
+
+    for path in &options.inputs {
+        let this = hound::WavReader::open(path)
+            .map_err(|err| anyhow::anyhow!("{err}: {}", path.display()))?
+            .spec();
+
+        match spec {
+            None => spec = Some(this),
+            Some(first)
+                if first.sample_rate != this.sample_rate || first.channels != this.channels =>
+            {
+                eprintln!(
+                    "Converting {} from {} Hz, {} channels",
+                    path.display(),
+                    this.sample_rate,
+                    this.channels
+                );
+            }
+            Some(_) => {}
+        }
+    }
+
+    let frames = audiort::concat_wav(&options.inputs, &options.output)?;
+    let sample_rate = spec.map_or(1, |spec| spec.sample_rate);
+
+    println!(
+        "Written to {} ({} files, {:.1}s)",
+        options.output.display(),
+        options.inputs.len(),
+        frames as f64 / f64::from(sample_rate)
+    );
+
+    Ok(())
+}
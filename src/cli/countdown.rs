@@ -0,0 +1,166 @@
+//! Counting down to a start, as `record --delay` does: a step at a time,
+//! each shown and sounded.
+
+use anyhow::Result;
+use audiort::generator::Generator;
+use audiort::generator::Wave;
+use audiort::playback::Player;
+use audiort::resample::Resampler;
+use std::path::Path;
+use std::time::Duration;
+
+/// Pitch of countdown beeps, and of the last one before the start.
+const BEEP_FREQ: f64 = 880.0;
+const LAST_BEEP_FREQ: f64 = 1760.0;
+
+/// How long a beep sounds, and how long it takes to fade in and out.
+const BEEP_SECONDS: f64 = 0.15;
+const BEEP_FADE: f64 = 0.005;
+
+/// The steps of a countdown, from the time left down to the last step.
+pub struct Countdown {
+    left: Duration,
+    step: Duration,
+}
+
+/// A point in a countdown, and how long until the next.
+pub struct Step {
+    pub left: Duration,
+    pub wait: Duration,
+    pub last: bool,
+}
+
+impl Countdown {
+    pub fn new(total: Duration, step: Duration) -> Countdown {
+        Countdown {
+            left: total,
+            step: step.max(Duration::from_millis(1)),
+        }
+    }
+}
+
+impl Iterator for Countdown {
+    type Item = Step;
+
+    fn next(&mut self) -> Option<Step> {
+        if self.left.is_zero() {
+            return None;
+        }
+
+        // The first step takes any part of a step, so the rest fall on whole
+        // ones, e.g. 2.5 2 1
+        let part = self.left.as_nanos() % self.step.as_nanos();
+        let wait = match part {
+            0 => self.step,
+            part => Duration::from_nanos(part as u64),
+        };
+
+        let step = Step {
+            left: self.left,
+            wait,
+            last: wait == self.left,
+        };
+
+        self.left -= wait;
+
+        Some(step)
+    }
+}
+
+impl Step {
+    /// The time left as shown, e.g. `3` or `0.5`.
+    pub fn label(&self) -> String {
+        let label = format!("{:.3}", self.left.as_secs_f64());
+        label.trim_end_matches('0').trim_end_matches('.').to_owned()
+    }
+}
+
+/// Sounds each step of a countdown on the default output.
+pub struct Beeper {
+    player: Player,
+    level: f32,
+    /// Played instead of a beep
+    sound: Option<Vec<f32>>,
+}
+
+impl Beeper {
+    /// Beeping at `level` (dBFS), or playing the WAV file at `sound`.
+    pub fn new(level: f32, sound: Option<&Path>) -> Result<Beeper> {
+        let device = audiort::DeviceBuilder::new_default_output()?;
+        let player = Player::new(
+            &device,
+            device.config().sample_rate().0,
+            device.config().channels(),
+        )?;
+
+        let sound = match sound {
+            Some(path) => Some(
+                load(path, player.sample_rate(), player.channels())
+                    .map_err(|err| anyhow::anyhow!("{err}: {}", path.display()))?,
+            ),
+            None => None,
+        };
+
+        player.play()?;
+
+        Ok(Beeper {
+            player,
+            level,
+            sound,
+        })
+    }
+
+    /// Sound a step, a higher beep for the `last` one.
+    pub fn beep(&self, last: bool) {
+        if let Some(sound) = &self.sound {
+            self.player.push(sound);
+            return;
+        }
+
+        let sample_rate = self.player.sample_rate();
+        let channels = usize::from(self.player.channels().max(1));
+
+        let mut generator = Generator::new(Wave::Sine, sample_rate);
+        generator
+            .freq(if last { LAST_BEEP_FREQ } else { BEEP_FREQ })
+            .level(self.level);
+
+        let frames = (BEEP_SECONDS * f64::from(sample_rate)) as usize;
+        let fade = (BEEP_FADE * f64::from(sample_rate)).max(1.0);
+        let mut samples = vec![0.0; frames * channels];
+
+        generator.fill(&mut samples, self.player.channels());
+
+        // Faded at both ends so it doesn't click
+        for (frame, values) in samples.chunks_mut(channels).enumerate() {
+            let edge = frame.min(frames - frame) as f64;
+            let gain = (edge / fade).min(1.0) as f32;
+
+            values.iter_mut().for_each(|value| *value *= gain);
+        }
+
+        self.player.push(&samples);
+    }
+}
+
+/// An audio file, converted to `sample_rate` and `channels`.
+pub fn load(path: &Path, sample_rate: u32, channels: u16) -> Result<Vec<f32>> {
+    let (spec, samples) = audiort::read_samples(path)?;
+
+    let channels = usize::from(channels.max(1));
+
+    let mut remapped = Vec::new();
+    audiort::resample::remap(
+        &samples,
+        usize::from(spec.channels),
+        channels,
+        &mut remapped,
+    );
+
+    let mut resampler = Resampler::new(spec.sample_rate, sample_rate, channels);
+    let mut sound = Vec::new();
+    resampler.process(&remapped, &mut sound);
+    resampler.finish(&mut sound);
+
+    Ok(sound)
+}
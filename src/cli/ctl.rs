@@ -0,0 +1,25 @@
+use crate::cli::daemon::Endpoint;
+use anyhow::Result;
+use clap::Args;
+
+#[derive(Args)]
+pub struct CtlOpts {
+    #[clap(flatten)]
+    endpoint: Endpoint,
+    /// start [path] | stop | pause | resume | device <in|out> | gain <linear> | status | levels | metrics
+    #[clap(required = true)]
+    command: Vec<String>,
+}
+
+pub fn run(options: CtlOpts) -> Result<()> {
+    let reply = options.endpoint.request(&options.command.join(" "))?;
+    let reply: serde_json::Value = serde_json::from_str(&reply)?;
+
+    if reply["ok"] != true {
+        anyhow::bail!("{}", reply["error"].as_str().unwrap_or("request failed"));
+    }
+
+    println!("{}", serde_json::to_string_pretty(&reply)?);
+
+    Ok(())
+}
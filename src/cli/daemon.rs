@@ -0,0 +1,526 @@
+use crate::cli::fanout::FanOut;
+use crate::cli::Listen;
+use anyhow::Result;
+use audiort::metadata::format_timestamp;
+use clap::Args;
+use serde_json::json;
+use std::io::BufRead;
+use std::io::BufReader;
+use std::io::Read;
+use std::io::Write;
+use std::net::TcpListener;
+use std::path::PathBuf;
+use std::sync::mpsc;
+use std::time::SystemTime;
+
+#[derive(Args)]
+pub struct DaemonOpts {
+    #[clap(flatten)]
+    endpoint: Endpoint,
+    /// Directory new recordings are written to
+    #[clap(long, default_value = ".")]
+    dir: PathBuf,
+    /// Default device to listen to
+    #[clap(short, long, default_value = "in")]
+    listen: Listen,
+    /// Also serve an HTTP control API on this address, e.g. 127.0.0.1:8080
+    #[clap(long)]
+    http: Option<String>,
+    /// Also accept OSC messages on this UDP port, e.g. 9000
+    #[clap(long)]
+    osc_port: Option<u16>,
+    /// Publish state and levels to an MQTT broker, e.g. localhost:1883
+    #[clap(long)]
+    mqtt: Option<String>,
+    /// MQTT topic prefix
+    #[clap(long, default_value = "audiort")]
+    mqtt_topic: String,
+    /// Accept daemon commands published to `<mqtt-topic>/command`
+    #[clap(long, requires = "mqtt")]
+    mqtt_commands: bool,
+    /// Also serve a gRPC API on this address, e.g. 127.0.0.1:50051
+    #[cfg(feature = "grpc")]
+    #[clap(long)]
+    grpc: Option<String>,
+}
+
+#[derive(Args, Clone)]
+pub struct Endpoint {
+    /// Unix socket path [default: $XDG_RUNTIME_DIR/audiort.sock]
+    #[clap(long)]
+    socket: Option<PathBuf>,
+    /// Listen on / connect to a TCP address instead, e.g. 127.0.0.1:7373
+    #[clap(long, conflicts_with = "socket")]
+    tcp: Option<String>,
+}
+
+pub enum Message {
+    Request(String, mpsc::Sender<serde_json::Value>),
+    StreamError(cpal::StreamError),
+    Shutdown,
+}
+
+impl Endpoint {
+    #[cfg(unix)]
+    pub fn socket_path(&self) -> PathBuf {
+        self.socket.clone().unwrap_or_else(|| {
+            std::env::var_os("XDG_RUNTIME_DIR")
+                .map(PathBuf::from)
+                .unwrap_or_else(std::env::temp_dir)
+                .join("audiort.sock")
+        })
+    }
+
+    pub fn tcp_addr(&self) -> Option<&str> {
+        if cfg!(unix) {
+            self.tcp.as_deref()
+        } else {
+            Some(self.tcp.as_deref().unwrap_or("127.0.0.1:7373"))
+        }
+    }
+
+    /// Send one command and return the daemon's JSON reply line.
+    pub fn request(&self, command: &str) -> Result<String> {
+        if let Some(addr) = self.tcp_addr() {
+            return exchange(std::net::TcpStream::connect(addr)?, command);
+        }
+
+        #[cfg(unix)]
+        return exchange(
+            std::os::unix::net::UnixStream::connect(self.socket_path())?,
+            command,
+        );
+
+        #[cfg(not(unix))]
+        unreachable!("non-unix endpoints are always tcp")
+    }
+
+    fn serve(&self, tx: mpsc::Sender<Message>) -> Result<()> {
+        if let Some(addr) = self.tcp_addr() {
+            let listener = TcpListener::bind(addr)?;
+            eprintln!("Listening on {addr}");
+
+            std::thread::spawn(move || {
+                for stream in listener.incoming().flatten() {
+                    let tx = tx.clone();
+                    std::thread::spawn(move || handle_connection(stream, tx));
+                }
+            });
+
+            return Ok(());
+        }
+
+        #[cfg(unix)]
+        {
+            let path = self.socket_path();
+
+            // Clear out a socket left behind by a daemon that didn't exit cleanly
+            if path.exists() && std::os::unix::net::UnixStream::connect(&path).is_err() {
+                std::fs::remove_file(&path)?;
+            }
+
+            let listener = std::os::unix::net::UnixListener::bind(&path)?;
+            eprintln!("Listening on {}", path.display());
+
+            std::thread::spawn(move || {
+                for stream in listener.incoming().flatten() {
+                    let tx = tx.clone();
+                    std::thread::spawn(move || handle_connection(stream, tx));
+                }
+            });
+        }
+
+        Ok(())
+    }
+
+    fn cleanup(&self) {
+        #[cfg(unix)]
+        if self.tcp_addr().is_none() {
+            let _ = std::fs::remove_file(self.socket_path());
+        }
+    }
+}
+
+fn exchange<S>(stream: S, command: &str) -> Result<String>
+where
+    S: Read + Write,
+{
+    let mut stream = BufReader::new(stream);
+
+    writeln!(stream.get_mut(), "{command}")?;
+    stream.get_mut().flush()?;
+
+    let mut reply = String::new();
+    stream.read_line(&mut reply)?;
+
+    Ok(reply)
+}
+
+fn handle_connection<S>(stream: S, tx: mpsc::Sender<Message>)
+where
+    S: Read + Write,
+{
+    let mut stream = BufReader::new(stream);
+    let mut line = String::new();
+
+    while matches!(stream.read_line(&mut line), Ok(read) if read > 0) {
+        let (reply_tx, reply_rx) = mpsc::channel();
+
+        if tx
+            .send(Message::Request(line.trim().to_owned(), reply_tx))
+            .is_err()
+        {
+            break;
+        }
+
+        let reply = reply_rx
+            .recv()
+            .unwrap_or_else(|_| json!({ "ok": false, "error": "daemon is shutting down" }));
+
+        if writeln!(stream.get_mut(), "{reply}").is_err() {
+            break;
+        }
+
+        line.clear();
+    }
+}
+
+struct Recording {
+    stream: audiort::StreamBuilder,
+    path: PathBuf,
+    device: Option<String>,
+    started: SystemTime,
+    paused: bool,
+}
+
+pub struct Daemon {
+    dir: PathBuf,
+    listen: Listen,
+    gain: f32,
+    last_error: Option<String>,
+    recording: Option<Recording>,
+    totals: Totals,
+    tx: mpsc::Sender<Message>,
+    audio: FanOut,
+}
+
+/// Counters accumulated over finished recordings
+#[derive(Default, Clone, Copy)]
+struct Totals {
+    frames: u64,
+    bytes: u64,
+    dropouts: u64,
+    clipped: u64,
+}
+
+impl Totals {
+    fn add(&mut self, stats: &audiort::Stats, config: &cpal::SupportedStreamConfig) {
+        self.frames += stats.frames(config.channels());
+        self.bytes += stats.samples * config.sample_format().sample_size() as u64;
+        self.dropouts += stats.dropouts;
+        self.clipped += stats.clipped;
+    }
+}
+
+impl Daemon {
+    pub fn handle(&mut self, request: &str) -> serde_json::Value {
+        let (command, arg) = request
+            .trim()
+            .split_once(char::is_whitespace)
+            .unwrap_or((request.trim(), ""));
+        let arg = Some(arg.trim()).filter(|arg| !arg.is_empty());
+
+        let result = match command {
+            "start" => self.start(arg.map(PathBuf::from)),
+            "stop" => self.stop(),
+            "pause" => self.pause(true),
+            "resume" => self.pause(false),
+            "device" => self.device(arg),
+            "status" => Ok(self.status()),
+            "levels" => self.levels(),
+            "metrics" => Ok(self.metrics()),
+            "gain" => self.gain(arg),
+            "" => Err(anyhow::anyhow!("empty command")),
+            other => Err(anyhow::anyhow!("unknown command `{other}`")),
+        };
+
+        match result {
+            Ok(mut reply) => {
+                reply["ok"] = json!(true);
+                reply
+            }
+            Err(err) => json!({ "ok": false, "error": err.to_string() }),
+        }
+    }
+
+    fn start(&mut self, path: Option<PathBuf>) -> Result<serde_json::Value> {
+        if self.recording.is_some() {
+            anyhow::bail!("already recording");
+        }
+
+        let path = path.unwrap_or_else(|| {
+            let stamp = format_timestamp(SystemTime::now()).replace([':', '-'], "");
+            self.dir.join(format!("audiort-{stamp}.wav"))
+        });
+
+        let device = match self.listen {
+            Listen::In => audiort::DeviceBuilder::new_default_input()?,
+            Listen::Out => audiort::DeviceBuilder::new_default_output()?,
+        };
+
+        let name = device.name().ok();
+        let mut stream = audiort::StreamBuilder::new(device)?;
+        let errors = self.tx.clone();
+
+        stream.on_error(move |err| {
+            let _ = errors.send(Message::StreamError(err));
+        });
+
+        let audio = self.audio.clone();
+
+        stream.tap(move |data| {
+            audio.send(|| data.iter().flat_map(|value| value.to_le_bytes()).collect())
+        });
+
+        stream.set_gain(self.gain);
+        stream.write_wav(&path)?;
+        stream.play()?;
+
+        eprintln!("Recording to {}", path.display());
+
+        self.last_error = None;
+
+        self.recording = Some(Recording {
+            stream,
+            path,
+            device: name,
+            started: SystemTime::now(),
+            paused: false,
+        });
+
+        Ok(self.status())
+    }
+
+    fn stop(&mut self) -> Result<serde_json::Value> {
+        let mut recording = self
+            .recording
+            .take()
+            .ok_or_else(|| anyhow::anyhow!("not recording"))?;
+
+        let stats = recording.stream.finish()?;
+        let config = recording.stream.config();
+        let frames = stats.frames(config.channels());
+
+        self.totals.add(&stats, config);
+
+        eprintln!("Written to {}", recording.path.display());
+
+        Ok(json!({
+            "state": "idle",
+            "path": recording.path,
+            "frames": frames,
+            "duration": frames as f64 / f64::from(config.sample_rate().0),
+            "peak_dbfs": audiort::to_dbfs(stats.peak),
+            "dropouts": stats.dropouts,
+        }))
+    }
+
+    fn pause(&mut self, paused: bool) -> Result<serde_json::Value> {
+        let recording = self
+            .recording
+            .as_mut()
+            .ok_or_else(|| anyhow::anyhow!("not recording"))?;
+
+        if paused {
+            recording.stream.pause()?;
+        } else {
+            recording.stream.play()?;
+        }
+
+        recording.paused = paused;
+
+        Ok(self.status())
+    }
+
+    fn device(&mut self, listen: Option<&str>) -> Result<serde_json::Value> {
+        if self.recording.is_some() {
+            anyhow::bail!("stop recording before switching device");
+        }
+
+        self.listen = match listen {
+            Some("in") => Listen::In,
+            Some("out") => Listen::Out,
+            _ => anyhow::bail!("expected `device in` or `device out`"),
+        };
+
+        Ok(self.status())
+    }
+
+    fn gain(&mut self, gain: Option<&str>) -> Result<serde_json::Value> {
+        let gain: f32 = gain
+            .and_then(|gain| gain.parse().ok())
+            .filter(|gain: &f32| gain.is_finite() && *gain >= 0.0)
+            .ok_or_else(|| anyhow::anyhow!("expected `gain <linear gain>`"))?;
+
+        self.gain = gain;
+
+        if let Some(recording) = &self.recording {
+            recording.stream.set_gain(gain);
+        }
+
+        Ok(self.status())
+    }
+
+    fn levels(&self) -> Result<serde_json::Value> {
+        let recording = self
+            .recording
+            .as_ref()
+            .ok_or_else(|| anyhow::anyhow!("not recording"))?;
+
+        let stats = recording.stream.stats();
+
+        Ok(json!({
+            "peak_dbfs": audiort::to_dbfs(stats.current_peak),
+            "rms_dbfs": audiort::to_dbfs(stats.current_rms),
+            "clipped": stats.clipped,
+            "load": stats.load,
+        }))
+    }
+
+    fn metrics(&self) -> serde_json::Value {
+        let mut totals = self.totals;
+
+        let (peak, rms, duration) = match &self.recording {
+            Some(recording) => {
+                let stats = recording.stream.stats();
+                totals.add(&stats, recording.stream.config());
+
+                (
+                    stats.current_peak,
+                    stats.current_rms,
+                    recording.stream.elapsed().as_secs_f64(),
+                )
+            }
+            None => (0.0, 0.0, 0.0),
+        };
+
+        json!({
+            "recording": self.recording.is_some(),
+            "frames_total": totals.frames,
+            "bytes_total": totals.bytes,
+            "dropouts_total": totals.dropouts,
+            "clipped_total": totals.clipped,
+            "peak": peak,
+            "rms": rms,
+            "duration": duration,
+        })
+    }
+
+    pub fn status(&self) -> serde_json::Value {
+        let listen = match self.listen {
+            Listen::In => "in",
+            Listen::Out => "out",
+        };
+
+        let Some(recording) = &self.recording else {
+            return json!({
+                "state": "idle",
+                "listen": listen,
+                "gain": self.gain,
+                "error": self.last_error,
+            });
+        };
+
+        let stats = recording.stream.stats();
+        let config = recording.stream.config();
+
+        json!({
+            "state": if recording.paused { "paused" } else { "recording" },
+            "listen": listen,
+            "gain": self.gain,
+            "error": self.last_error,
+            "device": recording.device,
+            "path": recording.path,
+            "started": format_timestamp(recording.started),
+            "frames": recording.stream.frames_captured(),
+            "duration": recording.stream.elapsed().as_secs_f64(),
+            "peak_dbfs": audiort::to_dbfs(stats.peak),
+            "dropouts": stats.dropouts,
+            "sample_rate": config.sample_rate().0,
+            "channels": config.channels(),
+        })
+    }
+}
+
+pub fn run(options: DaemonOpts) -> Result<()> {
+    let (tx, rx) = mpsc::channel();
+    let interrupt = tx.clone();
+
+    ctrlc::set_handler(move || {
+        let _ = interrupt.send(Message::Shutdown);
+    })?;
+
+    options.endpoint.serve(tx.clone())?;
+
+    if let Some(addr) = &options.http {
+        crate::cli::http::serve(addr, tx.clone())?;
+    }
+
+    if let Some(port) = options.osc_port {
+        crate::cli::osc::serve(port, tx.clone())?;
+    }
+
+    if let Some(broker) = &options.mqtt {
+        crate::cli::mqtt::serve(
+            broker,
+            options.mqtt_topic.clone(),
+            options.mqtt_commands,
+            tx.clone(),
+        )?;
+    }
+
+    let audio = FanOut::default();
+
+    #[cfg(feature = "grpc")]
+    if let Some(addr) = &options.grpc {
+        crate::cli::grpc::serve(addr, tx.clone(), audio.clone())?;
+    }
+
+    let mut daemon = Daemon {
+        dir: options.dir,
+        listen: options.listen,
+        gain: 1.0,
+        last_error: None,
+        recording: None,
+        totals: Totals::default(),
+        tx,
+        audio,
+    };
+
+    for message in rx {
+        match message {
+            Message::Request(request, reply) => {
+                let _ = reply.send(daemon.handle(&request));
+            }
+            Message::StreamError(err) => {
+                eprintln!("Error: {err}");
+                daemon.last_error = Some(err.to_string());
+
+                if daemon.recording.is_some() {
+                    if let Err(err) = daemon.stop() {
+                        eprintln!("Error: {err}");
+                    }
+                }
+            }
+            Message::Shutdown => break,
+        }
+    }
+
+    if daemon.recording.is_some() {
+        daemon.stop()?;
+    }
+
+    options.endpoint.cleanup();
+
+    Ok(())
+}
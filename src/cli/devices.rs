@@ -0,0 +1,100 @@
+use anyhow::Result;
+use clap::Args;
+
+#[derive(Args)]
+pub struct DevicesOpts {}
+
+pub fn run(_: DevicesOpts) -> Result<()> {
+    println!("Input devices");
+    list(
+        audiort::Device::Input,
+        audiort::DeviceBuilder::new_default_input(),
+    );
+
+    println!("Output devices");
+    list(
+        audiort::Device::Output,
+        audiort::DeviceBuilder::new_default_output(),
+    );
+
+    #[cfg(feature = "asio")]
+    {
+        println!("ASIO input devices");
+        asio(audiort::Device::Input);
+
+        println!("ASIO output devices");
+        asio(audiort::Device::Output);
+    }
+
+    let config = audiort::config::Config::load()?;
+
+    if !config.aliases.is_empty() {
+        println!("Aliases");
+
+        for (alias, spec) in &config.aliases {
+            println!("  {alias} = {spec}");
+        }
+    }
+
+    Ok(())
+}
+
+/// Print ASIO devices, by the names `--device asio:NAME` takes.
+#[cfg(feature = "asio")]
+fn asio(kind: audiort::Device) {
+    match audiort::DeviceBuilder::asio_names(kind) {
+        Ok(names) if names.is_empty() => println!("  (none)"),
+        Ok(names) => {
+            for name in names {
+                println!("      {name}");
+            }
+        }
+        Err(err) => println!("  {err}"),
+    }
+}
+
+/// Print devices with the index `--device` accepts, marking the default.
+fn list(kind: audiort::Device, default: Result<audiort::DeviceBuilder, audiort::Error>) {
+    let default = default.ok().and_then(|device| device.name().ok());
+
+    match audiort::DeviceBuilder::names(kind) {
+        Ok(names) if names.is_empty() => println!("  (none)"),
+        Ok(names) => {
+            for (index, name) in names.iter().enumerate() {
+                let mark = match Some(name) == default.as_ref() {
+                    true => " (default)",
+                    false => "",
+                };
+
+                println!("  {index:>2}  {name}{mark}");
+            }
+        }
+        Err(err) => println!("  {err}"),
+    }
+}
+
+/// Open a device as `DeviceBuilder::open` does, naming the devices a
+/// `--device` matched when it matched more than one.
+pub fn open(kind: audiort::Device, spec: &str) -> Result<audiort::DeviceBuilder> {
+    audiort::DeviceBuilder::open(kind, spec).map_err(|err| explain(kind, spec, err))
+}
+
+/// `err` from opening `spec`, listing the matches if it was ambiguous.
+pub fn explain(kind: audiort::Device, spec: &str, err: audiort::Error) -> anyhow::Error {
+    if err != audiort::Error::AmbiguousDeviceError {
+        return err.into();
+    }
+
+    let config = audiort::config::Config::load().unwrap_or_default();
+    let query = config.resolve(spec);
+
+    match audiort::DeviceBuilder::matching(kind, query) {
+        Ok(names) if !names.is_empty() => {
+            anyhow::anyhow!(
+                "`{spec}` matches more than one device: {}",
+                names.join(", ")
+            )
+        }
+        _ => err.into(),
+    }
+}
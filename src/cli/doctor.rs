@@ -0,0 +1,230 @@
+use anyhow::Result;
+use clap::Args;
+use std::sync::atomic::AtomicBool;
+use std::sync::atomic::Ordering;
+use std::sync::Arc;
+use std::time::Duration;
+
+#[derive(Args)]
+pub struct DoctorOpts {
+    /// Skip the short test recording from the default input
+    #[clap(long)]
+    no_capture: bool,
+}
+
+/// Tallies findings so the summary can point at what to fix.
+#[derive(Default)]
+struct Report {
+    warnings: usize,
+    failures: usize,
+}
+
+impl Report {
+    fn ok(&mut self, message: &str) {
+        println!("  ok    {message}");
+    }
+
+    fn warn(&mut self, message: &str, hint: &str) {
+        self.warnings += 1;
+        println!("  warn  {message}");
+        println!("        -> {hint}");
+    }
+
+    fn fail(&mut self, message: &str, hint: &str) {
+        self.failures += 1;
+        println!("  FAIL  {message}");
+        println!("        -> {hint}");
+    }
+}
+
+pub fn run(options: DoctorOpts) -> Result<()> {
+    let mut report = Report::default();
+
+    println!("Hosts");
+    check_hosts(&mut report);
+
+    println!("Default input");
+    let input_ok = check_default(&mut report, audiort::Device::Input);
+
+    println!("Default output");
+    check_default(&mut report, audiort::Device::Output);
+
+    #[cfg(target_os = "linux")]
+    {
+        println!("Sound server");
+        check_linux(&mut report);
+    }
+
+    if input_ok && !options.no_capture {
+        println!("Capture");
+        check_capture(&mut report);
+    }
+
+    println!();
+
+    match (report.failures, report.warnings) {
+        (0, 0) => println!("Everything looks good."),
+        (0, warnings) => println!("{warnings} warning(s); recording should work."),
+        (failures, warnings) => {
+            println!("{failures} problem(s) and {warnings} warning(s) found.");
+            std::process::exit(1);
+        }
+    }
+
+    Ok(())
+}
+
+fn check_hosts(report: &mut Report) {
+    let default = cpal::default_host().id();
+
+    for id in cpal::available_hosts() {
+        let marker = if id == default { " (default)" } else { "" };
+        report.ok(&format!("{}{marker}", id.name()));
+    }
+
+    if cpal::available_hosts().is_empty() {
+        report.fail(
+            "no audio hosts available",
+            "install or start your platform's audio system",
+        );
+    }
+}
+
+fn check_default(report: &mut Report, kind: audiort::Device) -> bool {
+    let device = match kind {
+        audiort::Device::Input => audiort::DeviceBuilder::new_default_input(),
+        audiort::Device::Output => audiort::DeviceBuilder::new_default_output(),
+    };
+
+    let device = match device {
+        Ok(device) => device,
+        Err(err) => {
+            report.fail(
+                &err.to_string(),
+                "connect a device or choose a default in your system sound settings",
+            );
+            return false;
+        }
+    };
+
+    let name = device.name().unwrap_or_else(|_| "<unnamed>".into());
+    let config = device.config();
+
+    report.ok(&format!(
+        "{name}: {}Hz, {} channels, {}",
+        config.sample_rate().0,
+        config.channels(),
+        config.sample_format()
+    ));
+
+    let format = config.sample_format();
+
+    if !matches!(
+        format,
+        cpal::SampleFormat::F32
+            | cpal::SampleFormat::I32
+            | cpal::SampleFormat::I16
+            | cpal::SampleFormat::I8
+    ) {
+        report.fail(
+            &format!("sample format {format} can't be recorded"),
+            "pick a 16/24/32-bit or float format for the device in your system sound settings",
+        );
+        return false;
+    }
+
+    true
+}
+
+#[cfg(target_os = "linux")]
+fn check_linux(report: &mut Report) {
+    let runtime = std::env::var_os("XDG_RUNTIME_DIR").map(std::path::PathBuf::from);
+
+    let has = |path: &str| runtime.as_ref().is_some_and(|dir| dir.join(path).exists());
+
+    match (has("pipewire-0"), has("pulse/native")) {
+        (true, _) => report.ok("PipeWire is running"),
+        (false, true) => report.ok("PulseAudio is running"),
+        (false, false) => report.warn(
+            "no PipeWire or PulseAudio socket found",
+            "without a sound server only raw ALSA devices are available; start pipewire or pulseaudio",
+        ),
+    }
+
+    match std::fs::read_dir("/dev/snd") {
+        Ok(_) => report.ok("/dev/snd is accessible"),
+        Err(err) => report.warn(
+            &format!("/dev/snd: {err}"),
+            "add your user to the `audio` group or run inside a session with device access",
+        ),
+    }
+}
+
+/// Record briefly and look for the all-zero input that usually means a
+/// denied permission or a muted device.
+fn check_capture(report: &mut Report) {
+    let result = (|| -> Result<audiort::Stats> {
+        let device = audiort::DeviceBuilder::new_default_input()?;
+        let mut stream = audiort::StreamBuilder::new(device)?;
+        let received = Arc::new(AtomicBool::new(false));
+        let callback = Arc::clone(&received);
+
+        stream.read(move |_| callback.store(true, Ordering::Relaxed))?;
+        stream.play()?;
+        std::thread::sleep(Duration::from_millis(1000));
+
+        let stats = stream.stats();
+        stream.stop();
+
+        if !received.load(Ordering::Relaxed) {
+            anyhow::bail!("the input stream delivered no audio");
+        }
+
+        Ok(stats)
+    })();
+
+    let stats = match result {
+        Ok(stats) => stats,
+        Err(err) => {
+            report.fail(
+                &format!("recording failed: {err}"),
+                "check the device isn't in exclusive use by another application",
+            );
+            return;
+        }
+    };
+
+    if stats.peak == 0.0 {
+        report.fail("the input records pure digital silence", silence_hint());
+    } else if audiort::to_dbfs(stats.peak) < -60.0 {
+        report.warn(
+            &format!(
+                "the input is very quiet (peak {:.1} dBFS)",
+                audiort::to_dbfs(stats.peak)
+            ),
+            "raise the input gain or check the right input is selected",
+        );
+    } else {
+        report.ok(&format!(
+            "input level peak {:.1} dBFS",
+            audiort::to_dbfs(stats.peak)
+        ));
+    }
+
+    if stats.dropouts > 0 {
+        report.warn(
+            &format!("{} dropouts in one second", stats.dropouts),
+            "close other audio applications or use a larger buffer",
+        );
+    }
+}
+
+fn silence_hint() -> &'static str {
+    if cfg!(target_os = "macos") {
+        "allow microphone access for your terminal in System Settings > Privacy & Security > Microphone"
+    } else if cfg!(target_os = "windows") {
+        "allow desktop apps to access the microphone in Settings > Privacy > Microphone"
+    } else {
+        "unmute the capture device, e.g. with pavucontrol or alsamixer"
+    }
+}
@@ -0,0 +1,137 @@
+use std::io::Read;
+use std::sync::mpsc;
+use std::sync::Arc;
+use std::sync::Mutex;
+
+/// Chunks buffered per client before it is considered too slow and skips audio.
+const CLIENT_BUFFER: usize = 64;
+
+/// Hands each chunk of audio to every connected client without ever blocking
+/// the sender.
+#[derive(Clone, Default)]
+pub struct FanOut {
+    clients: Arc<Mutex<Vec<Client>>>,
+}
+
+enum Client {
+    /// Skips chunks once `CLIENT_BUFFER` are waiting, like a listener
+    Lossy(mpsc::SyncSender<Arc<[u8]>>),
+    /// Queues every chunk however far behind it falls, like a file
+    Lossless(mpsc::Sender<Arc<[u8]>>),
+}
+
+impl FanOut {
+    pub fn subscribe(&self) -> mpsc::Receiver<Arc<[u8]>> {
+        let (tx, rx) = mpsc::sync_channel(CLIENT_BUFFER);
+        self.add(Client::Lossy(tx));
+        rx
+    }
+
+    /// Subscribe to every chunk, for something that can't skip audio, like a
+    /// file. Its queue has no bound, so it only suits a sender that isn't
+    /// the capture callback.
+    pub fn subscribe_all(&self) -> mpsc::Receiver<Arc<[u8]>> {
+        let (tx, rx) = mpsc::channel();
+        self.add(Client::Lossless(tx));
+        rx
+    }
+
+    fn add(&self, client: Client) {
+        if let Ok(mut clients) = self.clients.lock() {
+            clients.push(client);
+        }
+    }
+
+    /// Let every client go, ending their streams.
+    pub fn close(&self) {
+        if let Ok(mut clients) = self.clients.lock() {
+            clients.clear();
+        }
+    }
+
+    /// Encode and send a chunk, skipping the work when nobody is listening.
+    pub fn send<F>(&self, encode: F)
+    where
+        F: FnOnce() -> Arc<[u8]>,
+    {
+        let Ok(mut clients) = self.clients.lock() else {
+            return;
+        };
+
+        if clients.is_empty() {
+            return;
+        }
+
+        let chunk = encode();
+
+        // Slow lossy clients drop chunks rather than stall the capture callback
+        clients.retain(|client| match client {
+            Client::Lossy(client) => !matches!(
+                client.try_send(Arc::clone(&chunk)),
+                Err(mpsc::TrySendError::Disconnected(_))
+            ),
+            Client::Lossless(client) => client.send(Arc::clone(&chunk)).is_ok(),
+        });
+    }
+}
+
+/// A 16-bit PCM header with open-ended sizes, as the stream never ends.
+pub fn wav_header(sample_rate: u32, channels: u16) -> Vec<u8> {
+    let block_align = channels * 2;
+    let mut header = Vec::with_capacity(44);
+
+    header.extend_from_slice(b"RIFF");
+    header.extend_from_slice(&u32::MAX.to_le_bytes());
+    header.extend_from_slice(b"WAVEfmt ");
+    header.extend_from_slice(&16u32.to_le_bytes());
+    header.extend_from_slice(&1u16.to_le_bytes());
+    header.extend_from_slice(&channels.to_le_bytes());
+    header.extend_from_slice(&sample_rate.to_le_bytes());
+    header.extend_from_slice(&(sample_rate * u32::from(block_align)).to_le_bytes());
+    header.extend_from_slice(&block_align.to_le_bytes());
+    header.extend_from_slice(&16u16.to_le_bytes());
+    header.extend_from_slice(b"data");
+    header.extend_from_slice(&(u32::MAX - 36).to_le_bytes());
+
+    header
+}
+
+/// Reads a client's chunks as one stream, after `first`, until the
+/// `FanOut` lets it go.
+pub struct ChunkReader {
+    current: Arc<[u8]>,
+    offset: usize,
+    chunks: mpsc::Receiver<Arc<[u8]>>,
+}
+
+impl ChunkReader {
+    pub fn new(first: Vec<u8>, chunks: mpsc::Receiver<Arc<[u8]>>) -> ChunkReader {
+        ChunkReader {
+            current: first.into(),
+            offset: 0,
+            chunks,
+        }
+    }
+}
+
+impl Read for ChunkReader {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        while self.offset >= self.current.len() {
+            match self.chunks.recv() {
+                Ok(chunk) => {
+                    self.current = chunk;
+                    self.offset = 0;
+                }
+                Err(_) => return Ok(0),
+            }
+        }
+
+        let remaining = &self.current[self.offset..];
+        let len = remaining.len().min(buf.len());
+
+        buf[..len].copy_from_slice(&remaining[..len]);
+        self.offset += len;
+
+        Ok(len)
+    }
+}
@@ -0,0 +1,416 @@
+use crate::cli::devices;
+use crate::cli::record::warn_load;
+use crate::cli::record::CompressorOpts;
+use anyhow::Result;
+use audiort::effects::Band;
+use audiort::effects::Compressor;
+use audiort::effects::Effect;
+use audiort::effects::Equalizer;
+use audiort::effects::Gain;
+use audiort::effects::Gate;
+use audiort::effects::HighPass;
+use clap::Args;
+use std::sync::mpsc;
+use std::sync::mpsc::RecvTimeoutError;
+use std::sync::Arc;
+use std::sync::Mutex;
+use std::time::Duration;
+
+#[cfg(feature = "ladspa")]
+use crate::cli::record::parse_ladspa;
+#[cfg(feature = "ladspa")]
+use crate::cli::record::LadspaSpec;
+
+/// How often the callback load is checked between commands
+const LOAD_CHECK: Duration = Duration::from_secs(1);
+
+#[derive(Args)]
+pub struct FxOpts {
+    /// Device to play through: its name (any unique part of it, ignoring
+    /// case) or its index in `audiort devices` [default: the default input]
+    #[clap(long)]
+    device: Option<String>,
+    /// Device to play on, by name or index [default: the default output]
+    #[clap(long)]
+    output: Option<String>,
+    /// Output level in dB
+    #[clap(long, default_value = "0", allow_negative_numbers = true)]
+    gain: f32,
+    /// Filter out rumble below this frequency (Hz)
+    #[clap(long)]
+    highpass: Option<f32>,
+    /// Silence the input while it stays below this level (dBFS)
+    #[clap(long, allow_negative_numbers = true)]
+    gate: Option<f32>,
+    /// Equalize with bands of `kind:freq:gain[:q]`, e.g.
+    /// `peak:200:-3:1.0,highshelf:8000:+2`; kinds are peak, lowshelf and
+    /// highshelf
+    #[clap(long, value_delimiter = ',')]
+    eq: Vec<Band>,
+    #[clap(flatten)]
+    compressor: CompressorOpts,
+    /// Suppress background noise (RNNoise; needs 48 kHz)
+    #[cfg(feature = "denoise")]
+    #[clap(long)]
+    denoise: bool,
+    /// LV2 plugin to run, by URI; repeat to chain several
+    #[cfg(feature = "lv2")]
+    #[clap(long)]
+    lv2: Vec<String>,
+    /// LADSPA plugin to run, as `file.so:label[:control=value...]`; repeat
+    /// to chain several
+    #[cfg(feature = "ladspa")]
+    #[clap(long, value_parser = parse_ladspa)]
+    ladspa: Vec<LadspaSpec>,
+    /// Also take parameter changes as OSC messages on this UDP port, e.g.
+    /// `/audiort/fx/highpass 120`
+    #[clap(long)]
+    osc_port: Option<u16>,
+    /// For live monitoring: the smallest buffers the devices allow,
+    /// real-time priority, and no stages that hold audio back
+    #[clap(long)]
+    low_latency: bool,
+}
+
+/// The effects, in the order they run, with what can be changed while
+/// playing.
+struct Rack {
+    sample_rate: u32,
+    highpass: Option<HighPass>,
+    #[cfg(feature = "denoise")]
+    denoise: Option<audiort::denoise::Denoise>,
+    gate: Option<Gate>,
+    eq: Option<Equalizer>,
+    compressor: Option<Compressor>,
+    #[cfg(feature = "ladspa")]
+    ladspa: Vec<audiort::ladspa::Ladspa>,
+    #[cfg(feature = "lv2")]
+    lv2: Vec<audiort::lv2::Lv2>,
+    gain: Gain,
+}
+
+impl Rack {
+    /// Apply a command such as `highpass 120`, `gate off`,
+    /// `eq peak:200:-3,highshelf:8000:2`, `compress.ratio 6` or
+    /// `ladspa.1.gain 0.5`.
+    fn set(&mut self, name: &str, value: &str) -> Result<()> {
+        let off = value == "off";
+        let number = || {
+            value
+                .parse::<f32>()
+                .map_err(|_| anyhow::anyhow!("invalid value `{value}` for {name}"))
+        };
+
+        match name {
+            "gain" => self.gain = Gain(10f32.powf(number()? / 20.0)),
+            "highpass" if off => self.highpass = None,
+            "highpass" => self.highpass = Some(HighPass::new(number()?, self.sample_rate)),
+            "gate" if off => self.gate = None,
+            "gate" => self.gate = Some(Gate::new(number()?, self.sample_rate)),
+            "eq" if off => self.eq = None,
+            "eq" => self.eq = Some(Equalizer::new(&parse_bands(value)?, self.sample_rate)),
+            "compress" if off => self.compressor = None,
+            "compress" => match self.compressor.as_mut() {
+                Some(compressor) => _ = compressor.threshold(number()?),
+                None => self.compressor = Some(Compressor::new(number()?, 4.0, self.sample_rate)),
+            },
+            _ if name.starts_with("compress.") => self.set_compressor(name, number()?)?,
+            _ => return self.set_plugin(name, number()?),
+        }
+
+        Ok(())
+    }
+
+    fn set_compressor(&mut self, name: &str, value: f32) -> Result<()> {
+        let compressor = self.compressor.as_mut().ok_or_else(|| {
+            anyhow::anyhow!("the compressor is off; turn it on with `compress <dBFS>`")
+        })?;
+
+        match name {
+            "compress.ratio" => compressor.ratio(value),
+            "compress.attack" => compressor.attack(value),
+            "compress.release" => compressor.release(value),
+            "compress.makeup" => compressor.makeup(value),
+            _ => anyhow::bail!("unknown parameter `{name}`"),
+        };
+
+        Ok(())
+    }
+
+    /// `ladspa.N.control` or `lv2.N.symbol`, counting plugins of each kind
+    /// from 1. `N.` may be left out for the first.
+    #[cfg_attr(not(any(feature = "ladspa", feature = "lv2")), allow(unused_variables))]
+    fn set_plugin(&mut self, name: &str, value: f32) -> Result<()> {
+        let unknown = || anyhow::anyhow!("unknown parameter `{name}`");
+
+        let (kind, rest) = name.split_once('.').ok_or_else(unknown)?;
+
+        let (index, control) = match rest.split_once('.') {
+            Some((number, control)) if number.parse::<usize>().is_ok() => {
+                (number.parse::<usize>()?, control)
+            }
+            _ => (1, rest),
+        };
+
+        let index = index.checked_sub(1).ok_or_else(unknown)?;
+
+        match kind {
+            #[cfg(feature = "ladspa")]
+            "ladspa" => Ok(self
+                .ladspa
+                .get_mut(index)
+                .ok_or_else(unknown)?
+                .set(control, value)?),
+            #[cfg(feature = "lv2")]
+            "lv2" => Ok(self
+                .lv2
+                .get_mut(index)
+                .ok_or_else(unknown)?
+                .set(control, value)?),
+            _ => Err(unknown()),
+        }
+    }
+}
+
+fn parse_bands(value: &str) -> Result<Vec<Band>> {
+    value
+        .split(',')
+        .map(|band| band.parse().map_err(|err| anyhow::anyhow!("{err}")))
+        .collect()
+}
+
+impl Effect for Rack {
+    fn process(&mut self, frames: &mut [f32], channels: usize) {
+        if let Some(highpass) = self.highpass.as_mut() {
+            highpass.process(frames, channels);
+        }
+
+        #[cfg(feature = "denoise")]
+        if let Some(denoise) = self.denoise.as_mut() {
+            denoise.process(frames, channels);
+        }
+
+        if let Some(gate) = self.gate.as_mut() {
+            gate.process(frames, channels);
+        }
+
+        if let Some(eq) = self.eq.as_mut() {
+            eq.process(frames, channels);
+        }
+
+        if let Some(compressor) = self.compressor.as_mut() {
+            compressor.process(frames, channels);
+        }
+
+        #[cfg(feature = "ladspa")]
+        for plugin in self.ladspa.iter_mut() {
+            plugin.process(frames, channels);
+        }
+
+        #[cfg(feature = "lv2")]
+        for plugin in self.lv2.iter_mut() {
+            plugin.process(frames, channels);
+        }
+
+        self.gain.process(frames, channels);
+    }
+}
+
+pub fn run(options: FxOpts) -> Result<()> {
+    let input = match options.device.as_deref() {
+        Some(spec) => devices::open(audiort::Device::Input, spec)?,
+        None => audiort::DeviceBuilder::new_default_input()?,
+    };
+
+    let output = match options.output.as_deref() {
+        Some(spec) => devices::open(audiort::Device::Output, spec)?,
+        None => audiort::DeviceBuilder::new_default_output()?,
+    };
+
+    if let (Ok(input), Ok(output)) = (input.name(), output.name()) {
+        eprintln!("Playing {input} through to {output}");
+    }
+
+    let sample_rate = input.config().sample_rate().0;
+    let in_channels = usize::from(input.config().channels().max(1));
+    let channels = output.config().channels().max(1);
+
+    let mut rack = Rack {
+        sample_rate,
+        highpass: None,
+        #[cfg(feature = "denoise")]
+        denoise: None,
+        gate: None,
+        eq: None,
+        compressor: options.compressor.build(sample_rate),
+        #[cfg(feature = "ladspa")]
+        ladspa: Vec::new(),
+        #[cfg(feature = "lv2")]
+        lv2: Vec::new(),
+        gain: Gain(1.0),
+    };
+
+    rack.set("gain", &options.gain.to_string())?;
+
+    if let Some(cutoff) = options.highpass {
+        rack.set("highpass", &cutoff.to_string())?;
+    }
+
+    if let Some(threshold) = options.gate {
+        rack.set("gate", &threshold.to_string())?;
+    }
+
+    if !options.eq.is_empty() {
+        rack.eq = Some(Equalizer::new(&options.eq, sample_rate));
+    }
+
+    #[cfg(feature = "denoise")]
+    if options.denoise && options.low_latency {
+        // RNNoise works in 10 ms frames, which would be a buffer or more
+        eprintln!("Warning: --denoise holds audio back, so --low-latency leaves it out");
+    } else if options.denoise {
+        let denoise = audiort::denoise::Denoise::new(sample_rate).map_err(|_| {
+            anyhow::anyhow!("--denoise needs a 48000 Hz input, not {sample_rate} Hz")
+        })?;
+
+        rack.denoise = Some(denoise);
+    }
+
+    #[cfg(feature = "ladspa")]
+    for spec in &options.ladspa {
+        let mut plugin =
+            audiort::ladspa::Ladspa::new(&spec.path, &spec.label, sample_rate, channels)
+                .map_err(|err| anyhow::anyhow!("{err}: {}:{}", spec.path, spec.label))?;
+
+        for (control, value) in &spec.controls {
+            plugin
+                .set(control, *value)
+                .map_err(|err| anyhow::anyhow!("{err}: {control}"))?;
+        }
+
+        rack.ladspa.push(plugin);
+    }
+
+    #[cfg(feature = "lv2")]
+    for uri in &options.lv2 {
+        let plugin = audiort::lv2::Lv2::new(uri, sample_rate, channels)
+            .map_err(|err| anyhow::anyhow!("{err}: {uri}"))?;
+
+        rack.lv2.push(plugin);
+    }
+
+    let rack = Arc::new(Mutex::new(rack));
+    let processor = Arc::clone(&rack);
+    let out_channels = usize::from(channels);
+
+    let mut stream = audiort::StreamBuilder::new(input)?;
+
+    if options.low_latency {
+        stream.low_latency();
+    }
+
+    stream.with_processor(&output, move |input, output| {
+        let frames = input
+            .chunks(in_channels)
+            .zip(output.chunks_mut(out_channels));
+
+        for (captured, played) in frames {
+            for (channel, value) in played.iter_mut().enumerate() {
+                *value = captured[channel % in_channels];
+            }
+        }
+
+        if let Ok(mut rack) = processor.lock() {
+            rack.process(output, out_channels);
+        }
+    })?;
+
+    let (tx, commands) = mpsc::channel();
+
+    if let Some(port) = options.osc_port {
+        crate::cli::osc::params(port, tx.clone())?;
+    }
+
+    let stop = tx.clone();
+
+    ctrlc::set_handler(move || {
+        let _ = stop.send("q".to_owned());
+    })?;
+
+    std::thread::spawn(move || loop {
+        let mut line = String::new();
+
+        match std::io::stdin().read_line(&mut line) {
+            Ok(read) if read > 0 => {
+                if tx.send(line).is_err() {
+                    break;
+                }
+            }
+            _ => break,
+        }
+    });
+
+    stream.play()?;
+
+    if options.low_latency {
+        report_latency(&stream);
+    }
+
+    eprintln!("Change parameters with e.g. `gain -6`, `compress.ratio 6` or `ladspa.1.gain 0.5`");
+    eprintln!("`q` quits");
+
+    let mut overloaded = false;
+
+    loop {
+        let command = match commands.recv_timeout(LOAD_CHECK) {
+            Ok(command) => command,
+            Err(RecvTimeoutError::Timeout) => {
+                warn_load(&stream, &mut overloaded);
+                continue;
+            }
+            Err(RecvTimeoutError::Disconnected) => break,
+        };
+
+        let words: Vec<&str> = command
+            .split(|c: char| c == '=' || c.is_whitespace())
+            .filter(|word| !word.is_empty())
+            .collect();
+
+        match words.as_slice() {
+            [] => continue,
+            ["q"] => break,
+            [name, value] => {
+                let result = rack
+                    .lock()
+                    .map_err(|_| anyhow::anyhow!("the effects stopped"))
+                    .and_then(|mut rack| rack.set(name, value));
+
+                match result {
+                    Ok(()) => eprintln!("{name} = {value}"),
+                    Err(err) => eprintln!("Warning: {err}"),
+                }
+            }
+            _ => eprintln!("Warning: expected `name value`, not `{}`", command.trim()),
+        }
+    }
+
+    stream.stop();
+
+    Ok(())
+}
+
+/// Once audio has been flowing long enough for the queue to settle.
+fn report_latency(stream: &audiort::StreamBuilder) {
+    std::thread::sleep(Duration::from_millis(500));
+
+    match stream.realtime() {
+        Some(true) => eprintln!("Running with real-time priority"),
+        Some(false) => eprintln!("Warning: real-time priority was refused; audio may drop out"),
+        None => {}
+    }
+
+    match stream.latency() {
+        Some(latency) => eprintln!("Latency {:.1} ms", latency.as_secs_f64() * 1000.0),
+        None => eprintln!("Warning: the devices didn't say what buffer sizes they allow"),
+    }
+}
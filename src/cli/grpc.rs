@@ -0,0 +1,401 @@
+//! The gRPC service described by `proto/audiort.proto`, written out by hand
+//! so building doesn't need `protoc`.
+
+use crate::cli::daemon::Message;
+use crate::cli::fanout::FanOut;
+use anyhow::Result;
+use std::convert::Infallible;
+use std::future::Future;
+use std::net::SocketAddr;
+use std::pin::Pin;
+use std::sync::mpsc;
+use std::task::Context;
+use std::task::Poll;
+use std::time::Duration;
+use tokio_stream::wrappers::ReceiverStream;
+use tonic::body::BoxBody;
+use tonic::codec::ProstCodec;
+use tonic::codegen::http;
+use tonic::codegen::Body;
+use tonic::codegen::Service;
+use tonic::codegen::StdError;
+use tonic::server::Grpc;
+use tonic::server::NamedService;
+
+#[derive(Clone, PartialEq, prost::Message)]
+pub struct Empty {}
+
+#[derive(Clone, PartialEq, prost::Message)]
+pub struct StartRequest {
+    #[prost(string, tag = "1")]
+    pub path: String,
+}
+
+#[derive(Clone, PartialEq, prost::Message)]
+pub struct GainRequest {
+    #[prost(float, tag = "1")]
+    pub gain: f32,
+}
+
+#[derive(Clone, PartialEq, prost::Message)]
+pub struct DeviceRequest {
+    #[prost(string, tag = "1")]
+    pub listen: String,
+}
+
+#[derive(Clone, PartialEq, prost::Message)]
+pub struct LevelsRequest {
+    #[prost(uint32, tag = "1")]
+    pub interval_ms: u32,
+}
+
+#[derive(Clone, PartialEq, prost::Message)]
+pub struct Status {
+    #[prost(string, tag = "1")]
+    pub state: String,
+    #[prost(string, tag = "2")]
+    pub listen: String,
+    #[prost(float, tag = "3")]
+    pub gain: f32,
+    #[prost(string, tag = "4")]
+    pub error: String,
+    #[prost(string, tag = "5")]
+    pub device: String,
+    #[prost(string, tag = "6")]
+    pub path: String,
+    #[prost(string, tag = "7")]
+    pub started: String,
+    #[prost(uint64, tag = "8")]
+    pub frames: u64,
+    #[prost(double, tag = "9")]
+    pub duration: f64,
+    #[prost(float, tag = "10")]
+    pub peak_dbfs: f32,
+    #[prost(uint64, tag = "11")]
+    pub dropouts: u64,
+    #[prost(uint32, tag = "12")]
+    pub sample_rate: u32,
+    #[prost(uint32, tag = "13")]
+    pub channels: u32,
+}
+
+#[derive(Clone, PartialEq, prost::Message)]
+pub struct Levels {
+    #[prost(float, tag = "1")]
+    pub peak_dbfs: f32,
+    #[prost(float, tag = "2")]
+    pub rms_dbfs: f32,
+    #[prost(uint64, tag = "3")]
+    pub clipped: u64,
+}
+
+#[derive(Clone, PartialEq, prost::Message)]
+pub struct AudioChunk {
+    #[prost(uint32, tag = "1")]
+    pub sample_rate: u32,
+    #[prost(uint32, tag = "2")]
+    pub channels: u32,
+    #[prost(float, repeated, tag = "3")]
+    pub samples: Vec<f32>,
+}
+
+type Reply<T> = Result<tonic::Response<T>, tonic::Status>;
+type BoxFuture<T> = Pin<Box<dyn Future<Output = T> + Send + 'static>>;
+
+/// Serve the daemon's controls and live audio over gRPC.
+pub fn serve(addr: &str, tx: mpsc::Sender<Message>, audio: FanOut) -> Result<()> {
+    let addr: SocketAddr = addr.parse()?;
+
+    let runtime = tokio::runtime::Builder::new_multi_thread()
+        .enable_all()
+        .build()?;
+
+    eprintln!("gRPC API on {addr}");
+
+    std::thread::spawn(move || {
+        let service = Audiort { tx, audio };
+
+        let result = runtime.block_on(
+            tonic::transport::Server::builder()
+                .add_service(service)
+                .serve(addr),
+        );
+
+        if let Err(err) = result {
+            eprintln!("Warning: gRPC server stopped: {err}");
+        }
+    });
+
+    Ok(())
+}
+
+#[derive(Clone)]
+struct Audiort {
+    tx: mpsc::Sender<Message>,
+    audio: FanOut,
+}
+
+impl NamedService for Audiort {
+    const NAME: &'static str = "audiort.Audiort";
+}
+
+impl<B> Service<http::Request<B>> for Audiort
+where
+    B: Body + Send + 'static,
+    B::Error: Into<StdError> + Send + 'static,
+{
+    type Response = http::Response<BoxBody>;
+    type Error = Infallible;
+    type Future = BoxFuture<Result<Self::Response, Infallible>>;
+
+    fn poll_ready(&mut self, _: &mut Context<'_>) -> Poll<Result<(), Infallible>> {
+        Poll::Ready(Ok(()))
+    }
+
+    fn call(&mut self, request: http::Request<B>) -> Self::Future {
+        let service = self.clone();
+
+        Box::pin(async move {
+            let response = match request.uri().path() {
+                "/audiort.Audiort/Start" => {
+                    unary(
+                        request,
+                        move |start: StartRequest| match start.path.as_str() {
+                            "" => "start".to_owned(),
+                            path => format!("start {path}"),
+                        },
+                        &service,
+                    )
+                    .await
+                }
+                "/audiort.Audiort/Stop" => {
+                    unary(request, |_: Empty| "stop".to_owned(), &service).await
+                }
+                "/audiort.Audiort/Pause" => {
+                    unary(request, |_: Empty| "pause".to_owned(), &service).await
+                }
+                "/audiort.Audiort/Resume" => {
+                    unary(request, |_: Empty| "resume".to_owned(), &service).await
+                }
+                "/audiort.Audiort/GetStatus" => {
+                    unary(request, |_: Empty| "status".to_owned(), &service).await
+                }
+                "/audiort.Audiort/SetGain" => {
+                    unary(
+                        request,
+                        |gain: GainRequest| format!("gain {}", gain.gain),
+                        &service,
+                    )
+                    .await
+                }
+                "/audiort.Audiort/SetDevice" => {
+                    unary(
+                        request,
+                        |device: DeviceRequest| format!("device {}", device.listen),
+                        &service,
+                    )
+                    .await
+                }
+                "/audiort.Audiort/StreamLevels" => {
+                    let method = Handler(move |request: tonic::Request<LevelsRequest>| {
+                        let interval = match request.into_inner().interval_ms {
+                            0 => 100,
+                            ms => ms,
+                        };
+
+                        std::future::ready(Ok(
+                            service.levels(Duration::from_millis(interval.into()))
+                        ))
+                    });
+
+                    Grpc::new(ProstCodec::default())
+                        .server_streaming(method, request)
+                        .await
+                }
+                "/audiort.Audiort/StreamAudio" => {
+                    let method = Handler(move |_: tonic::Request<Empty>| {
+                        std::future::ready(Ok(service.audio()))
+                    });
+
+                    Grpc::new(ProstCodec::default())
+                        .server_streaming(method, request)
+                        .await
+                }
+                _ => tonic::Status::unimplemented("unknown method").into_http(),
+            };
+
+            Ok(response)
+        })
+    }
+}
+
+/// Run a control RPC as a daemon command and reply with the daemon's status.
+async fn unary<B, R, F>(
+    request: http::Request<B>,
+    command: F,
+    service: &Audiort,
+) -> http::Response<BoxBody>
+where
+    B: Body + Send + 'static,
+    B::Error: Into<StdError> + Send + 'static,
+    R: prost::Message + Default + Send + 'static,
+    F: Fn(R) -> String + Send + 'static,
+{
+    let tx = service.tx.clone();
+
+    let method = Handler(move |request: tonic::Request<R>| {
+        let command = command(request.into_inner());
+        let tx = tx.clone();
+
+        async move {
+            tokio::task::spawn_blocking(move || send(&tx, &command))
+                .await
+                .map_err(|err| tonic::Status::internal(err.to_string()))?
+                .map(|reply| tonic::Response::new(status(&reply)))
+                .map_err(|err| *err)
+        }
+    });
+
+    Grpc::new(ProstCodec::default())
+        .unary(method, request)
+        .await
+}
+
+impl Audiort {
+    fn levels(
+        &self,
+        interval: Duration,
+    ) -> tonic::Response<ReceiverStream<Result<Levels, tonic::Status>>> {
+        let (stream, rx) = tokio::sync::mpsc::channel(16);
+        let tx = self.tx.clone();
+
+        std::thread::spawn(move || {
+            while !stream.is_closed() {
+                // Nothing is sent while idle
+                if let Ok(reply) = send(&tx, "levels") {
+                    let levels = Levels {
+                        peak_dbfs: reply["peak_dbfs"].as_f64().unwrap_or(f64::NEG_INFINITY) as f32,
+                        rms_dbfs: reply["rms_dbfs"].as_f64().unwrap_or(f64::NEG_INFINITY) as f32,
+                        clipped: reply["clipped"].as_u64().unwrap_or_default(),
+                    };
+
+                    if stream.blocking_send(Ok(levels)).is_err() {
+                        break;
+                    }
+                }
+
+                std::thread::sleep(interval);
+            }
+        });
+
+        tonic::Response::new(ReceiverStream::new(rx))
+    }
+
+    fn audio(&self) -> tonic::Response<ReceiverStream<Result<AudioChunk, tonic::Status>>> {
+        let (stream, rx) = tokio::sync::mpsc::channel(64);
+        let tx = self.tx.clone();
+        let chunks = self.audio.subscribe();
+
+        std::thread::spawn(move || {
+            let mut format = None;
+
+            loop {
+                match chunks.recv_timeout(Duration::from_secs(1)) {
+                    Ok(chunk) => {
+                        // The device may change between recordings
+                        let (sample_rate, channels) = *format.get_or_insert_with(|| {
+                            let status = send(&tx, "status").unwrap_or_default();
+
+                            (
+                                status["sample_rate"].as_u64().unwrap_or_default() as u32,
+                                status["channels"].as_u64().unwrap_or_default() as u32,
+                            )
+                        });
+
+                        let samples = chunk
+                            .chunks_exact(4)
+                            .map(|b| f32::from_le_bytes([b[0], b[1], b[2], b[3]]))
+                            .collect();
+
+                        let chunk = AudioChunk {
+                            sample_rate,
+                            channels,
+                            samples,
+                        };
+
+                        if stream.blocking_send(Ok(chunk)).is_err() {
+                            break;
+                        }
+                    }
+                    Err(mpsc::RecvTimeoutError::Timeout) if !stream.is_closed() => format = None,
+                    Err(_) => break,
+                }
+            }
+        });
+
+        tonic::Response::new(ReceiverStream::new(rx))
+    }
+}
+
+/// Send a command to the daemon, turning `"ok": false` replies into errors.
+fn send(
+    tx: &mpsc::Sender<Message>,
+    command: &str,
+) -> Result<serde_json::Value, Box<tonic::Status>> {
+    let (reply_tx, reply_rx) = mpsc::channel();
+
+    let reply = tx
+        .send(Message::Request(command.to_owned(), reply_tx))
+        .ok()
+        .and_then(|_| reply_rx.recv().ok())
+        .ok_or_else(|| Box::new(tonic::Status::unavailable("daemon is shutting down")))?;
+
+    if reply["ok"] != true {
+        let error = reply["error"].as_str().unwrap_or("request failed");
+        return Err(Box::new(tonic::Status::failed_precondition(error)));
+    }
+
+    Ok(reply)
+}
+
+fn status(reply: &serde_json::Value) -> Status {
+    let string = |key: &str| reply[key].as_str().unwrap_or_default().to_owned();
+    let number = |key: &str| reply[key].as_u64().unwrap_or_default();
+
+    Status {
+        state: string("state"),
+        listen: string("listen"),
+        gain: reply["gain"].as_f64().unwrap_or(1.0) as f32,
+        error: string("error"),
+        device: string("device"),
+        path: string("path"),
+        started: string("started"),
+        frames: number("frames"),
+        duration: reply["duration"].as_f64().unwrap_or_default(),
+        peak_dbfs: reply["peak_dbfs"].as_f64().unwrap_or(f64::NEG_INFINITY) as f32,
+        dropouts: number("dropouts"),
+        sample_rate: number("sample_rate") as u32,
+        channels: number("channels") as u32,
+    }
+}
+
+/// Adapts a closure to the `Service` shape tonic's method handlers expect.
+struct Handler<F>(F);
+
+impl<F, R, Fut, T> Service<tonic::Request<R>> for Handler<F>
+where
+    F: FnMut(tonic::Request<R>) -> Fut,
+    Fut: Future<Output = Reply<T>>,
+{
+    type Response = tonic::Response<T>;
+    type Error = tonic::Status;
+    type Future = Fut;
+
+    fn poll_ready(&mut self, _: &mut Context<'_>) -> Poll<Result<(), tonic::Status>> {
+        Poll::Ready(Ok(()))
+    }
+
+    fn call(&mut self, request: tonic::Request<R>) -> Fut {
+        (self.0)(request)
+    }
+}
@@ -0,0 +1,169 @@
+use crate::cli::daemon::Message;
+use anyhow::Result;
+use serde_json::json;
+use std::sync::mpsc;
+use tiny_http::Header;
+use tiny_http::Method;
+use tiny_http::Request;
+use tiny_http::Response;
+
+/// Serve the daemon's controls as a small JSON HTTP API.
+pub fn serve(addr: &str, tx: mpsc::Sender<Message>) -> Result<()> {
+    let server = tiny_http::Server::http(addr).map_err(|err| anyhow::anyhow!(err))?;
+    eprintln!("HTTP API on http://{addr}");
+
+    std::thread::spawn(move || {
+        for request in server.incoming_requests() {
+            let tx = tx.clone();
+            std::thread::spawn(move || handle(request, &tx));
+        }
+    });
+
+    Ok(())
+}
+
+fn handle(mut request: Request, tx: &mpsc::Sender<Message>) {
+    let path = request
+        .url()
+        .split('?')
+        .next()
+        .unwrap_or_default()
+        .to_owned();
+
+    let command = match (request.method(), path.as_str()) {
+        (Method::Post, "/record/start") => match body(&mut request) {
+            Ok(body) => match body["path"].as_str() {
+                Some(path) => format!("start {path}"),
+                None => "start".to_owned(),
+            },
+            Err(err) => return respond(request, 400, json!({ "ok": false, "error": err })),
+        },
+        (Method::Post, "/record/stop") => "stop".to_owned(),
+        (Method::Post, "/record/pause") => "pause".to_owned(),
+        (Method::Post, "/record/resume") => "resume".to_owned(),
+        (Method::Post, "/device") => match body(&mut request) {
+            Ok(body) => format!("device {}", body["listen"].as_str().unwrap_or_default()),
+            Err(err) => return respond(request, 400, json!({ "ok": false, "error": err })),
+        },
+        (Method::Get, "/status") => "status".to_owned(),
+        (Method::Get, "/levels") => "levels".to_owned(),
+        (Method::Get, "/metrics") => return metrics(request, tx),
+        _ => return respond(request, 404, json!({ "ok": false, "error": "not found" })),
+    };
+
+    let (reply_tx, reply_rx) = mpsc::channel();
+
+    let reply = match tx.send(Message::Request(command, reply_tx)) {
+        Ok(()) => reply_rx.recv().ok(),
+        Err(_) => None,
+    }
+    .unwrap_or_else(|| json!({ "ok": false, "error": "daemon is shutting down" }));
+
+    let status = if reply["ok"] == true { 200 } else { 400 };
+
+    respond(request, status, reply);
+}
+
+fn metrics(request: Request, tx: &mpsc::Sender<Message>) {
+    let (reply_tx, reply_rx) = mpsc::channel();
+
+    let Some(metrics) = tx
+        .send(Message::Request("metrics".to_owned(), reply_tx))
+        .ok()
+        .and_then(|_| reply_rx.recv().ok())
+    else {
+        let _ = request.respond(Response::empty(503));
+        return;
+    };
+
+    let value = |key: &str| metrics[key].as_f64().unwrap_or_default();
+    let dbfs = |key: &str| match audiort::to_dbfs(value(key) as f32) {
+        db if db == f32::NEG_INFINITY => "-Inf".to_owned(),
+        db => db.to_string(),
+    };
+
+    let mut body = String::new();
+
+    for (name, kind, help, value) in [
+        (
+            "audiort_frames_captured_total",
+            "counter",
+            "Frames written to disk",
+            value("frames_total").to_string(),
+        ),
+        (
+            "audiort_bytes_written_total",
+            "counter",
+            "Audio data bytes written to disk",
+            value("bytes_total").to_string(),
+        ),
+        (
+            "audiort_dropouts_total",
+            "counter",
+            "Detected gaps in the capture stream",
+            value("dropouts_total").to_string(),
+        ),
+        (
+            "audiort_clipped_samples_total",
+            "counter",
+            "Samples at or above full scale",
+            value("clipped_total").to_string(),
+        ),
+        (
+            "audiort_recording",
+            "gauge",
+            "Whether a recording is in progress",
+            u8::from(metrics["recording"] == true).to_string(),
+        ),
+        (
+            "audiort_recording_duration_seconds",
+            "gauge",
+            "Length of the current recording",
+            value("duration").to_string(),
+        ),
+        (
+            "audiort_level_peak_dbfs",
+            "gauge",
+            "Peak level of the latest buffer",
+            dbfs("peak"),
+        ),
+        (
+            "audiort_level_rms_dbfs",
+            "gauge",
+            "RMS level of the latest buffer",
+            dbfs("rms"),
+        ),
+    ] {
+        body.push_str(&format!(
+            "# HELP {name} {help}.\n# TYPE {name} {kind}\n{name} {value}\n"
+        ));
+    }
+
+    let response = Response::from_string(body)
+        .with_header(Header::from_bytes("Content-Type", "text/plain; version=0.0.4").unwrap());
+
+    let _ = request.respond(response);
+}
+
+fn body(request: &mut Request) -> Result<serde_json::Value, String> {
+    let mut body = String::new();
+
+    request
+        .as_reader()
+        .read_to_string(&mut body)
+        .map_err(|err| err.to_string())?;
+
+    if body.trim().is_empty() {
+        return Ok(json!({}));
+    }
+
+    serde_json::from_str(&body).map_err(|err| err.to_string())
+}
+
+fn respond(request: Request, status: u16, body: serde_json::Value) {
+    let response = Response::from_string(body.to_string())
+        .with_status_code(status)
+        .with_header(Header::from_bytes("Content-Type", "application/json").unwrap());
+
+    let _ = request.respond(response);
+}
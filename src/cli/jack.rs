@@ -0,0 +1,31 @@
+use anyhow::Result;
+use std::process::Command;
+use std::process::Stdio;
+
+/// Connect the ports of the JACK client `client` in turn to `ports` with
+/// `jack_connect`: what each captures from, or plays to.
+pub fn connect(kind: audiort::Device, client: &str, ports: &[String]) -> Result<()> {
+    for (index, port) in ports.iter().enumerate() {
+        let own = match kind {
+            audiort::Device::Input => format!("{client}:in_{}", index + 1),
+            audiort::Device::Output => format!("{client}:out_{}", index + 1),
+        };
+
+        let (from, to) = match kind {
+            audiort::Device::Input => (port.as_str(), own.as_str()),
+            audiort::Device::Output => (own.as_str(), port.as_str()),
+        };
+
+        let status = Command::new("jack_connect")
+            .args([from, to])
+            .stdout(Stdio::null())
+            .status()
+            .map_err(|err| anyhow::anyhow!("{err}: jack_connect"))?;
+
+        if !status.success() {
+            eprintln!("Warning: couldn't connect {from} to {to}");
+        }
+    }
+
+    Ok(())
+}
@@ -0,0 +1,151 @@
+//! Single keys from the terminal, as they are pressed rather than a line at
+//! a time. Terminals only send repeats while a key is held, not when it is
+//! let go, so holding is followed by how recently it last came in.
+
+use anyhow::Result;
+use std::io::Read;
+use std::sync::atomic::AtomicBool;
+use std::sync::atomic::Ordering;
+use std::sync::Arc;
+use std::time::Duration;
+use std::time::Instant;
+
+/// How long after a key last came in it counts as let go. It has to outlast
+/// the delay before a held key starts repeating, which is usually 250-600ms.
+pub const RELEASE: Duration = Duration::from_millis(650);
+
+/// Keeps the terminal sending keys unechoed as they're pressed, until it is
+/// dropped. Ctrl-C still interrupts.
+#[cfg(unix)]
+pub struct RawTerminal {
+    original: libc::termios,
+}
+
+#[cfg(unix)]
+impl RawTerminal {
+    pub fn new() -> Result<RawTerminal> {
+        // SAFETY: `termios` is plain data, zeroed then filled in by
+        // `tcgetattr`, which only writes to it
+        let (result, original) = unsafe {
+            let mut original: libc::termios = std::mem::zeroed();
+            (libc::tcgetattr(libc::STDIN_FILENO, &mut original), original)
+        };
+
+        if result != 0 {
+            anyhow::bail!("keys can only be read from a terminal");
+        }
+
+        let mut raw = original;
+        raw.c_lflag &= !(libc::ICANON | libc::ECHO);
+        // Reads give up after a tenth of a second, to notice a key let go
+        raw.c_cc[libc::VMIN] = 0;
+        raw.c_cc[libc::VTIME] = 1;
+
+        // SAFETY: `raw` is a copy of the settings just read, changed above
+        if unsafe { libc::tcsetattr(libc::STDIN_FILENO, libc::TCSANOW, &raw) } != 0 {
+            anyhow::bail!(
+                "failed to set up the terminal: {}",
+                std::io::Error::last_os_error()
+            );
+        }
+
+        Ok(RawTerminal { original })
+    }
+}
+
+#[cfg(unix)]
+impl Drop for RawTerminal {
+    fn drop(&mut self) {
+        // SAFETY: restores the settings read in `new`
+        unsafe { libc::tcsetattr(libc::STDIN_FILENO, libc::TCSANOW, &self.original) };
+    }
+}
+
+#[cfg(not(unix))]
+pub struct RawTerminal;
+
+#[cfg(not(unix))]
+impl RawTerminal {
+    pub fn new() -> Result<RawTerminal> {
+        anyhow::bail!("keys can only be read from a terminal on Unix")
+    }
+}
+
+/// Read keys from a `RawTerminal` on a new thread, keeping `held` set while
+/// `key` is held down and handing any other to `on_key`, until it returns
+/// false.
+pub fn follow<F>(key: u8, held: Arc<AtomicBool>, mut on_key: F)
+where
+    F: FnMut(u8) -> bool + Send + 'static,
+{
+    std::thread::spawn(move || {
+        let mut stdin = std::io::stdin();
+        let mut last: Option<Instant> = None;
+
+        loop {
+            let mut byte = [0u8; 1];
+
+            match stdin.read(&mut byte) {
+                Ok(1) if byte[0] == key => last = Some(Instant::now()),
+                Ok(1) => {
+                    if !on_key(byte[0]) {
+                        break;
+                    }
+                }
+                // Timed out with nothing pressed
+                Ok(_) => {}
+                Err(_) => break,
+            }
+
+            let holding = last.is_some_and(|last| last.elapsed() < RELEASE);
+            held.store(holding, Ordering::Relaxed);
+        }
+
+        held.store(false, Ordering::Relaxed);
+    });
+}
+
+/// Read keys from a `RawTerminal` on a new thread, handing each to `on_key`,
+/// until it returns false.
+pub fn each<F>(mut on_key: F)
+where
+    F: FnMut(u8) -> bool + Send + 'static,
+{
+    std::thread::spawn(move || {
+        let mut stdin = std::io::stdin();
+
+        loop {
+            let mut byte = [0u8; 1];
+
+            match stdin.read(&mut byte) {
+                Ok(1) => {
+                    if !on_key(byte[0]) {
+                        break;
+                    }
+                }
+                // Timed out with nothing pressed
+                Ok(_) => {}
+                Err(_) => break,
+            }
+        }
+    });
+}
+
+/// A key as given on the command line: a single character or `space`.
+pub fn parse_key(s: &str) -> Result<u8, String> {
+    match s {
+        "space" => Ok(b' '),
+        _ if s.len() == 1 && s.is_ascii() => Ok(s.as_bytes()[0]),
+        _ => Err(format!(
+            "invalid key `{s}`, expected a character or `space`"
+        )),
+    }
+}
+
+/// How a key from `parse_key` is shown.
+pub fn key_name(key: u8) -> String {
+    match key {
+        b' ' => "space".to_owned(),
+        _ => char::from(key).to_string(),
+    }
+}
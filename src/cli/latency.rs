@@ -0,0 +1,142 @@
+use anyhow::Result;
+use audiort::generator::Generator;
+use audiort::generator::Wave;
+use audiort::playback::Player;
+use clap::Args;
+use std::sync::Arc;
+use std::sync::Mutex;
+use std::time::Duration;
+use std::time::Instant;
+
+#[derive(Args)]
+pub struct LatencyOpts {
+    /// Number of clicks to measure
+    #[clap(short, long, default_value = "5")]
+    trials: usize,
+    /// Level of the click in dBFS
+    #[clap(long, default_value = "-6", allow_negative_numbers = true)]
+    level: f32,
+    /// Input level in dBFS that counts as hearing the click
+    #[clap(long, default_value = "-30", allow_negative_numbers = true)]
+    threshold: f32,
+}
+
+/// When the click was first heard, once armed.
+#[derive(Default)]
+struct Detector {
+    armed: bool,
+    heard: Option<Instant>,
+}
+
+pub fn run(options: LatencyOpts) -> Result<()> {
+    let output = audiort::DeviceBuilder::new_default_output()?;
+    let input = audiort::DeviceBuilder::new_default_input()?;
+
+    if let (Ok(output), Ok(input)) = (output.name(), input.name()) {
+        eprintln!("Playing to {output}, listening to {input}");
+    }
+
+    let out_rate = output.config().sample_rate().0;
+    let out_channels = output.config().channels();
+    let in_rate = f64::from(input.config().sample_rate().0);
+    let in_channels = usize::from(input.config().channels().max(1));
+
+    let player = Player::new(&output, out_rate, out_channels)?;
+    let mut stream = audiort::StreamBuilder::new(input)?;
+
+    let detector = Arc::new(Mutex::new(Detector::default()));
+    let listener = Arc::clone(&detector);
+    let threshold = 10f32.powf(options.threshold / 20.0);
+
+    stream.read(move |data| {
+        let now = Instant::now();
+
+        let Ok(mut detector) = listener.lock() else {
+            return;
+        };
+
+        if !detector.armed {
+            return;
+        }
+
+        if let Some(index) = data.iter().position(|value| value.abs() >= threshold) {
+            // The callback arrives once the whole buffer has been captured
+            let frames_after = (data.len() - index) / in_channels;
+            let behind = Duration::from_secs_f64(frames_after as f64 / in_rate);
+
+            detector.heard = Some(now.checked_sub(behind).unwrap_or(now));
+            detector.armed = false;
+        }
+    })?;
+
+    // 5ms burst at 1kHz
+    let mut click = vec![0.0; out_rate as usize / 200 * usize::from(out_channels)];
+    Generator::new(Wave::Sine, out_rate)
+        .level(options.level)
+        .fill(&mut click, out_channels);
+
+    player.play()?;
+    stream.play()?;
+
+    // Let both devices settle
+    std::thread::sleep(Duration::from_millis(500));
+
+    let mut results = Vec::with_capacity(options.trials);
+
+    for trial in 1..=options.trials {
+        if let Ok(mut detector) = detector.lock() {
+            detector.armed = true;
+            detector.heard = None;
+        }
+
+        let sent = Instant::now();
+        player.push(&click);
+
+        let deadline = sent + Duration::from_secs(2);
+        let mut heard = None;
+
+        while heard.is_none() && Instant::now() < deadline {
+            std::thread::sleep(Duration::from_millis(5));
+            heard = detector.lock().ok().and_then(|detector| detector.heard);
+        }
+
+        match heard {
+            Some(heard) => {
+                let latency = heard.saturating_duration_since(sent).as_secs_f64() * 1000.0;
+                println!("Trial {trial}: {latency:.1} ms");
+                results.push(latency);
+            }
+            None => println!("Trial {trial}: no click heard"),
+        }
+
+        if let Ok(mut detector) = detector.lock() {
+            detector.armed = false;
+        }
+
+        // Let echoes die down
+        std::thread::sleep(Duration::from_millis(500));
+    }
+
+    stream.stop();
+
+    if results.is_empty() {
+        anyhow::bail!(
+            "the click was never heard; check routing or lower --threshold (now {} dBFS)",
+            options.threshold
+        );
+    }
+
+    let count = results.len() as f64;
+    let mean = results.iter().sum::<f64>() / count;
+    let min = results.iter().copied().fold(f64::INFINITY, f64::min);
+    let max = results.iter().copied().fold(f64::NEG_INFINITY, f64::max);
+    let stddev = (results.iter().map(|r| (r - mean).powi(2)).sum::<f64>() / count).sqrt();
+
+    println!(
+        "Round trip: mean {mean:.1} ms, min {min:.1} ms, max {max:.1} ms, stddev {stddev:.1} ms ({} of {} trials)",
+        results.len(),
+        options.trials
+    );
+
+    Ok(())
+}
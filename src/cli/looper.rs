@@ -0,0 +1,258 @@
+use anyhow::Result;
+use audiort::effects::Effect;
+use audiort::generator::Metronome;
+use clap::Args;
+use std::sync::mpsc;
+use std::sync::Arc;
+use std::sync::Mutex;
+
+#[derive(Args)]
+pub struct LoopOpts {
+    /// Loop length in seconds [default: 4 bars at --bpm]
+    #[clap(short, long)]
+    length: Option<f64>,
+    /// Tempo, used for the default length and the click
+    #[clap(long, default_value = "120")]
+    bpm: f64,
+    /// Beats per bar
+    #[clap(long, default_value = "4")]
+    beats: u32,
+    /// Play a metronome along with the loop; it isn't recorded
+    #[clap(long)]
+    click: bool,
+    /// Hear the input live as well as the loop
+    #[clap(long)]
+    monitor: bool,
+    /// Shift layers earlier by this many milliseconds, to make up for the
+    /// round trip from output to input (see `audiort latency`)
+    #[clap(long, default_value = "0")]
+    offset: f64,
+    /// Write the final mix to this file on quitting
+    #[clap(short, long)]
+    output: Option<String>,
+}
+
+/// The loop's layers, mixed as they play.
+struct Looper {
+    frames: usize,
+    channels: usize,
+    layers: Vec<Vec<f32>>,
+    /// Layer being recorded and the frames it has left
+    recording: Option<(Vec<f32>, usize)>,
+    position: usize,
+    /// Frames recorded input lands before the position it's captured at
+    offset: usize,
+    monitor: bool,
+}
+
+impl Looper {
+    fn process(&mut self, input: &[f32], in_channels: usize, output: &mut [f32]) {
+        let frames = input
+            .chunks(in_channels)
+            .zip(output.chunks_mut(self.channels));
+
+        for (captured, played) in frames {
+            for (channel, value) in played.iter_mut().enumerate() {
+                *value = self
+                    .layers
+                    .iter()
+                    .map(|layer| layer[self.position * self.channels + channel])
+                    .sum();
+
+                if self.monitor {
+                    *value += captured[channel % in_channels];
+                }
+            }
+
+            let done = match &mut self.recording {
+                Some((layer, left)) => {
+                    let at = (self.position + self.frames - self.offset) % self.frames;
+
+                    for (channel, value) in layer[at * self.channels..][..self.channels]
+                        .iter_mut()
+                        .enumerate()
+                    {
+                        *value = captured[channel % in_channels];
+                    }
+
+                    *left -= 1;
+                    *left == 0
+                }
+                None => false,
+            };
+
+            // A layer is at most one pass, so it never covers itself
+            if done {
+                self.stop_recording();
+            }
+
+            self.position = (self.position + 1) % self.frames;
+        }
+    }
+
+    fn start_recording(&mut self) {
+        self.recording = Some((vec![0.0; self.frames * self.channels], self.frames));
+    }
+
+    fn stop_recording(&mut self) {
+        if let Some((layer, _)) = self.recording.take() {
+            self.layers.push(layer);
+        }
+    }
+
+    fn mix(&self) -> Vec<f32> {
+        let mut mix = vec![0.0; self.frames * self.channels];
+
+        for layer in &self.layers {
+            for (value, sample) in mix.iter_mut().zip(layer) {
+                *value += sample;
+            }
+        }
+
+        mix
+    }
+}
+
+enum Event {
+    Line(String),
+    Stop,
+}
+
+pub fn run(options: LoopOpts) -> Result<()> {
+    if options.bpm <= 0.0 {
+        anyhow::bail!("--bpm must be above 0");
+    }
+
+    let input = audiort::DeviceBuilder::new_default_input()?;
+    let output = audiort::DeviceBuilder::new_default_output()?;
+
+    if let (Ok(input), Ok(output)) = (input.name(), output.name()) {
+        eprintln!("Looping {input} to {output}");
+    }
+
+    let sample_rate = input.config().sample_rate().0;
+    let in_channels = usize::from(input.config().channels().max(1));
+    let channels = usize::from(output.config().channels().max(1));
+
+    let seconds = options
+        .length
+        .unwrap_or(4.0 * f64::from(options.beats) * 60.0 / options.bpm);
+    let frames = (seconds * f64::from(sample_rate)) as usize;
+
+    if frames == 0 {
+        anyhow::bail!("--length is too short");
+    }
+
+    let offset = (options.offset / 1000.0 * f64::from(sample_rate)) as usize % frames;
+
+    let looper = Arc::new(Mutex::new(Looper {
+        frames,
+        channels,
+        layers: Vec::new(),
+        recording: None,
+        position: 0,
+        offset,
+        monitor: options.monitor,
+    }));
+
+    let player = Arc::clone(&looper);
+    let mut metronome = options.click.then(|| {
+        let mut metronome = Metronome::new(options.bpm, sample_rate);
+        metronome.beats(options.beats).level(-12.0);
+        metronome
+    });
+
+    let mut stream = audiort::StreamBuilder::new(input)?;
+
+    stream.with_processor(&output, move |input, output| {
+        if let Ok(mut looper) = player.lock() {
+            looper.process(input, in_channels, output);
+        }
+
+        if let Some(metronome) = metronome.as_mut() {
+            metronome.process(output, channels);
+        }
+    })?;
+
+    let (tx, events) = mpsc::channel();
+    let interrupt = tx.clone();
+
+    ctrlc::set_handler(move || {
+        let _ = interrupt.send(Event::Stop);
+    })?;
+
+    std::thread::spawn(move || loop {
+        let mut line = String::new();
+
+        match std::io::stdin().read_line(&mut line) {
+            Ok(read) if read > 0 => {
+                if tx.send(Event::Line(line)).is_err() {
+                    break;
+                }
+            }
+            _ => break,
+        }
+    });
+
+    stream.play()?;
+
+    eprintln!("{seconds:.2}s loop");
+    eprintln!("Enter starts or stops a layer, `u` undoes one, `c` clears, `q` quits");
+
+    for event in events {
+        let Ok(mut looper) = looper.lock() else {
+            break;
+        };
+
+        match event {
+            Event::Line(line) => match line.trim() {
+                "" if looper.recording.is_some() => looper.stop_recording(),
+                "" => looper.start_recording(),
+                // Throw away the layer being recorded, or else the last one
+                "u" if looper.recording.is_some() => looper.recording = None,
+                "u" => {
+                    looper.layers.pop();
+                }
+                "c" => {
+                    looper.recording = None;
+                    looper.layers.clear();
+                }
+                "q" => break,
+                other => eprintln!("Unknown command `{other}`"),
+            },
+            Event::Stop => break,
+        }
+
+        let state = match looper.recording {
+            Some(_) => "recording",
+            None => "playing",
+        };
+
+        eprintln!("{} layers, {state}", looper.layers.len());
+    }
+
+    stream.stop();
+
+    if let Some(path) = &options.output {
+        let mix = looper.lock().map(|looper| looper.mix()).unwrap_or_default();
+
+        let spec = hound::WavSpec {
+            channels: channels as u16,
+            sample_rate,
+            bits_per_sample: 32,
+            sample_format: hound::SampleFormat::Float,
+        };
+
+        let mut writer = hound::WavWriter::create(path, spec)?;
+
+        for value in mix {
+            writer.write_sample(value)?;
+        }
+
+        writer.finalize()?;
+
+        eprintln!("Loop written to {path}");
+    }
+
+    Ok(())
+}
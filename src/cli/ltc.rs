@@ -0,0 +1,88 @@
+use anyhow::Result;
+use audiort::ltc::Encoder;
+use audiort::ltc::Rate;
+use audiort::ltc::Timecode;
+use audiort::playback::Player;
+use clap::Args;
+use std::sync::atomic::AtomicBool;
+use std::sync::atomic::Ordering;
+use std::sync::Arc;
+use std::time::Duration;
+use std::time::Instant;
+use std::time::SystemTime;
+use std::time::UNIX_EPOCH;
+
+#[derive(Args)]
+pub struct LtcOpts {
+    /// Frame rate: 24, 25, 29.97df or 30
+    #[clap(long, default_value = "25")]
+    rate: Rate,
+    /// Timecode to count from, `HH:MM:SS:FF` [default: the time of day, UTC]
+    #[clap(long)]
+    start: Option<String>,
+    /// Output channel to play it on, from 1; the others are left silent
+    #[clap(long, default_value = "1")]
+    channel: usize,
+    /// Peak level in dBFS
+    #[clap(long, default_value = "-18", allow_negative_numbers = true)]
+    level: f32,
+    /// Seconds to play for [default: until interrupted]
+    #[clap(short, long)]
+    duration: Option<f64>,
+}
+
+pub fn run(options: LtcOpts) -> Result<()> {
+    if options.level > 0.0 {
+        anyhow::bail!("--level must be at most 0 dBFS");
+    }
+
+    let start = match &options.start {
+        Some(start) => Timecode::parse(start, options.rate)
+            .ok_or_else(|| anyhow::anyhow!("invalid timecode `{start}`, expected HH:MM:SS:FF"))?,
+        None => {
+            let now = SystemTime::now().duration_since(UNIX_EPOCH)?;
+            Timecode::from_seconds(now.as_secs_f64() % 86_400.0, options.rate)
+        }
+    };
+
+    let device = audiort::DeviceBuilder::new_default_output()?;
+    let channels = device.config().channels();
+
+    if options.channel == 0 || options.channel > usize::from(channels) {
+        anyhow::bail!("--channel must be from 1 to {channels}");
+    }
+
+    if let Ok(name) = device.name() {
+        eprintln!(
+            "Playing LTC at {} fps from {start} on channel {} of {name}",
+            options.rate.name(),
+            options.channel
+        );
+    }
+
+    let sample_rate = device.config().sample_rate().0;
+    let player = Player::new(&device, sample_rate, channels)?;
+
+    let mut encoder = Encoder::new(start, sample_rate);
+    encoder.channel(options.channel - 1).level(options.level);
+
+    // Nothing is queued, so the player only plays the timecode
+    player.effect(encoder);
+    player.play()?;
+
+    let interrupted = Arc::new(AtomicBool::new(false));
+    let interrupt = Arc::clone(&interrupted);
+
+    ctrlc::set_handler(move || interrupt.store(true, Ordering::Relaxed))?;
+
+    let started = Instant::now();
+    let duration = options.duration.map(Duration::from_secs_f64);
+
+    while !interrupted.load(Ordering::Relaxed)
+        && duration.is_none_or(|duration| started.elapsed() < duration)
+    {
+        std::thread::sleep(Duration::from_millis(10));
+    }
+
+    Ok(())
+}
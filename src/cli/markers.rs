@@ -0,0 +1,93 @@
+use anyhow::Result;
+use audiort::metadata::Marker;
+use audiort::metadata::MarkerFormat;
+use audiort::metadata::Sidecar;
+use clap::Args;
+use clap::Subcommand;
+use std::path::PathBuf;
+
+#[derive(Args)]
+pub struct MarkersOpts {
+    #[clap(subcommand)]
+    command: MarkersCommand,
+}
+
+#[derive(Subcommand)]
+enum MarkersCommand {
+    /// Write a recording's markers for other tools
+    Export(ExportOpts),
+}
+
+#[derive(Args)]
+struct ExportOpts {
+    /// Recording made with `record --sidecar`
+    file: PathBuf,
+    /// audacity (labels), cue (CUE sheet) or chapters (podcast chapters JSON)
+    #[clap(short, long)]
+    format: MarkerFormat,
+    /// Where to write them, `-` for stdout [default: next to the recording,
+    /// e.g. `<file>.cue`]
+    #[clap(short, long)]
+    output: Option<PathBuf>,
+}
+
+pub fn run(options: MarkersOpts) -> Result<()> {
+    match options.command {
+        MarkersCommand::Export(options) => export(options),
+    }
+}
+
+fn export(options: ExportOpts) -> Result<()> {
+    let sidecar = Sidecar::path(&options.file);
+
+    let json = std::fs::read_to_string(&sidecar)
+        .map_err(|err| anyhow::anyhow!("{err}: {}", sidecar.display()))?;
+    let json: serde_json::Value = serde_json::from_str(&json)
+        .map_err(|err| anyhow::anyhow!("{err}: {}", sidecar.display()))?;
+
+    let Some(sample_rate) = json["config"]["sample_rate"]
+        .as_u64()
+        .and_then(|rate| u32::try_from(rate).ok())
+        .filter(|&rate| rate > 0)
+    else {
+        anyhow::bail!("{} has no sample rate", sidecar.display());
+    };
+
+    let markers: Vec<_> = json["markers"]
+        .as_array()
+        .into_iter()
+        .flatten()
+        .filter_map(|marker| {
+            Some(Marker {
+                frame: marker["frame"].as_u64()?,
+                label: marker["label"].as_str().unwrap_or_default().to_owned(),
+            })
+        })
+        .collect();
+
+    let file = options
+        .file
+        .file_name()
+        .map(|name| name.to_string_lossy())
+        .unwrap_or_default();
+    let exported = options.format.export(&markers, sample_rate, &file);
+
+    match options.output {
+        Some(output) if output.as_os_str() == "-" => print!("{exported}"),
+        output => {
+            let output = output.unwrap_or_else(|| options.format.path(&options.file));
+
+            std::fs::write(&output, exported)
+                .map_err(|err| anyhow::anyhow!("{err}: {}", output.display()))?;
+
+            eprintln!(
+                "Written {} markers to {} ({})",
+                markers.len(),
+                output.display(),
+                options.format.name()
+            );
+        }
+    }
+
+    Ok(())
+}
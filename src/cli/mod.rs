@@ -0,0 +1,49 @@
+use clap::ValueEnum;
+
+pub mod bench;
+pub mod bitperfect;
+pub mod click;
+pub mod concat;
+pub mod countdown;
+pub mod ctl;
+pub mod daemon;
+pub mod devices;
+pub mod doctor;
+pub mod fanout;
+pub mod fx;
+#[cfg(feature = "grpc")]
+pub mod grpc;
+pub mod http;
+#[cfg(feature = "jack")]
+pub mod jack;
+pub mod keys;
+pub mod latency;
+pub mod looper;
+pub mod ltc;
+pub mod markers;
+pub mod mqtt;
+pub mod normalize;
+pub mod osc;
+pub mod pipewire;
+pub mod play;
+pub mod receive;
+pub mod record;
+pub mod rendezvous;
+pub mod rtp;
+pub mod selftest;
+pub mod serve;
+pub mod split;
+pub mod srt;
+pub mod tee;
+pub mod tone;
+pub mod trim;
+pub mod vban;
+pub mod verify;
+pub mod webhook;
+pub mod websocket;
+
+#[derive(ValueEnum, Clone, Copy, PartialEq)]
+pub enum Listen {
+    In,
+    Out,
+}
@@ -0,0 +1,124 @@
+use crate::cli::daemon::Message;
+use anyhow::Result;
+use rumqttc::Client;
+use rumqttc::Event;
+use rumqttc::LastWill;
+use rumqttc::MqttOptions;
+use rumqttc::Packet;
+use rumqttc::QoS;
+use serde_json::json;
+use std::sync::mpsc;
+use std::time::Duration;
+
+/// Publish daemon state, levels and errors under `topic`, optionally taking
+/// commands from `<topic>/command`.
+pub fn serve(broker: &str, topic: String, commands: bool, tx: mpsc::Sender<Message>) -> Result<()> {
+    let (host, port) = match broker.rsplit_once(':') {
+        Some((host, port)) => (host, port.parse()?),
+        None => (broker, 1883),
+    };
+
+    let mut options = MqttOptions::new(format!("audiort-{}", std::process::id()), host, port);
+    options.set_keep_alive(Duration::from_secs(30));
+    options.set_last_will(LastWill::new(
+        format!("{topic}/state"),
+        json!({ "state": "offline" }).to_string(),
+        QoS::AtLeastOnce,
+        true,
+    ));
+
+    let (client, mut connection) = Client::new(options, 16);
+    let command_topic = format!("{topic}/command");
+
+    if commands {
+        client.subscribe(&command_topic, QoS::AtLeastOnce)?;
+    }
+
+    eprintln!("Publishing to MQTT broker {broker} under {topic}/");
+
+    let replies = client.clone();
+    let reply_topic = format!("{topic}/reply");
+    let command_tx = tx.clone();
+
+    std::thread::spawn(move || {
+        for notification in connection.iter() {
+            match notification {
+                Ok(Event::Incoming(Packet::Publish(publish))) if publish.topic == command_topic => {
+                    let command = String::from_utf8_lossy(&publish.payload).into_owned();
+
+                    let Some(reply) = request(&command_tx, command) else {
+                        break;
+                    };
+
+                    let _ =
+                        replies.publish(&reply_topic, QoS::AtLeastOnce, false, reply.to_string());
+                }
+                Ok(_) => {}
+                Err(err) => {
+                    // The next poll reconnects
+                    eprintln!("Warning: MQTT connection error: {err}");
+                    std::thread::sleep(Duration::from_secs(1));
+                }
+            }
+        }
+    });
+
+    std::thread::spawn(move || {
+        let mut last_state = serde_json::Value::Null;
+        let mut last_error = serde_json::Value::Null;
+
+        while let Some(status) = request(&tx, "status".to_owned()) {
+            let state = json!({
+                "state": status["state"],
+                "listen": status["listen"],
+                "device": status["device"],
+                "path": status["path"],
+            });
+
+            if state != last_state {
+                let _ = client.publish(
+                    format!("{topic}/state"),
+                    QoS::AtLeastOnce,
+                    true,
+                    state.to_string(),
+                );
+                last_state = state;
+            }
+
+            if status["error"] != last_error {
+                if !status["error"].is_null() {
+                    let _ = client.publish(
+                        format!("{topic}/error"),
+                        QoS::AtLeastOnce,
+                        false,
+                        status["error"].to_string(),
+                    );
+                }
+
+                last_error = status["error"].clone();
+            }
+
+            if status["state"] == "recording" {
+                if let Some(levels) = request(&tx, "levels".to_owned()) {
+                    let _ = client.publish(
+                        format!("{topic}/level"),
+                        QoS::AtMostOnce,
+                        false,
+                        levels.to_string(),
+                    );
+                }
+            }
+
+            std::thread::sleep(Duration::from_secs(1));
+        }
+    });
+
+    Ok(())
+}
+
+fn request(tx: &mpsc::Sender<Message>, command: String) -> Option<serde_json::Value> {
+    let (reply_tx, reply_rx) = mpsc::channel();
+
+    tx.send(Message::Request(command, reply_tx)).ok()?;
+    reply_rx.recv().ok()
+}
@@ -0,0 +1,129 @@
+use anyhow::Result;
+use audiort::dither::Dither;
+use clap::Args;
+use std::path::Path;
+use std::path::PathBuf;
+
+#[derive(Args)]
+pub struct NormalizeOpts {
+    /// WAV file to normalize, or with the `decode` feature a FLAC, MP3 or
+    /// Ogg file, written out as WAV
+    file: PathBuf,
+    /// Integrated loudness or peak level to reach, e.g. `-16LUFS` or
+    /// `-1dBFS`
+    #[clap(long, value_parser = parse_target, allow_hyphen_values = true)]
+    target: Target,
+    /// Write the result here instead of over the file, or beside it with a
+    /// `.wav` extension for a compressed file
+    #[clap(short, long)]
+    output: Option<PathBuf>,
+    /// Dither for integer files: `none`, `tpdf`, or `shibata` for TPDF
+    /// shaped towards high frequencies, quieter to the ear for a little
+    /// more CPU
+    #[clap(long, default_value = "none")]
+    dither: Dither,
+    /// Write a 64-bit float file, working in 64-bit floats throughout, for
+    /// further processing without loss. 64-bit float files always are
+    #[clap(long)]
+    float64: bool,
+}
+
+#[derive(Debug, Clone, Copy)]
+pub enum Target {
+    Loudness(f32),
+    Peak(f32),
+}
+
+pub fn run(options: NormalizeOpts) -> Result<()> {
+    let measurement = audiort::measure_wav(&options.file)
+        .map_err(|err| anyhow::anyhow!("{err}: {}", options.file.display()))?;
+    let peak = audiort::to_dbfs(measurement.peak);
+
+    let gain_db = match (options.target, measurement.integrated) {
+        (_, _) if measurement.peak == 0.0 => anyhow::bail!("the file is silent"),
+        (Target::Loudness(_), None) => anyhow::bail!("the file is too quiet to measure"),
+        (Target::Loudness(target), Some(loudness)) => target - loudness,
+        (Target::Peak(target), _) => target - peak,
+    };
+
+    match measurement.integrated {
+        Some(loudness) => eprintln!("Measured {loudness:.1} LUFS, peak {peak:.1} dBFS"),
+        None => eprintln!("Measured peak {peak:.1} dBFS"),
+    }
+
+    if peak + gain_db > 0.0 {
+        eprintln!(
+            "Warning: peaks would reach {:+.1} dBFS; they will clip unless the file is float",
+            peak + gain_db
+        );
+    }
+
+    let compressed = is_compressed(&options.file);
+    let output = match &options.output {
+        Some(output) => output.clone(),
+        None if compressed => options.file.with_extension("wav"),
+        None => options.file.clone(),
+    };
+
+    // Rewrite a copy, then swap it in, so a failure leaves the file alone
+    let mut name = output.file_name().unwrap_or_default().to_os_string();
+    name.push(".normalizing");
+    let temp = output.with_file_name(name);
+
+    let float64 =
+        options.float64 || (!compressed && audiort::wav64::spec(&options.file)?.is_some());
+
+    let written = match float64 {
+        true => {
+            audiort::gain_wav_float64(&options.file, &temp, 10f64.powf(f64::from(gain_db) / 20.0))
+        }
+        false => audiort::gain_wav(
+            &options.file,
+            &temp,
+            10f32.powf(gain_db / 20.0),
+            options.dither,
+        ),
+    };
+
+    written.map_err(|err| {
+        let _ = std::fs::remove_file(&temp);
+        anyhow::anyhow!("{err}: {}", temp.display())
+    })?;
+
+    std::fs::rename(&temp, &output)?;
+
+    println!("Written to {} ({gain_db:+.1} dB)", output.display());
+
+    Ok(())
+}
+
+#[cfg(feature = "decode")]
+fn is_compressed(path: &Path) -> bool {
+    audiort::decode::is_compressed(path)
+}
+
+#[cfg(not(feature = "decode"))]
+fn is_compressed(_: &Path) -> bool {
+    false
+}
+
+fn parse_target(s: &str) -> Result<Target, String> {
+    let invalid = || format!("invalid target `{s}`, expected e.g. `-16LUFS` or `-1dBFS`");
+    let lower = s.trim().to_lowercase();
+
+    let (level, target): (&str, fn(f32) -> Target) = if let Some(level) = lower
+        .strip_suffix("lufs")
+        .or_else(|| lower.strip_suffix("lkfs"))
+    {
+        (level, Target::Loudness)
+    } else if let Some(level) = lower
+        .strip_suffix("dbfs")
+        .or_else(|| lower.strip_suffix("db"))
+    {
+        (level, Target::Peak)
+    } else {
+        return Err(invalid());
+    };
+
+    level.trim().parse().map(target).map_err(|_| invalid())
+}
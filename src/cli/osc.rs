@@ -0,0 +1,120 @@
+use crate::cli::daemon::Message;
+use anyhow::Result;
+use rosc::OscMessage;
+use rosc::OscPacket;
+use rosc::OscType;
+use std::net::UdpSocket;
+use std::sync::mpsc;
+
+/// Map OSC messages such as `/audiort/record/start` onto daemon commands.
+pub fn serve(port: u16, tx: mpsc::Sender<Message>) -> Result<()> {
+    receive(port, move |packet| dispatch(packet, &tx))
+}
+
+/// Turn OSC messages such as `/audiort/fx/highpass 120` into `fx` commands
+/// (`highpass 120`), as if typed.
+pub fn params(port: u16, tx: mpsc::Sender<String>) -> Result<()> {
+    receive(port, move |packet| send_params(packet, &tx))
+}
+
+fn receive<F>(port: u16, mut handler: F) -> Result<()>
+where
+    F: FnMut(OscPacket) + Send + 'static,
+{
+    let socket = UdpSocket::bind(("0.0.0.0", port))?;
+    eprintln!("OSC on udp port {port}");
+
+    std::thread::spawn(move || {
+        let mut buf = [0u8; rosc::decoder::MTU];
+
+        while let Ok((len, _)) = socket.recv_from(&mut buf) {
+            match rosc::decoder::decode_udp(&buf[..len]) {
+                Ok((_, packet)) => handler(packet),
+                Err(err) => eprintln!("Warning: invalid OSC packet: {err:?}"),
+            }
+        }
+    });
+
+    Ok(())
+}
+
+fn send_params(packet: OscPacket, tx: &mpsc::Sender<String>) {
+    match packet {
+        OscPacket::Message(message) => {
+            let name = message.addr.strip_prefix("/audiort/fx/");
+
+            let value = match message.args.first() {
+                Some(OscType::Float(value)) => value.to_string(),
+                Some(OscType::Double(value)) => value.to_string(),
+                Some(OscType::Int(value)) => value.to_string(),
+                Some(OscType::String(value)) => value.clone(),
+                _ => String::new(),
+            };
+
+            match name {
+                Some(name) if !value.is_empty() => {
+                    let _ = tx.send(format!("{} {value}", name.replace('/', ".")));
+                }
+                _ => eprintln!("Warning: unhandled OSC message {}", message.addr),
+            }
+        }
+        OscPacket::Bundle(bundle) => {
+            for packet in bundle.content {
+                send_params(packet, tx);
+            }
+        }
+    }
+}
+
+fn dispatch(packet: OscPacket, tx: &mpsc::Sender<Message>) {
+    match packet {
+        OscPacket::Message(message) => {
+            let Some(command) = command(&message) else {
+                eprintln!("Warning: unhandled OSC message {}", message.addr);
+                return;
+            };
+
+            let (reply_tx, reply_rx) = mpsc::channel();
+
+            if tx.send(Message::Request(command, reply_tx)).is_ok() {
+                if let Ok(reply) = reply_rx.recv() {
+                    if reply["ok"] != true {
+                        eprintln!("Warning: {}: {}", message.addr, reply["error"]);
+                    }
+                }
+            }
+        }
+        OscPacket::Bundle(bundle) => {
+            for packet in bundle.content {
+                dispatch(packet, tx);
+            }
+        }
+    }
+}
+
+fn command(message: &OscMessage) -> Option<String> {
+    let arg = message.args.first();
+
+    let command = match message.addr.as_str() {
+        "/audiort/record/start" => match arg {
+            Some(OscType::String(path)) => format!("start {path}"),
+            _ => "start".to_owned(),
+        },
+        "/audiort/record/stop" => "stop".to_owned(),
+        "/audiort/record/pause" => "pause".to_owned(),
+        "/audiort/record/resume" => "resume".to_owned(),
+        "/audiort/device" => match arg {
+            Some(OscType::String(listen)) => format!("device {listen}"),
+            _ => return None,
+        },
+        "/audiort/gain" => match arg {
+            Some(OscType::Float(gain)) => format!("gain {gain}"),
+            Some(OscType::Double(gain)) => format!("gain {gain}"),
+            Some(OscType::Int(gain)) => format!("gain {gain}"),
+            _ => return None,
+        },
+        _ => return None,
+    };
+
+    Some(command)
+}
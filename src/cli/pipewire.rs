@@ -0,0 +1,70 @@
+use clap::Args;
+
+#[derive(Args)]
+pub struct NodeOpts {
+    /// Node name of audiort's streams under PipeWire or PulseAudio
+    #[clap(long, global = true, value_name = "NAME")]
+    node_name: Option<String>,
+    /// Description shown for audiort's streams in mixers and patchbays
+    #[clap(long, global = true, value_name = "TEXT")]
+    node_description: Option<String>,
+    /// Media role of audiort's streams, e.g. `Production`, `Music` or
+    /// `Communication`
+    #[clap(long, global = true, value_name = "ROLE")]
+    media_role: Option<String>,
+}
+
+/// Name the streams `command` opens, through the environment PipeWire and
+/// PulseAudio clients read their properties from. Has to run before any
+/// device is opened; properties already set there are left alone.
+pub fn apply(options: &NodeOpts, command: &str) {
+    if !cfg!(target_os = "linux") {
+        return;
+    }
+
+    let name = options
+        .node_name
+        .clone()
+        .unwrap_or(format!("audiort-{command}"));
+    let description = options
+        .node_description
+        .clone()
+        .unwrap_or(format!("audiort {command}"));
+    let role = options.media_role.clone().unwrap_or(
+        match command {
+            "receive" => "Music",
+            _ => "Production",
+        }
+        .to_owned(),
+    );
+
+    let props = [
+        ("application.name", "audiort"),
+        ("node.name", name.as_str()),
+        ("node.description", description.as_str()),
+        ("media.name", description.as_str()),
+        ("media.role", role.as_str()),
+    ];
+
+    if std::env::var_os("PIPEWIRE_PROPS").is_none() {
+        let props = props
+            .iter()
+            .map(|(key, value)| format!("{key} = {}", quote(value)))
+            .collect::<Vec<_>>();
+
+        std::env::set_var("PIPEWIRE_PROPS", format!("{{ {} }}", props.join(" ")));
+    }
+
+    if std::env::var_os("PULSE_PROP").is_none() {
+        let props = props
+            .iter()
+            .map(|(key, value)| format!("{key}={}", quote(value)))
+            .collect::<Vec<_>>();
+
+        std::env::set_var("PULSE_PROP", props.join(" "));
+    }
+}
+
+fn quote(value: &str) -> String {
+    format!("\"{}\"", value.replace('\\', "\\\\").replace('"', "\\\""))
+}
@@ -0,0 +1,328 @@
+use crate::cli::countdown::load;
+use crate::cli::devices;
+use crate::cli::keys;
+use crate::cli::keys::RawTerminal;
+use anyhow::Result;
+use audiort::playback::Clip;
+use audiort::playback::Player;
+use audiort::resample::Resampler;
+use audiort::stretch::Stretcher;
+use audiort::Device;
+use clap::Args;
+use std::borrow::Cow;
+use std::io::IsTerminal;
+use std::path::Path;
+use std::path::PathBuf;
+use std::sync::atomic::AtomicBool;
+use std::sync::atomic::Ordering;
+use std::sync::mpsc;
+use std::sync::Arc;
+use std::time::Duration;
+
+#[derive(Args)]
+pub struct PlayOpts {
+    /// WAV files, or FLAC, MP3 and Ogg files with the `decode` feature, or
+    /// M3U playlists of them, played in order without gaps
+    #[clap(required = true)]
+    files: Vec<PathBuf>,
+    /// Device to play to, as `record --device` takes it [default: the
+    /// default output]
+    #[clap(long)]
+    device: Option<String>,
+    /// Where to start in the first file, e.g. `1:23` or `83.5`
+    #[clap(long, value_parser = parse_time)]
+    seek: Option<Duration>,
+    /// Where to stop in the last file [default: its end]
+    #[clap(long, value_parser = parse_time)]
+    end: Option<Duration>,
+    /// Play again from `--seek` after `--end`, until interrupted
+    #[clap(long = "loop")]
+    looping: bool,
+    /// Linear gain, up to 4; louder samples are softly limited rather than
+    /// clipped. + and - change it while playing
+    #[clap(long, default_value = "1")]
+    volume: f32,
+    /// How fast to play, from 0.25 to 4, e.g. 1.5 to get through a long
+    /// recording quicker. Pitch is kept by time-stretching
+    #[clap(long, default_value = "1")]
+    speed: f64,
+    /// Change speed by resampling instead, which shifts the pitch with it
+    #[clap(long)]
+    resample: bool,
+}
+
+const MAX_VOLUME: f32 = 4.0;
+
+/// How much each + or - changes the volume.
+const VOLUME_STEP: f32 = 0.05;
+
+/// Fade where a loop jumps back, long enough to hide the seam.
+const LOOP_FADE: Duration = Duration::from_millis(10);
+
+/// How the speed is changed, carried across files so there are no gaps.
+enum Speed {
+    Normal,
+    /// Keeping the pitch
+    Stretch(Stretcher),
+    Resample(Resampler),
+}
+
+impl Speed {
+    fn process(&mut self, data: &[f32], output: &mut Vec<f32>) {
+        match self {
+            Speed::Normal => output.extend_from_slice(data),
+            Speed::Stretch(stretcher) => stretcher.process(data, output),
+            Speed::Resample(resampler) => resampler.process(data, output),
+        }
+    }
+
+    fn finish(&mut self, output: &mut Vec<f32>) {
+        match self {
+            Speed::Normal => {}
+            Speed::Stretch(stretcher) => stretcher.finish(output),
+            Speed::Resample(resampler) => resampler.finish(output),
+        }
+    }
+}
+
+/// A file, decoded and converted for the player, and the part of it to play.
+struct Track {
+    path: PathBuf,
+    clip: Arc<Clip>,
+    start: usize,
+    end: usize,
+    first: bool,
+    last: bool,
+}
+
+pub fn run(options: PlayOpts) -> Result<()> {
+    if !(0.0..=MAX_VOLUME).contains(&options.volume) {
+        anyhow::bail!("--volume must be from 0 to {MAX_VOLUME}");
+    }
+
+    if !(0.25..=4.0).contains(&options.speed) {
+        anyhow::bail!("--speed must be from 0.25 to 4");
+    }
+
+    let device = match &options.device {
+        Some(spec) => devices::open(Device::Output, spec)?,
+        None => audiort::DeviceBuilder::new_default_output()?,
+    };
+
+    if let Ok(name) = device.name() {
+        eprintln!("Playing to {name}");
+    }
+
+    let mut paths = Vec::new();
+
+    for path in options.files {
+        match is_playlist(&path) {
+            true => paths.extend(read_playlist(&path)?),
+            false => paths.push(path),
+        }
+    }
+
+    let sample_rate = device.config().sample_rate().0;
+    let channels = device.config().channels();
+    let player = Player::new(&device, sample_rate, channels)?;
+    player.set_volume(options.volume);
+
+    // Kept to the end, to give the terminal back as it was
+    let _terminal = match std::io::stdin().is_terminal() {
+        true => {
+            let terminal = RawTerminal::new()?;
+            let control = player.volume_control();
+
+            eprintln!("Press + and - to change the volume");
+
+            keys::each(move |key| {
+                let step = match key {
+                    b'+' | b'=' => VOLUME_STEP,
+                    b'-' | b'_' => -VOLUME_STEP,
+                    _ => return true,
+                };
+
+                let volume = (control.get() + step).clamp(0.0, MAX_VOLUME);
+                control.set(volume);
+                eprintln!("Volume {volume:.2} ({:.1} dB)", audiort::to_dbfs(volume));
+
+                true
+            });
+
+            Some(terminal)
+        }
+        false => None,
+    };
+
+    // Decoded a file ahead, so the next is ready when the current one ends
+    let (sender, tracks) = mpsc::sync_channel::<Track>(1);
+    let (seek, end, looping) = (options.seek, options.end, options.looping);
+
+    std::thread::spawn(move || {
+        // A lone file is decoded once, however often it loops
+        let mut cached: Option<Arc<Clip>> = None;
+
+        loop {
+            let mut sent = false;
+
+            for (index, path) in paths.iter().enumerate() {
+                let clip = match &cached {
+                    Some(clip) => Arc::clone(clip),
+                    None => match load(path, sample_rate, channels) {
+                        Ok(samples) => Arc::new(Clip::new(samples, sample_rate, channels)),
+                        Err(err) => {
+                            eprintln!("Warning: skipping {}: {err}", path.display());
+                            continue;
+                        }
+                    },
+                };
+
+                if paths.len() == 1 {
+                    cached = Some(Arc::clone(&clip));
+                }
+
+                let first = index == 0;
+                let last = index + 1 == paths.len();
+
+                let track = Track {
+                    path: path.clone(),
+                    start: seek.filter(|_| first).map_or(0, |seek| clip.frame_at(seek)),
+                    end: end
+                        .filter(|_| last)
+                        .map_or(clip.frames(), |end| clip.frame_at(end)),
+                    clip,
+                    first,
+                    last,
+                };
+
+                if sender.send(track).is_err() {
+                    return;
+                }
+
+                sent = true;
+            }
+
+            if !looping || !sent {
+                break;
+            }
+        }
+    });
+
+    let interrupted = Arc::new(AtomicBool::new(false));
+    let interrupt = Arc::clone(&interrupted);
+
+    ctrlc::set_handler(move || interrupt.store(true, Ordering::Relaxed))?;
+
+    // Keep about 100ms queued ahead of the device
+    let ahead = sample_rate as usize / 10;
+    let chunk = ahead / 4 * usize::from(channels);
+
+    player.play()?;
+
+    let fade = (LOOP_FADE.as_secs_f64() * f64::from(sample_rate)) as usize;
+    let mut lead = Vec::new();
+
+    let mut speed = match (options.speed, options.resample) {
+        (1.0, _) => Speed::Normal,
+        (speed, false) => Speed::Stretch(Stretcher::new(speed, sample_rate, channels.into())),
+        (speed, true) => Speed::Resample(Resampler::new(
+            (f64::from(sample_rate) * speed).round() as u32,
+            sample_rate,
+            channels.into(),
+        )),
+    };
+    let mut changed = Vec::new();
+
+    for track in tracks {
+        let clip = &track.clip;
+
+        if track.first && track.start >= clip.frames() {
+            anyhow::bail!(
+                "--seek is past the end of {} ({:.1}s)",
+                track.path.display(),
+                clip.frames() as f64 / f64::from(sample_rate)
+            );
+        }
+
+        if track.first && track.last && track.end <= track.start {
+            anyhow::bail!("--end must come after --seek");
+        }
+
+        eprintln!("Playing {}", track.path.display());
+
+        let mut samples = Cow::Borrowed(clip.section(track.start, track.end));
+
+        if options.looping {
+            if track.first {
+                lead = clip
+                    .section(track.start.saturating_sub(fade), track.start)
+                    .to_vec();
+            }
+
+            if track.last {
+                audiort::playback::crossfade_loop(samples.to_mut(), &lead, channels, fade);
+            }
+        }
+
+        for part in samples.chunks(chunk.max(1)) {
+            while !interrupted.load(Ordering::Relaxed) && player.queued() >= ahead {
+                std::thread::sleep(Duration::from_millis(10));
+            }
+
+            if interrupted.load(Ordering::Relaxed) {
+                return Ok(());
+            }
+
+            changed.clear();
+            speed.process(part, &mut changed);
+            player.push(&changed);
+        }
+    }
+
+    changed.clear();
+    speed.finish(&mut changed);
+    player.push(&changed);
+
+    // Let the tail play out
+    while !interrupted.load(Ordering::Relaxed) && player.queued() > 0 {
+        std::thread::sleep(Duration::from_millis(10));
+    }
+
+    Ok(())
+}
+
+fn is_playlist(path: &Path) -> bool {
+    let extension = path.extension().and_then(|extension| extension.to_str());
+    matches!(
+        extension.map(str::to_lowercase).as_deref(),
+        Some("m3u" | "m3u8")
+    )
+}
+
+/// The files an M3U playlist lists, relative to where it is.
+fn read_playlist(path: &Path) -> Result<Vec<PathBuf>> {
+    let text = std::fs::read_to_string(path)
+        .map_err(|err| anyhow::anyhow!("{err}: {}", path.display()))?;
+    let dir = path.parent().unwrap_or(Path::new(""));
+
+    Ok(text
+        .lines()
+        .map(|line| line.trim_start_matches('\u{feff}').trim())
+        .filter(|line| !line.is_empty() && !line.starts_with('#'))
+        .map(|line| dir.join(line))
+        .collect())
+}
+
+/// `83.5`, `1:23` or `1:02:03.5`.
+fn parse_time(s: &str) -> Result<Duration, String> {
+    let invalid = || format!("invalid time `{s}`, expected e.g. `1:23` or `83.5`");
+
+    let mut seconds = 0.0;
+
+    for part in s.trim().split(':') {
+        let value: f64 = part.parse().map_err(|_| invalid())?;
+        seconds = seconds * 60.0 + value;
+    }
+
+    Duration::try_from_secs_f64(seconds).map_err(|_| invalid())
+}
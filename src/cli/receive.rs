@@ -0,0 +1,181 @@
+use crate::cli::rtp;
+use crate::cli::tee::Endian;
+use crate::cli::vban;
+use anyhow::Result;
+use audiort::playback::Player;
+use clap::Args;
+use clap::ValueEnum;
+use std::net::UdpSocket;
+use std::sync::atomic::AtomicBool;
+use std::sync::atomic::Ordering;
+use std::sync::Arc;
+use std::time::Duration;
+
+#[derive(Args)]
+pub struct ReceiveOpts {
+    /// UDP address to receive audio on
+    #[clap(long, default_value = "0.0.0.0:5004")]
+    listen: String,
+    /// Packet format; `auto` detects VBAN and RTP and treats anything else as raw
+    #[clap(long, value_enum, default_value = "auto")]
+    format: Format,
+    /// Sample rate of raw and dynamic-payload RTP audio
+    #[clap(long, default_value = "48000")]
+    sample_rate: u32,
+    /// Channel count of raw and dynamic-payload RTP audio
+    #[clap(long, default_value = "2")]
+    channels: u16,
+    /// Byte order of raw audio
+    #[clap(long, value_enum, default_value = "little")]
+    endian: Endian,
+    /// Also record the received audio to this file
+    #[clap(short, long)]
+    output: Option<String>,
+    /// Most audio (milliseconds) to queue before dropping the oldest
+    #[clap(long, default_value = "200")]
+    max_latency: u64,
+}
+
+#[derive(ValueEnum, Clone, Copy, PartialEq)]
+enum Format {
+    Auto,
+    Rtp,
+    Vban,
+    /// Interleaved 16-bit PCM, in the byte order of `--endian`
+    Raw,
+}
+
+/// Audio decoded from a single network packet.
+pub struct Audio {
+    pub sample_rate: u32,
+    pub channels: u16,
+    pub samples: Vec<f32>,
+}
+
+type WavWriter = hound::WavWriter<std::io::BufWriter<std::fs::File>>;
+
+pub fn run(options: ReceiveOpts) -> Result<()> {
+    let socket = UdpSocket::bind(&options.listen)?;
+    socket.set_read_timeout(Some(Duration::from_millis(250)))?;
+
+    eprintln!("Receiving on udp://{}", options.listen);
+
+    let device = audiort::DeviceBuilder::new_default_output()?;
+
+    if let Ok(name) = device.name() {
+        eprintln!("Playing to {name}");
+    }
+
+    let interrupted = Arc::new(AtomicBool::new(false));
+    let interrupt = Arc::clone(&interrupted);
+
+    ctrlc::set_handler(move || interrupt.store(true, Ordering::Relaxed))?;
+
+    let fallback = (options.sample_rate, options.channels);
+    let mut player: Option<Player> = None;
+    let mut writer: Option<WavWriter> = None;
+    let mut mismatched = false;
+    let mut buf = [0u8; 65_536];
+
+    while !interrupted.load(Ordering::Relaxed) {
+        let (len, peer) = match socket.recv_from(&mut buf) {
+            Ok(received) => received,
+            Err(err)
+                if matches!(
+                    err.kind(),
+                    std::io::ErrorKind::WouldBlock | std::io::ErrorKind::TimedOut
+                ) =>
+            {
+                continue
+            }
+            Err(err) => return Err(err.into()),
+        };
+
+        let Some(audio) = decode(&buf[..len], options.format, fallback, options.endian) else {
+            continue;
+        };
+
+        let player = match &player {
+            Some(player) => player,
+            None => {
+                eprintln!(
+                    "Receiving {}Hz {} channels from {peer}",
+                    audio.sample_rate, audio.channels
+                );
+
+                let mut new = Player::new(&device, audio.sample_rate, audio.channels)?;
+                new.set_max_latency(Duration::from_millis(options.max_latency));
+                new.play()?;
+
+                if let Some(output) = &options.output {
+                    let spec = hound::WavSpec {
+                        channels: audio.channels,
+                        sample_rate: audio.sample_rate,
+                        bits_per_sample: 16,
+                        sample_format: hound::SampleFormat::Int,
+                    };
+
+                    writer = Some(hound::WavWriter::create(output, spec)?);
+                }
+
+                player.insert(new)
+            }
+        };
+
+        if (audio.sample_rate, audio.channels) != (player.sample_rate(), player.channels()) {
+            if !mismatched {
+                eprintln!(
+                    "Warning: ignoring {}Hz {} channel packets from {peer}",
+                    audio.sample_rate, audio.channels
+                );
+                mismatched = true;
+            }
+
+            continue;
+        }
+
+        player.push(&audio.samples);
+
+        if let Some(writer) = writer.as_mut() {
+            for &value in &audio.samples {
+                writer.write_sample((value.clamp(-1.0, 1.0) * f32::from(i16::MAX)) as i16)?;
+            }
+        }
+    }
+
+    if let Some(writer) = writer {
+        writer.finalize()?;
+
+        if let Some(output) = &options.output {
+            eprintln!("Written to {output}");
+        }
+    }
+
+    Ok(())
+}
+
+fn decode(packet: &[u8], format: Format, fallback: (u32, u16), endian: Endian) -> Option<Audio> {
+    let format = match format {
+        Format::Auto if packet.starts_with(b"VBAN") => Format::Vban,
+        Format::Auto if packet.first().is_some_and(|&b| b >> 6 == 2) => Format::Rtp,
+        Format::Auto => Format::Raw,
+        format => format,
+    };
+
+    match format {
+        Format::Vban => vban::decode(packet),
+        Format::Rtp => rtp::decode(packet, fallback),
+        _ => Some(Audio {
+            sample_rate: fallback.0,
+            channels: fallback.1,
+            samples: packet
+                .chunks_exact(2)
+                .map(|b| match endian {
+                    Endian::Little => i16::from_le_bytes([b[0], b[1]]),
+                    Endian::Big => i16::from_be_bytes([b[0], b[1]]),
+                })
+                .map(|value| f32::from(value) / 32768.0)
+                .collect(),
+        }),
+    }
+}
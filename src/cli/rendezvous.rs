@@ -0,0 +1,121 @@
+use anyhow::Result;
+use std::io::BufRead;
+use std::io::BufReader;
+use std::io::ErrorKind;
+use std::io::Write;
+use std::net::TcpListener;
+use std::net::TcpStream;
+use std::time::Duration;
+use std::time::SystemTime;
+use std::time::UNIX_EPOCH;
+
+/// Time from everyone joining to the start, for devices to open
+const LEAD: Duration = Duration::from_secs(1);
+
+/// Clock readings taken from the leader; the quickest round trip is used
+const PINGS: usize = 8;
+
+/// How often to try again while the leader isn't listening yet
+const RETRY: Duration = Duration::from_millis(200);
+
+/// Meet the other instances at `addr`, `instances` in all, returning when to
+/// start by this machine's clock. The first to bind the address leads,
+/// waiting for the rest to join; the others connect to it.
+pub fn start_time(addr: &str, instances: usize) -> Result<SystemTime> {
+    match TcpListener::bind(addr) {
+        Ok(listener) => lead(listener, instances.saturating_sub(1)),
+        Err(err) => match err.kind() {
+            // Another instance leads, here or on another machine
+            ErrorKind::AddrInUse | ErrorKind::AddrNotAvailable => follow(addr),
+            _ => Err(anyhow::anyhow!("{err}: {addr}")),
+        },
+    }
+}
+
+fn lead(listener: TcpListener, followers: usize) -> Result<SystemTime> {
+    let mut peers = Vec::new();
+
+    while peers.len() < followers {
+        let (peer, from) = listener.accept()?;
+        peer.set_nodelay(true)?;
+
+        // Answer its clock readings until it's ready
+        for line in BufReader::new(peer.try_clone()?).lines() {
+            match line?.trim() {
+                "time" => writeln!(&peer, "{}", nanos(SystemTime::now()))?,
+                "ready" => break,
+                other => anyhow::bail!("unexpected `{other}` from {from}"),
+            }
+        }
+
+        eprintln!("{from} joined ({} of {})", peers.len() + 2, followers + 1);
+        peers.push(peer);
+    }
+
+    // On a whole second, which is easy to spot in logs
+    let start = nanos(SystemTime::now() + LEAD).div_ceil(1_000_000_000) * 1_000_000_000;
+
+    for peer in &peers {
+        writeln!(&*peer, "start {start}")?;
+    }
+
+    Ok(from_nanos(start))
+}
+
+fn follow(addr: &str) -> Result<SystemTime> {
+    let peer = loop {
+        match TcpStream::connect(addr) {
+            Ok(peer) => break peer,
+            Err(_) => std::thread::sleep(RETRY),
+        }
+    };
+
+    peer.set_nodelay(true)?;
+
+    let mut lines = BufReader::new(peer.try_clone()?).lines();
+    let mut next = || -> Result<String> {
+        lines
+            .next()
+            .ok_or_else(|| anyhow::anyhow!("the leader at {addr} went away"))?
+            .map_err(Into::into)
+    };
+
+    // How far the leader's clock is ahead of ours, assuming the trip there
+    // takes as long as the trip back
+    let mut offset = 0;
+    let mut quickest = Duration::MAX;
+
+    for _ in 0..PINGS {
+        let sent = SystemTime::now();
+        writeln!(&peer, "time")?;
+
+        let theirs: i128 = next()?.trim().parse()?;
+        let round_trip = sent.elapsed().unwrap_or_default();
+
+        if round_trip < quickest {
+            quickest = round_trip;
+            offset = theirs - nanos(sent + round_trip / 2) as i128;
+        }
+    }
+
+    writeln!(&peer, "ready")?;
+
+    let line = next()?;
+    let start: i128 = line
+        .trim()
+        .strip_prefix("start ")
+        .and_then(|start| start.parse().ok())
+        .ok_or_else(|| anyhow::anyhow!("unexpected `{}` from the leader", line.trim()))?;
+
+    Ok(from_nanos((start - offset).max(0) as u128))
+}
+
+fn nanos(time: SystemTime) -> u128 {
+    time.duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_nanos()
+}
+
+fn from_nanos(nanos: u128) -> SystemTime {
+    UNIX_EPOCH + Duration::from_nanos(nanos as u64)
+}
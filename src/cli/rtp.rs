@@ -0,0 +1,125 @@
+use crate::cli::receive::Audio;
+use anyhow::Result;
+use std::net::UdpSocket;
+use std::time::SystemTime;
+use std::time::UNIX_EPOCH;
+
+/// Keeps packets under a typical Ethernet MTU once IP/UDP/RTP headers are added.
+const MAX_PAYLOAD: usize = 1200;
+
+/// Dynamic payload type used when no static L16 type matches the stream.
+const DYNAMIC_PAYLOAD_TYPE: u8 = 96;
+
+/// Sends audio as RTP packets with an L16 (16-bit big-endian PCM) payload.
+pub struct RtpSender {
+    socket: UdpSocket,
+    payload_type: u8,
+    channels: usize,
+    sequence: u16,
+    timestamp: u32,
+    ssrc: u32,
+    packet: Vec<u8>,
+}
+
+impl RtpSender {
+    pub fn new(addr: &str, config: &cpal::SupportedStreamConfig) -> Result<RtpSender> {
+        let socket = UdpSocket::bind("0.0.0.0:0")?;
+        socket.connect(addr)?;
+        // Never stall the capture callback on a full socket buffer
+        socket.set_nonblocking(true)?;
+
+        let sample_rate = config.sample_rate().0;
+        let channels = config.channels();
+
+        // RFC 3551 static payload types
+        let payload_type = match (sample_rate, channels) {
+            (44_100, 2) => 10,
+            (44_100, 1) => 11,
+            _ => DYNAMIC_PAYLOAD_TYPE,
+        };
+
+        eprintln!("Sending RTP to {addr}: payload {payload_type} L16/{sample_rate}/{channels}");
+
+        let seed = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|d| d.subsec_nanos() ^ d.as_secs() as u32)
+            .unwrap_or_default();
+
+        Ok(RtpSender {
+            socket,
+            payload_type,
+            channels: usize::from(channels.max(1)),
+            sequence: seed as u16,
+            timestamp: seed.rotate_left(16),
+            ssrc: seed ^ std::process::id(),
+            packet: Vec::with_capacity(12 + MAX_PAYLOAD),
+        })
+    }
+
+    pub fn send(&mut self, data: &[f32]) {
+        let samples_per_packet = MAX_PAYLOAD / 2 / self.channels * self.channels;
+
+        for chunk in data.chunks(samples_per_packet) {
+            self.packet.clear();
+            // Version 2, no padding, extension or CSRCs, marker unset
+            self.packet.push(0x80);
+            self.packet.push(self.payload_type);
+            self.packet.extend_from_slice(&self.sequence.to_be_bytes());
+            self.packet.extend_from_slice(&self.timestamp.to_be_bytes());
+            self.packet.extend_from_slice(&self.ssrc.to_be_bytes());
+
+            for &value in chunk {
+                let sample = (value.clamp(-1.0, 1.0) * f32::from(i16::MAX)) as i16;
+                self.packet.extend_from_slice(&sample.to_be_bytes());
+            }
+
+            // Dropped packets are left to the receiver's jitter buffer
+            let _ = self.socket.send(&self.packet);
+
+            self.sequence = self.sequence.wrapping_add(1);
+            self.timestamp = self
+                .timestamp
+                .wrapping_add((chunk.len() / self.channels) as u32);
+        }
+    }
+}
+
+/// Decode an RTP packet with an L16 payload. Static payload types carry their
+/// own format; dynamic ones are assumed to match `fallback`.
+pub fn decode(packet: &[u8], fallback: (u32, u16)) -> Option<Audio> {
+    if packet.len() < 12 || packet[0] >> 6 != 2 {
+        return None;
+    }
+
+    let csrcs = usize::from(packet[0] & 0x0F);
+    let mut offset = 12 + csrcs * 4;
+
+    if packet[0] & 0x10 != 0 {
+        let words = packet.get(offset + 2..offset + 4)?;
+        offset += 4 + usize::from(u16::from_be_bytes([words[0], words[1]])) * 4;
+    }
+
+    let mut end = packet.len();
+
+    if packet[0] & 0x20 != 0 {
+        end = end.checked_sub(usize::from(*packet.last()?))?;
+    }
+
+    let (sample_rate, channels) = match packet[1] & 0x7F {
+        10 => (44_100, 2),
+        11 => (44_100, 1),
+        _ => fallback,
+    };
+
+    let samples = packet
+        .get(offset..end)?
+        .chunks_exact(2)
+        .map(|b| f32::from(i16::from_be_bytes([b[0], b[1]])) / 32768.0)
+        .collect();
+
+    Some(Audio {
+        sample_rate,
+        channels,
+        samples,
+    })
+}
@@ -0,0 +1,107 @@
+use crate::cli::devices;
+use anyhow::Result;
+use audiort::playback::Player;
+use audiort::Device;
+use clap::Args;
+use std::io::Write;
+use std::sync::Arc;
+use std::sync::Mutex;
+use std::time::Duration;
+
+/// Below this peak level the input is reported as silent.
+const SILENCE_DBFS: f32 = -60.0;
+
+#[derive(Args)]
+pub struct TestOpts {
+    /// Input device name, any unique part of it, or index in `audiort devices`
+    /// [default: the default input]
+    device: Option<String>,
+    /// Seconds to record
+    #[clap(short, long, default_value = "3")]
+    duration: u64,
+}
+
+pub fn run(options: TestOpts) -> Result<()> {
+    let input = match &options.device {
+        Some(spec) => devices::open(Device::Input, spec)?,
+        None => audiort::DeviceBuilder::new_default_input()?,
+    };
+
+    let output = audiort::DeviceBuilder::new_default_output()?;
+
+    let sample_rate = input.config().sample_rate().0;
+    let in_channels = usize::from(input.config().channels().max(1));
+    let out_channels = output.config().channels();
+
+    if let (Ok(input), Ok(output)) = (input.name(), output.name()) {
+        println!("Input:  {input}");
+        println!("Output: {output}");
+    }
+
+    let captured = Arc::new(Mutex::new(Vec::new()));
+    let recorder = Arc::clone(&captured);
+
+    let mut stream = audiort::StreamBuilder::new(input)?;
+
+    stream.read(move |data| {
+        if let Ok(mut captured) = recorder.lock() {
+            captured.extend_from_slice(data);
+        }
+    })?;
+
+    stream.play()?;
+
+    for remaining in (1..=options.duration).rev() {
+        print!("\rRecording, make some noise... {remaining} ");
+        std::io::stdout().flush()?;
+        std::thread::sleep(Duration::from_secs(1));
+    }
+
+    println!();
+
+    let stats = stream.stats();
+    stream.stop();
+
+    let peak = audiort::to_dbfs(stats.peak);
+
+    println!(
+        "Level: peak {peak:.1} dBFS, RMS {:.1} dBFS, {} dropouts",
+        audiort::to_dbfs(stats.rms()),
+        stats.dropouts
+    );
+
+    if peak < SILENCE_DBFS {
+        println!("The input looks silent. Check that it isn't muted, that audiort may use the microphone, and that the right device is selected.");
+    } else if stats.clipped > 0 {
+        println!("The input clipped; lower its gain.");
+    }
+
+    let captured = std::mem::take(&mut *captured.lock().unwrap_or_else(|err| err.into_inner()));
+
+    // Spread the recording over the output's channels
+    let mut playback = Vec::with_capacity(captured.len() / in_channels * usize::from(out_channels));
+
+    for frame in captured.chunks_exact(in_channels) {
+        for channel in 0..usize::from(out_channels) {
+            playback.push(frame[channel % in_channels]);
+        }
+    }
+
+    println!("Playing it back...");
+
+    let mut player = Player::new(&output, sample_rate, out_channels)?;
+    player.set_max_latency(Duration::from_secs(options.duration + 1));
+    player.push(&playback);
+    player.play()?;
+
+    while player.queued() > 0 {
+        std::thread::sleep(Duration::from_millis(50));
+    }
+
+    // Let the device buffer drain
+    std::thread::sleep(Duration::from_millis(200));
+
+    println!("Done. If you heard yourself, recording and playback work.");
+
+    Ok(())
+}
@@ -0,0 +1,108 @@
+use crate::cli::fanout::wav_header;
+use crate::cli::fanout::ChunkReader;
+use crate::cli::fanout::FanOut;
+use crate::cli::Listen;
+use anyhow::Result;
+use clap::Args;
+use std::sync::mpsc;
+use tiny_http::Header;
+use tiny_http::Method;
+use tiny_http::Request;
+use tiny_http::Response;
+
+#[derive(Args)]
+pub struct ServeOpts {
+    /// Device to listen to
+    #[clap(short, long, default_value = "in")]
+    listen: Listen,
+    /// Address to serve the live stream on
+    #[clap(long, default_value = "0.0.0.0:8000")]
+    addr: String,
+}
+
+pub fn run(options: ServeOpts) -> Result<()> {
+    let device = match options.listen {
+        Listen::In => audiort::DeviceBuilder::new_default_input()?,
+        Listen::Out => audiort::DeviceBuilder::new_default_output()?,
+    };
+
+    if let Ok(name) = device.name() {
+        eprintln!("Listening to {name}");
+    }
+
+    let mut stream = audiort::StreamBuilder::new(device)?;
+    let sample_rate = stream.config().sample_rate().0;
+    let channels = stream.config().channels();
+
+    let clients = FanOut::default();
+    let fan_out = clients.clone();
+
+    stream.read(move |data| {
+        fan_out.send(|| {
+            data.iter()
+                .flat_map(|&value| {
+                    ((value.clamp(-1.0, 1.0) * f32::from(i16::MAX)) as i16).to_le_bytes()
+                })
+                .collect()
+        })
+    })?;
+
+    stream.play()?;
+
+    let server = tiny_http::Server::http(&options.addr).map_err(|err| anyhow::anyhow!(err))?;
+    eprintln!("Live stream on http://{}/stream", options.addr);
+
+    std::thread::spawn(move || {
+        for request in server.incoming_requests() {
+            let clients = clients.clone();
+            std::thread::spawn(move || handle(request, &clients, sample_rate, channels));
+        }
+    });
+
+    let (tx, rx) = mpsc::channel();
+    ctrlc::set_handler(move || {
+        let _ = tx.send(());
+    })?;
+
+    let _ = rx.recv();
+    stream.stop();
+
+    Ok(())
+}
+
+fn handle(request: Request, clients: &FanOut, sample_rate: u32, channels: u16) {
+    let path = request.url().split('?').next().unwrap_or_default();
+
+    let _ = match (request.method(), path) {
+        (Method::Get, "/") => request
+            .respond(Response::from_string(PAGE).with_header(header("Content-Type", "text/html"))),
+        (Method::Get, "/stream") => {
+            let reader = ChunkReader::new(wav_header(sample_rate, channels), clients.subscribe());
+
+            request.respond(Response::new(
+                200.into(),
+                vec![
+                    header("Content-Type", "audio/wav"),
+                    header("Cache-Control", "no-cache"),
+                ],
+                reader,
+                None,
+                None,
+            ))
+        }
+        _ => request.respond(Response::empty(404)),
+    };
+}
+
+fn header(field: &str, value: &str) -> Header {
+    Header::from_bytes(field, value).expect("static header is valid")
+}
+
+const PAGE: &str = r#"<!DOCTYPE html>
+<html>
+<head><title>audiort</title></head>
+<body>
+<audio src="/stream" controls autoplay></audio>
+</body>
+</html>
+"#;
@@ -0,0 +1,111 @@
+use crate::cli::record::segment_path;
+use anyhow::Result;
+use clap::Args;
+use std::path::PathBuf;
+
+#[derive(Args)]
+pub struct SplitOpts {
+    /// WAV file to split
+    input: PathBuf,
+    /// What counts as a gap between tracks: a level it stays under and for
+    /// how long, e.g. `-45dB,2s`
+    #[clap(long, value_parser = parse_silence, allow_hyphen_values = true)]
+    silence: Silence,
+    /// Name for the tracks, which get numbered from 1 [default: the input's]
+    #[clap(short, long)]
+    output: Option<String>,
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct Silence {
+    threshold_dbfs: f32,
+    seconds: f64,
+}
+
+pub fn run(options: SplitOpts) -> Result<()> {
+    let reader = hound::WavReader::open(&options.input)
+        .map_err(|err| anyhow::anyhow!("{err}: {}", options.input.display()))?;
+    let sample_rate = reader.spec().sample_rate;
+    let duration = u64::from(reader.duration());
+
+    drop(reader);
+
+    let min_frames = (options.silence.seconds * f64::from(sample_rate)).round() as u64;
+    let silences = audiort::find_silences(
+        &options.input,
+        options.silence.threshold_dbfs,
+        min_frames.max(1),
+    )?;
+
+    // Cut in the middle of each gap between sounds, leaving quiet at the
+    // start and end of the file where it is
+    let cuts: Vec<u64> = silences
+        .iter()
+        .filter(|silence| silence.start > 0 && silence.end < duration)
+        .map(|silence| (silence.start + silence.end) / 2)
+        .collect();
+
+    if cuts.is_empty() {
+        println!("No gaps found in {}", options.input.display());
+        return Ok(());
+    }
+
+    let output = options
+        .output
+        .unwrap_or_else(|| options.input.to_string_lossy().into_owned());
+    let seconds = |frame: u64| frame as f64 / f64::from(sample_rate);
+
+    let starts = std::iter::once(0).chain(cuts.iter().copied());
+    let ends = cuts.iter().copied().chain(std::iter::once(duration));
+
+    for (index, (start, end)) in starts.zip(ends).enumerate() {
+        let path = segment_path(&output, index + 1);
+
+        audiort::trim_wav(&options.input, &path, start, end)?;
+
+        println!(
+            "{:>3}  {:>10.3}s  {:>10.3}s  {path}",
+            index + 1,
+            seconds(start),
+            seconds(end)
+        );
+    }
+
+    let cuts: Vec<String> = cuts
+        .iter()
+        .map(|&frame| format!("{frame} ({:.3}s)", seconds(frame)))
+        .collect();
+
+    println!("Cut at frames {}", cuts.join(", "));
+
+    Ok(())
+}
+
+fn parse_silence(s: &str) -> Result<Silence, String> {
+    let invalid = || format!("invalid silence `{s}`, expected e.g. `-45dB,2s`");
+
+    let (level, duration) = s.split_once(',').ok_or_else(invalid)?;
+
+    let level = level.trim().to_lowercase();
+    let level = level.trim_end_matches("fs").trim_end_matches("db");
+    let threshold_dbfs = level.trim().parse().map_err(|_| invalid())?;
+
+    let duration = duration.trim().to_lowercase();
+    let seconds: f64 = match duration.strip_suffix("ms") {
+        Some(millis) => millis.trim().parse::<f64>().map_err(|_| invalid())? / 1000.0,
+        None => duration
+            .trim_end_matches('s')
+            .trim()
+            .parse()
+            .map_err(|_| invalid())?,
+    };
+
+    if seconds <= 0.0 {
+        return Err(invalid());
+    }
+
+    Ok(Silence {
+        threshold_dbfs,
+        seconds,
+    })
+}
@@ -0,0 +1,451 @@
+use anyhow::bail;
+use anyhow::Result;
+use std::collections::VecDeque;
+use std::net::IpAddr;
+use std::net::SocketAddr;
+use std::net::ToSocketAddrs;
+use std::net::UdpSocket;
+use std::sync::mpsc;
+use std::thread::JoinHandle;
+use std::time::Duration;
+use std::time::Instant;
+use std::time::SystemTime;
+use std::time::UNIX_EPOCH;
+
+/// The conventional SRT payload size (7 MPEG-TS packets).
+const PAYLOAD_SIZE: usize = 1316;
+
+/// Sent packets kept around for retransmission.
+const SEND_BUFFER: usize = 1024;
+
+/// Receiver and sender latency requested in the handshake.
+const LATENCY_MS: u16 = 120;
+
+const HANDSHAKE: u16 = 0;
+const KEEPALIVE: u16 = 1;
+const ACK: u16 = 2;
+const NAK: u16 = 3;
+const SHUTDOWN: u16 = 5;
+const ACKACK: u16 = 6;
+
+const INDUCTION: u32 = 1;
+const CONCLUSION: u32 = 0xFFFF_FFFF;
+
+const SRT_MAGIC: u16 = 0x4A17;
+const HSREQ: u16 = 1;
+
+// TSBPD send/receive, too-late packet drop, periodic NAK, retransmit flag
+const SRT_FLAGS: u32 = 0x01 | 0x02 | 0x08 | 0x10 | 0x20;
+const SRT_VERSION: u32 = 0x0001_0501;
+
+const SEQ_MASK: u32 = 0x7FFF_FFFF;
+
+/// Sends audio as raw 16-bit little-endian PCM over an SRT live-mode
+/// connection, calling the given `host:port`.
+pub struct SrtSender {
+    tx: Option<mpsc::SyncSender<Vec<u8>>>,
+    worker: Option<JoinHandle<()>>,
+}
+
+impl SrtSender {
+    pub fn new(addr: &str, config: &cpal::SupportedStreamConfig) -> Result<SrtSender> {
+        let peer = addr
+            .to_socket_addrs()?
+            .next()
+            .ok_or_else(|| anyhow::anyhow!("could not resolve {addr}"))?;
+
+        let mut connection = Connection::connect(peer)?;
+
+        eprintln!(
+            "Sending SRT to {addr}: s16le {}Hz {} channels",
+            config.sample_rate().0,
+            config.channels()
+        );
+
+        let (tx, rx) = mpsc::sync_channel::<Vec<u8>>(256);
+
+        let worker = std::thread::spawn(move || {
+            let mut pending = Vec::with_capacity(PAYLOAD_SIZE * 2);
+
+            loop {
+                match rx.recv_timeout(Duration::from_millis(10)) {
+                    Ok(data) => {
+                        pending.extend_from_slice(&data);
+
+                        while pending.len() >= PAYLOAD_SIZE {
+                            let rest = pending.split_off(PAYLOAD_SIZE);
+                            connection.send(&pending);
+                            pending = rest;
+                        }
+                    }
+                    Err(mpsc::RecvTimeoutError::Timeout) => {}
+                    Err(mpsc::RecvTimeoutError::Disconnected) => break,
+                }
+
+                if let Err(err) = connection.poll() {
+                    eprintln!("Warning: SRT connection closed: {err}");
+                    return;
+                }
+            }
+
+            connection.shutdown();
+        });
+
+        Ok(SrtSender {
+            tx: Some(tx),
+            worker: Some(worker),
+        })
+    }
+
+    pub fn send(&mut self, data: &[f32]) {
+        let bytes = data
+            .iter()
+            .flat_map(|&value| {
+                ((value.clamp(-1.0, 1.0) * f32::from(i16::MAX)) as i16).to_le_bytes()
+            })
+            .collect();
+
+        // Drop audio rather than block the capture callback
+        if let Some(tx) = &self.tx {
+            let _ = tx.try_send(bytes);
+        }
+    }
+}
+
+impl Drop for SrtSender {
+    fn drop(&mut self) {
+        // Lets the worker send the final shutdown packet
+        self.tx.take();
+
+        if let Some(worker) = self.worker.take() {
+            let _ = worker.join();
+        }
+    }
+}
+
+struct Connection {
+    socket: UdpSocket,
+    start: Instant,
+    socket_id: u32,
+    peer_id: u32,
+    sequence: u32,
+    message: u32,
+    sent: VecDeque<(u32, Vec<u8>)>,
+    last_sent: Instant,
+}
+
+impl Connection {
+    fn connect(peer: SocketAddr) -> Result<Connection> {
+        let bind = if peer.is_ipv4() {
+            "0.0.0.0:0"
+        } else {
+            "[::]:0"
+        };
+
+        let socket = UdpSocket::bind(bind)?;
+        socket.connect(peer)?;
+        socket.set_read_timeout(Some(Duration::from_millis(250)))?;
+
+        let start = Instant::now();
+        let seed = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|d| d.subsec_nanos() ^ d.as_secs() as u32)
+            .unwrap_or_default()
+            ^ std::process::id().rotate_left(13);
+
+        let mut connection = Connection {
+            socket,
+            start,
+            socket_id: (seed | 1) & 0x3FFF_FFFF,
+            peer_id: 0,
+            sequence: seed.rotate_left(7) & SEQ_MASK,
+            message: 1,
+            sent: VecDeque::with_capacity(SEND_BUFFER),
+            last_sent: start,
+        };
+
+        let cookie = connection.handshake(peer.ip(), INDUCTION, 0, 4, 2)?.cookie;
+        let response = connection.handshake(peer.ip(), CONCLUSION, cookie, 5, HSREQ)?;
+
+        if response.kind != CONCLUSION {
+            bail!(
+                "SRT listener rejected the connection ({:#x})",
+                response.kind
+            );
+        }
+
+        connection.peer_id = response.socket_id;
+        connection
+            .socket
+            .set_read_timeout(Some(Duration::from_millis(1)))?;
+
+        Ok(connection)
+    }
+
+    fn handshake(
+        &mut self,
+        ip: IpAddr,
+        kind: u32,
+        cookie: u32,
+        version: u32,
+        extension: u16,
+    ) -> Result<Handshake> {
+        let cif = self.handshake_cif(ip, kind, cookie, version, extension);
+        let mut buf = [0u8; 1500];
+
+        for _ in 0..12 {
+            self.control(HANDSHAKE, 0, &cif);
+
+            let Ok(len) = self.socket.recv(&mut buf) else {
+                continue;
+            };
+
+            if let Some(handshake) = Handshake::parse(&buf[..len]) {
+                // A repeated induction reply means our conclusion was lost
+                if kind == INDUCTION || handshake.kind != INDUCTION {
+                    if kind == INDUCTION && handshake.extension != SRT_MAGIC {
+                        bail!("peer does not speak SRT version 5");
+                    }
+
+                    return Ok(handshake);
+                }
+            }
+        }
+
+        bail!("no SRT handshake response")
+    }
+
+    /// The handshake's fields, with the HSREQ extension for a conclusion.
+    fn handshake_cif(
+        &self,
+        ip: IpAddr,
+        kind: u32,
+        cookie: u32,
+        version: u32,
+        extension: u16,
+    ) -> Vec<u8> {
+        let mut cif = Vec::with_capacity(64);
+        cif.extend_from_slice(&version.to_be_bytes());
+        cif.extend_from_slice(&0u16.to_be_bytes());
+        cif.extend_from_slice(&extension.to_be_bytes());
+        cif.extend_from_slice(&self.sequence.to_be_bytes());
+        cif.extend_from_slice(&1500u32.to_be_bytes());
+        cif.extend_from_slice(&8192u32.to_be_bytes());
+        cif.extend_from_slice(&kind.to_be_bytes());
+        cif.extend_from_slice(&self.socket_id.to_be_bytes());
+        cif.extend_from_slice(&cookie.to_be_bytes());
+        cif.extend_from_slice(&peer_ip(ip));
+
+        if version == 5 {
+            let latency = u32::from(LATENCY_MS);
+
+            cif.extend_from_slice(&HSREQ.to_be_bytes());
+            cif.extend_from_slice(&3u16.to_be_bytes());
+            cif.extend_from_slice(&SRT_VERSION.to_be_bytes());
+            cif.extend_from_slice(&SRT_FLAGS.to_be_bytes());
+            cif.extend_from_slice(&(latency << 16 | latency).to_be_bytes());
+        }
+
+        cif
+    }
+
+    fn send(&mut self, payload: &[u8]) {
+        let mut packet = Vec::with_capacity(16 + payload.len());
+        packet.extend_from_slice(&self.sequence.to_be_bytes());
+        // Solo packet, no encryption, not retransmitted
+        packet.extend_from_slice(&(0xC000_0000 | self.message).to_be_bytes());
+        packet.extend_from_slice(&self.timestamp().to_be_bytes());
+        packet.extend_from_slice(&self.peer_id.to_be_bytes());
+        packet.extend_from_slice(payload);
+
+        let _ = self.socket.send(&packet);
+        self.last_sent = Instant::now();
+
+        if self.sent.len() == SEND_BUFFER {
+            self.sent.pop_front();
+        }
+
+        self.sent.push_back((self.sequence, packet));
+        self.sequence = (self.sequence + 1) & SEQ_MASK;
+        self.message = (self.message + 1) & 0x03FF_FFFF;
+    }
+
+    /// Handle control packets from the receiver and keep the link alive.
+    fn poll(&mut self) -> Result<()> {
+        let mut buf = [0u8; 1500];
+
+        while let Ok(len) = self.socket.recv(&mut buf) {
+            let packet = &buf[..len];
+
+            if len < 16 || packet[0] & 0x80 == 0 {
+                continue;
+            }
+
+            let kind = u16::from_be_bytes([packet[0], packet[1]]) & 0x7FFF;
+            let info = u32::from_be_bytes([packet[4], packet[5], packet[6], packet[7]]);
+            let cif = &packet[16..];
+
+            match kind {
+                ACK => {
+                    if info != 0 {
+                        self.control(ACKACK, info, &[]);
+                    }
+
+                    if let Some(acked) = word(cif, 0) {
+                        self.sent.retain(|(seq, _)| !seq_before(*seq, acked));
+                    }
+                }
+                NAK => {
+                    let mut words = cif
+                        .chunks_exact(4)
+                        .map(|w| u32::from_be_bytes([w[0], w[1], w[2], w[3]]));
+
+                    while let Some(first) = words.next() {
+                        let (from, to) = if first & 0x8000_0000 != 0 {
+                            (first & SEQ_MASK, words.next().unwrap_or(first) & SEQ_MASK)
+                        } else {
+                            (first, first)
+                        };
+
+                        self.retransmit(from, to);
+                    }
+                }
+                SHUTDOWN => bail!("receiver shut down"),
+                _ => {}
+            }
+        }
+
+        if self.last_sent.elapsed() > Duration::from_secs(1) {
+            self.control(KEEPALIVE, 0, &[]);
+        }
+
+        Ok(())
+    }
+
+    fn retransmit(&mut self, from: u32, to: u32) {
+        for (seq, packet) in self.sent.iter_mut() {
+            if !seq_before(*seq, from) && !seq_before(to, *seq) {
+                // Set the retransmitted flag
+                packet[4] |= 0x04;
+                let _ = self.socket.send(packet);
+            }
+        }
+
+        self.last_sent = Instant::now();
+    }
+
+    fn shutdown(&mut self) {
+        self.control(SHUTDOWN, 0, &[0; 4]);
+    }
+
+    fn control(&mut self, kind: u16, info: u32, cif: &[u8]) {
+        let packet = self.control_packet(kind, info, cif);
+
+        let _ = self.socket.send(&packet);
+        self.last_sent = Instant::now();
+    }
+
+    fn control_packet(&self, kind: u16, info: u32, cif: &[u8]) -> Vec<u8> {
+        let mut packet = Vec::with_capacity(16 + cif.len());
+        packet.extend_from_slice(&(0x8000 | kind).to_be_bytes());
+        packet.extend_from_slice(&0u16.to_be_bytes());
+        packet.extend_from_slice(&info.to_be_bytes());
+        packet.extend_from_slice(&self.timestamp().to_be_bytes());
+        packet.extend_from_slice(&self.peer_id.to_be_bytes());
+        packet.extend_from_slice(cif);
+
+        packet
+    }
+
+    fn timestamp(&self) -> u32 {
+        self.start.elapsed().as_micros() as u32
+    }
+}
+
+struct Handshake {
+    extension: u16,
+    kind: u32,
+    socket_id: u32,
+    cookie: u32,
+}
+
+impl Handshake {
+    fn parse(packet: &[u8]) -> Option<Handshake> {
+        if packet.len() < 64 || u16::from_be_bytes([packet[0], packet[1]]) != 0x8000 | HANDSHAKE {
+            return None;
+        }
+
+        let cif = &packet[16..];
+
+        Some(Handshake {
+            extension: u16::from_be_bytes([cif[6], cif[7]]),
+            kind: word(cif, 5)?,
+            socket_id: word(cif, 6)?,
+            cookie: word(cif, 7)?,
+        })
+    }
+}
+
+fn word(data: &[u8], index: usize) -> Option<u32> {
+    let bytes = data.get(index * 4..index * 4 + 4)?;
+    Some(u32::from_be_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]))
+}
+
+/// Whether sequence number `a` comes before `b`, allowing for wraparound.
+fn seq_before(a: u32, b: u32) -> bool {
+    let diff = b.wrapping_sub(a) & SEQ_MASK;
+    diff != 0 && diff < 0x4000_0000
+}
+
+/// The peer address as libsrt lays it out: 32-bit words in host order.
+fn peer_ip(ip: IpAddr) -> [u8; 16] {
+    let mut field = [0u8; 16];
+
+    match ip {
+        IpAddr::V4(ip) => {
+            field[..4].copy_from_slice(&u32::from_be_bytes(ip.octets()).to_le_bytes())
+        }
+        IpAddr::V6(ip) => {
+            for (i, chunk) in ip.octets().chunks_exact(4).enumerate() {
+                let value = u32::from_be_bytes([chunk[0], chunk[1], chunk[2], chunk[3]]);
+                field[i * 4..i * 4 + 4].copy_from_slice(&value.to_le_bytes());
+            }
+        }
+    }
+
+    field
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::net::Ipv4Addr;
+
+    #[test]
+    fn handshake_round_trip() {
+        let start = Instant::now();
+        let connection = Connection {
+            socket: UdpSocket::bind("127.0.0.1:0").unwrap(),
+            start,
+            socket_id: 0x1234_5678,
+            peer_id: 0,
+            sequence: 42,
+            message: 1,
+            sent: VecDeque::new(),
+            last_sent: start,
+        };
+
+        let ip = IpAddr::V4(Ipv4Addr::new(192, 0, 2, 1));
+
+        for (kind, version, extension) in [(INDUCTION, 4, 2), (CONCLUSION, 5, HSREQ)] {
+            let cif = connection.handshake_cif(ip, kind, 0xC00C_1E00, version, extension);
+            let packet = connection.control_packet(HANDSHAKE, 0, &cif);
+            let handshake = Handshake::parse(&packet).unwrap();
+
+            assert_eq!(handshake.extension, extension);
+            assert_eq!(handshake.kind, kind);
+            assert_eq!(handshake.socket_id, 0x1234_5678);
+            assert_eq!(handshake.cookie, 0xC00C_1E00);
+        }
+    }
+}
@@ -0,0 +1,111 @@
+use anyhow::Result;
+use audiort::generator::Generator;
+use audiort::generator::Wave;
+use audiort::playback::Player;
+use clap::Args;
+use clap::ValueEnum;
+use std::sync::atomic::AtomicBool;
+use std::sync::atomic::Ordering;
+use std::sync::Arc;
+use std::time::Duration;
+
+#[derive(Args)]
+pub struct ToneOpts {
+    /// Signal to play
+    #[clap(long, value_enum, default_value = "sine")]
+    wave: WaveOpt,
+    /// Frequency in Hz, or where a sweep starts
+    #[clap(long, default_value = "1000")]
+    freq: f64,
+    /// Where a sweep ends, in Hz
+    #[clap(long, default_value = "20000")]
+    to: f64,
+    /// Peak level in dBFS
+    #[clap(long, default_value = "-12", allow_negative_numbers = true)]
+    level: f32,
+    /// Seconds to play for [default: until interrupted]
+    #[clap(short, long)]
+    duration: Option<f64>,
+}
+
+#[derive(ValueEnum, Clone, Copy, PartialEq)]
+enum WaveOpt {
+    Sine,
+    Square,
+    White,
+    Pink,
+    Sweep,
+}
+
+impl From<WaveOpt> for Wave {
+    fn from(wave: WaveOpt) -> Wave {
+        match wave {
+            WaveOpt::Sine => Wave::Sine,
+            WaveOpt::Square => Wave::Square,
+            WaveOpt::White => Wave::White,
+            WaveOpt::Pink => Wave::Pink,
+            WaveOpt::Sweep => Wave::Sweep,
+        }
+    }
+}
+
+pub fn run(options: ToneOpts) -> Result<()> {
+    if options.level > 0.0 {
+        anyhow::bail!("--level must be at most 0 dBFS");
+    }
+
+    let device = audiort::DeviceBuilder::new_default_output()?;
+
+    if let Ok(name) = device.name() {
+        eprintln!("Playing to {name}");
+    }
+
+    let sample_rate = device.config().sample_rate().0;
+    let channels = device.config().channels();
+    let player = Player::new(&device, sample_rate, channels)?;
+
+    let mut generator = Generator::new(options.wave.into(), sample_rate);
+    generator
+        .freq(options.freq)
+        .sweep(options.to, options.duration.unwrap_or(10.0))
+        .level(options.level);
+
+    let interrupted = Arc::new(AtomicBool::new(false));
+    let interrupt = Arc::clone(&interrupted);
+
+    ctrlc::set_handler(move || interrupt.store(true, Ordering::Relaxed))?;
+
+    let total = options
+        .duration
+        .map(|seconds| (seconds * f64::from(sample_rate)) as u64);
+
+    // Keep about 100ms queued ahead of the device
+    let ahead = sample_rate as usize / 10;
+    let mut buffer = vec![0.0; ahead / 4 * usize::from(channels)];
+    let mut played = 0u64;
+
+    player.play()?;
+
+    while !interrupted.load(Ordering::Relaxed) && total.is_none_or(|total| played < total) {
+        if player.queued() >= ahead {
+            std::thread::sleep(Duration::from_millis(10));
+            continue;
+        }
+
+        let frames = total.map_or(buffer.len() / usize::from(channels), |total| {
+            (total - played).min((buffer.len() / usize::from(channels)) as u64) as usize
+        });
+
+        let data = &mut buffer[..frames * usize::from(channels)];
+        generator.fill(data, channels);
+        player.push(data);
+        played += frames as u64;
+    }
+
+    // Let the tail play out
+    while !interrupted.load(Ordering::Relaxed) && player.queued() > 0 {
+        std::thread::sleep(Duration::from_millis(10));
+    }
+
+    Ok(())
+}
@@ -0,0 +1,55 @@
+use anyhow::Result;
+use clap::Args;
+use std::path::PathBuf;
+
+#[derive(Args)]
+pub struct TrimOpts {
+    /// WAV file to cut from
+    input: PathBuf,
+    /// Where to write the cut
+    output: PathBuf,
+    /// Where the cut starts (seconds)
+    #[clap(long, default_value = "0")]
+    start: f64,
+    /// Where the cut ends (seconds) [default: the end of the file]
+    #[clap(long)]
+    end: Option<f64>,
+}
+
+pub fn run(options: TrimOpts) -> Result<()> {
+    if options.start < 0.0 || options.end.is_some_and(|end| end <= options.start) {
+        anyhow::bail!("--end must come after --start, and --start can't be negative");
+    }
+
+    let reader = hound::WavReader::open(&options.input)
+        .map_err(|err| anyhow::anyhow!("{err}: {}", options.input.display()))?;
+    let sample_rate = reader.spec().sample_rate;
+    let duration = u64::from(reader.duration());
+
+    drop(reader);
+
+    // Times land on the nearest frame
+    let frame = |seconds: f64| (seconds * f64::from(sample_rate)).round() as u64;
+
+    let start = frame(options.start);
+    let end = options.end.map_or(duration, frame);
+
+    if start >= duration {
+        anyhow::bail!(
+            "--start is past the end of {} ({:.1}s)",
+            options.input.display(),
+            duration as f64 / f64::from(sample_rate)
+        );
+    }
+
+    let frames = audiort::trim_wav(&options.input, &options.output, start, end)?;
+
+    println!(
+        "Written to {} ({frames} frames, {:.3}s from {:.3}s)",
+        options.output.display(),
+        frames as f64 / f64::from(sample_rate),
+        start as f64 / f64::from(sample_rate)
+    );
+
+    Ok(())
+}
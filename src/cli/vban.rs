@@ -0,0 +1,157 @@
+use crate::cli::receive::Audio;
+use anyhow::bail;
+use anyhow::Result;
+use std::net::UdpSocket;
+
+pub const DEFAULT_PORT: u16 = 6980;
+
+/// VBAN sample rates, indexed by the header's rate field.
+const SAMPLE_RATES: [u32; 21] = [
+    6000, 12000, 24000, 48000, 96000, 192000, 384000, 8000, 16000, 32000, 64000, 128000, 256000,
+    512000, 11025, 22050, 44100, 88200, 176400, 352800, 705600,
+];
+
+const HEADER_SIZE: usize = 28;
+const MAX_PAYLOAD: usize = 1436;
+const MAX_FRAMES: usize = 256;
+
+const FORMAT_INT16: u8 = 1;
+
+/// Sends audio as a VBAN stream of 16-bit PCM, as understood by Voicemeeter.
+pub struct VbanSender {
+    socket: UdpSocket,
+    header: [u8; HEADER_SIZE],
+    channels: usize,
+    frame_counter: u32,
+    packet: Vec<u8>,
+}
+
+impl VbanSender {
+    pub fn new(
+        addr: &str,
+        stream_name: &str,
+        config: &cpal::SupportedStreamConfig,
+    ) -> Result<VbanSender> {
+        let sample_rate = config.sample_rate().0;
+        let channels = config.channels();
+
+        let Some(rate_index) = SAMPLE_RATES.iter().position(|&rate| rate == sample_rate) else {
+            bail!("VBAN does not support a sample rate of {sample_rate}Hz");
+        };
+
+        if !(1..=256).contains(&channels) {
+            bail!("VBAN does not support {channels} channels");
+        }
+
+        if stream_name.len() > 16 || !stream_name.is_ascii() {
+            bail!("VBAN stream names are at most 16 ASCII characters");
+        }
+
+        let socket = UdpSocket::bind("0.0.0.0:0")?;
+        socket.set_broadcast(true)?;
+
+        if addr.contains(':') {
+            socket.connect(addr)?;
+        } else {
+            socket.connect((addr, DEFAULT_PORT))?;
+        }
+
+        // Never stall the capture callback on a full socket buffer
+        socket.set_nonblocking(true)?;
+
+        let mut header = [0u8; HEADER_SIZE];
+        header[..4].copy_from_slice(b"VBAN");
+        // Audio sub-protocol is 0 in the upper bits
+        header[4] = rate_index as u8;
+        header[6] = (channels - 1) as u8;
+        header[7] = FORMAT_INT16;
+        header[8..8 + stream_name.len()].copy_from_slice(stream_name.as_bytes());
+
+        eprintln!("Sending VBAN stream {stream_name} to {addr}");
+
+        Ok(VbanSender {
+            socket,
+            header,
+            channels: usize::from(channels),
+            frame_counter: 0,
+            packet: Vec::with_capacity(HEADER_SIZE + MAX_PAYLOAD),
+        })
+    }
+
+    pub fn send(&mut self, data: &[f32]) {
+        let frames_per_packet = (MAX_PAYLOAD / 2 / self.channels).clamp(1, MAX_FRAMES);
+
+        for chunk in data.chunks(frames_per_packet * self.channels) {
+            let frames = chunk.len() / self.channels;
+
+            if frames == 0 {
+                continue;
+            }
+
+            self.packet.clear();
+            self.packet.extend_from_slice(&self.header);
+            self.packet[5] = (frames - 1) as u8;
+            self.packet[24..28].copy_from_slice(&self.frame_counter.to_le_bytes());
+
+            for &value in &chunk[..frames * self.channels] {
+                let sample = (value.clamp(-1.0, 1.0) * f32::from(i16::MAX)) as i16;
+                self.packet.extend_from_slice(&sample.to_le_bytes());
+            }
+
+            let _ = self.socket.send(&self.packet);
+
+            self.frame_counter = self.frame_counter.wrapping_add(1);
+        }
+    }
+}
+
+/// Decode a VBAN audio packet. Returns `None` for other sub-protocols and
+/// codecs.
+pub fn decode(packet: &[u8]) -> Option<Audio> {
+    if packet.len() < HEADER_SIZE || &packet[..4] != b"VBAN" || packet[4] & 0xE0 != 0 {
+        return None;
+    }
+
+    // Only uncompressed PCM
+    if packet[7] & 0xF0 != 0 {
+        return None;
+    }
+
+    let sample_rate = *SAMPLE_RATES.get(usize::from(packet[4] & 0x1F))?;
+    let channels = u16::from(packet[6]) + 1;
+    let data = &packet[HEADER_SIZE..];
+
+    let samples = match packet[7] & 0x07 {
+        0 => data
+            .iter()
+            .map(|&b| (f32::from(b) - 128.0) / 128.0)
+            .collect(),
+        1 => data
+            .chunks_exact(2)
+            .map(|b| f32::from(i16::from_le_bytes([b[0], b[1]])) / 32768.0)
+            .collect(),
+        2 => data
+            .chunks_exact(3)
+            .map(|b| i32::from_le_bytes([0, b[0], b[1], b[2]]) as f32 / 2_147_483_648.0)
+            .collect(),
+        3 => data
+            .chunks_exact(4)
+            .map(|b| i32::from_le_bytes([b[0], b[1], b[2], b[3]]) as f32 / 2_147_483_648.0)
+            .collect(),
+        4 => data
+            .chunks_exact(4)
+            .map(|b| f32::from_le_bytes([b[0], b[1], b[2], b[3]]))
+            .collect(),
+        5 => data
+            .chunks_exact(8)
+            .map(|b| f64::from_le_bytes(b.try_into().unwrap_or_default()) as f32)
+            .collect(),
+        _ => return None,
+    };
+
+    Some(Audio {
+        sample_rate,
+        channels,
+        samples,
+    })
+}
@@ -0,0 +1,50 @@
+use anyhow::Result;
+use audiort::checksum::Algorithm;
+use audiort::checksum::Checksum;
+use audiort::metadata::Sidecar;
+use clap::Args;
+use std::path::PathBuf;
+
+#[derive(Args)]
+pub struct VerifyOpts {
+    /// Recording made with `record --checksum`
+    file: PathBuf,
+}
+
+pub fn run(options: VerifyOpts) -> Result<()> {
+    let sidecar = Sidecar::path(&options.file);
+
+    let json = std::fs::read_to_string(&sidecar)
+        .map_err(|err| anyhow::anyhow!("{err}: {}", sidecar.display()))?;
+    let json: serde_json::Value = serde_json::from_str(&json)
+        .map_err(|err| anyhow::anyhow!("{err}: {}", sidecar.display()))?;
+
+    let checksum = &json["checksum"];
+
+    let (Some(algorithm), Some(expected)) =
+        (checksum["algorithm"].as_str(), checksum["value"].as_str())
+    else {
+        anyhow::bail!(
+            "{} has no checksum; record with --checksum",
+            sidecar.display()
+        );
+    };
+
+    let algorithm: Algorithm = algorithm.parse().map_err(|err| anyhow::anyhow!("{err}"))?;
+
+    let actual = Checksum::of_file(&options.file, algorithm)
+        .map_err(|err| anyhow::anyhow!("{err}: {}", options.file.display()))?
+        .hex();
+
+    if actual.eq_ignore_ascii_case(expected) {
+        println!("{}: OK ({})", options.file.display(), algorithm.name());
+
+        Ok(())
+    } else {
+        anyhow::bail!(
+            "{}: checksum mismatch, expected {} {expected}, got {actual}",
+            options.file.display(),
+            algorithm.name()
+        )
+    }
+}
@@ -0,0 +1,60 @@
+use serde_json::json;
+use std::sync::mpsc;
+use std::thread::JoinHandle;
+use std::time::Duration;
+use std::time::SystemTime;
+
+/// Posts recording events as JSON from a background thread, in order.
+pub struct Webhook {
+    tx: Option<mpsc::Sender<serde_json::Value>>,
+    worker: Option<JoinHandle<()>>,
+}
+
+impl Webhook {
+    pub fn new(url: String) -> Webhook {
+        let (tx, rx) = mpsc::channel::<serde_json::Value>();
+
+        let worker = std::thread::spawn(move || {
+            for event in rx {
+                let result = ureq::post(&url)
+                    .timeout(Duration::from_secs(10))
+                    .set("Content-Type", "application/json")
+                    .send_string(&event.to_string());
+
+                if let Err(err) = result {
+                    eprintln!("Warning: webhook request failed: {err}");
+                }
+            }
+        });
+
+        Webhook {
+            tx: Some(tx),
+            worker: Some(worker),
+        }
+    }
+
+    pub fn send(&self, event: &str, mut data: serde_json::Value) {
+        if let Some(fields) = data.as_object_mut() {
+            fields.insert("event".into(), json!(event));
+            fields.insert(
+                "timestamp".into(),
+                json!(audiort::metadata::format_timestamp(SystemTime::now())),
+            );
+        }
+
+        if let Some(tx) = &self.tx {
+            let _ = tx.send(data);
+        }
+    }
+}
+
+impl Drop for Webhook {
+    fn drop(&mut self) {
+        // Deliver everything queued before the process exits
+        self.tx.take();
+
+        if let Some(worker) = self.worker.take() {
+            let _ = worker.join();
+        }
+    }
+}
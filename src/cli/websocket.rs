@@ -0,0 +1,61 @@
+use crate::cli::fanout::FanOut;
+use anyhow::Result;
+use serde_json::json;
+use std::net::TcpListener;
+use std::net::TcpStream;
+use tungstenite::Message;
+
+/// Pushes audio to WebSocket clients as binary frames of interleaved
+/// 32-bit float little-endian PCM. Each client first receives a text frame
+/// describing the format.
+pub struct WebSocketSender {
+    clients: FanOut,
+}
+
+impl WebSocketSender {
+    pub fn new(addr: &str, config: &cpal::SupportedStreamConfig) -> Result<WebSocketSender> {
+        let listener = TcpListener::bind(addr)?;
+        let clients = FanOut::default();
+        let fan_out = clients.clone();
+
+        let format = json!({
+            "format": "f32le",
+            "sample_rate": config.sample_rate().0,
+            "channels": config.channels(),
+        })
+        .to_string();
+
+        eprintln!("WebSocket PCM on ws://{addr}");
+
+        std::thread::spawn(move || {
+            for stream in listener.incoming().flatten() {
+                let fan_out = fan_out.clone();
+                let format = format.clone();
+                std::thread::spawn(move || serve_client(stream, &fan_out, format));
+            }
+        });
+
+        Ok(WebSocketSender { clients })
+    }
+
+    pub fn send(&mut self, data: &[f32]) {
+        self.clients
+            .send(|| data.iter().flat_map(|value| value.to_le_bytes()).collect())
+    }
+}
+
+fn serve_client(stream: TcpStream, clients: &FanOut, format: String) {
+    let Ok(mut socket) = tungstenite::accept(stream) else {
+        return;
+    };
+
+    if socket.send(Message::Text(format)).is_err() {
+        return;
+    }
+
+    for chunk in clients.subscribe() {
+        if socket.send(Message::Binary(chunk.to_vec())).is_err() {
+            break;
+        }
+    }
+}
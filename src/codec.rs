@@ -0,0 +1,98 @@
+//! Compressed formats a finished recording can be converted to.
+
+use crate::adpcm;
+use crate::g711;
+use crate::g711::Law;
+use crate::wavpack;
+use crate::Error;
+use std::path::Path;
+use std::str::FromStr;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Codec {
+    /// G.711, at 8 kHz
+    G711(Law),
+    /// IMA ADPCM, at the recording's own rate
+    ImaAdpcm,
+    /// AAC-LC in an M4A file
+    #[cfg(feature = "aac")]
+    Aac,
+    /// Lossless, or hybrid with a lossy file at `bitrate` kbps and a
+    /// correction file
+    WavPack { bitrate: Option<u32> },
+}
+
+impl Codec {
+    pub fn name(&self) -> &'static str {
+        match self {
+            Codec::G711(law) => law.name(),
+            Codec::ImaAdpcm => "adpcm",
+            #[cfg(feature = "aac")]
+            Codec::Aac => "aac",
+            Codec::WavPack { .. } => "wavpack",
+        }
+    }
+
+    /// The extension of files in this format.
+    pub fn extension(&self) -> &'static str {
+        match self {
+            #[cfg(feature = "aac")]
+            Codec::Aac => "m4a",
+            Codec::WavPack { .. } => "wv",
+            _ => "wav",
+        }
+    }
+
+    /// Convert the WAV file at `from` to one at `to` in this format, with
+    /// its tags where the format can hold them.
+    pub fn encode_wav<P, Q>(self, from: P, to: Q) -> Result<(), Error>
+    where
+        P: AsRef<Path>,
+        Q: AsRef<Path>,
+    {
+        match self {
+            Codec::G711(law) => g711::encode_wav(&from, &to, law)?,
+            Codec::ImaAdpcm => adpcm::encode_wav(&from, &to)?,
+            #[cfg(feature = "aac")]
+            Codec::Aac => return crate::aac::encode_m4a(from, to),
+            // WavPack stores the whole WAV file, tag chunks included
+            Codec::WavPack { bitrate } => return wavpack::encode_wv(from, to, bitrate),
+        }
+
+        crate::copy_tags(from, to)
+    }
+}
+
+impl FromStr for Codec {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let expected = match cfg!(feature = "aac") {
+            true => "alaw, ulaw, adpcm, aac or wavpack[:KBPS]",
+            false => "alaw, ulaw, adpcm or wavpack[:KBPS]",
+        };
+
+        if let Some(("wavpack" | "wv", bitrate)) = s.split_once(':') {
+            return match bitrate.parse() {
+                // Lower `-b` values are bits per sample to `wavpack`
+                Ok(bitrate) if bitrate >= 24 => Ok(Codec::WavPack {
+                    bitrate: Some(bitrate),
+                }),
+                _ => Err(format!(
+                    "invalid WavPack bitrate `{bitrate}`, expected kbps from 24"
+                )),
+            };
+        }
+
+        match s.to_lowercase().replace('-', "").as_str() {
+            "adpcm" | "ima" | "imaadpcm" => Ok(Codec::ImaAdpcm),
+            #[cfg(feature = "aac")]
+            "aac" | "m4a" => Ok(Codec::Aac),
+            "wavpack" | "wv" => Ok(Codec::WavPack { bitrate: None }),
+            _ => s
+                .parse()
+                .map(Codec::G711)
+                .map_err(|_| format!("unknown format `{s}`, expected {expected}")),
+        }
+    }
+}
@@ -0,0 +1,104 @@
+//! The user's configuration file, `audiort/config.toml` in the config
+//! directory (e.g. `~/.config`), or wherever `AUDIORT_CONFIG` points:
+//!
+//! ```toml
+//! [aliases]
+//! mic = "Scarlett 2i2 USB"
+//! speakers = "2"
+//! ```
+//!
+//! Only tables of string values are read, which is all it holds.
+
+use crate::Error;
+use std::collections::BTreeMap;
+use std::path::Path;
+use std::path::PathBuf;
+
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Config {
+    /// Names for devices, standing for anything `DeviceBuilder::open` takes
+    pub aliases: BTreeMap<String, String>,
+}
+
+impl Config {
+    /// Where the configuration file is looked for.
+    pub fn path() -> Option<PathBuf> {
+        if let Some(path) = std::env::var_os("AUDIORT_CONFIG") {
+            return Some(path.into());
+        }
+
+        let dir = std::env::var_os("XDG_CONFIG_HOME")
+            .map(PathBuf::from)
+            .or_else(|| std::env::var_os("APPDATA").map(PathBuf::from))
+            .or_else(|| std::env::var_os("HOME").map(|home| Path::new(&home).join(".config")))?;
+
+        Some(dir.join("audiort").join("config.toml"))
+    }
+
+    /// The configuration file, or the defaults without one.
+    pub fn load() -> Result<Config, Error> {
+        match Config::path() {
+            Some(path) if path.exists() => Config::read(path),
+            _ => Ok(Config::default()),
+        }
+    }
+
+    pub fn read<P>(path: P) -> Result<Config, Error>
+    where
+        P: AsRef<Path>,
+    {
+        let text = std::fs::read_to_string(path).or(Err(Error::ConfigError))?;
+        text.parse()
+    }
+
+    /// What `spec` stands for, if it's an alias, or else `spec` itself.
+    pub fn resolve<'a>(&'a self, spec: &'a str) -> &'a str {
+        self.aliases.get(spec).map_or(spec, String::as_str)
+    }
+}
+
+impl std::str::FromStr for Config {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut config = Config::default();
+        let mut table = String::new();
+
+        for line in s.lines() {
+            let line = line.trim();
+
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+
+            if let Some(name) = line
+                .strip_prefix('[')
+                .and_then(|line| line.strip_suffix(']'))
+            {
+                table = name.trim().to_owned();
+                continue;
+            }
+
+            let (key, value) = line.split_once('=').ok_or(Error::ConfigError)?;
+            let key = unquote(key.trim()).unwrap_or(key.trim());
+            let value = unquote(value.trim()).ok_or(Error::ConfigError)?;
+
+            // Other tables are for other things, or newer versions
+            if table == "aliases" {
+                config.aliases.insert(key.to_owned(), value.to_owned());
+            }
+        }
+
+        Ok(config)
+    }
+}
+
+/// A quoted string, without the quotes and any comment after it.
+fn unquote(value: &str) -> Option<&str> {
+    let quote = value.chars().next().filter(|&c| c == '"' || c == '\'')?;
+    let rest = &value[1..];
+    let end = rest.find(quote)?;
+    let after = rest[end + 1..].trim();
+
+    (after.is_empty() || after.starts_with('#')).then_some(&rest[..end])
+}
@@ -0,0 +1,332 @@
+//! Compressed audio files, read wherever WAV files are: FLAC, MP3 and Ogg
+//! Vorbis by symphonia, and Opus by libopus with the `opus` feature.
+
+use crate::Error;
+use hound::WavSpec;
+use std::fs::File;
+use std::io::Read;
+use std::path::Path;
+use symphonia::core::audio::SampleBuffer;
+use symphonia::core::codecs::DecoderOptions;
+use symphonia::core::codecs::CODEC_TYPE_NULL;
+use symphonia::core::formats::FormatOptions;
+use symphonia::core::formats::FormatReader;
+use symphonia::core::io::MediaSourceStream;
+use symphonia::core::meta::MetadataOptions;
+use symphonia::core::probe::Hint;
+
+/// Whether the file at `path` isn't a RIFF WAV file, by how it starts.
+pub fn is_compressed<P>(path: P) -> bool
+where
+    P: AsRef<Path>,
+{
+    let mut magic = [0u8; 4];
+
+    let read = File::open(path).and_then(|mut file| file.read_exact(&mut magic));
+    read.is_ok() && !matches!(&magic, b"RIFF" | b"RF64" | b"BW64")
+}
+
+/// The spec of the file at `path` and its samples as 64-bit floats. The
+/// spec gives the bits the file was encoded from, where it says, else 16.
+pub fn samples<P>(path: P) -> Result<(WavSpec, Decoded), Error>
+where
+    P: AsRef<Path>,
+{
+    let path = path.as_ref();
+    let file = File::open(path).or(Err(Error::ReadError))?;
+    let stream = MediaSourceStream::new(Box::new(file), Default::default());
+
+    let mut hint = Hint::new();
+
+    if let Some(extension) = path.extension().and_then(|extension| extension.to_str()) {
+        hint.with_extension(extension);
+    }
+
+    let probed = symphonia::default::get_probe()
+        .format(
+            &hint,
+            stream,
+            &FormatOptions::default(),
+            &MetadataOptions::default(),
+        )
+        .or(Err(Error::ReadError))?;
+
+    let format = probed.format;
+    let track = format
+        .tracks()
+        .iter()
+        .find(|track| track.codec_params.codec != CODEC_TYPE_NULL)
+        .ok_or(Error::ReadError)?;
+
+    let params = track.codec_params.clone();
+    let track_id = track.id;
+
+    let channels = params
+        .channels
+        .map(|channels| channels.count() as u16)
+        .ok_or(Error::ReadError)?;
+
+    let spec = WavSpec {
+        channels,
+        sample_rate: params.sample_rate.ok_or(Error::ReadError)?,
+        bits_per_sample: params.bits_per_sample.map_or(16, |bits| bits as u16),
+        sample_format: hound::SampleFormat::Int,
+    };
+
+    #[cfg(feature = "opus")]
+    if params.codec == symphonia::core::codecs::CODEC_TYPE_OPUS {
+        let decoder = opus::Decoder::new(
+            spec.sample_rate,
+            match channels {
+                1 => opus::Channels::Mono,
+                2 => opus::Channels::Stereo,
+                _ => return Err(Error::StreamConfigFormatError),
+            },
+        )
+        .or(Err(Error::ReadError))?;
+
+        // The encoder's priming, from the `OpusHead` header
+        let skip = match params.extra_data.as_deref() {
+            Some([b'O', b'p', b'u', b's', b'H', b'e', b'a', b'd', _, _, low, high, ..]) => {
+                usize::from(u16::from_le_bytes([*low, *high]))
+            }
+            _ => 0,
+        };
+
+        let decoded = Decoded::new(format, track_id, Inner::Opus(decoder, channels.into()));
+        return Ok((spec, decoded.skip_samples(skip * usize::from(channels))));
+    }
+
+    let decoder = symphonia::default::get_codecs()
+        .make(&params, &DecoderOptions::default())
+        .or(Err(Error::ReadError))?;
+
+    let decoded = Decoded::new(format, track_id, Inner::Symphonia(decoder));
+    Ok((spec, decoded))
+}
+
+enum Inner {
+    Symphonia(Box<dyn symphonia::core::codecs::Decoder>),
+    #[cfg(feature = "opus")]
+    /// With its channel count
+    Opus(opus::Decoder, usize),
+}
+
+/// Interleaved samples, decoded a packet at a time as they're asked for.
+pub struct Decoded {
+    format: Box<dyn FormatReader>,
+    track_id: u32,
+    decoder: Inner,
+    buffer: Vec<f32>,
+    next: usize,
+    /// Samples still to drop from the start
+    skip: usize,
+}
+
+impl Decoded {
+    fn new(format: Box<dyn FormatReader>, track_id: u32, decoder: Inner) -> Decoded {
+        Decoded {
+            format,
+            track_id,
+            decoder,
+            buffer: Vec::new(),
+            next: 0,
+            skip: 0,
+        }
+    }
+
+    #[cfg(feature = "opus")]
+    fn skip_samples(mut self, samples: usize) -> Decoded {
+        self.skip = samples;
+        self
+    }
+
+    /// Decode the next packet of the track into `buffer`, returning false at
+    /// the end.
+    fn decode(&mut self) -> Result<bool, Error> {
+        use symphonia::core::errors::Error as DecodeError;
+
+        loop {
+            let packet = match self.format.next_packet() {
+                Ok(packet) => packet,
+                Err(DecodeError::IoError(err))
+                    if err.kind() == std::io::ErrorKind::UnexpectedEof =>
+                {
+                    return Ok(false)
+                }
+                Err(_) => return Err(Error::ReadError),
+            };
+
+            if packet.track_id() != self.track_id {
+                continue;
+            }
+
+            self.buffer.clear();
+            self.next = 0;
+
+            match &mut self.decoder {
+                Inner::Symphonia(decoder) => match decoder.decode(&packet) {
+                    Ok(decoded) => {
+                        let mut samples =
+                            SampleBuffer::<f32>::new(decoded.capacity() as u64, *decoded.spec());
+                        samples.copy_interleaved_ref(decoded);
+                        self.buffer.extend_from_slice(samples.samples());
+                    }
+                    // A damaged packet is skipped, as players do
+                    Err(DecodeError::DecodeError(_)) => continue,
+                    Err(_) => return Err(Error::ReadError),
+                },
+                #[cfg(feature = "opus")]
+                Inner::Opus(decoder, channels) => {
+                    let channels = *channels;
+                    // The longest an Opus packet can be, 120ms at 48 kHz
+                    self.buffer.resize(5760 * channels, 0.0);
+
+                    let frames = decoder
+                        .decode_float(&packet.data, &mut self.buffer, false)
+                        .or(Err(Error::ReadError))?;
+
+                    self.buffer.truncate(frames * channels);
+                }
+            }
+
+            let skipped = self.skip.min(self.buffer.len());
+            self.next = skipped;
+            self.skip -= skipped;
+
+            if self.next < self.buffer.len() {
+                return Ok(true);
+            }
+        }
+    }
+}
+
+impl Iterator for Decoded {
+    type Item = Result<f64, Error>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.next >= self.buffer.len() {
+            match self.decode() {
+                Ok(true) => {}
+                Ok(false) => return None,
+                Err(err) => return Some(Err(err)),
+            }
+        }
+
+        let sample = self.buffer[self.next];
+        self.next += 1;
+
+        Some(Ok(f64::from(sample)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const BLOCK_FRAMES: usize = 256;
+
+    fn crc8(data: &[u8]) -> u8 {
+        data.iter().fold(0u8, |crc, &byte| {
+            (0..8).fold(crc ^ byte, |crc, _| match crc & 0x80 != 0 {
+                true => crc << 1 ^ 0x07,
+                false => crc << 1,
+            })
+        })
+    }
+
+    fn crc16(data: &[u8]) -> u16 {
+        data.iter().fold(0u16, |crc, &byte| {
+            (0..8).fold(crc ^ u16::from(byte) << 8, |crc, _| {
+                match crc & 0x8000 != 0 {
+                    true => crc << 1 ^ 0x8005,
+                    false => crc << 1,
+                }
+            })
+        })
+    }
+
+    /// A 48 kHz 16-bit stereo FLAC file, stored verbatim in 256 frame blocks.
+    fn flac(samples: &[i16]) -> Vec<u8> {
+        let frames = samples.len() / 2;
+        let mut file = b"fLaC".to_vec();
+
+        // The last metadata block, STREAMINFO, with no MD5
+        file.extend_from_slice(&[0x80, 0, 0, 34]);
+        file.extend_from_slice(&(BLOCK_FRAMES as u16).to_be_bytes());
+        file.extend_from_slice(&(BLOCK_FRAMES as u16).to_be_bytes());
+        file.extend_from_slice(&[0; 6]);
+        let info = 48_000u64 << 44 | 1 << 41 | 15 << 36 | frames as u64;
+        file.extend_from_slice(&info.to_be_bytes());
+        file.extend_from_slice(&[0; 16]);
+
+        for (index, block) in samples.chunks(BLOCK_FRAMES * 2).enumerate() {
+            // Fixed blocks of 256 frames at 48 kHz, two channels of 16 bits
+            let mut frame = vec![0xff, 0xf8, 0x8a, 0x18, index as u8];
+            frame.push(crc8(&frame));
+
+            for channel in 0..2 {
+                frame.push(0x02);
+
+                for sample in block.iter().skip(channel).step_by(2) {
+                    frame.extend_from_slice(&sample.to_be_bytes());
+                }
+            }
+
+            let crc = crc16(&frame);
+            frame.extend_from_slice(&crc.to_be_bytes());
+            file.extend(frame);
+        }
+
+        file
+    }
+
+    #[test]
+    fn decodes_flac() {
+        let samples: Vec<i16> = (0..BLOCK_FRAMES * 2 * 8)
+            .map(|index| (index as i16).wrapping_mul(97))
+            .collect();
+
+        let dir = std::env::temp_dir();
+        let path = dir.join(format!("audiort-decode-{}.flac", std::process::id()));
+        std::fs::write(&path, flac(&samples)).unwrap();
+
+        assert!(is_compressed(&path));
+
+        let (spec, decoded) = super::samples(&path).unwrap();
+        let decoded: Vec<f64> = decoded.map(Result::unwrap).collect();
+        std::fs::remove_file(&path).unwrap();
+
+        assert_eq!(spec.channels, 2);
+        assert_eq!(spec.sample_rate, 48_000);
+        assert_eq!(spec.bits_per_sample, 16);
+
+        let expected: Vec<f64> = samples
+            .iter()
+            .map(|&sample| f64::from(sample) / 32768.0)
+            .collect();
+        assert_eq!(decoded, expected);
+    }
+
+    #[test]
+    fn wav_is_not_compressed() {
+        let path = std::env::temp_dir().join(format!("audiort-decode-{}.wav", std::process::id()));
+        let spec = WavSpec {
+            channels: 1,
+            sample_rate: 8000,
+            bits_per_sample: 16,
+            sample_format: hound::SampleFormat::Int,
+        };
+
+        hound::WavWriter::create(&path, spec)
+            .unwrap()
+            .finalize()
+            .unwrap();
+
+        assert!(!is_compressed(&path));
+        std::fs::remove_file(&path).unwrap();
+
+        // Nor is a file that isn't there
+        assert!(!is_compressed(&path));
+    }
+}
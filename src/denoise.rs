@@ -0,0 +1,67 @@
+//! Voice noise suppression with RNNoise, through its Rust port nnnoiseless.
+
+use crate::effects::Effect;
+use crate::Error;
+use nnnoiseless::DenoiseState;
+use std::collections::VecDeque;
+
+/// The only rate the model was trained for.
+const SAMPLE_RATE: u32 = 48_000;
+
+/// RNNoise works on 16-bit sample values.
+const SCALE: f32 = i16::MAX as f32;
+
+struct Channel {
+    state: Box<DenoiseState<'static>>,
+    input: Vec<f32>,
+    output: VecDeque<f32>,
+}
+
+/// Removes steady background noise (fans, hum, hiss) from speech. Adds one
+/// RNNoise frame (10 ms) of delay.
+pub struct Denoise {
+    channels: Vec<Channel>,
+    frame: Vec<f32>,
+}
+
+impl Denoise {
+    /// RNNoise only runs at 48 kHz.
+    pub fn new(sample_rate: u32) -> Result<Denoise, Error> {
+        if sample_rate != SAMPLE_RATE {
+            return Err(Error::StreamConfigFormatError);
+        }
+
+        Ok(Denoise {
+            channels: Vec::new(),
+            frame: vec![0.0; DenoiseState::FRAME_SIZE],
+        })
+    }
+}
+
+impl Effect for Denoise {
+    fn process(&mut self, frames: &mut [f32], channels: usize) {
+        let channels = channels.max(1);
+
+        self.channels.resize_with(channels, || Channel {
+            state: DenoiseState::new(),
+            input: Vec::with_capacity(DenoiseState::FRAME_SIZE),
+            output: VecDeque::from(vec![0.0; DenoiseState::FRAME_SIZE]),
+        });
+
+        for frame in frames.chunks_mut(channels) {
+            for (value, channel) in frame.iter_mut().zip(self.channels.iter_mut()) {
+                channel.input.push(*value * SCALE);
+
+                if channel.input.len() == DenoiseState::FRAME_SIZE {
+                    channel.state.process_frame(&mut self.frame, &channel.input);
+                    channel.input.clear();
+                    channel
+                        .output
+                        .extend(self.frame.iter().map(|value| value / SCALE));
+                }
+
+                *value = channel.output.pop_front().unwrap_or(0.0);
+            }
+        }
+    }
+}
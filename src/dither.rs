@@ -0,0 +1,183 @@
+//! Dither for reducing samples to fewer bits, optionally noise shaped to
+//! move the added noise to where it's hardest to hear.
+
+use std::str::FromStr;
+
+/// Error feedback of the `Shibata` curve: a fixed, psychoacoustically
+/// weighted filter (Wannamaker's 9-tap), pushing noise above ~15 kHz. It is
+/// designed for 44.1 and 48 kHz; at higher rates it shapes less usefully.
+const SHAPED: [f32; 9] = [
+    2.412, -3.370, 3.937, -4.174, 3.353, -2.205, 1.281, -0.569, 0.0847,
+];
+
+/// How far each shaped error is followed, in steps; at most what dither
+/// and rounding add, so clipped samples can't make the filter run away.
+const MAX_ERROR: f32 = 1.5;
+
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub enum Dither {
+    /// Plain rounding
+    #[default]
+    None,
+    /// Triangular noise of one step each way, flat across the spectrum
+    Tpdf,
+    /// TPDF noise shaped towards high frequencies, like Shibata's curves;
+    /// quieter to the ear, at a little more CPU
+    Shibata,
+}
+
+impl Dither {
+    pub fn name(&self) -> &'static str {
+        match self {
+            Dither::None => "none",
+            Dither::Tpdf => "tpdf",
+            Dither::Shibata => "shibata",
+        }
+    }
+}
+
+impl FromStr for Dither {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "none" | "off" => Ok(Dither::None),
+            "tpdf" => Ok(Dither::Tpdf),
+            "shibata" | "shaped" => Ok(Dither::Shibata),
+            _ => Err(format!(
+                "unknown dither `{s}`, expected none, tpdf or shibata"
+            )),
+        }
+    }
+}
+
+/// Turns interleaved float samples into integers of a given size, with a
+/// `Dither`.
+pub struct Ditherer {
+    dither: Dither,
+    scale: f32,
+    channels: usize,
+    channel: usize,
+    /// The last errors of each channel, newest first
+    errors: Vec<[f32; SHAPED.len()]>,
+    random: u32,
+}
+
+impl Ditherer {
+    pub fn new(dither: Dither, bits: u16, channels: usize) -> Ditherer {
+        let channels = channels.max(1);
+
+        Ditherer {
+            dither,
+            scale: (1u64 << (bits.clamp(2, 32) - 1)) as f32,
+            channels,
+            channel: 0,
+            errors: vec![[0.0; SHAPED.len()]; channels],
+            random: 0x2545_f491,
+        }
+    }
+
+    /// The next sample, in `bits` bits.
+    pub fn quantize(&mut self, sample: f32) -> i32 {
+        let channel = self.channel;
+        self.channel = (self.channel + 1) % self.channels;
+
+        let value = sample.clamp(-1.0, 1.0) * self.scale;
+
+        let shaped = match self.dither {
+            Dither::None => return self.clip(value.round()),
+            Dither::Tpdf => {
+                let noise = self.tpdf();
+                return self.clip((value + noise).round());
+            }
+            Dither::Shibata => {
+                let errors = &self.errors[channel];
+                value
+                    - SHAPED
+                        .iter()
+                        .zip(errors)
+                        .map(|(coefficient, error)| coefficient * error)
+                        .sum::<f32>()
+            }
+        };
+
+        let noise = self.tpdf();
+        let out = self.clip((shaped + noise).round());
+
+        let errors = &mut self.errors[channel];
+        errors.rotate_right(1);
+        errors[0] = (out as f32 - shaped).clamp(-MAX_ERROR, MAX_ERROR);
+
+        out
+    }
+
+    fn clip(&self, value: f32) -> i32 {
+        value.clamp(-self.scale, self.scale - 1.0) as i32
+    }
+
+    /// Triangular noise from -1 to 1 step.
+    fn tpdf(&mut self) -> f32 {
+        self.uniform() + self.uniform()
+    }
+
+    /// From -0.5 to 0.5, by xorshift32.
+    fn uniform(&mut self) -> f32 {
+        self.random ^= self.random << 13;
+        self.random ^= self.random >> 17;
+        self.random ^= self.random << 5;
+
+        (self.random >> 8) as f32 / (1u32 << 24) as f32 - 0.5
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// 100.3 steps of 16 bits, between two levels.
+    const SAMPLE: f32 = 100.3 / 32768.0;
+
+    fn quantized(dither: Dither) -> Vec<i32> {
+        let mut ditherer = Ditherer::new(dither, 16, 1);
+        (0..100_000).map(|_| ditherer.quantize(SAMPLE)).collect()
+    }
+
+    fn mean(values: &[i32]) -> f64 {
+        values.iter().map(|&value| f64::from(value)).sum::<f64>() / values.len() as f64
+    }
+
+    #[test]
+    fn none_rounds() {
+        assert!(quantized(Dither::None).iter().all(|&value| value == 100));
+    }
+
+    #[test]
+    fn tpdf_stays_within_a_step_and_keeps_the_level() {
+        let values = quantized(Dither::Tpdf);
+
+        assert!(values.iter().all(|value| (99..=101).contains(value)));
+        assert!((mean(&values) - 100.3).abs() < 0.01);
+    }
+
+    #[test]
+    fn shibata_keeps_the_level() {
+        let values = quantized(Dither::Shibata);
+
+        // The most the feedback and the dither can add up to
+        let bound = SHAPED.iter().map(|c| c.abs()).sum::<f32>() * MAX_ERROR + 1.5;
+        assert!(values
+            .iter()
+            .all(|&value| (value as f32 - 100.3).abs() <= bound));
+        assert!((mean(&values) - 100.3).abs() < 0.01);
+    }
+
+    #[test]
+    fn full_scale_clips() {
+        let mut ditherer = Ditherer::new(Dither::Tpdf, 16, 2);
+
+        for _ in 0..1000 {
+            assert!((-32768..=32767).contains(&ditherer.quantize(1.0)));
+            assert!((-32768..=32767).contains(&ditherer.quantize(-1.0)));
+        }
+    }
+}
@@ -0,0 +1,123 @@
+//! How often a recording is flushed and synced to disk as it's written,
+//! trading how much a crash or power cut can lose against throughput and,
+//! on flash media, wear.
+
+use crate::Error;
+use std::fs::File;
+use std::path::Path;
+use std::path::PathBuf;
+use std::str::FromStr;
+use std::time::Duration;
+use std::time::Instant;
+
+/// When files are synced, waiting for the disk to have what was written.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub enum Fsync {
+    /// Left to the OS
+    #[default]
+    Never,
+    /// Once each file is finalized
+    OnSegment,
+    /// At every flush, as well as once each file is finalized
+    OnFlush,
+}
+
+impl Fsync {
+    pub fn name(&self) -> &'static str {
+        match self {
+            Fsync::Never => "never",
+            Fsync::OnSegment => "on-segment",
+            Fsync::OnFlush => "on-flush",
+        }
+    }
+}
+
+impl FromStr for Fsync {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().replace('_', "-").as_str() {
+            "never" => Ok(Fsync::Never),
+            "on-segment" => Ok(Fsync::OnSegment),
+            "on-flush" => Ok(Fsync::OnFlush),
+            _ => Err(format!(
+                "unknown fsync `{s}`, expected never, on-segment or on-flush"
+            )),
+        }
+    }
+}
+
+/// Keeps track of the file being written and when it was last flushed.
+pub(crate) struct Durability {
+    interval: Option<Duration>,
+    fsync: Fsync,
+    path: Option<PathBuf>,
+    flushed: Instant,
+}
+
+impl Default for Durability {
+    fn default() -> Self {
+        Durability {
+            interval: None,
+            fsync: Fsync::Never,
+            path: None,
+            flushed: Instant::now(),
+        }
+    }
+}
+
+impl Durability {
+    pub(crate) fn set_interval(&mut self, interval: Duration) {
+        self.interval = Some(interval);
+    }
+
+    pub(crate) fn set_fsync(&mut self, fsync: Fsync) {
+        self.fsync = fsync;
+    }
+
+    /// Move on to a newly started file, returning the previous one to sync
+    /// once it's finalized.
+    pub(crate) fn open(&mut self, path: &Path) -> Option<PathBuf> {
+        self.flushed = Instant::now();
+        self.path.replace(path.to_owned())
+    }
+
+    /// Done with the current file, returning it to sync once it's finalized.
+    pub(crate) fn close(&mut self) -> Option<PathBuf> {
+        self.path.take()
+    }
+
+    /// Whether the interval has passed since the last flush.
+    pub(crate) fn is_due(&self) -> bool {
+        self.interval
+            .is_some_and(|interval| self.flushed.elapsed() >= interval)
+    }
+
+    /// After a flush of the current file, sync it if that's the policy.
+    pub(crate) fn flushed(&mut self) -> Result<(), Error> {
+        self.flushed = Instant::now();
+
+        match (&self.path, self.fsync) {
+            (Some(path), Fsync::OnFlush) => sync(path),
+            _ => Ok(()),
+        }
+    }
+
+    /// After a file is finalized, sync it if that's the policy.
+    pub(crate) fn finished(&self, path: &Path) -> Result<(), Error> {
+        match self.fsync {
+            Fsync::Never => Ok(()),
+            Fsync::OnSegment | Fsync::OnFlush => sync(path),
+        }
+    }
+}
+
+/// Wait for the disk to have what's been written to `path`. Any handle will
+/// do, as it's the file that's synced, not what was written through it.
+fn sync(path: &Path) -> Result<(), Error> {
+    File::options()
+        .write(true)
+        .open(path)
+        .and_then(|file| file.sync_all())
+        .or(Err(Error::WriteError))
+}
@@ -0,0 +1,394 @@
+//! Processing applied to interleaved `f32` frames as they pass through a
+//! stream. Recording, `with_processor` and `Player` all run an `EffectChain`.
+
+use std::f64::consts::PI;
+use std::str::FromStr;
+use std::sync::Arc;
+use std::sync::Mutex;
+
+pub trait Effect: Send {
+    /// Process `frames` (interleaved, `channels` per frame) in place.
+    fn process(&mut self, frames: &mut [f32], channels: usize);
+}
+
+impl<F> Effect for F
+where
+    F: FnMut(&mut [f32], usize) + Send,
+{
+    fn process(&mut self, frames: &mut [f32], channels: usize) {
+        self(frames, channels)
+    }
+}
+
+/// Effects run in the order they were added.
+#[derive(Default)]
+pub struct EffectChain {
+    effects: Vec<Box<dyn Effect>>,
+}
+
+impl EffectChain {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn push<E>(&mut self, effect: E) -> &mut Self
+    where
+        E: Effect + 'static,
+    {
+        self.effects.push(Box::new(effect));
+        self
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.effects.is_empty()
+    }
+}
+
+impl Effect for EffectChain {
+    fn process(&mut self, frames: &mut [f32], channels: usize) {
+        for effect in self.effects.iter_mut() {
+            effect.process(frames, channels);
+        }
+    }
+}
+
+/// A chain shared with a running stream, so effects can be added while it
+/// plays.
+pub(crate) type SharedChain = Arc<Mutex<EffectChain>>;
+
+/// Linear gain.
+pub struct Gain(pub f32);
+
+impl Effect for Gain {
+    fn process(&mut self, frames: &mut [f32], _: usize) {
+        for value in frames.iter_mut() {
+            *value *= self.0;
+        }
+    }
+}
+
+/// First-order high-pass filter, e.g. to remove rumble and DC offset.
+pub struct HighPass {
+    coefficient: f32,
+    /// Previous input and output per channel
+    state: Vec<(f32, f32)>,
+}
+
+impl HighPass {
+    pub fn new(cutoff: f32, sample_rate: u32) -> HighPass {
+        let rc = 1.0 / (2.0 * std::f32::consts::PI * cutoff.max(f32::EPSILON));
+        let dt = 1.0 / sample_rate.max(1) as f32;
+
+        HighPass {
+            coefficient: rc / (rc + dt),
+            state: Vec::new(),
+        }
+    }
+}
+
+impl Effect for HighPass {
+    fn process(&mut self, frames: &mut [f32], channels: usize) {
+        let channels = channels.max(1);
+        self.state.resize(channels, (0.0, 0.0));
+
+        for frame in frames.chunks_mut(channels) {
+            for (value, (input, output)) in frame.iter_mut().zip(self.state.iter_mut()) {
+                *output = self.coefficient * (*output + *value - *input);
+                *input = *value;
+                *value = *output;
+            }
+        }
+    }
+}
+
+/// Noise gate: silences frames while the level stays under a threshold,
+/// opening instantly and closing once it has been quiet for `hold`.
+pub struct Gate {
+    threshold: f32,
+    hold_frames: usize,
+    quiet_frames: usize,
+}
+
+impl Gate {
+    pub fn new(threshold_dbfs: f32, sample_rate: u32) -> Gate {
+        Gate {
+            threshold: 10f32.powf(threshold_dbfs / 20.0),
+            // Long enough not to chop the tails of words
+            hold_frames: sample_rate as usize / 5,
+            quiet_frames: usize::MAX,
+        }
+    }
+}
+
+impl Effect for Gate {
+    fn process(&mut self, frames: &mut [f32], channels: usize) {
+        for frame in frames.chunks_mut(channels.max(1)) {
+            let loud = frame.iter().any(|value| value.abs() >= self.threshold);
+
+            self.quiet_frames = match loud {
+                true => 0,
+                false => self.quiet_frames.saturating_add(1),
+            };
+
+            if self.quiet_frames > self.hold_frames {
+                frame.fill(0.0);
+            }
+        }
+    }
+}
+
+/// Compressor: turns down what goes over a threshold by a ratio, following
+/// the loudest channel, then adds makeup gain.
+pub struct Compressor {
+    threshold: f32,
+    ratio: f32,
+    sample_rate: f32,
+    attack: f32,
+    release: f32,
+    makeup: f32,
+    /// Gain reduction wanted in dB, held at peaks, and that applied
+    held: f32,
+    reduction: f32,
+}
+
+impl Compressor {
+    /// Starts with a 10 ms attack, 100 ms release and no makeup gain.
+    pub fn new(threshold_dbfs: f32, ratio: f32, sample_rate: u32) -> Compressor {
+        let mut compressor = Compressor {
+            threshold: threshold_dbfs,
+            ratio: ratio.max(1.0),
+            sample_rate: sample_rate.max(1) as f32,
+            attack: 0.0,
+            release: 0.0,
+            makeup: 1.0,
+            held: 0.0,
+            reduction: 0.0,
+        };
+
+        compressor.attack(10.0).release(100.0);
+        compressor
+    }
+
+    pub fn threshold(&mut self, dbfs: f32) -> &mut Self {
+        self.threshold = dbfs;
+        self
+    }
+
+    pub fn ratio(&mut self, ratio: f32) -> &mut Self {
+        self.ratio = ratio.max(1.0);
+        self
+    }
+
+    /// How quickly it turns down, in milliseconds.
+    pub fn attack(&mut self, ms: f32) -> &mut Self {
+        self.attack = self.smoothing(ms);
+        self
+    }
+
+    /// How quickly it recovers, in milliseconds.
+    pub fn release(&mut self, ms: f32) -> &mut Self {
+        self.release = self.smoothing(ms);
+        self
+    }
+
+    pub fn makeup(&mut self, db: f32) -> &mut Self {
+        self.makeup = 10f32.powf(db / 20.0);
+        self
+    }
+
+    fn smoothing(&self, ms: f32) -> f32 {
+        match ms > 0.0 {
+            true => (-1000.0 / (ms * self.sample_rate)).exp(),
+            false => 0.0,
+        }
+    }
+}
+
+impl Effect for Compressor {
+    fn process(&mut self, frames: &mut [f32], channels: usize) {
+        for frame in frames.chunks_mut(channels.max(1)) {
+            let peak = frame.iter().fold(0f32, |peak, value| peak.max(value.abs()));
+            let over = 20.0 * peak.max(1e-9).log10() - self.threshold;
+            let target = over.max(0.0) * (1.0 - 1.0 / self.ratio);
+
+            // Released slowly, so the gain doesn't follow each cycle
+            self.held = target.max(target + (self.held - target) * self.release);
+            self.reduction = self.held + (self.reduction - self.held) * self.attack;
+
+            let gain = 10f32.powf(-self.reduction / 20.0) * self.makeup;
+
+            for value in frame.iter_mut() {
+                *value *= gain;
+            }
+        }
+    }
+}
+
+/// A band of an `Equalizer`, e.g. `peak:200:-3:1.0` or `highshelf:8000:+2`:
+/// its kind, frequency (Hz), gain (dB) and optionally Q.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Band {
+    Peak { freq: f32, gain: f32, q: f32 },
+    LowShelf { freq: f32, gain: f32, q: f32 },
+    HighShelf { freq: f32, gain: f32, q: f32 },
+}
+
+impl FromStr for Band {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let invalid = || format!("invalid band `{s}`, expected `kind:freq:gain[:q]`");
+        let number = |part: &str| part.trim().parse::<f32>().map_err(|_| invalid());
+
+        let parts: Vec<&str> = s.split(':').collect();
+
+        let (kind, freq, gain, q) = match parts.as_slice() {
+            [kind, freq, gain] => (*kind, number(freq)?, number(gain)?, None),
+            [kind, freq, gain, q] => (*kind, number(freq)?, number(gain)?, Some(number(q)?)),
+            _ => return Err(invalid()),
+        };
+
+        if freq <= 0.0 || q.is_some_and(|q| q <= 0.0) {
+            return Err(invalid());
+        }
+
+        match kind.trim().to_lowercase().as_str() {
+            "peak" | "bell" => Ok(Band::Peak {
+                freq,
+                gain,
+                q: q.unwrap_or(1.0),
+            }),
+            "lowshelf" => Ok(Band::LowShelf {
+                freq,
+                gain,
+                q: q.unwrap_or(std::f32::consts::FRAC_1_SQRT_2),
+            }),
+            "highshelf" => Ok(Band::HighShelf {
+                freq,
+                gain,
+                q: q.unwrap_or(std::f32::consts::FRAC_1_SQRT_2),
+            }),
+            _ => Err(format!(
+                "unknown band `{kind}`, expected peak, lowshelf or highshelf"
+            )),
+        }
+    }
+}
+
+impl Band {
+    /// The filter for this band, from the Audio EQ Cookbook.
+    fn design(&self, sample_rate: u32) -> Biquad {
+        let (Band::Peak { freq, gain, q }
+        | Band::LowShelf { freq, gain, q }
+        | Band::HighShelf { freq, gain, q }) = *self;
+
+        let nyquist = f64::from(sample_rate.max(1)) / 2.0;
+        let w0 = PI * f64::from(freq).min(nyquist * 0.99) / nyquist;
+        let (sin, cos) = w0.sin_cos();
+        let a = 10f64.powf(f64::from(gain) / 40.0);
+        let alpha = sin / (2.0 * f64::from(q));
+        let shelf = 2.0 * a.sqrt() * alpha;
+
+        let (b, a) = match self {
+            Band::Peak { .. } => (
+                [1.0 + alpha * a, -2.0 * cos, 1.0 - alpha * a],
+                [1.0 + alpha / a, -2.0 * cos, 1.0 - alpha / a],
+            ),
+            Band::LowShelf { .. } => (
+                [
+                    a * ((a + 1.0) - (a - 1.0) * cos + shelf),
+                    2.0 * a * ((a - 1.0) - (a + 1.0) * cos),
+                    a * ((a + 1.0) - (a - 1.0) * cos - shelf),
+                ],
+                [
+                    (a + 1.0) + (a - 1.0) * cos + shelf,
+                    -2.0 * ((a - 1.0) + (a + 1.0) * cos),
+                    (a + 1.0) + (a - 1.0) * cos - shelf,
+                ],
+            ),
+            Band::HighShelf { .. } => (
+                [
+                    a * ((a + 1.0) + (a - 1.0) * cos + shelf),
+                    -2.0 * a * ((a - 1.0) + (a + 1.0) * cos),
+                    a * ((a + 1.0) + (a - 1.0) * cos - shelf),
+                ],
+                [
+                    (a + 1.0) - (a - 1.0) * cos + shelf,
+                    2.0 * ((a - 1.0) - (a + 1.0) * cos),
+                    (a + 1.0) - (a - 1.0) * cos - shelf,
+                ],
+            ),
+        };
+
+        Biquad::new(
+            [b[0] / a[0], b[1] / a[0], b[2] / a[0]],
+            [a[1] / a[0], a[2] / a[0]],
+        )
+    }
+}
+
+/// Parametric equalizer: `Band`s run in turn on every channel.
+pub struct Equalizer {
+    bands: Vec<Biquad>,
+    /// Each channel's own copy of the bands
+    filters: Vec<Vec<Biquad>>,
+}
+
+impl Equalizer {
+    pub fn new(bands: &[Band], sample_rate: u32) -> Equalizer {
+        Equalizer {
+            bands: bands.iter().map(|band| band.design(sample_rate)).collect(),
+            filters: Vec::new(),
+        }
+    }
+}
+
+impl Effect for Equalizer {
+    fn process(&mut self, frames: &mut [f32], channels: usize) {
+        let channels = channels.max(1);
+        self.filters.resize(channels, self.bands.clone());
+
+        for frame in frames.chunks_mut(channels) {
+            for (value, filters) in frame.iter_mut().zip(self.filters.iter_mut()) {
+                let mut sample = f64::from(*value);
+
+                for filter in filters.iter_mut() {
+                    sample = filter.process(sample);
+                }
+
+                *value = sample as f32;
+            }
+        }
+    }
+}
+
+/// A second-order IIR filter, with `a0` normalized to 1.
+#[derive(Debug, Clone, Copy)]
+pub(crate) struct Biquad {
+    b: [f64; 3],
+    a: [f64; 2],
+    x: [f64; 2],
+    y: [f64; 2],
+}
+
+impl Biquad {
+    pub(crate) fn new(b: [f64; 3], a: [f64; 2]) -> Biquad {
+        Biquad {
+            b,
+            a,
+            x: [0.0; 2],
+            y: [0.0; 2],
+        }
+    }
+
+    pub(crate) fn process(&mut self, x: f64) -> f64 {
+        let y = self.b[0] * x + self.b[1] * self.x[0] + self.b[2] * self.x[1]
+            - self.a[0] * self.y[0]
+            - self.a[1] * self.y[1];
+
+        self.x = [x, self.x[0]];
+        self.y = [y, self.y[0]];
+
+        y
+    }
+}
@@ -0,0 +1,275 @@
+//! G.711 A-law and µ-law: 8-bit companded audio at 8 kHz, as telephone
+//! systems use.
+
+use crate::effects::Biquad;
+use crate::resample::Resampler;
+use crate::Error;
+use std::f64::consts::PI;
+use std::path::Path;
+use std::str::FromStr;
+
+pub const SAMPLE_RATE: u32 = 8000;
+
+/// Top of the telephone band, which the audio is filtered to before it's
+/// resampled.
+const BANDWIDTH: f64 = 3400.0;
+
+/// Q of each stage of an 8th-order Butterworth lowpass.
+const LOWPASS_Q: [f64; 4] = [0.5098, 0.6013, 0.9000, 2.5629];
+
+/// Loudest µ-law input, leaving room for the bias.
+const MU_CLIP: i32 = 32635;
+const MU_BIAS: i32 = 0x84;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Law {
+    /// Used in Europe and most of the world
+    ALaw,
+    /// Used in North America and Japan
+    MuLaw,
+}
+
+impl Law {
+    pub fn name(&self) -> &'static str {
+        match self {
+            Law::ALaw => "alaw",
+            Law::MuLaw => "ulaw",
+        }
+    }
+
+    /// The WAV format tag.
+    fn format_tag(self) -> u16 {
+        match self {
+            Law::ALaw => 6,
+            Law::MuLaw => 7,
+        }
+    }
+
+    pub fn encode(self, sample: i16) -> u8 {
+        let value = i32::from(sample);
+
+        match self {
+            Law::ALaw => {
+                let (sign, magnitude) = match value >= 0 {
+                    true => (0x80, value),
+                    false => (0, -value - 1),
+                };
+
+                let exponent = segment(magnitude);
+                let mantissa = (magnitude >> (exponent.max(1) + 3)) & 0x0f;
+
+                ((sign | (exponent << 4) | mantissa) ^ 0x55) as u8
+            }
+            Law::MuLaw => {
+                let (sign, magnitude) = match value >= 0 {
+                    true => (0, value),
+                    false => (0x80, -value),
+                };
+
+                let magnitude = magnitude.min(MU_CLIP) + MU_BIAS;
+                let exponent = segment(magnitude);
+                let mantissa = (magnitude >> (exponent + 3)) & 0x0f;
+
+                !(sign | (exponent << 4) | mantissa) as u8
+            }
+        }
+    }
+}
+
+impl FromStr for Law {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().replace('-', "").as_str() {
+            "alaw" => Ok(Law::ALaw),
+            "ulaw" | "mulaw" | "µlaw" => Ok(Law::MuLaw),
+            _ => Err(format!("unknown format `{s}`, expected alaw or ulaw")),
+        }
+    }
+}
+
+/// Which of the eight segments a magnitude falls in, by its highest bit
+/// from 8 to 14.
+fn segment(magnitude: i32) -> i32 {
+    let bits = 32 - (magnitude >> 7).clamp(0, 0xff).leading_zeros() as i32;
+    bits.max(1) - 1
+}
+
+/// Convert the WAV file at `from` to a G.711 WAV file at `to`, filtered to
+/// the telephone band and resampled to 8 kHz. Channels are kept.
+pub fn encode_wav<P, Q>(from: P, to: Q, law: Law) -> Result<(), Error>
+where
+    P: AsRef<Path>,
+    Q: AsRef<Path>,
+{
+    let (spec, samples) = crate::read_samples_f64(from)?;
+    let channels = usize::from(spec.channels.max(1));
+
+    let mut filters = vec![lowpass(spec.sample_rate); channels];
+    let filter = spec.sample_rate > SAMPLE_RATE;
+
+    let mut resampler = Resampler::new(spec.sample_rate, SAMPLE_RATE, channels);
+    let mut samples = samples.peekable();
+    let mut buffer = Vec::new();
+    let mut output = Vec::new();
+    let mut encoded = Vec::new();
+
+    let encode = |output: &[f32], encoded: &mut Vec<u8>| {
+        encoded.extend(
+            output
+                .iter()
+                .map(|value| law.encode((value.clamp(-1.0, 1.0) * f32::from(i16::MAX)) as i16)),
+        );
+    };
+
+    while samples.peek().is_some() {
+        buffer.clear();
+
+        for (index, sample) in samples.by_ref().take(4096 * channels).enumerate() {
+            let mut value = sample?;
+
+            if filter {
+                for stage in filters[index % channels].iter_mut() {
+                    value = stage.process(value);
+                }
+            }
+
+            buffer.push(value as f32);
+        }
+
+        output.clear();
+        resampler.process(&buffer, &mut output);
+        encode(&output, &mut encoded);
+    }
+
+    output.clear();
+    resampler.finish(&mut output);
+    encode(&output, &mut encoded);
+
+    write_wav(to, law, spec.channels.max(1), &encoded)
+}
+
+/// The stages of a lowpass at `BANDWIDTH`, from the Audio EQ Cookbook.
+fn lowpass(sample_rate: u32) -> Vec<Biquad> {
+    let nyquist = f64::from(sample_rate.max(1)) / 2.0;
+    let w0 = PI * BANDWIDTH.min(nyquist * 0.99) / nyquist;
+    let (sin, cos) = w0.sin_cos();
+
+    LOWPASS_Q
+        .iter()
+        .map(|q| {
+            let alpha = sin / (2.0 * q);
+            let a0 = 1.0 + alpha;
+
+            Biquad::new(
+                [
+                    (1.0 - cos) / 2.0 / a0,
+                    (1.0 - cos) / a0,
+                    (1.0 - cos) / 2.0 / a0,
+                ],
+                [-2.0 * cos / a0, (1.0 - alpha) / a0],
+            )
+        })
+        .collect()
+}
+
+fn write_wav<P>(path: P, law: Law, channels: u16, data: &[u8]) -> Result<(), Error>
+where
+    P: AsRef<Path>,
+{
+    let data_len = u32::try_from(data.len()).or(Err(Error::WriteError))?;
+    let frames = data_len / u32::from(channels);
+    let pad = data.len() % 2;
+
+    let mut file = Vec::with_capacity(58 + data.len() + pad);
+    file.extend_from_slice(b"RIFF");
+    file.extend_from_slice(&(50 + data_len + pad as u32).to_le_bytes());
+    file.extend_from_slice(b"WAVE");
+
+    file.extend_from_slice(b"fmt ");
+    file.extend_from_slice(&18u32.to_le_bytes());
+    file.extend_from_slice(&law.format_tag().to_le_bytes());
+    file.extend_from_slice(&channels.to_le_bytes());
+    file.extend_from_slice(&SAMPLE_RATE.to_le_bytes());
+    file.extend_from_slice(&(SAMPLE_RATE * u32::from(channels)).to_le_bytes());
+    file.extend_from_slice(&channels.to_le_bytes());
+    file.extend_from_slice(&8u16.to_le_bytes());
+    file.extend_from_slice(&0u16.to_le_bytes());
+
+    // Required for formats other than PCM
+    file.extend_from_slice(b"fact");
+    file.extend_from_slice(&4u32.to_le_bytes());
+    file.extend_from_slice(&frames.to_le_bytes());
+
+    file.extend_from_slice(b"data");
+    file.extend_from_slice(&data_len.to_le_bytes());
+    file.extend_from_slice(data);
+    file.resize(file.len() + pad, 0);
+
+    std::fs::write(path, file).or(Err(Error::WriteError))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// The reference decoders, as in the ITU's sample code.
+    fn decode(law: Law, code: u8) -> i32 {
+        match law {
+            Law::ALaw => {
+                let code = i32::from(code ^ 0x55);
+                let exponent = (code & 0x70) >> 4;
+                let mut value = ((code & 0x0f) << 4) + 8;
+
+                if exponent > 0 {
+                    value = (value + 0x100) << (exponent - 1);
+                }
+
+                match code & 0x80 != 0 {
+                    true => value,
+                    false => -value,
+                }
+            }
+            Law::MuLaw => {
+                let code = i32::from(!code);
+                let value = (((code & 0x0f) << 3) + MU_BIAS) << ((code & 0x70) >> 4);
+
+                match code & 0x80 != 0 {
+                    true => MU_BIAS - value,
+                    false => value - MU_BIAS,
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn known_codes() {
+        for (sample, alaw, ulaw) in [
+            (0, 0xd5, 0xff),
+            (-1, 0x55, 0x7f),
+            (i16::MAX, 0xaa, 0x80),
+            (i16::MIN, 0x2a, 0x00),
+        ] {
+            assert_eq!(Law::ALaw.encode(sample), alaw, "{sample}");
+            assert_eq!(Law::MuLaw.encode(sample), ulaw, "{sample}");
+        }
+    }
+
+    #[test]
+    fn round_trip_within_a_step() {
+        for law in [Law::ALaw, Law::MuLaw] {
+            for sample in (i16::MIN..=i16::MAX).step_by(7) {
+                let value = i32::from(sample);
+                let decoded = decode(law, law.encode(sample));
+
+                // Half of each segment's step, plus µ-law's clip at the top
+                let bound =
+                    ((value.abs() + MU_BIAS) / 32).max(8) + 1 + (value.abs() - MU_CLIP).max(0);
+                assert!(
+                    (decoded - value).abs() <= bound,
+                    "{law:?} {value} {decoded}"
+                );
+            }
+        }
+    }
+}
@@ -0,0 +1,213 @@
+use crate::effects::Effect;
+use std::f64::consts::TAU;
+
+/// Pitch of a click, and of the accented first beat of a bar.
+const CLICK_FREQ: f64 = 1000.0;
+const ACCENT_FREQ: f64 = 1500.0;
+
+/// How long a click sounds, and how fast it fades.
+const CLICK_SECONDS: f64 = 0.03;
+const CLICK_DECAY: f64 = 0.008;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Wave {
+    Sine,
+    Square,
+    White,
+    Pink,
+    /// Logarithmic sweep between two frequencies
+    Sweep,
+}
+
+/// Synthesizes test signals one sample at a time.
+#[derive(Debug, Clone)]
+pub struct Generator {
+    wave: Wave,
+    sample_rate: f64,
+    freq: f64,
+    sweep_to: f64,
+    sweep_len: f64,
+    amplitude: f32,
+    phase: f64,
+    elapsed: f64,
+    rng: u32,
+    pink: [f32; 7],
+}
+
+impl Generator {
+    pub fn new(wave: Wave, sample_rate: u32) -> Generator {
+        Generator {
+            wave,
+            sample_rate: f64::from(sample_rate),
+            freq: 1000.0,
+            sweep_to: 20_000.0,
+            sweep_len: 10.0,
+            amplitude: 1.0,
+            phase: 0.0,
+            elapsed: 0.0,
+            rng: 0x9E37_79B9,
+            pink: [0.0; 7],
+        }
+    }
+
+    /// Tone frequency, or the start of a sweep.
+    pub fn freq(&mut self, freq: f64) -> &mut Self {
+        self.freq = freq;
+        self
+    }
+
+    /// End frequency and duration in seconds of a sweep, which then repeats.
+    pub fn sweep(&mut self, to: f64, seconds: f64) -> &mut Self {
+        self.sweep_to = to;
+        self.sweep_len = seconds;
+        self
+    }
+
+    /// Peak level in dBFS.
+    pub fn level(&mut self, dbfs: f32) -> &mut Self {
+        self.amplitude = 10f32.powf(dbfs / 20.0);
+        self
+    }
+
+    pub fn next_sample(&mut self) -> f32 {
+        let value = match self.wave {
+            Wave::Sine => (self.advance(self.freq) * TAU).sin() as f32,
+            Wave::Square => {
+                if self.advance(self.freq) < 0.5 {
+                    1.0
+                } else {
+                    -1.0
+                }
+            }
+            Wave::White => self.noise(),
+            Wave::Pink => self.pink(),
+            Wave::Sweep => {
+                let t = self.elapsed % self.sweep_len.max(f64::EPSILON);
+                let freq = self.freq * (self.sweep_to / self.freq).powf(t / self.sweep_len);
+                self.elapsed += 1.0 / self.sample_rate;
+                (self.advance(freq) * TAU).sin() as f32
+            }
+        };
+
+        value * self.amplitude
+    }
+
+    /// Fill an interleaved buffer with the same signal on every channel.
+    pub fn fill(&mut self, data: &mut [f32], channels: u16) {
+        for frame in data.chunks_mut(usize::from(channels.max(1))) {
+            let value = self.next_sample();
+            frame.fill(value);
+        }
+    }
+
+    /// Step the phase and return its value before the step, in `0..1`.
+    fn advance(&mut self, freq: f64) -> f64 {
+        let phase = self.phase;
+        self.phase = (self.phase + freq / self.sample_rate).fract();
+        phase
+    }
+
+    // xorshift32, uniform in -1..1
+    fn noise(&mut self) -> f32 {
+        self.rng ^= self.rng << 13;
+        self.rng ^= self.rng >> 17;
+        self.rng ^= self.rng << 5;
+        (self.rng as f32 / u32::MAX as f32) * 2.0 - 1.0
+    }
+
+    // Paul Kellet's refined pink noise filter
+    fn pink(&mut self) -> f32 {
+        let white = self.noise();
+        let b = &mut self.pink;
+
+        b[0] = 0.99886 * b[0] + white * 0.0555179;
+        b[1] = 0.99332 * b[1] + white * 0.0750759;
+        b[2] = 0.969 * b[2] + white * 0.153_852;
+        b[3] = 0.8665 * b[3] + white * 0.3104856;
+        b[4] = 0.55 * b[4] + white * 0.5329522;
+        b[5] = -0.7616 * b[5] - white * 0.0168980;
+
+        let pink = b[0] + b[1] + b[2] + b[3] + b[4] + b[5] + b[6] + white * 0.5362;
+        b[6] = white * 0.115926;
+
+        // Roughly normalize to the white noise peak
+        (pink * 0.11).clamp(-1.0, 1.0)
+    }
+}
+
+/// A metronome: a short tone on every beat, higher on the first beat of each
+/// bar. As an `Effect` it's mixed into whatever passes through, so a `Player`
+/// with nothing queued plays just the click.
+#[derive(Debug, Clone)]
+pub struct Metronome {
+    sample_rate: f64,
+    /// Frames per beat
+    beat_len: f64,
+    beats: u32,
+    amplitude: f32,
+    /// Frames into the current beat
+    elapsed: f64,
+    beat: u32,
+}
+
+impl Metronome {
+    pub fn new(bpm: f64, sample_rate: u32) -> Metronome {
+        let sample_rate = f64::from(sample_rate);
+
+        Metronome {
+            sample_rate,
+            beat_len: sample_rate * 60.0 / bpm.max(f64::EPSILON),
+            beats: 4,
+            amplitude: 1.0,
+            elapsed: 0.0,
+            beat: 0,
+        }
+    }
+
+    /// Beats per bar; with 1, no beat is accented.
+    pub fn beats(&mut self, beats: u32) -> &mut Self {
+        self.beats = beats.max(1);
+        self
+    }
+
+    /// Peak level in dBFS.
+    pub fn level(&mut self, dbfs: f32) -> &mut Self {
+        self.amplitude = 10f32.powf(dbfs / 20.0);
+        self
+    }
+
+    pub fn next_sample(&mut self) -> f32 {
+        let t = self.elapsed / self.sample_rate;
+
+        let freq = match self.beat == 0 && self.beats > 1 {
+            true => ACCENT_FREQ,
+            false => CLICK_FREQ,
+        };
+
+        let value = match t < CLICK_SECONDS {
+            true => (t * freq * TAU).sin() * (-t / CLICK_DECAY).exp(),
+            false => 0.0,
+        };
+
+        self.elapsed += 1.0;
+
+        if self.elapsed >= self.beat_len {
+            self.elapsed -= self.beat_len;
+            self.beat = (self.beat + 1) % self.beats;
+        }
+
+        value as f32 * self.amplitude
+    }
+}
+
+impl Effect for Metronome {
+    fn process(&mut self, frames: &mut [f32], channels: usize) {
+        for frame in frames.chunks_mut(channels.max(1)) {
+            let value = self.next_sample();
+
+            for sample in frame.iter_mut() {
+                *sample += value;
+            }
+        }
+    }
+}
@@ -0,0 +1,317 @@
+//! LADSPA plugins as effects, loaded straight from their shared library.
+
+use crate::effects::Effect;
+use crate::Error;
+use std::ffi::c_char;
+use std::ffi::c_int;
+use std::ffi::c_ulong;
+use std::ffi::c_void;
+use std::ffi::CStr;
+use std::path::Path;
+use std::path::PathBuf;
+
+/// Frames handed to a plugin per run.
+const BLOCK_FRAMES: usize = 1024;
+
+/// Searched for plugins given without a directory, unless `LADSPA_PATH` is
+/// set.
+const DEFAULT_PATH: &str = "/usr/local/lib/ladspa:/usr/lib/ladspa";
+
+const PORT_INPUT: c_int = 0x1;
+const PORT_OUTPUT: c_int = 0x2;
+const PORT_CONTROL: c_int = 0x4;
+const PORT_AUDIO: c_int = 0x8;
+
+const HINT_SAMPLE_RATE: c_int = 0x8;
+const HINT_LOGARITHMIC: c_int = 0x10;
+const HINT_DEFAULT_MASK: c_int = 0x3c0;
+
+type Handle = *mut c_void;
+
+#[repr(C)]
+struct RangeHint {
+    hints: c_int,
+    lower: f32,
+    upper: f32,
+}
+
+/// `LADSPA_Descriptor` from ladspa.h
+#[repr(C)]
+struct Descriptor {
+    unique_id: c_ulong,
+    label: *const c_char,
+    properties: c_int,
+    name: *const c_char,
+    maker: *const c_char,
+    copyright: *const c_char,
+    port_count: c_ulong,
+    port_descriptors: *const c_int,
+    port_names: *const *const c_char,
+    port_range_hints: *const RangeHint,
+    implementation_data: *mut c_void,
+    instantiate: Option<unsafe extern "C" fn(*const Descriptor, c_ulong) -> Handle>,
+    connect_port: Option<unsafe extern "C" fn(Handle, c_ulong, *mut f32)>,
+    activate: Option<unsafe extern "C" fn(Handle)>,
+    run: Option<unsafe extern "C" fn(Handle, c_ulong)>,
+    run_adding: Option<unsafe extern "C" fn(Handle, c_ulong)>,
+    set_run_adding_gain: Option<unsafe extern "C" fn(Handle, f32)>,
+    deactivate: Option<unsafe extern "C" fn(Handle)>,
+    cleanup: Option<unsafe extern "C" fn(Handle)>,
+}
+
+type DescriptorFn = unsafe extern "C" fn(c_ulong) -> *const Descriptor;
+
+/// A LADSPA plugin covering every channel of a stream: one instance when the
+/// plugin has an audio port per channel, or one per channel for mono plugins.
+pub struct Ladspa {
+    descriptor: *const Descriptor,
+    handles: Vec<Handle>,
+    /// Channels each instance processes
+    width: usize,
+    /// Control input names and port numbers
+    controls: Vec<(String, usize)>,
+    /// Control port values, shared by the instances
+    values: Box<[f32]>,
+    inputs: Vec<Vec<f32>>,
+    outputs: Vec<Vec<f32>>,
+    /// Dropped last, after the instances are cleaned up
+    _library: libloading::Library,
+}
+
+// The plugin is only ever used from one thread at a time, which is all
+// LADSPA asks of hosts
+unsafe impl Send for Ladspa {}
+
+impl Ladspa {
+    /// Load the plugin called `label` from `path`, a file name being looked
+    /// up in `LADSPA_PATH`.
+    pub fn new(path: &str, label: &str, sample_rate: u32, channels: u16) -> Result<Ladspa, Error> {
+        let path = find(path).ok_or(Error::PluginNotFoundError)?;
+
+        let library = unsafe { libloading::Library::new(path) }.or(Err(Error::PluginLoadError))?;
+
+        let descriptor = unsafe {
+            let descriptor_fn = library
+                .get::<DescriptorFn>(b"ladspa_descriptor\0")
+                .or(Err(Error::PluginLoadError))?;
+
+            (0..)
+                .map(|index| descriptor_fn(index))
+                .take_while(|descriptor| !descriptor.is_null())
+                .find(|&descriptor| {
+                    CStr::from_ptr((*descriptor).label).to_bytes() == label.as_bytes()
+                })
+                .ok_or(Error::PluginNotFoundError)?
+        };
+
+        let desc = unsafe { &*descriptor };
+        let ports = desc.port_count as usize;
+
+        let (instantiate, connect_port) = match (desc.instantiate, desc.connect_port) {
+            (Some(instantiate), Some(connect_port)) if desc.run.is_some() && ports > 0 => {
+                (instantiate, connect_port)
+            }
+            _ => return Err(Error::PluginLoadError),
+        };
+
+        let port_types = unsafe { std::slice::from_raw_parts(desc.port_descriptors, ports) };
+        let port_names = unsafe { std::slice::from_raw_parts(desc.port_names, ports) };
+        let hints = unsafe { std::slice::from_raw_parts(desc.port_range_hints, ports) };
+
+        let is = |port: usize, kind: c_int| port_types[port] & kind == kind;
+        let audio_inputs: Vec<usize> = (0..ports)
+            .filter(|&port| is(port, PORT_AUDIO | PORT_INPUT))
+            .collect();
+        let audio_outputs: Vec<usize> = (0..ports)
+            .filter(|&port| is(port, PORT_AUDIO | PORT_OUTPUT))
+            .collect();
+
+        let channels = usize::from(channels.max(1));
+
+        let width = match (audio_inputs.len(), audio_outputs.len()) {
+            (inputs, outputs) if inputs == channels && outputs == channels => channels,
+            (1, 1) => 1,
+            _ => return Err(Error::PluginLoadError),
+        };
+
+        let controls = (0..ports)
+            .filter(|&port| is(port, PORT_CONTROL | PORT_INPUT))
+            .map(|port| {
+                let name = unsafe { CStr::from_ptr(port_names[port]) };
+                (name.to_string_lossy().into_owned(), port)
+            })
+            .collect();
+
+        let values = hints
+            .iter()
+            .map(|hint| default_value(hint, sample_rate))
+            .collect();
+
+        let mut plugin = Ladspa {
+            descriptor,
+            handles: Vec::new(),
+            width,
+            controls,
+            values,
+            inputs: vec![vec![0.0; BLOCK_FRAMES]; width],
+            outputs: vec![vec![0.0; BLOCK_FRAMES]; width],
+            _library: library,
+        };
+
+        for _ in 0..channels / width {
+            let handle = unsafe { instantiate(descriptor, c_ulong::from(sample_rate)) };
+
+            if handle.is_null() {
+                return Err(Error::PluginLoadError);
+            }
+
+            plugin.handles.push(handle);
+
+            // Buffers are never resized, so the ports stay connected
+            unsafe {
+                for (port, value) in plugin.values.iter_mut().enumerate() {
+                    connect_port(handle, port as c_ulong, value);
+                }
+
+                for (&port, input) in audio_inputs.iter().zip(plugin.inputs.iter_mut()) {
+                    connect_port(handle, port as c_ulong, input.as_mut_ptr());
+                }
+
+                for (&port, output) in audio_outputs.iter().zip(plugin.outputs.iter_mut()) {
+                    connect_port(handle, port as c_ulong, output.as_mut_ptr());
+                }
+
+                if let Some(activate) = desc.activate {
+                    activate(handle);
+                }
+            }
+        }
+
+        Ok(plugin)
+    }
+
+    /// Names of the plugin's controls, in port order.
+    pub fn controls(&self) -> impl Iterator<Item = &str> {
+        self.controls.iter().map(|(name, _)| name.as_str())
+    }
+
+    /// Set a control by its name or a part of it, ignoring case. An exact
+    /// match wins over partial ones, and otherwise only one may match.
+    pub fn set(&mut self, name: &str, value: f32) -> Result<(), Error> {
+        let lower = name.to_lowercase();
+
+        let exact = self
+            .controls
+            .iter()
+            .find(|(control, _)| control.to_lowercase() == lower);
+
+        let mut partial = self
+            .controls
+            .iter()
+            .filter(|(control, _)| control.to_lowercase().contains(&lower));
+
+        let port = match (exact, partial.next(), partial.next()) {
+            (Some(&(_, port)), _, _) => port,
+            (None, Some(&(_, port)), None) => port,
+            _ => return Err(Error::PluginNotFoundError),
+        };
+
+        self.values[port] = value;
+
+        Ok(())
+    }
+}
+
+impl Effect for Ladspa {
+    fn process(&mut self, frames: &mut [f32], channels: usize) {
+        let channels = channels.max(1);
+        let run = match unsafe { (*self.descriptor).run } {
+            Some(run) => run,
+            None => return,
+        };
+
+        for block in frames.chunks_mut(BLOCK_FRAMES * channels) {
+            let len = block.len() / channels;
+
+            // Instances beyond the stream's channels have nothing to process
+            let handles = self.handles.iter().take(channels / self.width);
+
+            for (number, &handle) in handles.enumerate() {
+                let first = number * self.width;
+
+                for (channel, input) in self.inputs.iter_mut().enumerate() {
+                    for (frame, value) in input[..len].iter_mut().enumerate() {
+                        *value = block[frame * channels + first + channel];
+                    }
+                }
+
+                unsafe { run(handle, len as c_ulong) };
+
+                for (channel, output) in self.outputs.iter().enumerate() {
+                    for (frame, &value) in output[..len].iter().enumerate() {
+                        block[frame * channels + first + channel] = value;
+                    }
+                }
+            }
+        }
+    }
+}
+
+impl Drop for Ladspa {
+    fn drop(&mut self) {
+        let desc = unsafe { &*self.descriptor };
+
+        for &handle in self.handles.iter() {
+            unsafe {
+                if let Some(deactivate) = desc.deactivate {
+                    deactivate(handle);
+                }
+
+                if let Some(cleanup) = desc.cleanup {
+                    cleanup(handle);
+                }
+            }
+        }
+    }
+}
+
+fn find(path: &str) -> Option<PathBuf> {
+    if path.contains('/') {
+        return Some(PathBuf::from(path));
+    }
+
+    let dirs = std::env::var("LADSPA_PATH").unwrap_or_else(|_| DEFAULT_PATH.to_string());
+
+    dirs.split(':')
+        .map(|dir| Path::new(dir).join(path))
+        .find(|path| path.is_file())
+}
+
+/// The value a control starts at, from its range hint.
+fn default_value(hint: &RangeHint, sample_rate: u32) -> f32 {
+    let scale = match hint.hints & HINT_SAMPLE_RATE {
+        0 => 1.0,
+        _ => sample_rate as f32,
+    };
+
+    let (lower, upper) = (hint.lower * scale, hint.upper * scale);
+    let logarithmic = hint.hints & HINT_LOGARITHMIC != 0 && lower > 0.0 && upper > 0.0;
+
+    // Between the bounds, a quarter, half or three quarters of the way
+    let between = |amount: f32| match logarithmic {
+        true => (lower.ln() * (1.0 - amount) + upper.ln() * amount).exp(),
+        false => lower * (1.0 - amount) + upper * amount,
+    };
+
+    match hint.hints & HINT_DEFAULT_MASK {
+        0x40 => lower,
+        0x80 => between(0.25),
+        0xc0 => between(0.5),
+        0x100 => between(0.75),
+        0x140 => upper,
+        0x240 => 1.0,
+        0x280 => 100.0,
+        0x2c0 => 440.0,
+        _ => 0.0,
+    }
+}
@@ -1,14 +1,88 @@
 use cpal::traits::DeviceTrait;
 use cpal::traits::HostTrait;
 use cpal::traits::StreamTrait;
+use cpal::Sample;
 use cpal::SupportedStreamConfig;
 use hound::WavSpec;
+#[cfg(not(target_arch = "wasm32"))]
+use ringbuf::HeapConsumer;
+#[cfg(not(target_arch = "wasm32"))]
+use ringbuf::HeapProducer;
+#[cfg(not(target_arch = "wasm32"))]
+use ringbuf::HeapRb;
+use std::collections::VecDeque;
 use std::error;
 use std::fs::File;
+#[cfg(not(target_arch = "wasm32"))]
+use std::io::BufReader;
 use std::io::BufWriter;
+use std::ops::ControlFlow;
+#[cfg(not(target_arch = "wasm32"))]
 use std::path::Path;
+#[cfg(not(target_arch = "wasm32"))]
+use std::path::PathBuf;
+use std::sync::atomic::AtomicBool;
+use std::sync::atomic::AtomicU32;
+use std::sync::atomic::AtomicU64;
+use std::sync::atomic::AtomicU8;
+use std::sync::atomic::Ordering;
+use std::sync::mpsc;
 use std::sync::Arc;
 use std::sync::Mutex;
+use std::time::Duration;
+use std::time::SystemTime;
+use std::time::UNIX_EPOCH;
+
+#[cfg(feature = "aac")]
+pub mod aac;
+#[cfg(not(target_arch = "wasm32"))]
+pub mod adpcm;
+pub mod aec;
+#[cfg(not(target_arch = "wasm32"))]
+pub mod app;
+#[cfg(feature = "capi")]
+pub mod capi;
+pub mod checksum;
+#[cfg(not(target_arch = "wasm32"))]
+pub mod codec;
+pub mod config;
+#[cfg(feature = "decode")]
+pub mod decode;
+#[cfg(feature = "denoise")]
+pub mod denoise;
+pub mod dither;
+#[cfg(not(target_arch = "wasm32"))]
+pub mod durability;
+pub mod effects;
+#[cfg(not(target_arch = "wasm32"))]
+pub mod g711;
+pub mod generator;
+#[cfg(feature = "ladspa")]
+pub mod ladspa;
+pub mod loudness;
+pub mod ltc;
+#[cfg(feature = "lv2")]
+pub mod lv2;
+#[cfg(not(target_arch = "wasm32"))]
+pub mod merge;
+pub mod metadata;
+pub mod mock;
+#[cfg(feature = "node")]
+pub mod node;
+pub mod playback;
+#[cfg(not(target_arch = "wasm32"))]
+pub mod preallocate;
+pub mod priority;
+#[cfg(feature = "python")]
+pub mod python;
+pub mod resample;
+pub mod stretch;
+#[cfg(not(target_arch = "wasm32"))]
+pub mod transcribe;
+#[cfg(not(target_arch = "wasm32"))]
+pub mod wav64;
+#[cfg(not(target_arch = "wasm32"))]
+pub mod wavpack;
 
 #[macro_export]
 macro_rules! fail {
@@ -42,18 +116,543 @@ impl WavExt for SupportedStreamConfig {
     }
 }
 
+pub trait WavSpecExt {
+    /// The config to request for playing (or capturing) audio in this spec.
+    /// 24-bit samples map to `I32`, the narrowest cpal format that holds them,
+    /// so they don't round-trip through `as_wav_spec`.
+    fn as_stream_config(&self) -> Result<SupportedStreamConfig, Error>;
+}
+
+impl WavSpecExt for WavSpec {
+    fn as_stream_config(&self) -> Result<SupportedStreamConfig, Error> {
+        let sample_format = match (self.sample_format, self.bits_per_sample) {
+            (hound::SampleFormat::Float, 32) => cpal::SampleFormat::F32,
+            (hound::SampleFormat::Float, 64) => cpal::SampleFormat::F64,
+            (hound::SampleFormat::Int, 8) => cpal::SampleFormat::I8,
+            (hound::SampleFormat::Int, 16) => cpal::SampleFormat::I16,
+            (hound::SampleFormat::Int, 24 | 32) => cpal::SampleFormat::I32,
+            _ => return Err(Error::StreamConfigFormatError),
+        };
+
+        Ok(SupportedStreamConfig::new(
+            self.channels,
+            cpal::SampleRate(self.sample_rate),
+            cpal::SupportedBufferSize::Unknown,
+            sample_format,
+        ))
+    }
+}
+
+/// Encode interleaved samples as an in-memory 32-bit float WAV file.
+pub fn encode_wav(config: &SupportedStreamConfig, samples: &[f32]) -> Result<Vec<u8>, Error> {
+    let spec = WavSpec {
+        channels: config.channels(),
+        sample_rate: config.sample_rate().0,
+        bits_per_sample: 32,
+        sample_format: hound::SampleFormat::Float,
+    };
+
+    let mut wav = std::io::Cursor::new(Vec::new());
+    let mut writer = hound::WavWriter::new(&mut wav, spec).or(Err(Error::WriteError))?;
+
+    for &sample in samples {
+        writer.write_sample(sample).or(Err(Error::WriteError))?;
+    }
+
+    writer.finalize().or(Err(Error::WriteError))?;
+
+    Ok(wav.into_inner())
+}
+
+/// Copy frames `start..end` of the WAV file at `from` to a new file at
+/// `to`, sample for sample in the same format, keeping its tags. `end` is
+/// clamped to the length of the file. Returns the number of frames copied.
+#[cfg(not(target_arch = "wasm32"))]
+pub fn trim_wav<P, Q>(from: P, to: Q, start: u64, end: u64) -> Result<u64, Error>
+where
+    P: AsRef<Path>,
+    Q: AsRef<Path>,
+{
+    let mut reader = hound::WavReader::open(&from).or(Err(Error::ReadError))?;
+    let spec = reader.spec();
+
+    let end = end.min(u64::from(reader.duration()));
+    let start = start.min(end);
+
+    // Both fit, being no more than the duration
+    reader.seek(start as u32).or(Err(Error::ReadError))?;
+
+    let samples = (end - start) as usize * usize::from(spec.channels);
+    let mut writer = hound::WavWriter::create(&to, spec).or(Err(Error::WriteError))?;
+
+    copy_samples(&mut reader, &mut writer, samples)?;
+
+    writer.finalize().or(Err(Error::WriteError))?;
+    copy_tags(from, to)?;
+
+    Ok(end - start)
+}
+
+/// Like `gain_wav`, but working in 64-bit floats and writing a 64-bit float
+/// file, so nothing is lost however much it's processed after.
+#[cfg(not(target_arch = "wasm32"))]
+pub fn gain_wav_float64<P, Q>(from: P, to: Q, gain: f64) -> Result<(), Error>
+where
+    P: AsRef<Path>,
+    Q: AsRef<Path>,
+{
+    let (spec, samples) = read_samples_f64(&from)?;
+    let mut writer = wav64::Writer::create(&to, spec.channels, spec.sample_rate)?;
+    let mut buffer = Vec::with_capacity(4096);
+
+    for sample in samples {
+        buffer.push(sample? * gain);
+
+        if buffer.len() == 4096 {
+            writer.write(&buffer)?;
+            buffer.clear();
+        }
+    }
+
+    writer.write(&buffer)?;
+    writer.finalize()?;
+
+    copy_tags(from, to)
+}
+
+/// Join WAV files end to end in a new file at `to`, in the format of the
+/// first and with its tags. Files in other formats are resampled and have
+/// their channels remapped. Returns the number of frames written.
+#[cfg(not(target_arch = "wasm32"))]
+pub fn concat_wav<P, Q>(from: &[P], to: Q) -> Result<u64, Error>
+where
+    P: AsRef<Path>,
+    Q: AsRef<Path>,
+{
+    let first = from.first().ok_or(Error::ReadError)?;
+    let spec = hound::WavReader::open(first)
+        .or(Err(Error::ReadError))?
+        .spec();
+
+    let mut writer = hound::WavWriter::create(&to, spec).or(Err(Error::WriteError))?;
+
+    for path in from {
+        let mut reader = hound::WavReader::open(path).or(Err(Error::ReadError))?;
+        let samples = reader.len() as usize;
+
+        if reader.spec() == spec {
+            copy_samples(&mut reader, &mut writer, samples)?;
+        } else {
+            convert_samples(&mut reader, &mut writer)?;
+        }
+    }
+
+    let frames = u64::from(writer.duration());
+
+    writer.finalize().or(Err(Error::WriteError))?;
+    copy_tags(first, to)?;
+
+    Ok(frames)
+}
+
+/// Find where the WAV file at `path` stays below `threshold_dbfs` for at
+/// least `min_frames`, as ranges of frames. The start and end of the file
+/// are included if they are quiet.
+#[cfg(not(target_arch = "wasm32"))]
+pub fn find_silences<P>(
+    path: P,
+    threshold_dbfs: f32,
+    min_frames: u64,
+) -> Result<Vec<std::ops::Range<u64>>, Error>
+where
+    P: AsRef<Path>,
+{
+    let mut reader = hound::WavReader::open(path).or(Err(Error::ReadError))?;
+    let channels = usize::from(reader.spec().channels.max(1));
+    let threshold = 10f32.powf(threshold_dbfs / 20.0);
+
+    let mut silences = Vec::new();
+    let mut quiet_since = Some(0);
+    let mut frame = Vec::with_capacity(channels);
+    let mut position = 0;
+
+    for sample in decode_samples(&mut reader) {
+        frame.push(sample.or(Err(Error::ReadError))?);
+
+        if frame.len() < channels {
+            continue;
+        }
+
+        let loud = frame.iter().any(|value| value.abs() >= threshold);
+        frame.clear();
+
+        match (loud, quiet_since) {
+            (true, Some(start)) => {
+                if position - start >= min_frames {
+                    silences.push(start..position);
+                }
+
+                quiet_since = None;
+            }
+            (false, None) => quiet_since = Some(position),
+            _ => {}
+        }
+
+        position += 1;
+    }
+
+    if let Some(start) = quiet_since {
+        if position - start >= min_frames {
+            silences.push(start..position);
+        }
+    }
+
+    Ok(silences)
+}
+
+/// Measure the loudness and peak of the WAV file at `path`.
+#[cfg(not(target_arch = "wasm32"))]
+pub fn measure_wav<P>(path: P) -> Result<loudness::Measurement, Error>
+where
+    P: AsRef<Path>,
+{
+    let (spec, samples) = read_samples_f64(path)?;
+
+    let channels = usize::from(spec.channels.max(1));
+
+    let mut meter = loudness::Meter::new(spec.sample_rate, channels);
+    let mut samples = samples.peekable();
+    let mut buffer = Vec::new();
+
+    while samples.peek().is_some() {
+        buffer.clear();
+
+        for sample in samples.by_ref().take(4096 * channels) {
+            buffer.push(sample? as f32);
+        }
+
+        meter.process(&buffer);
+    }
+
+    Ok(meter.measurement())
+}
+
+/// Copy the WAV file at `from` to `to` in the same format, with its tags,
+/// changing its level by `gain`. Integer samples are requantized with
+/// `dither`, and clamped where they would clip. With the `decode` feature a
+/// compressed file is written as WAV, at the bit depth it was encoded from.
+#[cfg(not(target_arch = "wasm32"))]
+pub fn gain_wav<P, Q>(from: P, to: Q, gain: f32, dither: dither::Dither) -> Result<(), Error>
+where
+    P: AsRef<Path>,
+    Q: AsRef<Path>,
+{
+    let (spec, samples) = read_samples_f64(&from)?;
+    let mut writer = hound::WavWriter::create(&to, spec).or(Err(Error::WriteError))?;
+    let mut dither = ditherer(&writer, dither);
+    let mut buffer = Vec::new();
+
+    for sample in samples {
+        buffer.push(sample? as f32 * gain);
+
+        if buffer.len() == 4096 {
+            write_converted(&mut writer, &buffer, &mut dither)?;
+            buffer.clear();
+        }
+    }
+
+    write_converted(&mut writer, &buffer, &mut dither)?;
+    writer.finalize().or(Err(Error::WriteError))?;
+
+    copy_tags(from, to)
+}
+
+/// Carry the tags of one file over to another.
+#[cfg(not(target_arch = "wasm32"))]
+fn copy_tags<P, Q>(from: P, to: Q) -> Result<(), Error>
+where
+    P: AsRef<Path>,
+    Q: AsRef<Path>,
+{
+    // Compressed files keep their tags their own way
+    #[cfg(feature = "decode")]
+    if decode::is_compressed(&from) {
+        return Ok(());
+    }
+
+    for id in [b"LIST", b"iXML"] {
+        if let Some(data) = metadata::read_chunk(&from, id)? {
+            metadata::append_chunk(&to, id, &data)?;
+        }
+    }
+
+    Ok(())
+}
+
+/// Copy `samples` as they are from a file in the writer's format.
+#[cfg(not(target_arch = "wasm32"))]
+fn copy_samples(
+    reader: &mut hound::WavReader<BufReader<File>>,
+    writer: &mut hound::WavWriter<BufWriter<File>>,
+    samples: usize,
+) -> Result<(), Error> {
+    match writer.spec().sample_format {
+        hound::SampleFormat::Float => copy_samples_as::<f32>(reader, writer, samples),
+        hound::SampleFormat::Int => copy_samples_as::<i32>(reader, writer, samples),
+    }
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+fn copy_samples_as<S>(
+    reader: &mut hound::WavReader<BufReader<File>>,
+    writer: &mut hound::WavWriter<BufWriter<File>>,
+    samples: usize,
+) -> Result<(), Error>
+where
+    S: hound::Sample,
+{
+    for sample in reader.samples::<S>().take(samples) {
+        let sample = sample.or(Err(Error::ReadError))?;
+        writer.write_sample(sample).or(Err(Error::WriteError))?;
+    }
+
+    Ok(())
+}
+
+/// Copy a file in another format, converting it to the writer's.
+#[cfg(not(target_arch = "wasm32"))]
+fn convert_samples(
+    reader: &mut hound::WavReader<BufReader<File>>,
+    writer: &mut hound::WavWriter<BufWriter<File>>,
+) -> Result<(), Error> {
+    let from = reader.spec();
+    let to = writer.spec();
+
+    let from_channels = usize::from(from.channels.max(1));
+    let to_channels = usize::from(to.channels.max(1));
+
+    let mut resampler = resample::Resampler::new(from.sample_rate, to.sample_rate, to_channels);
+    let mut dither = ditherer(writer, dither::Dither::None);
+
+    let mut samples = decode_samples(reader).peekable();
+    let mut buffer = Vec::new();
+    let mut remapped = Vec::new();
+    let mut output = Vec::new();
+
+    while samples.peek().is_some() {
+        buffer.clear();
+
+        for sample in samples.by_ref().take(4096 * from_channels) {
+            buffer.push(sample.or(Err(Error::ReadError))?);
+        }
+
+        remapped.clear();
+        resample::remap(&buffer, from_channels, to_channels, &mut remapped);
+
+        output.clear();
+        resampler.process(&remapped, &mut output);
+        write_converted(writer, &output, &mut dither)?;
+    }
+
+    output.clear();
+    resampler.finish(&mut output);
+    write_converted(writer, &output, &mut dither)
+}
+
+/// The samples of the audio file at `path` with its spec, interleaved, as
+/// floats. With the `decode` feature that's FLAC, MP3 and Ogg Vorbis files
+/// as well as WAV, and Opus with the `opus` feature.
+#[cfg(not(target_arch = "wasm32"))]
+pub fn read_samples<P>(path: P) -> Result<(WavSpec, Vec<f32>), Error>
+where
+    P: AsRef<Path>,
+{
+    let (spec, samples) = read_samples_f64(path)?;
+    let samples = samples
+        .map(|sample| sample.map(|value| value as f32))
+        .collect::<Result<_, _>>()?;
+
+    Ok((spec, samples))
+}
+
+/// The samples of the WAV file at `path` as 64-bit floats, whatever its
+/// format, including the 64-bit float files hound can't read, and the
+/// compressed files the `decode` feature reads.
+#[cfg(not(target_arch = "wasm32"))]
+#[allow(clippy::type_complexity)]
+pub(crate) fn read_samples_f64<P>(
+    path: P,
+) -> Result<(WavSpec, Box<dyn Iterator<Item = Result<f64, Error>>>), Error>
+where
+    P: AsRef<Path>,
+{
+    #[cfg(feature = "decode")]
+    if decode::is_compressed(&path) {
+        let (spec, samples) = decode::samples(&path)?;
+        return Ok((spec, Box::new(samples)));
+    }
+
+    if let Some(spec) = wav64::spec(&path)? {
+        let file = File::open(&path).or(Err(Error::ReadError))?;
+        return Ok((spec, Box::new(wav64::samples(file)?)));
+    }
+
+    let reader = hound::WavReader::open(&path).or(Err(Error::ReadError))?;
+    let spec = reader.spec();
+
+    let samples: Box<dyn Iterator<Item = Result<f64, Error>>> = match spec.sample_format {
+        hound::SampleFormat::Float => Box::new(
+            reader
+                .into_samples::<f32>()
+                .map(|sample| sample.map(f64::from).or(Err(Error::ReadError))),
+        ),
+        hound::SampleFormat::Int => {
+            let scale = (1u64 << (spec.bits_per_sample - 1)) as f64;
+
+            Box::new(reader.into_samples::<i32>().map(move |sample| {
+                sample
+                    .map(|value| f64::from(value) / scale)
+                    .or(Err(Error::ReadError))
+            }))
+        }
+    };
+
+    Ok((spec, samples))
+}
+
+/// The samples of a file as floats, whatever its format.
+#[cfg(not(target_arch = "wasm32"))]
+fn decode_samples(
+    reader: &mut hound::WavReader<BufReader<File>>,
+) -> Box<dyn Iterator<Item = hound::Result<f32>> + '_> {
+    let spec = reader.spec();
+
+    match spec.sample_format {
+        hound::SampleFormat::Float => Box::new(reader.samples::<f32>()),
+        hound::SampleFormat::Int => {
+            let scale = (1u64 << (spec.bits_per_sample - 1)) as f32;
+
+            Box::new(
+                reader
+                    .samples::<i32>()
+                    .map(move |sample| sample.map(|value| value as f32 / scale)),
+            )
+        }
+    }
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+fn ditherer(
+    writer: &hound::WavWriter<BufWriter<File>>,
+    dither: dither::Dither,
+) -> dither::Ditherer {
+    let spec = writer.spec();
+    dither::Ditherer::new(dither, spec.bits_per_sample, usize::from(spec.channels))
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+fn write_converted(
+    writer: &mut hound::WavWriter<BufWriter<File>>,
+    samples: &[f32],
+    dither: &mut dither::Ditherer,
+) -> Result<(), Error> {
+    let spec = writer.spec();
+
+    for &sample in samples {
+        match spec.sample_format {
+            hound::SampleFormat::Float => writer.write_sample(sample),
+            hound::SampleFormat::Int => writer.write_sample(dither.quantize(sample)),
+        }
+        .or(Err(Error::WriteError))?;
+    }
+
+    Ok(())
+}
+
 #[derive(Debug, Clone, Copy, PartialEq)]
 pub enum Device {
     Input,
     Output,
 }
 
+/// Whether a device is shared with other programs through the system's
+/// mixer, or held by one stream, which gets its samples untouched.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ShareMode {
+    Shared,
+    Exclusive,
+}
+
+impl ShareMode {
+    pub fn name(&self) -> &'static str {
+        match self {
+            ShareMode::Shared => "shared",
+            ShareMode::Exclusive => "exclusive",
+        }
+    }
+}
+
+impl std::str::FromStr for ShareMode {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "shared" => Ok(ShareMode::Shared),
+            "exclusive" => Ok(ShareMode::Exclusive),
+            _ => Err(format!(
+                "unknown share mode `{s}`, expected shared or exclusive"
+            )),
+        }
+    }
+}
+
 pub struct DeviceBuilder {
     kind: Device,
-    inner: cpal::Device,
+    inner: Backend,
     config: SupportedStreamConfig,
 }
 
+enum Backend {
+    Cpal(cpal::Device),
+    Mock(mock::MockDevice),
+}
+
+impl Backend {
+    fn cpal(&self) -> Result<&cpal::Device, Error> {
+        match self {
+            Backend::Cpal(device) => Ok(device),
+            Backend::Mock(_) => Err(Error::StreamCreationError),
+        }
+    }
+}
+
+enum Stream {
+    Cpal(cpal::Stream),
+    Mock(mock::MockStream),
+}
+
+impl Stream {
+    fn play(&self) -> Result<(), Error> {
+        match self {
+            Stream::Cpal(stream) => stream.play().or(Err(Error::PlayError)),
+            Stream::Mock(stream) => {
+                stream.play();
+                Ok(())
+            }
+        }
+    }
+
+    fn pause(&self) -> Result<(), Error> {
+        match self {
+            Stream::Cpal(stream) => stream.pause().or(Err(Error::PauseError)),
+            Stream::Mock(stream) => {
+                stream.pause();
+                Ok(())
+            }
+        }
+    }
+}
+
 #[derive(Debug, Clone, Copy, PartialEq)]
 pub enum Error {
     DefaultInputDeviceError,
@@ -64,6 +663,16 @@ pub enum Error {
     OutputLockError,
     WriteError,
     PlayError,
+    MetadataError,
+    PauseError,
+    DeviceNotFoundError,
+    ReadError,
+    AmbiguousDeviceError,
+    PluginNotFoundError,
+    PluginLoadError,
+    TranscribeError,
+    ConfigError,
+    EncodeError,
 }
 
 impl error::Error for Error {}
@@ -73,18 +682,88 @@ impl std::fmt::Display for Error {
         match self {
             Error::DefaultInputDeviceError => f.write_str("Error getting default input device"),
             Error::DefaultOutputDeviceError => f.write_str("Error getting default output device"),
-            Error::DefaultConfigError => f.write_str("Error getting output lock"),
+            Error::DefaultConfigError => f.write_str("Error getting default device config"),
             Error::StreamConfigFormatError => f.write_str("Bad stream config format"),
             Error::StreamCreationError => f.write_str("Error creating stream"),
-            Error::OutputLockError => f.write_str("Error getting default device config"),
+            Error::OutputLockError => f.write_str("Error getting output lock"),
             Error::WriteError => f.write_str("Error writing data"),
             Error::PlayError => f.write_str("Error recording data"),
+            Error::MetadataError => f.write_str("Error writing metadata"),
+            Error::PauseError => f.write_str("Error pausing stream"),
+            Error::DeviceNotFoundError => f.write_str("No device with that name"),
+            Error::ReadError => f.write_str("Error reading audio file"),
+            Error::AmbiguousDeviceError => f.write_str("More than one device matches"),
+            Error::PluginNotFoundError => f.write_str("No plugin with that name"),
+            Error::PluginLoadError => f.write_str("Error loading plugin"),
+            Error::TranscribeError => f.write_str("Error transcribing audio"),
+            Error::ConfigError => f.write_str("Error reading the configuration file"),
+            Error::EncodeError => f.write_str("Error encoding audio"),
+        }
+    }
+}
+
+fn devices(kind: Device) -> Result<impl Iterator<Item = cpal::Device>, Error> {
+    let host = cpal::default_host();
+
+    match kind {
+        Device::Input => host.input_devices(),
+        Device::Output => host.output_devices(),
+    }
+    .or(Err(Error::DeviceNotFoundError))
+}
+
+/// Devices of the ASIO host, which is never the default.
+#[cfg(feature = "asio")]
+fn asio_devices(kind: Device) -> Result<Box<dyn Iterator<Item = cpal::Device>>, Error> {
+    #[cfg(target_os = "windows")]
+    {
+        let host = cpal::host_from_id(cpal::HostId::Asio).or(Err(Error::DeviceNotFoundError))?;
+
+        match kind {
+            Device::Input => Ok(Box::new(
+                host.input_devices().or(Err(Error::DeviceNotFoundError))?,
+            )),
+            Device::Output => Ok(Box::new(
+                host.output_devices().or(Err(Error::DeviceNotFoundError))?,
+            )),
         }
     }
+
+    #[cfg(not(target_os = "windows"))]
+    {
+        let _ = kind;
+        Err(Error::DeviceNotFoundError)
+    }
+}
+
+/// `hw:2,0` as ALSA lists it, `hw:CARD=USB,DEV=0`, or `pcm` unchanged if
+/// it doesn't name a card by number.
+fn alsa_card_ids(pcm: &str) -> String {
+    let Some((plugin, args)) = pcm.split_once(':') else {
+        return pcm.to_owned();
+    };
+
+    let mut args = args.split(',');
+    let card = args.next().unwrap_or_default();
+    let dev = args.next().unwrap_or("0");
+
+    if card.parse::<u32>().is_err() {
+        return pcm.to_owned();
+    }
+
+    match std::fs::read_to_string(format!("/proc/asound/card{card}/id")) {
+        Ok(id) => format!("{plugin}:CARD={},DEV={dev}", id.trim()),
+        Err(_) => pcm.to_owned(),
+    }
 }
 
 impl DeviceBuilder {
     pub fn new_default_input() -> Result<DeviceBuilder, Error> {
+        #[cfg(all(feature = "mock-host", not(target_arch = "wasm32")))]
+        if let Some(device) = mock::from_env()? {
+            return Ok(device);
+        }
+
         let host = cpal::default_host();
         let device = host
             .default_input_device()
@@ -96,7 +775,7 @@ impl DeviceBuilder {
 
         Ok(DeviceBuilder {
             kind: Device::Input,
-            inner: device,
+            inner: Backend::Cpal(device),
             config,
         })
     }
@@ -113,179 +792,2235 @@ impl DeviceBuilder {
 
         Ok(DeviceBuilder {
             kind: Device::Output,
-            inner: device,
+            inner: Backend::Cpal(device),
             config,
         })
     }
 
-    pub fn kind(&self) -> Device {
-        self.kind
-    }
+    pub fn from_name(kind: Device, name: &str) -> Result<DeviceBuilder, Error> {
+        let device = devices(kind)?
+            .find(|device| device.name().is_ok_and(|n| n == name))
+            .ok_or(Error::DeviceNotFoundError)?;
 
-    pub fn name(&self) -> Result<String, cpal::DeviceNameError> {
-        self.inner.name()
+        DeviceBuilder::from_cpal(kind, device)
     }
 
-    pub fn config(&self) -> &SupportedStreamConfig {
-        &self.config
+    /// Find a device whose name contains `query`, ignoring case. An exact
+    /// name wins; otherwise more than one match is an
+    /// `AmbiguousDeviceError`, and `matching` lists them.
+    pub fn find(kind: Device, query: &str) -> Result<DeviceBuilder, Error> {
+        DeviceBuilder::find_among(kind, devices(kind)?, query)
     }
 
-    pub fn use_config(&mut self, config: SupportedStreamConfig) -> &mut Self {
-        self.config = config;
-        self
+    /// Names of the devices `find` would choose from for `query`.
+    pub fn matching(kind: Device, query: &str) -> Result<Vec<String>, Error> {
+        let query = query.to_lowercase();
+
+        Ok(devices(kind)?
+            .filter_map(|device| device.name().ok())
+            .filter(|name| name.to_lowercase().contains(&query))
+            .collect())
     }
-}
 
-pub struct StreamBuilder {
-    device: DeviceBuilder,
-    config: SupportedStreamConfig,
-    stream: Option<cpal::Stream>,
-    writer: Option<WavWriter>,
-    from_kind: Device,
-}
+    fn find_among(
+        kind: Device,
+        devices: impl Iterator<Item = cpal::Device>,
+        query: &str,
+    ) -> Result<DeviceBuilder, Error> {
+        let query = query.to_lowercase();
 
-type WavWriter = Arc<Mutex<Option<hound::WavWriter<BufWriter<File>>>>>;
+        let mut matches = devices
+            .filter_map(|device| device.name().ok().map(|name| (name, device)))
+            .filter(|(name, _)| name.to_lowercase().contains(&query))
+            .collect::<Vec<_>>();
 
-impl StreamBuilder {
-    pub fn new(device: DeviceBuilder) -> Result<StreamBuilder, Error> {
-        let from_kind = device.kind;
+        if let Some(exact) = matches
+            .iter()
+            .position(|(name, _)| name.to_lowercase() == query)
+        {
+            let (_, device) = matches.swap_remove(exact);
+            return DeviceBuilder::from_cpal(kind, device);
+        }
 
-        let config = match device.kind {
-            Device::Input => device
-                .inner
-                .default_input_config()
-                .or(Err(Error::DefaultConfigError))?,
-            Device::Output => device
-                .inner
-                .default_output_config()
-                .or(Err(Error::DefaultConfigError))?,
-        };
+        match matches.len() {
+            0 => Err(Error::DeviceNotFoundError),
+            1 => {
+                let (_, device) = matches.remove(0);
+                DeviceBuilder::from_cpal(kind, device)
+            }
+            _ => Err(Error::AmbiguousDeviceError),
+        }
+    }
 
-        Ok(StreamBuilder {
-            device,
-            config,
-            stream: None,
-            writer: None,
-            from_kind,
-        })
+    /// A JACK client named `client`, its ports (`client:in_1`, ... or
+    /// `out_1`, ...) left for the caller to connect, rather than to the
+    /// system's. Needs the `jack` feature, and a system cpal runs JACK on.
+    #[cfg(feature = "jack")]
+    pub fn jack(kind: Device, client: &str) -> Result<DeviceBuilder, Error> {
+        #[cfg(any(
+            target_os = "linux",
+            target_os = "dragonfly",
+            target_os = "freebsd",
+            target_os = "netbsd"
+        ))]
+        {
+            let mut host = cpal::host::jack::Host::new().or(Err(Error::DeviceNotFoundError))?;
+            host.set_connect_automatically(false);
+
+            let device = match kind {
+                Device::Input => host.input_device_with_name(client),
+                Device::Output => host.output_device_with_name(client),
+            }
+            .ok_or(Error::DeviceNotFoundError)?;
+
+            DeviceBuilder::from_cpal(kind, device.into())
+        }
+
+        #[cfg(not(any(
+            target_os = "linux",
+            target_os = "dragonfly",
+            target_os = "freebsd",
+            target_os = "netbsd"
+        )))]
+        {
+            let _ = (kind, client);
+            Err(Error::DeviceNotFoundError)
+        }
     }
 
-    pub fn from_input(&mut self) -> &mut Self {
-        self.from_kind = Device::Input;
-        self
+    /// A device of the ASIO host, found as by `find`, for the low latency
+    /// of a pro audio interface's own driver. Needs the `asio` feature, on
+    /// Windows.
+    #[cfg(feature = "asio")]
+    pub fn asio(kind: Device, query: &str) -> Result<DeviceBuilder, Error> {
+        DeviceBuilder::find_among(kind, asio_devices(kind)?, query)
     }
 
-    pub fn from_output(&mut self) -> &mut Self {
-        self.from_kind = Device::Output;
-        self
+    /// Names of the ASIO host's devices of `kind`, usable with `asio`.
+    #[cfg(feature = "asio")]
+    pub fn asio_names(kind: Device) -> Result<Vec<String>, Error> {
+        Ok(asio_devices(kind)?
+            .filter_map(|device| device.name().ok())
+            .collect())
     }
 
+    /// A device by its ALSA PCM name, like `hw:2,0` or `plughw:CARD=USB`,
+    /// to capture straight from the hardware rather than through PulseAudio
+    /// or PipeWire. Card numbers are looked up in `/proc/asound`, as ALSA
+    /// lists devices by card id. Linux only.
+    pub fn alsa(kind: Device, pcm: &str) -> Result<DeviceBuilder, Error> {
+        if !cfg!(target_os = "linux") {
+            return Err(Error::DeviceNotFoundError);
+        }
+
+        let wanted = [pcm.to_owned(), alsa_card_ids(pcm)];
+
+        let device = devices(kind)?
+            .find(|device| device.name().is_ok_and(|name| wanted.contains(&name)))
+            .ok_or(Error::DeviceNotFoundError)?;
+
+        DeviceBuilder::from_cpal(kind, device)
+    }
+
+    /// The device at `index` in the order `names` lists them. Indices are
+    /// only stable while the set of devices doesn't change.
+    pub fn from_index(kind: Device, index: usize) -> Result<DeviceBuilder, Error> {
+        let device = devices(kind)?
+            .nth(index)
+            .ok_or(Error::DeviceNotFoundError)?;
+
+        DeviceBuilder::from_cpal(kind, device)
+    }
+
+    /// Open a device as given on a command line: `file:PATH` for a WAV file
+    /// input, `alsa:PCM` for `alsa`, `asio:NAME` for `asio`, a number for
+    /// `from_index`, otherwise a name for `find`. Any of these may be an
+    /// alias from the `config` file.
+    pub fn open(kind: Device, spec: &str) -> Result<DeviceBuilder, Error> {
+        let config = config::Config::load()?;
+        let spec = config.resolve(spec);
+
+        #[cfg(not(target_arch = "wasm32"))]
+        if let Some(path) = spec.strip_prefix("file:") {
+            return match kind {
+                Device::Input => DeviceBuilder::from_file(path),
+                Device::Output => Err(Error::DeviceNotFoundError),
+            };
+        }
+
+        if let Some(pcm) = spec.strip_prefix("alsa:") {
+            return DeviceBuilder::alsa(kind, pcm);
+        }
+
+        #[cfg(feature = "asio")]
+        if let Some(name) = spec.strip_prefix("asio:") {
+            return DeviceBuilder::asio(kind, name);
+        }
+
+        match spec.parse() {
+            Ok(index) => DeviceBuilder::from_index(kind, index),
+            Err(_) => DeviceBuilder::find(kind, spec),
+        }
+    }
+
+    fn from_cpal(kind: Device, device: cpal::Device) -> Result<DeviceBuilder, Error> {
+        let config = match kind {
+            Device::Input => device.default_input_config(),
+            Device::Output => device.default_output_config(),
+        }
+        .or(Err(Error::DefaultConfigError))?;
+
+        Ok(DeviceBuilder {
+            kind,
+            inner: Backend::Cpal(device),
+            config,
+        })
+    }
+
+    /// Names of the default host's devices of `kind`, usable with `from_name`.
+    pub fn names(kind: Device) -> Result<Vec<String>, Error> {
+        Ok(devices(kind)?
+            .filter_map(|device| device.name().ok())
+            .collect())
+    }
+
+    pub fn kind(&self) -> Device {
+        self.kind
+    }
+
+    pub fn name(&self) -> Result<String, cpal::DeviceNameError> {
+        match &self.inner {
+            Backend::Cpal(device) => device.name(),
+            Backend::Mock(device) => Ok(device.name()),
+        }
+    }
+
+    pub fn config(&self) -> &SupportedStreamConfig {
+        &self.config
+    }
+
+    pub fn use_config(&mut self, config: SupportedStreamConfig) -> &mut Self {
+        self.config = config;
+        self
+    }
+
+    /// Switch to the first of `formats` the device supports at its current
+    /// sample rate and channels, returning it; `None` leaves the config as
+    /// it was. Virtual devices keep theirs.
+    pub fn prefer_formats(&mut self, formats: &[cpal::SampleFormat]) -> Option<cpal::SampleFormat> {
+        let Backend::Cpal(device) = &self.inner else {
+            return None;
+        };
+
+        let rate = self.config.sample_rate();
+        let channels = self.config.channels();
+
+        let supported = match self.kind {
+            Device::Input => device.supported_input_configs().ok()?.collect::<Vec<_>>(),
+            Device::Output => device.supported_output_configs().ok()?.collect::<Vec<_>>(),
+        };
+
+        let config = formats.iter().find_map(|&format| {
+            supported
+                .iter()
+                .filter(|range| range.sample_format() == format && range.channels() == channels)
+                .find(|range| range.min_sample_rate() <= rate && rate <= range.max_sample_rate())
+                .map(|range| (*range).with_sample_rate(rate))
+        })?;
+
+        let format = config.sample_format();
+        self.config = config;
+
+        Some(format)
+    }
+
+    pub fn share_mode(&self) -> ShareMode {
+        match (&self.inner, self.name()) {
+            (Backend::Cpal(_), Ok(name)) if name.starts_with("hw:") => ShareMode::Exclusive,
+            _ => ShareMode::Shared,
+        }
+    }
+
+    /// The same device for exclusive use, if the host has one: on ALSA the
+    /// card's raw `hw:` device, bypassing the mixer. WASAPI and CoreAudio
+    /// devices only open shared. Busy devices aren't found.
+    pub fn exclusive(&self) -> Option<DeviceBuilder> {
+        let name = self.name().ok()?;
+        let (_, fields) = name.split_once(':')?;
+
+        let field = |key: &str| {
+            fields
+                .split(',')
+                .find_map(|field| field.strip_prefix(key)?.strip_prefix('='))
+        };
+
+        let card = field("CARD")?;
+        let hw = format!("hw:CARD={card},DEV={}", field("DEV").unwrap_or("0"));
+
+        let device = devices(self.kind)
+            .ok()?
+            .find(|device| device.name().is_ok_and(|name| name == hw))?;
+
+        DeviceBuilder::from_cpal(self.kind, device).ok()
+    }
+
+    /// Check the device can be used now by opening a stream on it and
+    /// closing it again: a busy device is found, but fails here.
+    pub fn probe(&self) -> Result<(), Error> {
+        let Backend::Cpal(device) = &self.inner else {
+            return Ok(());
+        };
+
+        let cfg = self.config.config();
+        let format = self.config.sample_format();
+
+        match self.kind {
+            Device::Input => device
+                .build_input_stream_raw(&cfg, format, |_, _| {}, |_| {}, None)
+                .map(drop),
+            Device::Output => device
+                .build_output_stream_raw(&cfg, format, |_, _| {}, |_| {}, None)
+                .map(drop),
+        }
+        .or(Err(Error::StreamCreationError))
+    }
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct Stats {
+    pub samples: u64,
+    pub peak: f32,
+    pub sum_squares: f64,
+    pub dropouts: u64,
+    pub clipped: u64,
+    /// Peak of the most recent buffer
+    pub current_peak: f32,
+    /// RMS of the most recent buffer
+    pub current_rms: f32,
+    /// Of the audio written so far, with `StreamBuilder::checksum`
+    pub checksum: Option<checksum::Checksum>,
+    /// Time spent in recent callbacks against the audio they carried, where
+    /// 1.0 takes as long as the buffer lasts and buffers start being missed
+    pub load: f32,
+}
+
+/// What a running stream was actually given, which can differ from what the
+/// device's default config suggested.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct StreamInfo {
+    pub sample_rate: u32,
+    pub channels: u16,
+    pub sample_format: cpal::SampleFormat,
+    /// Frames in the most recent callback
+    pub buffer_frames: u32,
+}
+
+/// Where a stream is, e.g. for an application to show.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum StreamState {
+    Playing,
+    /// Created but not playing, including before the first `play`
+    Paused,
+    /// Not created yet, stopped, or a mock device's file ran out
+    Stopped,
+    /// The backend reported an error; `play` or `reconnect` may recover it
+    Errored,
+}
+
+impl StreamState {
+    pub fn name(&self) -> &'static str {
+        match self {
+            StreamState::Playing => "playing",
+            StreamState::Paused => "paused",
+            StreamState::Stopped => "stopped",
+            StreamState::Errored => "errored",
+        }
+    }
+
+    fn from_u8(value: u8) -> StreamState {
+        match value {
+            0 => StreamState::Playing,
+            1 => StreamState::Paused,
+            3 => StreamState::Errored,
+            _ => StreamState::Stopped,
+        }
+    }
+}
+
+/// When `StreamBuilder::start_at` passes audio on from, by the system clock.
+/// Audio is placed on it by the device's timestamps, so the start falls on
+/// the right frame however callbacks are scheduled.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Trigger {
+    At(SystemTime),
+    /// At the next whole multiple of this since the Unix epoch, e.g. the
+    /// next second, the same moment for every recorder with a synced clock
+    Next(Duration),
+}
+
+impl Trigger {
+    /// The time to start at, for a buffer captured at `at`.
+    fn resolve(self, at: SystemTime) -> SystemTime {
+        let period = match self {
+            Trigger::At(time) => return time,
+            Trigger::Next(period) if period.is_zero() => return at,
+            Trigger::Next(period) => period.as_nanos(),
+        };
+
+        let since_epoch = at.duration_since(UNIX_EPOCH).unwrap_or_default();
+        let periods = since_epoch.as_nanos().div_ceil(period);
+
+        UNIX_EPOCH + Duration::from_nanos((periods * period) as u64)
+    }
+}
+
+/// Holds audio back until a `Trigger`, to the frame.
+struct Start {
+    trigger: Option<Trigger>,
+    sample_rate: u32,
+    /// The system time and device timestamp of the first callback
+    origin: Option<SystemTime>,
+    device_origin: Option<cpal::StreamInstant>,
+    /// Without timestamps, e.g. from a mock device, time is counted in frames
+    frames: u64,
+    started_at: Arc<Mutex<Option<SystemTime>>>,
+}
+
+impl Start {
+    /// Frames to leave out at the beginning of a buffer of `frames`, or
+    /// `None` to leave it all out. `at` is when it was captured and when its
+    /// callback came in, by the device's clock.
+    fn skip(&mut self, frames: usize, at: Option<DeviceTime>) -> Option<usize> {
+        let Some(trigger) = self.trigger else {
+            return Some(0);
+        };
+
+        let at = self.system_time(at);
+
+        self.frames += frames as u64;
+
+        // Resolved once, so a `Next` can't slip on to the one after
+        let time = trigger.resolve(at);
+        self.trigger = Some(Trigger::At(time));
+
+        let ahead = time.duration_since(at).unwrap_or_default();
+        let skip = (ahead.as_nanos() * u128::from(self.sample_rate)).div_ceil(1_000_000_000);
+        let skip = usize::try_from(skip).unwrap_or(usize::MAX);
+
+        if skip >= frames {
+            return None;
+        }
+
+        self.trigger = None;
+
+        if let Ok(mut started_at) = self.started_at.lock() {
+            *started_at = Some(at + frames_to_duration(skip as u64, self.sample_rate));
+        }
+
+        Some(skip)
+    }
+
+    /// Where a buffer captured at `at` falls on the system clock.
+    fn system_time(&mut self, at: Option<DeviceTime>) -> SystemTime {
+        let origin = *self.origin.get_or_insert_with(SystemTime::now);
+
+        let Some((at, now)) = at else {
+            return origin + frames_to_duration(self.frames, self.sample_rate);
+        };
+
+        let device_origin = *self.device_origin.get_or_insert(now);
+
+        match at.duration_since(&device_origin) {
+            Some(after) => origin + after,
+            None => origin - device_origin.duration_since(&at).unwrap_or_default(),
+        }
+    }
+}
+
+/// What `StreamBuilder::hold` and `arm` pass audio on for.
+#[derive(Clone)]
+struct HoldSwitch {
+    held: Arc<AtomicBool>,
+    /// Held from the input's level instead, by `arm`: the threshold, and how
+    /// long under it lets go
+    level: Option<(f32, Duration)>,
+    preroll: Duration,
+    postroll: Duration,
+}
+
+/// Passes audio on only while a `HoldSwitch` is held, with the pre-roll from
+/// just before and the post-roll after.
+struct Hold<T> {
+    held: Arc<AtomicBool>,
+    /// The threshold, and samples under it that let go
+    level: Option<(f32, usize)>,
+    /// Samples under the threshold so far
+    quiet: usize,
+    /// In samples
+    preroll: usize,
+    postroll: usize,
+    /// The latest audio not passed on, up to the pre-roll
+    recent: VecDeque<T>,
+    /// Samples left of the post-roll
+    remaining: usize,
+    /// The pre-roll and the buffer it comes before, together
+    joined: Vec<T>,
+}
+
+impl<T> Hold<T>
+where
+    T: cpal::Sample,
+    f32: cpal::FromSample<T>,
+{
+    fn new(switch: &HoldSwitch, sample_rate: u32, channels: usize) -> Hold<T> {
+        let samples = |duration: Duration| {
+            (duration.as_secs_f64() * f64::from(sample_rate)).round() as usize * channels
+        };
+        let preroll = samples(switch.preroll);
+
+        Hold {
+            held: Arc::clone(&switch.held),
+            level: switch
+                .level
+                .map(|(threshold, silence)| (threshold, samples(silence))),
+            quiet: 0,
+            preroll,
+            postroll: samples(switch.postroll),
+            recent: VecDeque::with_capacity(preroll),
+            remaining: 0,
+            joined: Vec::new(),
+        }
+    }
+
+    /// The part of a buffer to pass on, whole frames of `channels`.
+    fn pass<'a>(&'a mut self, data: &'a [T], channels: usize) -> &'a [T] {
+        if let Some((threshold, silence)) = self.level {
+            let peak = data.iter().fold(0.0f32, |peak, &value| {
+                peak.max(value.to_sample::<f32>().abs())
+            });
+
+            if peak >= threshold {
+                self.quiet = 0;
+                self.held.store(true, Ordering::Relaxed);
+            } else if self.held.load(Ordering::Relaxed) {
+                self.quiet += data.len();
+
+                if self.quiet >= silence {
+                    self.held.store(false, Ordering::Relaxed);
+                }
+            }
+        }
+
+        if self.held.load(Ordering::Relaxed) {
+            self.remaining = self.postroll;
+
+            if self.recent.is_empty() {
+                return data;
+            }
+
+            self.joined.clear();
+            self.joined.extend(self.recent.drain(..));
+            self.joined.extend_from_slice(data);
+
+            return &self.joined;
+        }
+
+        let tail = self.remaining.min(data.len()) / channels * channels;
+        self.remaining -= tail;
+
+        self.recent.extend(&data[tail..]);
+
+        let excess = self.recent.len().saturating_sub(self.preroll);
+        self.recent.drain(..excess);
+
+        &data[..tail]
+    }
+}
+
+/// When a buffer was captured and when its callback came in
+type DeviceTime = (cpal::StreamInstant, cpal::StreamInstant);
+
+fn frames_to_duration(frames: u64, sample_rate: u32) -> Duration {
+    let nanos = u128::from(frames) * 1_000_000_000 / u128::from(sample_rate.max(1));
+    Duration::from_nanos(nanos as u64)
+}
+
+/// Output queued by `with_processor` before the oldest audio is dropped
+const PROCESSOR_LATENCY: Duration = Duration::from_millis(100);
+
+/// `StreamBuilder::low_latency` goes no smaller, as drivers offering less
+/// tend not to keep up
+const MIN_BUFFER_FRAMES: u32 = 64;
+
+/// Audio a `write_wav` stream can queue while the file is slow to take it
+#[cfg(not(target_arch = "wasm32"))]
+const WRITE_BUFFER: Duration = Duration::from_secs(2);
+
+/// How often the writer thread looks for queued audio
+#[cfg(not(target_arch = "wasm32"))]
+const WRITE_INTERVAL: Duration = Duration::from_millis(10);
+
+/// Most the writer thread takes from the queue at once
+#[cfg(not(target_arch = "wasm32"))]
+const WRITE_CHUNK_FRAMES: usize = 4096;
+
+/// Samples at or above this level are counted as clipped
+pub const CLIP_LEVEL: f32 = 0.999;
+
+/// Callback load at which `Stats::is_overloaded`, leaving some headroom
+/// before buffers are missed
+pub const HIGH_LOAD: f32 = 0.8;
+
+/// Kept of the callback load each callback, so a spike shows for a while
+#[cfg(not(target_arch = "wasm32"))]
+const LOAD_DECAY: f32 = 0.95;
+
+impl Stats {
+    pub fn frames(&self, channels: u16) -> u64 {
+        self.samples / u64::from(channels.max(1))
+    }
+
+    pub fn rms(&self) -> f32 {
+        if self.samples == 0 {
+            return 0.0;
+        }
+
+        (self.sum_squares / self.samples as f64).sqrt() as f32
+    }
+
+    /// Whether callbacks are close to taking longer than the audio lasts.
+    pub fn is_overloaded(&self) -> bool {
+        self.load >= HIGH_LOAD
+    }
+
+    fn update(&mut self, levels: &Levels) {
+        self.peak = self.peak.max(levels.peak);
+        self.sum_squares += levels.sum_squares;
+        self.samples += levels.samples;
+        self.clipped += levels.clipped;
+        self.current_peak = levels.peak;
+
+        if levels.samples > 0 {
+            self.current_rms = (levels.sum_squares / levels.samples as f64).sqrt() as f32;
+        }
+    }
+}
+
+/// Levels of a single callback buffer
+#[derive(Default)]
+struct Levels {
+    peak: f32,
+    sum_squares: f64,
+    samples: u64,
+    clipped: u64,
+}
+
+impl Levels {
+    fn add(&mut self, value: f32) {
+        let value = value.abs();
+
+        self.peak = self.peak.max(value);
+        self.sum_squares += f64::from(value) * f64::from(value);
+        self.samples += 1;
+        self.clipped += u64::from(value >= CLIP_LEVEL);
+    }
+}
+
+pub fn to_dbfs(level: f32) -> f32 {
+    20.0 * level.log10()
+}
+
+/// What `read` and `tap` callbacks return: nothing, whether to carry on as
+/// a `bool`, or a `ControlFlow`. Breaking ends the stream as a file device
+/// running out does: no more audio is taken and `on_end` is called, leaving
+/// `finish` or `stop` to tear it down away from the audio thread.
+pub trait Flow {
+    fn stops(self) -> bool;
+}
+
+impl Flow for () {
+    fn stops(self) -> bool {
+        false
+    }
+}
+
+impl Flow for bool {
+    fn stops(self) -> bool {
+        !self
+    }
+}
+
+impl<B> Flow for ControlFlow<B> {
+    fn stops(self) -> bool {
+        ControlFlow::is_break(&self)
+    }
+}
+
+/// Ends a stream from its callbacks, once
+#[derive(Clone)]
+struct Ending {
+    ended: Arc<AtomicBool>,
+    on_end: Arc<Mutex<Option<EndCallback>>>,
+    /// A `StreamState`, shared with the `StreamBuilder`
+    state: Arc<AtomicU8>,
+}
+
+impl Default for Ending {
+    fn default() -> Ending {
+        Ending {
+            ended: Arc::default(),
+            on_end: Arc::default(),
+            state: Arc::new(AtomicU8::new(StreamState::Stopped as u8)),
+        }
+    }
+}
+
+impl Ending {
+    fn has_ended(&self) -> bool {
+        self.ended.load(Ordering::Relaxed)
+    }
+
+    fn end(&self) {
+        if self.ended.swap(true, Ordering::Relaxed) {
+            return;
+        }
+
+        self.state
+            .store(StreamState::Stopped as u8, Ordering::Relaxed);
+
+        let on_end = self.on_end.lock().ok().and_then(|mut on_end| on_end.take());
+
+        if let Some(on_end) = on_end {
+            on_end();
+        }
+    }
+}
+
+pub struct StreamBuilder {
+    device: DeviceBuilder,
+    config: SupportedStreamConfig,
+    stream: Option<Stream>,
+    player: Option<playback::Player>,
+    writer: Option<WavWriter>,
+    #[cfg(not(target_arch = "wasm32"))]
+    writer_thread: Option<WriterThread>,
+    stats: SharedStats,
+    gain: Arc<AtomicU32>,
+    on_error: Option<ErrorCallback>,
+    taps: Taps,
+    effects: effects::SharedChain,
+    ending: Ending,
+    from_kind: Device,
+    #[cfg(not(target_arch = "wasm32"))]
+    segments: SharedSegments,
+    checksum: SharedHasher,
+    #[cfg(not(target_arch = "wasm32"))]
+    reserve: SharedReserve,
+    #[cfg(not(target_arch = "wasm32"))]
+    durability: SharedDurability,
+    buffer_size: cpal::BufferSize,
+    priority: Option<priority::Priority>,
+    /// Seen in callbacks, as backends may not keep to the size asked for
+    buffer_frames: Arc<AtomicU32>,
+    /// Since the stream was first built, across segments and reconnects
+    frames: Arc<AtomicU64>,
+    load: Load,
+    trigger: Option<Trigger>,
+    /// When the first frame passed on after a `trigger` was captured
+    started_at: Arc<Mutex<Option<SystemTime>>>,
+    hold: Option<HoldSwitch>,
+}
+
+type WavWriter = Arc<Mutex<Option<hound::WavWriter<BufWriter<File>>>>>;
+type SharedHasher = Arc<Mutex<Option<checksum::Hasher>>>;
+#[cfg(not(target_arch = "wasm32"))]
+type SharedSegments = Arc<Mutex<Option<Segments>>>;
+#[cfg(not(target_arch = "wasm32"))]
+type SharedReserve = Arc<Mutex<Option<preallocate::Reserve>>>;
+#[cfg(not(target_arch = "wasm32"))]
+type SharedDurability = Arc<Mutex<durability::Durability>>;
+type SharedStats = Arc<Mutex<Stats>>;
+pub type SharedSamples = Arc<Mutex<Vec<f32>>>;
+// Shared so a reconnected stream keeps the same callbacks
+type ErrorCallback = Arc<Mutex<dyn FnMut(cpal::StreamError) + Send + 'static>>;
+/// Returns whether to stop the stream
+type DataCallback = Box<dyn FnMut(&[f32]) -> bool + Send + 'static>;
+type Taps = Arc<Mutex<Vec<DataCallback>>>;
+pub(crate) type EndCallback = Box<dyn FnOnce() + Send + 'static>;
+
+impl StreamBuilder {
+    pub fn new(device: DeviceBuilder) -> Result<StreamBuilder, Error> {
+        let from_kind = device.kind;
+
+        let config = match (&device.inner, device.kind) {
+            (Backend::Cpal(inner), Device::Input) => inner
+                .default_input_config()
+                .or(Err(Error::DefaultConfigError))?,
+            (Backend::Cpal(inner), Device::Output) => inner
+                .default_output_config()
+                .or(Err(Error::DefaultConfigError))?,
+            (Backend::Mock(_), _) => device.config.clone(),
+        };
+
+        Ok(StreamBuilder {
+            device,
+            config,
+            stream: None,
+            player: None,
+            writer: None,
+            #[cfg(not(target_arch = "wasm32"))]
+            writer_thread: None,
+            stats: Arc::default(),
+            gain: Arc::new(AtomicU32::new(1.0f32.to_bits())),
+            on_error: None,
+            taps: Taps::default(),
+            effects: effects::SharedChain::default(),
+            ending: Ending::default(),
+            from_kind,
+            #[cfg(not(target_arch = "wasm32"))]
+            segments: SharedSegments::default(),
+            checksum: SharedHasher::default(),
+            #[cfg(not(target_arch = "wasm32"))]
+            reserve: SharedReserve::default(),
+            #[cfg(not(target_arch = "wasm32"))]
+            durability: SharedDurability::default(),
+            buffer_size: cpal::BufferSize::Default,
+            priority: None,
+            buffer_frames: Arc::default(),
+            frames: Arc::default(),
+            load: Load::default(),
+            trigger: None,
+            started_at: Arc::default(),
+            hold: None,
+        })
+    }
+
+    pub fn config(&self) -> &SupportedStreamConfig {
+        &self.config
+    }
+
+    pub fn stats(&self) -> Stats {
+        let mut stats = self.stats.lock().map(|stats| *stats).unwrap_or_default();
+
+        if let Ok(checksum) = self.checksum.lock() {
+            stats.checksum = checksum.as_ref().map(checksum::Hasher::checksum);
+        }
+
+        stats.load = self.load.get();
+
+        stats
+    }
+
+    pub fn gain(&self) -> f32 {
+        f32::from_bits(self.gain.load(Ordering::Relaxed))
+    }
+
+    /// Linear gain applied to samples before they are written. Can be
+    /// changed while the stream is running.
+    pub fn set_gain(&self, gain: f32) {
+        self.gain.store(gain.to_bits(), Ordering::Relaxed);
+    }
+
+    pub fn from_input(&mut self) -> &mut Self {
+        self.from_kind = Device::Input;
+        self
+    }
+
+    pub fn from_output(&mut self) -> &mut Self {
+        self.from_kind = Device::Output;
+        self
+    }
+
+    /// Handle stream errors (e.g. device loss) instead of exiting the process.
+    /// A `write_wav` file failing to take audio is reported here too, and
+    /// ends the stream. Must be set before the stream is created.
+    pub fn on_error<F>(&mut self, callback: F) -> &mut Self
+    where
+        F: FnMut(cpal::StreamError) + Send + 'static,
+    {
+        self.on_error = Some(Arc::new(Mutex::new(callback)));
+        self
+    }
+
+    /// Also hand each buffer written by `write_wav` to `callback` as
+    /// interleaved `f32` samples. Must be set before the stream is created.
+    /// It may stop the stream; see `Flow`.
+    pub fn tap<F, R>(&mut self, mut callback: F) -> &mut Self
+    where
+        F: FnMut(&[f32]) -> R + Send + 'static,
+        R: Flow,
+    {
+        if let Ok(mut taps) = self.taps.lock() {
+            taps.push(Box::new(move |data| callback(data).stops()));
+        }
+
+        self
+    }
+
+    /// Transcribe what `write_wav` records with `transcriber`, writing a
+    /// WebVTT transcript to `path` as it goes. Must be set before the stream
+    /// is created.
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fn transcribe<T, P>(
+        &mut self,
+        transcriber: T,
+        path: P,
+    ) -> Result<transcribe::Transcription, Error>
+    where
+        T: transcribe::Transcriber + 'static,
+        P: AsRef<Path>,
+    {
+        let transcription = transcribe::Transcription::new(
+            transcriber,
+            path,
+            self.config.sample_rate().0,
+            self.config.channels(),
+        )?;
+
+        self.tap(transcription.feeder());
+
+        Ok(transcription)
+    }
+
+    /// Run captured audio through `effect` before it's written, read or
+    /// tapped, after any effects already added. Can be added while the
+    /// stream is running.
+    pub fn effect<E>(&mut self, effect: E) -> &mut Self
+    where
+        E: effects::Effect + 'static,
+    {
+        if let Ok(mut effects) = self.effects.lock() {
+            effects.push(effect);
+        }
+
+        self
+    }
+
+    /// Called once when a finite source (a file device) runs out, or a
+    /// callback stops the stream. Must be set before the stream is created.
+    pub fn on_end<F>(&mut self, callback: F) -> &mut Self
+    where
+        F: FnOnce() + Send + 'static,
+    {
+        if let Ok(mut on_end) = self.ending.on_end.lock() {
+            *on_end = Some(Box::new(callback));
+        }
+
+        self
+    }
+
+    /// Hash the audio written by `write_wav` as it goes; the checksum of
+    /// each file comes with its stats. Call before `write_wav`.
+    pub fn checksum(&mut self, algorithm: checksum::Algorithm) -> &mut Self {
+        if let Ok(mut checksum) = self.checksum.lock() {
+            *checksum = Some(checksum::Hasher::new(algorithm));
+        }
+
+        self
+    }
+
+    /// Reserve disk space for `write_wav` files as they're written: `bytes`
+    /// up front, e.g. the expected size or 0 if unknown, then more in
+    /// growing steps as each fills. Unused space is given back once a file
+    /// is finalized. Call before `write_wav`.
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fn preallocate(&mut self, bytes: u64) -> &mut Self {
+        if let Ok(mut reserve) = self.reserve.lock() {
+            *reserve = Some(preallocate::Reserve::new(bytes));
+        }
+
+        self
+    }
+
+    /// Flush `write_wav` files this often as they're written, header
+    /// included, so a crash loses at most about this much. Otherwise data
+    /// goes out as buffers fill and the header is only written at the end.
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fn flush_interval(&mut self, interval: Duration) -> &mut Self {
+        if let Ok(mut durability) = self.durability.lock() {
+            durability.set_interval(interval);
+        }
+
+        self
+    }
+
+    /// When to sync `write_wav` files, waiting for the disk to have them
+    /// rather than leaving that to the OS.
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fn fsync(&mut self, fsync: durability::Fsync) -> &mut Self {
+        if let Ok(mut durability) = self.durability.lock() {
+            durability.set_fsync(fsync);
+        }
+
+        self
+    }
+
+    /// Split a `write_wav` recording into files of exactly `frames` frames,
+    /// naming each one after the first with `path`, which gets its number
+    /// (from 1). Call before `write_wav`.
+    ///
+    /// A buffer crossing a boundary is split on that frame, so no samples
+    /// are lost or repeated between files. Collect the files as they are
+    /// done with `finished_segments`.
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fn segment_wav<F>(&mut self, frames: u64, path: F) -> &mut Self
+    where
+        F: FnMut(usize) -> PathBuf + Send + 'static,
+    {
+        if let Ok(mut segments) = self.segments.lock() {
+            *segments = Some(Segments {
+                frames: frames.max(1),
+                written: 0,
+                count: 0,
+                path: PathBuf::new(),
+                next_path: Box::new(path),
+                spec: self.device.config().as_wav_spec(),
+                finished: Vec::new(),
+            });
+        }
+
+        self
+    }
+
+    /// Finalize the files `segment_wav` has moved on from since the last
+    /// call, returning their paths and stats in order.
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fn finished_segments(&mut self) -> Result<Vec<(PathBuf, Stats)>, Error> {
+        let finished = self
+            .segments
+            .lock()
+            .or(Err(Error::OutputLockError))?
+            .as_mut()
+            .map(|segments| std::mem::take(&mut segments.finished))
+            .unwrap_or_default();
+
+        finished
+            .into_iter()
+            .map(|(path, writer, stats)| {
+                writer.finalize().or(Err(Error::WriteError))?;
+                self.release(&path);
+                self.durability
+                    .lock()
+                    .or(Err(Error::OutputLockError))?
+                    .finished(&path)?;
+                Ok((path, stats))
+            })
+            .collect()
+    }
+
+    #[cfg(not(target_arch = "wasm32"))]
     pub fn write_wav<P>(&mut self, path: P) -> Result<WavWriter, Error>
     where
-        P: AsRef<Path>,
+        P: AsRef<Path>,
+    {
+        let writer = hound::WavWriter::create(&path, self.device.config().as_wav_spec())
+            .or(Err(Error::WriteError))?;
+
+        self.start_wav(writer, path.as_ref())
+    }
+
+    /// Like `write_wav`, but carry on at the end of an existing recording,
+    /// e.g. one stopped for a break. The file must have the stream's format.
+    /// Chunks after the audio, such as tags, are dropped; write them again
+    /// once finished.
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fn append_wav<P>(&mut self, path: P) -> Result<WavWriter, Error>
+    where
+        P: AsRef<Path>,
+    {
+        let spec = hound::WavReader::open(&path)
+            .or(Err(Error::ReadError))?
+            .spec();
+
+        if spec != self.device.config().as_wav_spec() {
+            return Err(Error::StreamConfigFormatError);
+        }
+
+        // The checksum covers the whole file
+        if let Some(hasher) = self
+            .checksum
+            .lock()
+            .or(Err(Error::OutputLockError))?
+            .as_mut()
+        {
+            *hasher = checksum::Hasher::new(hasher.algorithm());
+            checksum::hash_file(&path, hasher)?;
+        }
+
+        metadata::strip_after_data(&path)?;
+
+        let writer = hound::WavWriter::append(&path).or(Err(Error::WriteError))?;
+
+        self.start_wav(writer, path.as_ref())
+    }
+
+    #[cfg(not(target_arch = "wasm32"))]
+    fn start_wav(
+        &mut self,
+        writer: hound::WavWriter<BufWriter<File>>,
+        path: &Path,
+    ) -> Result<WavWriter, Error> {
+        let writer = Arc::new(Mutex::new(Some(writer)));
+
+        if let Ok(mut reserve) = self.reserve.lock() {
+            if let Some(reserve) = reserve.as_mut() {
+                reserve.open(path);
+            }
+        }
+
+        if let Ok(mut durability) = self.durability.lock() {
+            durability.open(path);
+        }
+
+        if let Ok(mut segments) = self.segments.lock() {
+            if let Some(segments) = segments.as_mut() {
+                segments.path = path.to_owned();
+                segments.written = 0;
+            }
+        }
+
+        self.writer = Some(Arc::clone(&writer));
+        self.stream = Some(self.build_wav_stream(Arc::clone(&writer))?);
+
+        Ok(writer)
+    }
+
+    /// Move a `write_wav` recording onto `device`, e.g. once a lost device
+    /// comes back, and keep appending to the same file. The new stream uses
+    /// the original config, so the device must support it, and starts paused.
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fn reconnect(&mut self, device: DeviceBuilder) -> Result<(), Error> {
+        let writer = self.writer.clone().ok_or(Error::WriteError)?;
+
+        self.stream = None;
+        self.device = device;
+        self.stream = Some(self.build_wav_stream(writer)?);
+
+        Ok(())
+    }
+
+    #[cfg(not(target_arch = "wasm32"))]
+    fn build_wav_stream(&mut self, writer: WavWriter) -> Result<Stream, Error> {
+        // What the previous stream captured goes in first
+        self.writer_thread = None;
+
+        match self.config.sample_format() {
+            cpal::SampleFormat::F32 => self.build_wav_stream_as::<f32>(writer),
+            cpal::SampleFormat::I32 => self.build_wav_stream_as::<i32>(writer),
+            cpal::SampleFormat::I16 => self.build_wav_stream_as::<i16>(writer),
+            cpal::SampleFormat::I8 => self.build_wav_stream_as::<i8>(writer),
+            _ => Err(Error::StreamConfigFormatError),
+        }
+    }
+
+    /// The callback only queues what it captures; a thread of its own does
+    /// the writing, so the audio thread never waits on the file.
+    #[cfg(not(target_arch = "wasm32"))]
+    fn build_wav_stream_as<T>(&mut self, writer: WavWriter) -> Result<Stream, Error>
+    where
+        T: cpal::SizedSample + cpal::FromSample<f32> + hound::Sample + Send + 'static,
+        f32: cpal::FromSample<T>,
+    {
+        let sink = self.sink();
+        let taps = Arc::clone(&self.taps);
+        let segments = Arc::clone(&self.segments);
+
+        let frames = WRITE_BUFFER.as_secs_f64() * f64::from(self.config.sample_rate().0);
+        let (producer, consumer) = HeapRb::<T>::new(frames as usize * sink.channels).split();
+        let dropouts = Arc::new(AtomicU64::new(0));
+
+        // A file device reading faster than real time can wait for room
+        let wait = matches!(&self.device.inner, Backend::Mock(mock) if !mock.is_realtime());
+
+        let thread = WriterThread::spawn(wav_writer(
+            consumer,
+            writer,
+            sink,
+            taps,
+            segments,
+            Arc::clone(&dropouts),
+        ));
+
+        let queued = Arc::clone(&thread.queued);
+        let stream = self.build_stream::<T, _>(wav_capture(producer, queued, dropouts, wait))?;
+        self.writer_thread = Some(thread);
+
+        Ok(stream)
+    }
+
+    /// Capture into memory rather than a file, e.g. in a browser. The buffer
+    /// collects interleaved `f32` samples with gain applied; see `encode_wav`.
+    pub fn read_to_memory(&mut self) -> Result<SharedSamples, Error> {
+        let samples = SharedSamples::default();
+        let sink = Arc::clone(&samples);
+
+        self.read(move |data| {
+            if let Ok(mut samples) = sink.lock() {
+                samples.extend_from_slice(data);
+            }
+        })?;
+
+        Ok(samples)
+    }
+
+    /// Capture onto a channel, for taking audio on another thread. Each
+    /// message is a buffer of interleaved `f32` samples with gain applied.
+    /// Dropping the receiver stops the stream.
+    pub fn into_channel(&mut self) -> Result<mpsc::Receiver<Vec<f32>>, Error> {
+        let (tx, rx) = mpsc::channel();
+
+        // Unbounded, so the audio thread never waits on the receiver
+        self.read(move |data| tx.send(data.to_vec()).is_ok())?;
+
+        Ok(rx)
+    }
+
+    /// Frames passed on by the stream so far, kept by the callback rather
+    /// than per file like `stats`. Audio held back by `hold` or `arm`, or
+    /// before a `start_at` trigger, isn't counted; a pre-roll is.
+    pub fn frames_captured(&self) -> u64 {
+        self.frames.load(Ordering::Relaxed)
+    }
+
+    /// `frames_captured` as time at the stream's sample rate.
+    pub fn elapsed(&self) -> Duration {
+        let sample_rate = self.config.sample_rate().0.max(1);
+        Duration::from_secs_f64(self.frames_captured() as f64 / f64::from(sample_rate))
+    }
+
+    /// Only pass audio on from `trigger`, to the frame, e.g. so recordings
+    /// started on the next second line up. Applies to the next stream
+    /// created; `started_at` says when it went off.
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fn start_at(&mut self, trigger: Trigger) -> &mut Self {
+        self.trigger = Some(trigger);
+
+        if let Ok(mut started_at) = self.started_at.lock() {
+            *started_at = None;
+        }
+
+        self
+    }
+
+    /// When the first frame passed on after a `start_at` trigger was
+    /// captured. `None` until then.
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fn started_at(&self) -> Option<SystemTime> {
+        self.started_at
+            .lock()
+            .ok()
+            .and_then(|started_at| *started_at)
+    }
+
+    /// Only pass audio on while `held` is set, e.g. by a push-to-talk key,
+    /// along with `preroll` from just before it was and `postroll` after,
+    /// so the edges of a take aren't clipped. Applies to streams created
+    /// after this, reconnects included.
+    pub fn hold(
+        &mut self,
+        held: Arc<AtomicBool>,
+        preroll: Duration,
+        postroll: Duration,
+    ) -> &mut Self {
+        self.hold = Some(HoldSwitch {
+            held,
+            level: None,
+            preroll,
+            postroll,
+        });
+
+        self
+    }
+
+    /// Wait for the input to reach `threshold` (dBFS) before passing audio
+    /// on, `preroll` from before it did included, and stop again once it
+    /// has stayed under it for `silence`: a sound-activated recorder.
+    /// `is_held` says which it's doing. Applies to streams created after
+    /// this, reconnects included.
+    pub fn arm(&mut self, threshold: f32, preroll: Duration, silence: Duration) -> &mut Self {
+        self.hold = Some(HoldSwitch {
+            held: Arc::default(),
+            level: Some((10f32.powf(threshold / 20.0), silence)),
+            preroll,
+            postroll: Duration::ZERO,
+        });
+
+        self
+    }
+
+    /// Whether audio is passed on after `hold` or `arm`; always when neither
+    /// was called.
+    pub fn is_held(&self) -> bool {
+        self.hold
+            .as_ref()
+            .is_none_or(|switch| switch.held.load(Ordering::Relaxed))
+    }
+
+    pub fn state(&self) -> StreamState {
+        StreamState::from_u8(self.ending.state.load(Ordering::Relaxed))
+    }
+
+    fn set_state(&self, state: StreamState) {
+        self.ending.state.store(state as u8, Ordering::Relaxed);
+    }
+
+    /// The configuration the stream runs with, once audio has reached it.
+    pub fn info(&self) -> Option<StreamInfo> {
+        let buffer_frames = self.buffer_frames.load(Ordering::Relaxed);
+
+        (buffer_frames > 0).then(|| StreamInfo {
+            sample_rate: self.config.sample_rate().0,
+            channels: self.config.channels(),
+            sample_format: self.config.sample_format(),
+            buffer_frames,
+        })
+    }
+
+    /// For live monitoring: the device's smallest buffers, real-time priority
+    /// for the audio callbacks, and no more queued by `with_processor` than
+    /// a buffer each way. Set before the stream is built.
+    pub fn low_latency(&mut self) -> &mut Self {
+        if let cpal::SupportedBufferSize::Range { min, max } = *self.config.buffer_size() {
+            let frames = min.max(MIN_BUFFER_FRAMES).min(max);
+            self.buffer_size = cpal::BufferSize::Fixed(frames);
+        }
+
+        self.priority = Some(priority::Priority::default());
+        self
+    }
+
+    /// Ask the device for buffers of `frames`, clamped to what it supports.
+    /// On ALSA this is the whole hardware buffer, split in four periods.
+    /// Set before the stream is built.
+    pub fn buffer_frames(&mut self, frames: u32) -> &mut Self {
+        self.buffer_size = cpal::BufferSize::Fixed(match *self.config.buffer_size() {
+            cpal::SupportedBufferSize::Range { min, max } => frames.clamp(min, max),
+            cpal::SupportedBufferSize::Unknown => frames,
+        });
+        self
+    }
+
+    /// Whether the audio callbacks got the real-time priority `low_latency`
+    /// asks for; `None` until they've run.
+    pub fn realtime(&self) -> Option<bool> {
+        self.priority.as_ref().and_then(priority::Priority::granted)
+    }
+
+    /// How long audio takes from capture to playback with `with_processor`:
+    /// a buffer each way and what's queued between. Only known when buffer
+    /// sizes are, as with `low_latency`.
+    pub fn latency(&self) -> Option<Duration> {
+        let cpal::BufferSize::Fixed(input) = self.buffer_size else {
+            return None;
+        };
+
+        let player = self.player.as_ref()?;
+        let frames = input + player.buffer_frames()? + player.queued() as u32;
+
+        Some(Duration::from_secs_f64(
+            f64::from(frames) / f64::from(self.config.sample_rate().0),
+        ))
+    }
+
+    /// Play captured audio on `output` after passing it through `processor`,
+    /// e.g. for monitoring or live effects. Each call gets a captured buffer
+    /// (with gain applied) and a zeroed buffer to fill with the same number
+    /// of frames in the output's channel count. The output device must
+    /// support the capture sample rate.
+    pub fn with_processor<F>(
+        &mut self,
+        output: &DeviceBuilder,
+        mut processor: F,
+    ) -> Result<(), Error>
+    where
+        F: FnMut(&[f32], &mut [f32]) + Send + 'static,
+    {
+        let in_channels = usize::from(self.config.channels().max(1));
+        let out_channels = output.config().channels();
+
+        let sample_rate = self.config.sample_rate().0;
+
+        let (buffer_size, max_latency) = match self.buffer_size {
+            cpal::BufferSize::Fixed(input) => {
+                let frames = match *output.config().buffer_size() {
+                    cpal::SupportedBufferSize::Range { min, max } => input.clamp(min, max),
+                    cpal::SupportedBufferSize::Unknown => input,
+                };
+
+                // Room for a buffer arriving while one is played
+                let queued = f64::from(input + frames) / f64::from(sample_rate);
+
+                (
+                    cpal::BufferSize::Fixed(frames),
+                    Duration::from_secs_f64(queued),
+                )
+            }
+            cpal::BufferSize::Default => (cpal::BufferSize::Default, PROCESSOR_LATENCY),
+        };
+
+        let priority = self.priority.clone();
+        let mut player =
+            playback::Player::build(output, sample_rate, out_channels, buffer_size, priority)?;
+        player.set_max_latency(max_latency);
+
+        let feeder = player.feeder();
+        let mut buffer = Vec::new();
+
+        self.read(move |data| {
+            buffer.clear();
+            buffer.resize(data.len() / in_channels * usize::from(out_channels), 0.0);
+
+            processor(data, &mut buffer);
+            feeder.push(&buffer);
+        })?;
+
+        self.player = Some(player);
+
+        Ok(())
+    }
+
+    /// Hand each captured buffer to `callback` as interleaved `f32` samples.
+    /// It may stop the stream; see `Flow`.
+    pub fn read<F, R>(&mut self, callback: F) -> Result<(), Error>
+    where
+        F: FnMut(&[f32]) -> R + Send + 'static,
+        R: Flow,
+    {
+        let sink = self.sink();
+
+        let stream = match self.config.sample_format() {
+            cpal::SampleFormat::F32 => self.build_stream::<f32, _>(read_sink(sink, callback)),
+            cpal::SampleFormat::I32 => self.build_stream::<i32, _>(read_sink(sink, callback)),
+            cpal::SampleFormat::I16 => self.build_stream::<i16, _>(read_sink(sink, callback)),
+            cpal::SampleFormat::I8 => self.build_stream::<i8, _>(read_sink(sink, callback)),
+            _ => return Err(Error::StreamConfigFormatError),
+        }?;
+
+        self.stream = Some(stream);
+
+        Ok(())
+    }
+
+    fn sink(&self) -> Sink {
+        Sink {
+            stats: Arc::clone(&self.stats),
+            gain: Arc::clone(&self.gain),
+            effects: Arc::clone(&self.effects),
+            #[cfg(not(target_arch = "wasm32"))]
+            checksum: Arc::clone(&self.checksum),
+            #[cfg(not(target_arch = "wasm32"))]
+            reserve: Arc::clone(&self.reserve),
+            #[cfg(not(target_arch = "wasm32"))]
+            durability: Arc::clone(&self.durability),
+            #[cfg(not(target_arch = "wasm32"))]
+            on_error: self.on_error.clone(),
+            channels: usize::from(self.config.channels().max(1)),
+            ending: self.ending.clone(),
+        }
+    }
+
+    fn build_stream<T, D>(&mut self, mut on_data: D) -> Result<Stream, Error>
+    where
+        T: cpal::SizedSample + cpal::FromSample<f32> + Send + 'static,
+        f32: cpal::FromSample<T>,
+        D: FnMut(&[T], bool) + Send + 'static,
     {
-        let writer = hound::WavWriter::create(path, self.device.config().as_wav_spec())
-            .or(Err(Error::WriteError))?;
-        let writer = Arc::new(Mutex::new(Some(writer)));
+        let mut cfg = self.config.config();
+        cfg.buffer_size = self.buffer_size;
 
-        self.writer = Some(Arc::clone(&writer));
+        let buffer_frames = Arc::clone(&self.buffer_frames);
+        let frames = Arc::clone(&self.frames);
+        let load = self.load.clone();
+        let channels = usize::from(cfg.channels.max(1));
+        let sample_rate = cfg.sample_rate.0.max(1) as f32;
 
-        let cfg = self.config.clone(); // TODO: Try to remove this clone
-        let wav_writer = Arc::clone(&writer);
+        buffer_frames.store(0, Ordering::Relaxed);
+        load.reset();
 
-        let stream = match self.config.sample_format() {
-            cpal::SampleFormat::F32 if self.from_kind == Device::Input => {
-                make_wav_input_stream::<f32>(&self.device.inner, &cfg.into(), wav_writer)
-                    .or(Err(Error::StreamCreationError))?
-            }
-            cpal::SampleFormat::F32 if self.from_kind == Device::Output => {
-                make_wav_output_stream::<f32>(&self.device.inner, &cfg.into(), wav_writer)
-                    .or(Err(Error::StreamCreationError))?
-            }
-            cpal::SampleFormat::I32 if self.from_kind == Device::Input => {
-                make_wav_input_stream::<i32>(&self.device.inner, &cfg.into(), wav_writer)
-                    .or(Err(Error::StreamCreationError))?
-            }
-            cpal::SampleFormat::I32 if self.from_kind == Device::Output => {
-                make_wav_output_stream::<i32>(&self.device.inner, &cfg.into(), wav_writer)
-                    .or(Err(Error::StreamCreationError))?
+        let ending = self.ending.clone();
+        let mut start = Start {
+            trigger: self.trigger.take(),
+            sample_rate: cfg.sample_rate.0,
+            origin: None,
+            device_origin: None,
+            frames: 0,
+            started_at: Arc::clone(&self.started_at),
+        };
+        let mut hold = self
+            .hold
+            .as_ref()
+            .map(|switch| Hold::new(switch, cfg.sample_rate.0, channels));
+
+        let mut on_data = move |data: &[T], dropout, at: Option<DeviceTime>| {
+            // Whatever arrives before the stream is torn down is left out
+            if ending.has_ended() {
+                return;
             }
-            cpal::SampleFormat::I16 if self.from_kind == Device::Input => {
-                make_wav_input_stream::<i16>(&self.device.inner, &cfg.into(), wav_writer)
-                    .or(Err(Error::StreamCreationError))?
+
+            buffer_frames.store((data.len() / channels) as u32, Ordering::Relaxed);
+
+            let Some(skip) = start.skip(data.len() / channels, at) else {
+                return;
+            };
+
+            let data = &data[skip * channels..];
+            let len = data.len() / channels;
+
+            let data = match hold.as_mut() {
+                Some(hold) => hold.pass(data, channels),
+                None => data,
+            };
+
+            // Counted once held back, so as not to include what wasn't kept
+            frames.fetch_add((data.len() / channels) as u64, Ordering::Relaxed);
+
+            if data.is_empty() {
+                return;
             }
-            cpal::SampleFormat::I16 if self.from_kind == Device::Output => {
-                make_wav_output_stream::<i16>(&self.device.inner, &cfg.into(), wav_writer)
-                    .or(Err(Error::StreamCreationError))?
+
+            load.measure(len as f32 / sample_rate, || on_data(data, dropout))
+        };
+
+        if let Backend::Mock(device) = &self.device.inner {
+            if self.from_kind == Device::Output {
+                return Err(Error::StreamCreationError);
             }
-            cpal::SampleFormat::I8 if self.from_kind == Device::Input => {
-                make_wav_input_stream::<i8>(&self.device.inner, &cfg.into(), wav_writer)
-                    .or(Err(Error::StreamCreationError))?
+
+            let ending = self.ending.clone();
+            let on_end: EndCallback = Box::new(move || ending.end());
+
+            self.set_state(StreamState::Paused);
+
+            let on_data = move |data: &[T], dropout| on_data(data, dropout, None);
+
+            return Ok(Stream::Mock(device.build(&cfg, on_data, Some(on_end))));
+        }
+
+        let device = self.device.inner.cpal()?;
+
+        let mut timing = Timing::new(&cfg);
+        let on_error = self.on_error.clone();
+        let mut priority = self.priority.clone();
+        let state = Arc::clone(&self.ending.state);
+
+        let error_callback = move |err| {
+            state.store(StreamState::Errored as u8, Ordering::Relaxed);
+
+            match on_error.as_ref().map(|callback| callback.lock()) {
+                Some(Ok(mut callback)) => callback(err),
+                _ => fail!("writing data to buffer failed", err),
             }
-            cpal::SampleFormat::I8 if self.from_kind == Device::Output => {
-                make_wav_output_stream::<i8>(&self.device.inner, &cfg.into(), wav_writer)
-                    .or(Err(Error::StreamCreationError))?
+        };
+
+        let stream = match self.from_kind {
+            Device::Input => device.build_input_stream(
+                &cfg,
+                move |data: &[T], info: &cpal::InputCallbackInfo| {
+                    if let Some(priority) = priority.take() {
+                        priority.promote();
+                    }
+
+                    let at = info.timestamp();
+                    let dropout = timing.is_gap(at.capture, data.len());
+                    on_data(data, dropout, Some((at.capture, at.callback)))
+                },
+                error_callback,
+                None,
+            ),
+            Device::Output => device.build_output_stream(
+                &cfg,
+                move |data: &mut [T], info: &cpal::OutputCallbackInfo| {
+                    if let Some(priority) = priority.take() {
+                        priority.promote();
+                    }
+
+                    let at = info.timestamp();
+                    let dropout = timing.is_gap(at.playback, data.len());
+                    on_data(data, dropout, Some((at.playback, at.callback)))
+                },
+                error_callback,
+                None,
+            ),
+        }
+        .map(Stream::Cpal)
+        .or(Err(Error::StreamCreationError))?;
+
+        self.set_state(StreamState::Paused);
+
+        Ok(stream)
+    }
+
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fn rotate_wav<P>(&mut self, path: P) -> Result<Stats, Error>
+    where
+        P: AsRef<Path>,
+    {
+        let writer = self.writer.as_ref().ok_or(Error::WriteError)?;
+
+        // Audio captured before now belongs to the file being finished
+        if let Some(thread) = &self.writer_thread {
+            thread.drain();
+        }
+
+        let next = hound::WavWriter::create(&path, self.device.config().as_wav_spec())
+            .or(Err(Error::WriteError))?;
+
+        let (previous, previous_path, stats) = {
+            // Locked first, like the stream does
+            let mut segments = self.segments.lock().or(Err(Error::OutputLockError))?;
+            let mut durability = self.durability.lock().or(Err(Error::OutputLockError))?;
+
+            let previous_path = durability.open(path.as_ref());
+
+            if let Some(segments) = segments.as_mut() {
+                segments.path = path.as_ref().to_owned();
+                segments.written = 0;
+                segments.count += 1;
             }
-            _ => return Err(Error::StreamConfigFormatError),
+
+            let mut wlock = writer.lock().or(Err(Error::OutputLockError))?;
+            let stats = take_stats(&self.stats, &self.checksum)?;
+
+            (wlock.replace(next), previous_path, stats)
         };
 
-        self.stream = Some(stream);
+        let reserved = self
+            .reserve
+            .lock()
+            .ok()
+            .and_then(|mut reserve| reserve.as_mut()?.open(path.as_ref()));
 
-        Ok(writer)
+        if let Some(previous) = previous {
+            previous.finalize().or(Err(Error::WriteError))?;
+        }
+
+        if let Some(reserved) = reserved {
+            preallocate::release(&reserved);
+        }
+
+        if let Some(previous_path) = previous_path {
+            self.durability
+                .lock()
+                .or(Err(Error::OutputLockError))?
+                .finished(&previous_path)?;
+        }
+
+        Ok(stats)
     }
 
     pub fn play(&self) -> Result<(), Error> {
+        if let Some(player) = &self.player {
+            player.play()?;
+        }
+
+        if let Some(stream) = &self.stream {
+            stream.play()?;
+            self.set_state(StreamState::Playing);
+        }
+
+        Ok(())
+    }
+
+    pub fn pause(&self) -> Result<(), Error> {
         if let Some(stream) = &self.stream {
-            stream.play().or(Err(Error::PlayError))?;
+            stream.pause()?;
+            self.set_state(StreamState::Paused);
+        }
+
+        if let Some(player) = &self.player {
+            player.pause()?;
         }
 
         Ok(())
     }
+
+    pub fn stop(&mut self) {
+        self.stream = None;
+        self.player = None;
+        #[cfg(not(target_arch = "wasm32"))]
+        {
+            self.writer_thread = None;
+        }
+        self.set_state(StreamState::Stopped);
+    }
+
+    /// Stop the stream and finalize the file, returning its stats.
+    pub fn finish(&mut self) -> Result<Stats, Error> {
+        self.stop();
+
+        let writer = self.writer.take().ok_or(Error::WriteError)?;
+        let writer = writer
+            .lock()
+            .or(Err(Error::OutputLockError))?
+            .take()
+            .ok_or(Error::WriteError)?;
+
+        writer.finalize().or(Err(Error::WriteError))?;
+
+        #[cfg(not(target_arch = "wasm32"))]
+        if let Some(path) = self
+            .reserve
+            .lock()
+            .ok()
+            .and_then(|mut reserve| reserve.as_mut()?.close())
+        {
+            preallocate::release(&path);
+        }
+
+        #[cfg(not(target_arch = "wasm32"))]
+        {
+            let mut durability = self.durability.lock().or(Err(Error::OutputLockError))?;
+
+            if let Some(path) = durability.close() {
+                durability.finished(&path)?;
+            }
+        }
+
+        Ok(self.stats())
+    }
+
+    /// Give back the space a finished `segment_wav` file didn't use.
+    #[cfg(not(target_arch = "wasm32"))]
+    fn release(&self, path: &Path) {
+        if self.reserve.lock().is_ok_and(|reserve| reserve.is_some()) {
+            preallocate::release(path);
+        }
+    }
 }
 
-fn write_wav_data<T>(data: &[T], writer: &WavWriter)
+#[cfg(not(target_arch = "wasm32"))]
+fn write_wav_data<T>(
+    data: &[T],
+    writer: &WavWriter,
+    sink: &Sink,
+    gain: f32,
+    dropouts: u64,
+) -> Result<(), cpal::StreamError>
 where
-    T: cpal::FromSample<T> + cpal::Sample + hound::Sample,
+    T: cpal::FromSample<T> + cpal::FromSample<f32> + cpal::Sample + hound::Sample,
+    f32: cpal::FromSample<T>,
 {
+    let mut levels = Levels::default();
+
     if let Ok(mut wlock) = writer.lock() {
         if let Some(writer) = wlock.as_mut() {
+            let mut checksum = sink.checksum.lock().ok();
+            let hasher = checksum.as_mut().and_then(|checksum| checksum.as_mut());
+
+            // The samples as they are stored, for the checksum
+            let mut bytes = Vec::new();
+            let bits = writer.spec().bits_per_sample;
+
             for &d in data.iter() {
+                let sample = if gain == 1.0 {
+                    T::from_sample(d)
+                } else {
+                    T::from_sample(f32::from_sample(d) * gain)
+                };
+
                 writer
-                    .write_sample(T::from_sample(d))
-                    .unwrap_or_else(|err| fail!("failed writing sample", err));
+                    .write_sample(sample)
+                    .map_err(|err| write_failed("failed writing sample", err))?;
+
+                if hasher.is_some() {
+                    let _ = hound::Sample::write(sample, &mut bytes, bits);
+                }
+
+                levels.add(f32::from_sample(sample));
+            }
+
+            if let Some(hasher) = hasher {
+                hasher.update(&bytes);
+            }
+
+            if let Ok(mut stats) = sink.stats.lock() {
+                stats.update(&levels);
+                stats.dropouts += dropouts;
             }
         }
     }
+
+    Ok(())
 }
 
-fn make_wav_input_stream<T>(
-    device: &cpal::Device,
-    cfg: &cpal::StreamConfig,
-    writer: WavWriter,
-) -> Result<cpal::Stream, cpal::BuildStreamError>
+/// Take the stats of a finished file, with its checksum, and start afresh
+/// for the next.
+#[cfg(not(target_arch = "wasm32"))]
+fn take_stats(stats: &SharedStats, checksum: &SharedHasher) -> Result<Stats, Error> {
+    let mut stats = std::mem::take(&mut *stats.lock().or(Err(Error::OutputLockError))?);
+
+    if let Some(hasher) = checksum.lock().or(Err(Error::OutputLockError))?.as_mut() {
+        stats.checksum = Some(hasher.checksum());
+        *hasher = checksum::Hasher::new(hasher.algorithm());
+    }
+
+    Ok(stats)
+}
+
+/// Where a `segment_wav` recording is up to
+#[cfg(not(target_arch = "wasm32"))]
+struct Segments {
+    frames: u64,
+    /// Frames in the current file
+    written: u64,
+    /// Files started after the first
+    count: usize,
+    path: PathBuf,
+    next_path: Box<dyn FnMut(usize) -> PathBuf + Send>,
+    spec: WavSpec,
+    /// Done with, waiting for `finished_segments` to finalize them
+    finished: Vec<(PathBuf, hound::WavWriter<BufWriter<File>>, Stats)>,
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+impl Segments {
+    /// Swap the writer for the next file, keeping the previous one and its
+    /// stats for `finished_segments`.
+    fn roll(&mut self, writer: &WavWriter, sink: &Sink) -> Result<(), Error> {
+        self.count += 1;
+
+        let path = (self.next_path)(self.count);
+        let next = hound::WavWriter::create(&path, self.spec).or(Err(Error::WriteError))?;
+
+        // The previous file is released by `finished_segments`
+        if let Ok(mut reserve) = sink.reserve.lock() {
+            if let Some(reserve) = reserve.as_mut() {
+                reserve.open(&path);
+            }
+        }
+
+        // Synced by `finished_segments` too
+        if let Ok(mut durability) = sink.durability.lock() {
+            durability.open(&path);
+        }
+
+        let mut wlock = writer.lock().or(Err(Error::OutputLockError))?;
+        let previous = wlock.replace(next);
+        let stats = take_stats(&sink.stats, &sink.checksum)?;
+
+        if let Some(previous) = previous {
+            let path = std::mem::replace(&mut self.path, path);
+            self.finished.push((path, previous, stats));
+        }
+
+        self.written = 0;
+
+        Ok(())
+    }
+}
+
+/// Write `data`, moving on to the next segment at any boundary inside it.
+#[cfg(not(target_arch = "wasm32"))]
+fn write_segments<T>(
+    mut data: &[T],
+    writer: &WavWriter,
+    segments: &SharedSegments,
+    sink: &Sink,
+    gain: f32,
+    mut dropouts: u64,
+) -> Result<(), cpal::StreamError>
+where
+    T: cpal::FromSample<T> + cpal::FromSample<f32> + cpal::Sample + hound::Sample,
+    f32: cpal::FromSample<T>,
+{
+    let mut segments = segments.lock().ok();
+
+    let Some(segments) = segments.as_mut().and_then(|segments| segments.as_mut()) else {
+        return write_wav_data::<T>(data, writer, sink, gain, dropouts);
+    };
+
+    while !data.is_empty() {
+        let room = (segments.frames - segments.written) as usize * sink.channels;
+        let (now, rest) = data.split_at(room.min(data.len()));
+
+        write_wav_data::<T>(now, writer, sink, gain, dropouts)?;
+
+        segments.written += (now.len() / sink.channels) as u64;
+        dropouts = 0;
+        data = rest;
+
+        if segments.written == segments.frames {
+            segments
+                .roll(writer, sink)
+                .map_err(|err| write_failed("failed starting the next segment", err))?;
+        }
+    }
+
+    Ok(())
+}
+
+/// A write on the writer thread failing, as the stream error it's reported as
+#[cfg(not(target_arch = "wasm32"))]
+fn write_failed<E>(msg: &str, err: E) -> cpal::StreamError
+where
+    E: std::fmt::Display,
+{
+    cpal::StreamError::BackendSpecific {
+        err: cpal::BackendSpecificError {
+            description: format!("{msg}: {err}"),
+        },
+    }
+}
+
+/// How long callbacks take against how long their audio lasts, jumping to
+/// a slow one's and easing off over the next few dozen.
+#[derive(Clone, Default)]
+struct Load(Arc<AtomicU32>);
+
+impl Load {
+    fn get(&self) -> f32 {
+        f32::from_bits(self.0.load(Ordering::Relaxed))
+    }
+
+    fn reset(&self) {
+        self.0.store(0, Ordering::Relaxed);
+    }
+
+    /// Run `callback`, which handles `seconds` of audio, timing it.
+    #[cfg(not(target_arch = "wasm32"))]
+    fn measure<R>(&self, seconds: f32, callback: impl FnOnce() -> R) -> R {
+        let started = std::time::Instant::now();
+        let result = callback();
+
+        if seconds > 0.0 {
+            let load = started.elapsed().as_secs_f32() / seconds;
+            let load = load.max(self.get() * LOAD_DECAY);
+            self.0.store(load.to_bits(), Ordering::Relaxed);
+        }
+
+        result
+    }
+
+    // No clock to time it with in the browser
+    #[cfg(target_arch = "wasm32")]
+    fn measure<R>(&self, _seconds: f32, callback: impl FnOnce() -> R) -> R {
+        callback()
+    }
+}
+
+/// Detects gaps between callbacks from the stream timestamps
+struct Timing {
+    sample_rate: u32,
+    channels: u16,
+    last: Option<(cpal::StreamInstant, Duration)>,
+}
+
+impl Timing {
+    fn new(cfg: &cpal::StreamConfig) -> Timing {
+        Timing {
+            sample_rate: cfg.sample_rate.0,
+            channels: cfg.channels,
+            last: None,
+        }
+    }
+
+    fn is_gap(&mut self, at: cpal::StreamInstant, samples: usize) -> bool {
+        let frames = samples / usize::from(self.channels.max(1));
+        let len = Duration::from_secs_f64(frames as f64 / f64::from(self.sample_rate));
+
+        // At least one whole buffer went missing since the previous callback
+        let gap = self.last.is_some_and(|(prev, prev_len)| {
+            at.duration_since(&prev)
+                .is_some_and(|elapsed| elapsed > prev_len * 2)
+        });
+
+        self.last = Some((at, len));
+
+        gap
+    }
+}
+
+/// What every sink shares: level stats, gain, effects and the checksum
+struct Sink {
+    stats: SharedStats,
+    gain: Arc<AtomicU32>,
+    effects: effects::SharedChain,
+    #[cfg(not(target_arch = "wasm32"))]
+    checksum: SharedHasher,
+    #[cfg(not(target_arch = "wasm32"))]
+    reserve: SharedReserve,
+    #[cfg(not(target_arch = "wasm32"))]
+    durability: SharedDurability,
+    #[cfg(not(target_arch = "wasm32"))]
+    on_error: Option<ErrorCallback>,
+    channels: usize,
+    ending: Ending,
+}
+
+impl Sink {
+    fn gain(&self) -> f32 {
+        f32::from_bits(self.gain.load(Ordering::Relaxed))
+    }
+
+    #[cfg(not(target_arch = "wasm32"))]
+    fn has_effects(&self) -> bool {
+        self.effects.lock().is_ok_and(|effects| !effects.is_empty())
+    }
+
+    /// Report a write the writer thread couldn't make to `on_error`, and end
+    /// the stream, as nothing after it can be written either.
+    #[cfg(not(target_arch = "wasm32"))]
+    fn fail(&self, err: cpal::StreamError) {
+        match self.on_error.as_ref().map(|callback| callback.lock()) {
+            Some(Ok(mut callback)) => callback(err),
+            _ => eprintln!("Error: {err}"),
+        }
+
+        self.ending.end();
+        self.ending
+            .state
+            .store(StreamState::Errored as u8, Ordering::Relaxed);
+    }
+
+    /// Convert `data` into `buffer`, applying gain and effects.
+    fn process<T>(&self, data: &[T], buffer: &mut Vec<f32>)
+    where
+        T: cpal::Sample,
+        f32: cpal::FromSample<T>,
+    {
+        let gain = self.gain();
+
+        buffer.clear();
+        buffer.extend(data.iter().map(|&d| f32::from_sample(d) * gain));
+
+        if let Ok(mut effects) = self.effects.lock() {
+            effects::Effect::process(&mut *effects, buffer, self.channels);
+        }
+    }
+}
+
+/// The audio thread's side of `write_wav`: queue samples without waiting.
+/// With `wait`, for sources that aren't real time, it waits for room.
+#[cfg(not(target_arch = "wasm32"))]
+fn wav_capture<T>(
+    mut producer: HeapProducer<T>,
+    queued: Arc<AtomicU64>,
+    dropouts: Arc<AtomicU64>,
+    wait: bool,
+) -> impl FnMut(&[T], bool) + Send + 'static
 where
-    T: cpal::SizedSample + hound::Sample,
+    T: Copy + Send + 'static,
 {
-    device.build_input_stream(
-        cfg,
-        move |data: &[T], _| write_wav_data::<T>(data, &writer),
-        move |err| fail!("writing data to buffer failed", err),
-        None,
-    )
+    move |data, dropout| {
+        while wait && producer.free_len() < data.len().min(producer.capacity()) {
+            std::thread::sleep(WRITE_INTERVAL);
+        }
+
+        // A buffer that doesn't fit is lost whole, keeping channels aligned
+        if producer.free_len() < data.len() {
+            dropouts.fetch_add(1, Ordering::Relaxed);
+            return;
+        }
+
+        producer.push_slice(data);
+        queued.fetch_add(data.len() as u64, Ordering::Release);
+        dropouts.fetch_add(u64::from(dropout), Ordering::Relaxed);
+    }
 }
 
-fn make_wav_output_stream<T>(
-    device: &cpal::Device,
-    cfg: &cpal::StreamConfig,
+/// The writer thread's side of `write_wav`: apply gain and effects, write,
+/// and hand the audio to taps. Returns how many samples it took.
+#[cfg(not(target_arch = "wasm32"))]
+fn wav_writer<T>(
+    mut consumer: HeapConsumer<T>,
     writer: WavWriter,
-) -> Result<cpal::Stream, cpal::BuildStreamError>
+    sink: Sink,
+    taps: Taps,
+    segments: SharedSegments,
+    dropouts: Arc<AtomicU64>,
+) -> impl FnMut() -> usize + Send + 'static
+where
+    T: cpal::SizedSample + cpal::FromSample<f32> + hound::Sample + Send + 'static,
+    f32: cpal::FromSample<T>,
+{
+    let mut chunk = vec![T::EQUILIBRIUM; WRITE_CHUNK_FRAMES * sink.channels];
+    let mut buffer = Vec::new();
+    let mut samples = Vec::new();
+    let mut failed = false;
+
+    move || {
+        let read = consumer.pop_slice(&mut chunk);
+
+        // Once a write has failed, what's left is only taken off the queue
+        if read == 0 || failed {
+            return read;
+        }
+
+        let data = &chunk[..read];
+        let dropouts = dropouts.swap(0, Ordering::Relaxed);
+        let mut taps = taps.lock().ok().filter(|taps| !taps.is_empty());
+
+        // Without effects, samples are written as captured so integer
+        // formats stay lossless
+        let mut written = if sink.has_effects() {
+            sink.process(data, &mut buffer);
+
+            samples.clear();
+            samples.extend(buffer.iter().map(|&value| T::from_sample(value)));
+
+            write_segments::<T>(&samples, &writer, &segments, &sink, 1.0, dropouts)
+        } else {
+            if taps.is_some() {
+                sink.process(data, &mut buffer);
+            }
+
+            write_segments::<T>(data, &writer, &segments, &sink, sink.gain(), dropouts)
+        };
+
+        if let Ok(mut reserve) = sink.reserve.lock() {
+            if let Some(reserve) = reserve.as_mut() {
+                reserve.grow();
+            }
+        }
+
+        let due = sink
+            .durability
+            .lock()
+            .is_ok_and(|durability| durability.is_due());
+
+        if due && written.is_ok() {
+            written = flush_wav(&writer, &sink.durability);
+        }
+
+        if let Err(err) = written {
+            failed = true;
+            sink.fail(err);
+            return read;
+        }
+
+        for tap in taps.iter_mut().flat_map(|taps| taps.iter_mut()) {
+            if tap(&buffer) {
+                sink.ending.end();
+            }
+        }
+
+        read
+    }
+}
+
+/// Flush what's been written to the file, header included, so it's complete
+/// up to here if the recording goes no further.
+#[cfg(not(target_arch = "wasm32"))]
+fn flush_wav(writer: &WavWriter, durability: &SharedDurability) -> Result<(), cpal::StreamError> {
+    if let Ok(mut wlock) = writer.lock() {
+        if let Some(writer) = wlock.as_mut() {
+            writer
+                .flush()
+                .map_err(|err| write_failed("failed flushing the file", err))?;
+        }
+    }
+
+    if let Ok(mut durability) = durability.lock() {
+        durability
+            .flushed()
+            .map_err(|err| write_failed("failed syncing the file", err))?;
+    }
+
+    Ok(())
+}
+
+/// Runs a `write_wav` stream's writing until dropped, which waits for what
+/// was queued before then to be written.
+#[cfg(not(target_arch = "wasm32"))]
+struct WriterThread {
+    stop: Arc<AtomicBool>,
+    /// Samples the callback has queued, counted by `wav_capture`
+    queued: Arc<AtomicU64>,
+    /// Samples the thread has taken off the queue and written
+    written: Arc<AtomicU64>,
+    handle: Option<std::thread::JoinHandle<()>>,
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+impl WriterThread {
+    fn spawn<F>(mut write: F) -> WriterThread
+    where
+        F: FnMut() -> usize + Send + 'static,
+    {
+        let stop = Arc::new(AtomicBool::new(false));
+        let stopping = Arc::clone(&stop);
+        let written = Arc::new(AtomicU64::new(0));
+        let counted = Arc::clone(&written);
+
+        let handle = std::thread::spawn(move || loop {
+            // Read first, so whatever was queued before the stop is written
+            let stopped = stopping.load(Ordering::Acquire);
+            let read = write();
+
+            counted.fetch_add(read as u64, Ordering::Release);
+
+            if read == 0 {
+                if stopped {
+                    break;
+                }
+
+                std::thread::sleep(WRITE_INTERVAL);
+            }
+        });
+
+        WriterThread {
+            stop,
+            queued: Arc::default(),
+            written,
+            handle: Some(handle),
+        }
+    }
+
+    /// Wait for what's been queued so far to be written, e.g. before the
+    /// file is swapped for another.
+    fn drain(&self) {
+        let queued = self.queued.load(Ordering::Acquire);
+
+        while self.written.load(Ordering::Acquire) < queued {
+            if self
+                .handle
+                .as_ref()
+                .is_none_or(|handle| handle.is_finished())
+            {
+                break;
+            }
+
+            std::thread::sleep(WRITE_INTERVAL);
+        }
+    }
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+impl Drop for WriterThread {
+    fn drop(&mut self) {
+        self.stop.store(true, Ordering::Release);
+
+        if let Some(handle) = self.handle.take() {
+            let _ = handle.join();
+        }
+    }
+}
+
+fn read_sink<T, F, R>(sink: Sink, mut callback: F) -> impl FnMut(&[T], bool) + Send + 'static
 where
-T: cpal::SizedSample + hound::Sample,
+    T: cpal::SizedSample,
+    f32: cpal::FromSample<T>,
+    F: FnMut(&[f32]) -> R + Send + 'static,
+    R: Flow,
 {
-    device.build_output_stream(
-        cfg,
-        move |data: &mut [T], _| write_wav_data::<T>(data, &writer),
-        move |err| fail!("writing data to buffer failed", err),
-        None,
-    )
+    let mut buffer = Vec::new();
+
+    move |data, dropout| {
+        let mut levels = Levels::default();
+
+        sink.process(data, &mut buffer);
+
+        for &value in buffer.iter() {
+            levels.add(value);
+        }
+
+        if let Ok(mut stats) = sink.stats.lock() {
+            stats.update(&levels);
+            stats.dropouts += u64::from(dropout);
+        }
+
+        if callback(&buffer).stops() {
+            sink.ending.end();
+        }
+    }
 }
@@ -0,0 +1,218 @@
+//! Loudness as ITU-R BS.1770 measures it: K-weighted, in 400 ms blocks,
+//! gated to leave out silence and quiet passages.
+
+use crate::effects::Biquad;
+use std::f64::consts::PI;
+
+/// Blocks start every 100 ms and last four of those
+const STEPS_PER_BLOCK: usize = 4;
+const ABSOLUTE_GATE: f64 = -70.0;
+const RELATIVE_GATE: f64 = -10.0;
+
+/// What was measured over a whole file.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct Measurement {
+    /// Integrated loudness in LUFS, if anything was loud enough to count
+    pub integrated: Option<f32>,
+    /// Highest absolute sample value
+    pub peak: f32,
+}
+
+/// Measures loudness a buffer at a time.
+pub struct Meter {
+    channels: usize,
+    filters: Vec<[Biquad; 2]>,
+    weights: Vec<f64>,
+    step_frames: usize,
+    /// Weighted power summed over the current step, and its frames
+    power: f64,
+    frames: usize,
+    /// The last few steps, for the block they end
+    steps: Vec<f64>,
+    blocks: Vec<f64>,
+    peak: f32,
+}
+
+impl Meter {
+    pub fn new(sample_rate: u32, channels: usize) -> Meter {
+        let channels = channels.max(1);
+        let sample_rate = f64::from(sample_rate);
+
+        Meter {
+            channels,
+            filters: vec![[shelf(sample_rate), highpass(sample_rate)]; channels],
+            weights: (0..channels)
+                .map(|channel| weight(channel, channels))
+                .collect(),
+            step_frames: (sample_rate / 10.0).round().max(1.0) as usize,
+            power: 0.0,
+            frames: 0,
+            steps: Vec::with_capacity(STEPS_PER_BLOCK),
+            blocks: Vec::new(),
+            peak: 0.0,
+        }
+    }
+
+    pub fn process(&mut self, data: &[f32]) {
+        for frame in data.chunks_exact(self.channels) {
+            for (channel, &value) in frame.iter().enumerate() {
+                let [shelf, highpass] = &mut self.filters[channel];
+                let filtered = highpass.process(shelf.process(f64::from(value)));
+
+                self.power += self.weights[channel] * filtered * filtered;
+                self.peak = self.peak.max(value.abs());
+            }
+
+            self.frames += 1;
+
+            if self.frames == self.step_frames {
+                self.end_step();
+            }
+        }
+    }
+
+    fn end_step(&mut self) {
+        if self.steps.len() == STEPS_PER_BLOCK {
+            self.steps.remove(0);
+        }
+
+        self.steps.push(self.power);
+        self.power = 0.0;
+        self.frames = 0;
+
+        if self.steps.len() == STEPS_PER_BLOCK {
+            let frames = (self.step_frames * STEPS_PER_BLOCK) as f64;
+            self.blocks.push(self.steps.iter().sum::<f64>() / frames);
+        }
+    }
+
+    pub fn measurement(&self) -> Measurement {
+        let loud: Vec<f64> = self
+            .blocks
+            .iter()
+            .copied()
+            .filter(|&power| lufs(power) > ABSOLUTE_GATE)
+            .collect();
+
+        let integrated = mean(&loud).and_then(|average| {
+            let gate = lufs(average) + RELATIVE_GATE;
+
+            let gated: Vec<f64> = loud
+                .iter()
+                .copied()
+                .filter(|&power| lufs(power) > gate)
+                .collect();
+
+            mean(&gated).map(|power| lufs(power) as f32)
+        });
+
+        Measurement {
+            integrated,
+            peak: self.peak,
+        }
+    }
+}
+
+fn lufs(power: f64) -> f64 {
+    -0.691 + 10.0 * power.log10()
+}
+
+fn mean(values: &[f64]) -> Option<f64> {
+    match values.len() {
+        0 => None,
+        len => Some(values.iter().sum::<f64>() / len as f64),
+    }
+}
+
+/// Surround channels of 5.1 count for more, and the LFE not at all.
+fn weight(channel: usize, channels: usize) -> f64 {
+    match (channel, channels) {
+        (3, 6..) => 0.0,
+        (4 | 5, 6..) => 1.41,
+        _ => 1.0,
+    }
+}
+
+/// The first stage of K-weighting: a boost of about 4 dB above 1.5 kHz,
+/// for the head.
+fn shelf(sample_rate: f64) -> Biquad {
+    let k = (PI * 1681.974450955533 / sample_rate).tan();
+    let q = 0.7071752369554196;
+    let vh = 10f64.powf(3.999843853973347 / 20.0);
+    let vb = vh.powf(0.4996667741545416);
+    let a0 = 1.0 + k / q + k * k;
+
+    Biquad::new(
+        [
+            (vh + vb * k / q + k * k) / a0,
+            2.0 * (k * k - vh) / a0,
+            (vh - vb * k / q + k * k) / a0,
+        ],
+        [2.0 * (k * k - 1.0) / a0, (1.0 - k / q + k * k) / a0],
+    )
+}
+
+/// The second stage: a roll-off below 38 Hz.
+fn highpass(sample_rate: f64) -> Biquad {
+    let k = (PI * 38.13547087602444 / sample_rate).tan();
+    let q = 0.5003270373238773;
+    let a0 = 1.0 + k / q + k * k;
+
+    Biquad::new(
+        [1.0, -2.0, 1.0],
+        [2.0 * (k * k - 1.0) / a0, (1.0 - k / q + k * k) / a0],
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SAMPLE_RATE: u32 = 48_000;
+
+    /// A stereo 1 kHz tone at `dbfs` for `seconds`, into `meter`.
+    fn tone(meter: &mut Meter, dbfs: f64, seconds: u32) {
+        let amplitude = 10f64.powf(dbfs / 20.0);
+
+        let data: Vec<f32> = (0..SAMPLE_RATE * seconds)
+            .map(|frame| f64::from(frame) * 1000.0 / f64::from(SAMPLE_RATE))
+            .map(|phase| (amplitude * (phase * 2.0 * PI).sin()) as f32)
+            .flat_map(|value| [value, value])
+            .collect();
+
+        meter.process(&data);
+    }
+
+    // The cases from EBU Tech 3341, each to read -23 LUFS within 0.1
+
+    #[test]
+    fn tone_reads_its_level() {
+        let mut meter = Meter::new(SAMPLE_RATE, 2);
+        tone(&mut meter, -23.0, 20);
+
+        let measurement = meter.measurement();
+        let integrated = measurement.integrated.unwrap();
+
+        assert!((integrated + 23.0).abs() < 0.1, "{integrated}");
+        assert!((measurement.peak - 10f32.powf(-23.0 / 20.0)).abs() < 1e-4);
+    }
+
+    #[test]
+    fn quiet_passages_are_gated() {
+        let mut meter = Meter::new(SAMPLE_RATE, 2);
+        tone(&mut meter, -36.0, 10);
+        tone(&mut meter, -23.0, 60);
+        tone(&mut meter, -36.0, 10);
+
+        let integrated = meter.measurement().integrated.unwrap();
+        assert!((integrated + 23.0).abs() < 0.1, "{integrated}");
+    }
+
+    #[test]
+    fn silence_has_no_loudness() {
+        let mut meter = Meter::new(SAMPLE_RATE, 2);
+        meter.process(&vec![0.0; SAMPLE_RATE as usize * 2]);
+
+        assert_eq!(meter.measurement(), Measurement::default());
+    }
+}
@@ -0,0 +1,547 @@
+//! SMPTE linear timecode (LTC), the audio signal cameras and field recorders
+//! share a clock with: `Encoder` generates it on one channel and `Decoder`
+//! reads it from one.
+
+use crate::effects::Effect;
+use std::fmt;
+use std::str::FromStr;
+
+/// Bits in a frame, the last 16 of them the sync word
+const FRAME_BITS: usize = 80;
+
+/// The sync word as it arrives, first bit lowest: 0011 1111 1111 1101
+const SYNC_WORD: u16 = 0b1011_1111_1111_1100;
+
+/// Signal level the decoder's threshold follows, falling this much a sample
+const ENVELOPE_DECAY: f32 = 0.9995;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Rate {
+    Fps24,
+    Fps25,
+    /// 29.97 frames per second, dropping frame numbers to keep to the clock
+    Fps2997Drop,
+    Fps30,
+}
+
+impl Rate {
+    pub fn name(&self) -> &'static str {
+        match self {
+            Rate::Fps24 => "24",
+            Rate::Fps25 => "25",
+            Rate::Fps2997Drop => "29.97df",
+            Rate::Fps30 => "30",
+        }
+    }
+
+    /// Frames numbered in each second.
+    pub fn frames(&self) -> u64 {
+        match self {
+            Rate::Fps24 => 24,
+            Rate::Fps25 => 25,
+            Rate::Fps2997Drop | Rate::Fps30 => 30,
+        }
+    }
+
+    /// Frames in each second of real time.
+    pub fn fps(&self) -> f64 {
+        match self {
+            Rate::Fps2997Drop => 30_000.0 / 1001.0,
+            _ => self.frames() as f64,
+        }
+    }
+
+    /// As a fraction, e.g. `30000/1001`, the way iXML gives it.
+    pub fn ratio(&self) -> &'static str {
+        match self {
+            Rate::Fps24 => "24/1",
+            Rate::Fps25 => "25/1",
+            Rate::Fps2997Drop => "30000/1001",
+            Rate::Fps30 => "30/1",
+        }
+    }
+
+    pub fn is_drop(&self) -> bool {
+        *self == Rate::Fps2997Drop
+    }
+
+    /// Frame numbers in a day.
+    fn per_day(&self) -> u64 {
+        match self {
+            // Two dropped each minute but every tenth
+            Rate::Fps2997Drop => 24 * 60 * 60 * 30 - 2 * (24 * 60 - 24 * 6),
+            _ => 24 * 60 * 60 * self.frames(),
+        }
+    }
+
+    /// The rate whose bits are closest to `fps` frames a second.
+    fn nearest(fps: f64, drop: bool) -> Rate {
+        match fps {
+            _ if drop => Rate::Fps2997Drop,
+            fps if fps < 24.5 => Rate::Fps24,
+            fps if fps < 27.5 => Rate::Fps25,
+            _ => Rate::Fps30,
+        }
+    }
+}
+
+impl FromStr for Rate {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().replace(' ', "").as_str() {
+            "24" => Ok(Rate::Fps24),
+            "25" => Ok(Rate::Fps25),
+            "29.97df" | "29.97" | "2997df" => Ok(Rate::Fps2997Drop),
+            "30" => Ok(Rate::Fps30),
+            _ => Err(format!(
+                "unknown frame rate `{s}`, expected 24, 25, 29.97df or 30"
+            )),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Timecode {
+    pub hours: u8,
+    pub minutes: u8,
+    pub seconds: u8,
+    pub frames: u8,
+    pub rate: Rate,
+}
+
+impl Timecode {
+    /// The timecode `count` frames after midnight, wrapping at the end of
+    /// the day.
+    pub fn from_count(count: u64, rate: Rate) -> Timecode {
+        let mut count = count % rate.per_day();
+
+        if rate.is_drop() {
+            // Skip the numbers dropped so far: 18 every ten minutes, and two
+            // for each minute into these ten after the first
+            let tens = count / 17_982;
+            let rest = count % 17_982;
+            count += 18 * tens + 2 * (rest.saturating_sub(2) / 1798);
+        }
+
+        let frames = rate.frames();
+
+        Timecode {
+            hours: (count / (frames * 3600)) as u8,
+            minutes: (count / (frames * 60) % 60) as u8,
+            seconds: (count / frames % 60) as u8,
+            frames: (count % frames) as u8,
+            rate,
+        }
+    }
+
+    /// The timecode at `seconds` of real time after midnight, e.g. the time
+    /// of day.
+    pub fn from_seconds(seconds: f64, rate: Rate) -> Timecode {
+        Timecode::from_count((seconds.max(0.0) * rate.fps()) as u64, rate)
+    }
+
+    /// Frames since midnight.
+    pub fn count(&self) -> u64 {
+        let minutes = u64::from(self.hours) * 60 + u64::from(self.minutes);
+        let count =
+            (minutes * 60 + u64::from(self.seconds)) * self.rate.frames() + u64::from(self.frames);
+
+        match self.rate.is_drop() {
+            true => count - 2 * (minutes - minutes / 10),
+            false => count,
+        }
+    }
+
+    /// Real time since midnight, in seconds.
+    pub fn seconds(&self) -> f64 {
+        self.count() as f64 / self.rate.fps()
+    }
+
+    pub fn next(&self) -> Timecode {
+        Timecode::from_count(self.count() + 1, self.rate)
+    }
+
+    /// Parse `HH:MM:SS:FF`, or `HH:MM:SS;FF` as drop frame timecode shows.
+    pub fn parse(s: &str, rate: Rate) -> Option<Timecode> {
+        let fields: Vec<u8> = s
+            .trim()
+            .split([':', ';', '.'])
+            .map(|field| field.parse().ok())
+            .collect::<Option<_>>()?;
+
+        let &[hours, minutes, seconds, frames] = fields.as_slice() else {
+            return None;
+        };
+
+        let timecode = Timecode {
+            hours,
+            minutes,
+            seconds,
+            frames,
+            rate,
+        };
+
+        timecode.is_valid().then_some(timecode)
+    }
+
+    fn is_valid(&self) -> bool {
+        let dropped = self.rate.is_drop()
+            && self.seconds == 0
+            && self.frames < 2
+            && !self.minutes.is_multiple_of(10);
+
+        self.hours < 24
+            && self.minutes < 60
+            && self.seconds < 60
+            && u64::from(self.frames) < self.rate.frames()
+            && !dropped
+    }
+
+    /// The frame's 80 bits, first sent lowest.
+    fn to_bits(self) -> u128 {
+        let fields = [
+            (self.frames % 10, 0),
+            (self.frames / 10, 8),
+            (self.seconds % 10, 16),
+            (self.seconds / 10, 24),
+            (self.minutes % 10, 32),
+            (self.minutes / 10, 40),
+            (self.hours % 10, 48),
+            (self.hours / 10, 56),
+        ];
+
+        let mut bits = fields
+            .iter()
+            .fold(0u128, |bits, &(value, at)| bits | u128::from(value) << at);
+
+        bits |= u128::from(self.rate.is_drop()) << 10;
+        bits |= u128::from(SYNC_WORD) << 64;
+
+        // Even parity, so every frame starts on the same polarity
+        let parity = match self.rate {
+            Rate::Fps25 => 59,
+            _ => 27,
+        };
+
+        bits | u128::from(bits.count_ones() % 2) << parity
+    }
+
+    fn from_bits(bits: u128, rate: Rate) -> Option<Timecode> {
+        let field = |at: u32, len: u32| ((bits >> at) as u8) & ((1 << len) - 1);
+
+        let timecode = Timecode {
+            hours: field(56, 2) * 10 + field(48, 4),
+            minutes: field(40, 3) * 10 + field(32, 4),
+            seconds: field(24, 3) * 10 + field(16, 4),
+            frames: field(8, 2) * 10 + field(0, 4),
+            rate,
+        };
+
+        timecode.is_valid().then_some(timecode)
+    }
+}
+
+impl fmt::Display for Timecode {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let separator = if self.rate.is_drop() { ';' } else { ':' };
+
+        write!(
+            f,
+            "{:02}:{:02}:{:02}{separator}{:02}",
+            self.hours, self.minutes, self.seconds, self.frames
+        )
+    }
+}
+
+/// Generates LTC counting up from a timecode. As an `Effect` it's mixed into
+/// one channel of whatever passes through, so a `Player` with nothing queued
+/// plays just the timecode.
+#[derive(Debug, Clone)]
+pub struct Encoder {
+    timecode: Timecode,
+    bits: u128,
+    /// Half bits per sample, as a fraction, so frames never drift
+    numerator: u128,
+    denominator: u128,
+    samples: u128,
+    half_bits: u128,
+    high: bool,
+    channel: usize,
+    amplitude: f32,
+}
+
+impl Encoder {
+    pub fn new(start: Timecode, sample_rate: u32) -> Encoder {
+        Encoder {
+            timecode: start,
+            bits: start.to_bits(),
+            numerator: start.rate.frames() as u128 * 2 * FRAME_BITS as u128 * 1000,
+            denominator: u128::from(sample_rate.max(1))
+                * if start.rate.is_drop() { 1001 } else { 1000 },
+            samples: 0,
+            half_bits: 0,
+            high: true,
+            channel: 0,
+            amplitude: 1.0,
+        }
+    }
+
+    /// Which channel to play on, from 0.
+    pub fn channel(&mut self, channel: usize) -> &mut Self {
+        self.channel = channel;
+        self
+    }
+
+    /// Peak level in dBFS.
+    pub fn level(&mut self, dbfs: f32) -> &mut Self {
+        self.amplitude = 10f32.powf(dbfs / 20.0);
+        self
+    }
+
+    /// The timecode of the frame being sent.
+    pub fn timecode(&self) -> Timecode {
+        self.timecode
+    }
+
+    pub fn next_sample(&mut self) -> f32 {
+        let value = if self.high {
+            self.amplitude
+        } else {
+            -self.amplitude
+        };
+
+        self.samples += 1;
+        let half_bits = self.samples * self.numerator / self.denominator;
+
+        // Biphase mark: every bit starts with a transition, and ones have
+        // another halfway through
+        while self.half_bits < half_bits {
+            self.half_bits += 1;
+
+            let bit = (self.half_bits / 2) as usize % FRAME_BITS;
+
+            if self.half_bits % 2 == 1 {
+                if (self.bits >> bit) & 1 == 1 {
+                    self.high = !self.high;
+                }
+            } else {
+                self.high = !self.high;
+
+                if bit == 0 {
+                    self.timecode = self.timecode.next();
+                    self.bits = self.timecode.to_bits();
+                }
+            }
+        }
+
+        value
+    }
+}
+
+impl Effect for Encoder {
+    fn process(&mut self, frames: &mut [f32], channels: usize) {
+        for frame in frames.chunks_mut(channels.max(1)) {
+            let value = self.next_sample();
+
+            if let Some(sample) = frame.get_mut(self.channel) {
+                *sample += value;
+            }
+        }
+    }
+}
+
+/// Reads LTC from one channel of interleaved audio as it arrives, at any of
+/// the frame rates, following its speed.
+#[derive(Debug, Clone)]
+pub struct Decoder {
+    sample_rate: f64,
+    channel: usize,
+    /// Frames seen so far
+    position: u64,
+    high: bool,
+    envelope: f32,
+    /// Where the last transition was, and the bit being received began
+    edge: u64,
+    bit_start: u64,
+    /// Halfway through a one
+    half: bool,
+    /// Samples a bit lasts, as measured
+    period: f64,
+    /// The last 80 bits, the latest highest, and where each began
+    bits: u128,
+    starts: [u64; FRAME_BITS],
+    received: usize,
+}
+
+impl Decoder {
+    pub fn new(sample_rate: u32) -> Decoder {
+        let sample_rate = f64::from(sample_rate.max(1));
+
+        Decoder {
+            sample_rate,
+            channel: 0,
+            position: 0,
+            high: false,
+            envelope: 0.0,
+            edge: 0,
+            bit_start: 0,
+            half: false,
+            period: sample_rate / (25.0 * FRAME_BITS as f64),
+            bits: 0,
+            starts: [0; FRAME_BITS],
+            received: 0,
+        }
+    }
+
+    /// Which channel to read, from 0.
+    pub fn channel(&mut self, channel: usize) -> &mut Self {
+        self.channel = channel;
+        self
+    }
+
+    /// Read `data`, calling `found` with each timecode and the frame it
+    /// started on, counted from the first frame given to the decoder.
+    pub fn process<F>(&mut self, data: &[f32], channels: usize, mut found: F)
+    where
+        F: FnMut(Timecode, u64),
+    {
+        for frame in data.chunks(channels.max(1)) {
+            let value = frame.get(self.channel).copied().unwrap_or(0.0);
+
+            self.envelope = value.abs().max(self.envelope * ENVELOPE_DECAY);
+
+            // With hysteresis, so noise around zero isn't taken for edges
+            let threshold = self.envelope / 4.0;
+            let edge = match self.high {
+                true => value < -threshold,
+                false => value > threshold,
+            };
+
+            if edge {
+                self.high = !self.high;
+
+                if let Some((timecode, start)) = self.on_edge() {
+                    found(timecode, start);
+                }
+            }
+
+            self.position += 1;
+        }
+    }
+
+    fn on_edge(&mut self) -> Option<(Timecode, u64)> {
+        let interval = (self.position - self.edge) as f64;
+        self.edge = self.position;
+
+        // Far off a bit at any rate: noise, or the signal coming back
+        if interval > self.period * 2.0 {
+            self.half = false;
+            self.bit_start = self.position;
+            self.received = 0;
+            return None;
+        }
+
+        let whole = interval > self.period * 0.75;
+        let measured = if whole { interval } else { interval * 2.0 };
+
+        self.period += (measured - self.period) / 8.0;
+        self.period = self.period.clamp(
+            self.sample_rate / (40.0 * FRAME_BITS as f64),
+            self.sample_rate / (20.0 * FRAME_BITS as f64),
+        );
+
+        if whole {
+            self.half = false;
+            return self.push(0);
+        }
+
+        if !self.half {
+            self.half = true;
+            return None;
+        }
+
+        self.half = false;
+        self.push(1)
+    }
+
+    fn push(&mut self, bit: u8) -> Option<(Timecode, u64)> {
+        self.bits = self.bits >> 1 | u128::from(bit) << (FRAME_BITS - 1);
+        self.starts[self.received % FRAME_BITS] = self.bit_start;
+        self.received += 1;
+        self.bit_start = self.position;
+
+        if self.received < FRAME_BITS || (self.bits >> 64) as u16 != SYNC_WORD {
+            return None;
+        }
+
+        let fps = self.sample_rate / (self.period * FRAME_BITS as f64);
+        let rate = Rate::nearest(fps, (self.bits >> 10) & 1 == 1);
+
+        // The oldest of the 80, where this frame began
+        let start = self.starts[self.received % FRAME_BITS];
+
+        Timecode::from_bits(self.bits, rate).map(|timecode| (timecode, start))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SAMPLE_RATE: u32 = 48_000;
+
+    #[test]
+    fn drop_frame_skips_numbers() {
+        let rate = Rate::Fps2997Drop;
+
+        assert_eq!(Timecode::from_count(1799, rate).to_string(), "00:00:59;29");
+        assert_eq!(Timecode::from_count(1800, rate).to_string(), "00:01:00;02");
+        assert_eq!(
+            Timecode::from_count(17_982, rate).to_string(),
+            "00:10:00;00"
+        );
+
+        for count in 0..100_000 {
+            assert_eq!(Timecode::from_count(count, rate).count(), count);
+        }
+    }
+
+    #[test]
+    fn bits_round_trip() {
+        for rate in [Rate::Fps24, Rate::Fps25, Rate::Fps2997Drop, Rate::Fps30] {
+            let timecode = Timecode::parse("23:59:58:12", rate).unwrap();
+            let bits = timecode.to_bits();
+
+            assert_eq!((bits >> 64) as u16, SYNC_WORD);
+            assert_eq!(bits.count_ones() % 2, 0);
+            assert_eq!(Timecode::from_bits(bits, rate), Some(timecode));
+        }
+    }
+
+    #[test]
+    fn decodes_what_it_encodes() {
+        for rate in [Rate::Fps24, Rate::Fps25, Rate::Fps2997Drop, Rate::Fps30] {
+            let start = Timecode::parse("10:00:00:00", rate).unwrap();
+            let mut encoder = Encoder::new(start, SAMPLE_RATE);
+            encoder.level(-20.0);
+
+            let samples: Vec<f32> = (0..SAMPLE_RATE).map(|_| encoder.next_sample()).collect();
+
+            let mut found = Vec::new();
+            Decoder::new(SAMPLE_RATE)
+                .process(&samples, 1, |timecode, at| found.push((timecode, at)));
+
+            // Every frame begun but the last, whose closing edge is cut off
+            assert_eq!(found.len() as f64, rate.fps().ceil() - 1.0, "{rate:?}");
+
+            let frame = f64::from(SAMPLE_RATE) / rate.fps();
+
+            for (index, &(timecode, at)) in found.iter().enumerate() {
+                assert_eq!(timecode.count(), start.count() + index as u64);
+                assert_eq!(timecode.rate, rate);
+                assert!((at as f64 - index as f64 * frame).abs() <= 2.0, "{at}");
+            }
+        }
+    }
+}
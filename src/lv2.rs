@@ -0,0 +1,136 @@
+//! LV2 plugins as effects, found and loaded through lilv.
+
+use crate::effects::Effect;
+use crate::Error;
+use livi::event::LV2AtomSequence;
+use std::sync::Arc;
+
+/// Frames handed to a plugin per run.
+const BLOCK_FRAMES: usize = 1024;
+
+/// Bytes for each (empty) event port.
+const SEQUENCE_CAPACITY: usize = 1024;
+
+/// An LV2 plugin covering every channel of a stream: one instance when the
+/// plugin has an audio port per channel, or one per channel for mono plugins.
+pub struct Lv2 {
+    instances: Vec<livi::Instance>,
+    /// Channels each instance processes
+    width: usize,
+    controls: Vec<(String, livi::PortIndex)>,
+    inputs: Vec<Vec<f32>>,
+    outputs: Vec<Vec<f32>>,
+    event_inputs: Vec<LV2AtomSequence>,
+    event_outputs: Vec<LV2AtomSequence>,
+    /// Kept alive for the instances
+    _features: Arc<livi::Features>,
+}
+
+impl Lv2 {
+    pub fn new(uri: &str, sample_rate: u32, channels: u16) -> Result<Lv2, Error> {
+        let world = livi::World::new();
+        let plugin = world.plugin_by_uri(uri).ok_or(Error::PluginNotFoundError)?;
+
+        let channels = usize::from(channels.max(1));
+        let counts = plugin.port_counts();
+
+        let width = match (counts.audio_inputs, counts.audio_outputs) {
+            (inputs, outputs) if inputs == channels && outputs == channels => channels,
+            (1, 1) => 1,
+            _ => return Err(Error::PluginLoadError),
+        };
+
+        // CV ports would need a signal we don't have
+        if counts.cv_inputs > 0 || counts.cv_outputs > 0 {
+            return Err(Error::PluginLoadError);
+        }
+
+        let features = world.build_features(livi::FeaturesBuilder {
+            min_block_length: 1,
+            max_block_length: BLOCK_FRAMES,
+        });
+
+        let instances = (0..channels / width)
+            .map(|_| unsafe { plugin.instantiate(Arc::clone(&features), f64::from(sample_rate)) })
+            .collect::<Result<Vec<_>, _>>()
+            .or(Err(Error::PluginLoadError))?;
+
+        let controls = plugin
+            .ports_with_type(livi::PortType::ControlInput)
+            .map(|port| (port.symbol, port.index))
+            .collect();
+
+        let sequences = |count| {
+            (0..count)
+                .map(|_| LV2AtomSequence::new(&features, SEQUENCE_CAPACITY))
+                .collect()
+        };
+
+        Ok(Lv2 {
+            instances,
+            width,
+            controls,
+            inputs: vec![vec![0.0; BLOCK_FRAMES]; width],
+            outputs: vec![vec![0.0; BLOCK_FRAMES]; width],
+            event_inputs: sequences(counts.atom_sequence_inputs),
+            event_outputs: sequences(counts.atom_sequence_outputs),
+            _features: features,
+        })
+    }
+
+    /// Set a control port, by its symbol, on every instance.
+    pub fn set(&mut self, symbol: &str, value: f32) -> Result<(), Error> {
+        let index = self
+            .controls
+            .iter()
+            .find(|(name, _)| name == symbol)
+            .map(|&(_, index)| index)
+            .ok_or(Error::PluginNotFoundError)?;
+
+        for instance in self.instances.iter_mut() {
+            instance.set_control_input(index, value);
+        }
+
+        Ok(())
+    }
+}
+
+impl Effect for Lv2 {
+    fn process(&mut self, frames: &mut [f32], channels: usize) {
+        let channels = channels.max(1);
+
+        for block in frames.chunks_mut(BLOCK_FRAMES * channels) {
+            let len = block.len() / channels;
+
+            // Instances beyond the stream's channels have nothing to process
+            let instances = self.instances.iter_mut().take(channels / self.width);
+
+            for (number, instance) in instances.enumerate() {
+                let first = number * self.width;
+
+                for (channel, input) in self.inputs.iter_mut().enumerate() {
+                    for (frame, value) in input[..len].iter_mut().enumerate() {
+                        *value = block[frame * channels + first + channel];
+                    }
+                }
+
+                let ports = livi::EmptyPortConnections::new()
+                    .with_audio_inputs(self.inputs.iter().map(|input| &input[..len]))
+                    .with_audio_outputs(self.outputs.iter_mut().map(|output| &mut output[..len]))
+                    .with_atom_sequence_inputs(self.event_inputs.iter())
+                    .with_atom_sequence_outputs(self.event_outputs.iter_mut());
+
+                // A failed run leaves the block unprocessed
+                if unsafe { instance.run(len, ports) }.is_err() {
+                    continue;
+                }
+
+                for (channel, output) in self.outputs.iter().enumerate() {
+                    for (frame, &value) in output[..len].iter().enumerate() {
+                        block[frame * channels + first + channel] = value;
+                    }
+                }
+            }
+        }
+    }
+}
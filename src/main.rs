@@ -1,79 +1,98 @@
 use anyhow::Result;
+use clap::CommandFactory;
+use clap::FromArgMatches;
 use clap::Parser;
-use clap::ValueEnum;
-use std::io::Write;
+use clap::Subcommand;
+
+mod cli;
 
 #[derive(Parser)]
 struct Opts {
-    /// Specify file output location
-    #[clap(short, long)]
-    output: Option<String>,
-    /// Default device to listen to
-    #[clap(short, long)]
-    listen: Listen,
-    /// Use recorded audio as input
-    #[clap(long)]
-    loopback: bool,
-    /// Delay recording (seconds)
-    #[clap(short, long)]
-    delay: Option<usize>,
+    #[clap(subcommand)]
+    command: Command,
+    #[clap(flatten)]
+    node: cli::pipewire::NodeOpts,
 }
 
-#[derive(ValueEnum, Clone, PartialEq)]
-enum Listen {
-    In,
-    Out,
+#[derive(Subcommand)]
+enum Command {
+    /// Record from a device to a file
+    Record(Box<cli::record::RecordOpts>),
+    /// Run a recording service controlled over a socket
+    Daemon(cli::daemon::DaemonOpts),
+    /// Send a command to a running daemon
+    Ctl(cli::ctl::CtlOpts),
+    /// Stream a device live over HTTP
+    Serve(cli::serve::ServeOpts),
+    /// Play (and optionally record) audio received over the network
+    Receive(cli::receive::ReceiveOpts),
+    /// Play audio files and M3U playlists back to back, without gaps
+    Play(cli::play::PlayOpts),
+    /// Play a test signal on the output device
+    Tone(cli::tone::ToneOpts),
+    /// Play a metronome on the output device
+    Click(cli::click::ClickOpts),
+    /// Play SMPTE LTC timecode on an output channel
+    Ltc(cli::ltc::LtcOpts),
+    /// Record a loop and overdub layers on it as it plays
+    Loop(cli::looper::LoopOpts),
+    /// Play the input through effects to the output, live
+    Fx(cli::fx::FxOpts),
+    /// Measure round-trip latency from the output to the input device
+    Latency(cli::latency::LatencyOpts),
+    /// Check audio played out one device comes back unchanged on another
+    BitPerfect(cli::bitperfect::BitPerfectOpts),
+    /// Record a few seconds and play them back to check a setup works
+    Test(cli::selftest::TestOpts),
+    /// Cut part of a WAV file out to a new file
+    Trim(cli::trim::TrimOpts),
+    /// Bring a recording to a loudness or peak level
+    Normalize(cli::normalize::NormalizeOpts),
+    /// Cut a recording into tracks at the gaps between them
+    Split(cli::split::SplitOpts),
+    /// Join WAV files end to end
+    Concat(cli::concat::ConcatOpts),
+    /// Check a recording against the checksum in its sidecar
+    Verify(cli::verify::VerifyOpts),
+    /// Work with the markers in a recording's sidecar
+    Markers(cli::markers::MarkersOpts),
+    /// List devices with the indices `--device` accepts
+    Devices(cli::devices::DevicesOpts),
+    /// Check the audio setup and suggest fixes
+    Doctor(cli::doctor::DoctorOpts),
+    /// Measure callback timing and CPU use of a capture stream
+    Bench(cli::bench::BenchOpts),
 }
 
 fn main() -> Result<()> {
-    let options = Opts::parse();
-    let mut stdout = std::io::stdout();
-
-    let device = if options.listen == Listen::In {
-        audiort::DeviceBuilder::new_default_input()?
-    } else {
-        audiort::DeviceBuilder::new_default_output()?
-    };
-
-    if let Ok(name) = device.name() {
-        eprintln!("Listening to {name}");
+    let matches = Opts::command().get_matches();
+    let opts = Opts::from_arg_matches(&matches).unwrap_or_else(|err| err.exit());
+
+    cli::pipewire::apply(&opts.node, matches.subcommand_name().unwrap_or_default());
+
+    match opts.command {
+        Command::Record(options) => cli::record::run(*options),
+        Command::Daemon(options) => cli::daemon::run(options),
+        Command::Ctl(options) => cli::ctl::run(options),
+        Command::Serve(options) => cli::serve::run(options),
+        Command::Receive(options) => cli::receive::run(options),
+        Command::Play(options) => cli::play::run(options),
+        Command::Tone(options) => cli::tone::run(options),
+        Command::Click(options) => cli::click::run(options),
+        Command::Ltc(options) => cli::ltc::run(options),
+        Command::Loop(options) => cli::looper::run(options),
+        Command::Fx(options) => cli::fx::run(options),
+        Command::Latency(options) => cli::latency::run(options),
+        Command::BitPerfect(options) => cli::bitperfect::run(options),
+        Command::Test(options) => cli::selftest::run(options),
+        Command::Trim(options) => cli::trim::run(options),
+        Command::Normalize(options) => cli::normalize::run(options),
+        Command::Split(options) => cli::split::run(options),
+        Command::Concat(options) => cli::concat::run(options),
+        Command::Verify(options) => cli::verify::run(options),
+        Command::Markers(options) => cli::markers::run(options),
+        Command::Devices(options) => cli::devices::run(options),
+        Command::Doctor(options) => cli::doctor::run(options),
+        Command::Bench(options) => cli::bench::run(options),
     }
-
-    let mut stream = audiort::StreamBuilder::new(device)?;
-
-    if options.loopback {
-        stream.from_input();
-    }
-
-    let writer = stream.write_wav(options.output.unwrap_or_else(|| "out.wav".into()))?;
-
-    if let Some(delay) = options.delay {
-        write!(&stdout, "Recording in ")?;
-        stdout.flush()?;
-
-        for i in (1..=delay).rev() {
-            write!(&stdout, "{i} ")?;
-            stdout.flush()?;
-            std::thread::sleep(std::time::Duration::from_secs(1));
-        }
-
-        println!();
-    }
-
-    stream.play()?;
-
-    write!(&stdout, "Press `Enter` to stop recording... ")?;
-
-    stdout.flush()?;
-
-    if std::io::stdin().read_line(&mut String::new()).is_ok() {
-        if let Ok(mut wlock) = writer.lock() {
-            if let Some(writer) = wlock.take() {
-                writer.finalize()?;
-                eprintln!("Written to out.wav");
-            }
-        }
-    }
-
-    Ok(())
 }
@@ -0,0 +1,245 @@
+//! Separate devices captured as the channels of one, e.g. two mono USB
+//! microphones as a stereo pair. Each device runs on its own clock, so each
+//! is resampled a little faster or slower to keep pace with the recording.
+//! A device's channels can also be picked out this way.
+
+use crate::DeviceBuilder;
+use crate::Error;
+use crate::StreamBuilder;
+use std::collections::VecDeque;
+use std::sync::Arc;
+use std::sync::Mutex;
+use std::time::Duration;
+
+/// Audio kept queued from each device, against the jitter of their
+/// callbacks.
+pub(crate) const TARGET_LATENCY: Duration = Duration::from_millis(80);
+
+/// Most a device is sped up or slowed down by to keep pace, as a share of
+/// its rate; real clocks are well within this.
+const MAX_DRIFT: f64 = 0.002;
+
+/// How strongly the rate follows how full the queue is.
+const DRIFT_GAIN: f64 = 0.002;
+
+/// Samples between rate adjustments.
+const ADJUST_EVERY: usize = 64;
+
+/// Of the last measurement of how full the queue is, smoothing out the
+/// bursts in which callbacks fill it.
+const FILL_SMOOTHING: f64 = 0.99;
+
+pub(crate) type Queue = Arc<Mutex<VecDeque<f32>>>;
+
+/// The captures feeding a merged device, running until dropped.
+pub struct Merge {
+    readers: Vec<DriftReader>,
+    name: String,
+    sample_rate: u32,
+    _streams: Vec<StreamBuilder>,
+}
+
+impl Merge {
+    /// Each device, mixed to mono, as one channel of a new one at the first
+    /// device's sample rate.
+    pub fn new(devices: Vec<DeviceBuilder>) -> Result<Merge, Error> {
+        let first = devices.first().ok_or(Error::DeviceNotFoundError)?;
+        let sample_rate = first.config().sample_rate().0;
+
+        let mut names = Vec::new();
+        let mut readers = Vec::new();
+        let mut streams = Vec::new();
+
+        for device in devices {
+            names.push(device.name().unwrap_or_else(|_| "?".into()));
+
+            let rate = device.config().sample_rate().0;
+            let target = (TARGET_LATENCY.as_secs_f64() * f64::from(rate)) as usize;
+            let queue = Queue::default();
+            let capture = Arc::clone(&queue);
+            let max_queued = target * 8;
+
+            let mut stream = StreamBuilder::new(device)?;
+            let channels = usize::from(stream.config().channels().max(1));
+
+            stream.read(move |data| {
+                if let Ok(mut queue) = capture.lock() {
+                    queue.extend(
+                        data.chunks(channels)
+                            .map(|frame| frame.iter().sum::<f32>() / frame.len() as f32),
+                    );
+
+                    // Nothing is taking it; don't grow forever
+                    let excess = queue.len().saturating_sub(max_queued);
+                    queue.drain(..excess);
+                }
+            })?;
+
+            stream.play()?;
+
+            readers.push(DriftReader::new(queue, rate, sample_rate, target));
+            streams.push(stream);
+        }
+
+        Ok(Merge {
+            readers,
+            name: names.join(" + "),
+            sample_rate,
+            _streams: streams,
+        })
+    }
+
+    /// Some of `device`'s channels, counted from 0, as a new device, e.g.
+    /// one input pair of a many-channel interface.
+    pub fn channels(device: DeviceBuilder, channels: &[usize]) -> Result<Merge, Error> {
+        let available = usize::from(device.config().channels().max(1));
+
+        if channels.is_empty() || channels.iter().any(|&channel| channel >= available) {
+            return Err(Error::StreamConfigFormatError);
+        }
+
+        let name = device.name().unwrap_or_else(|_| "?".into());
+        let sample_rate = device.config().sample_rate().0;
+        let target = (TARGET_LATENCY.as_secs_f64() * f64::from(sample_rate)) as usize;
+        let max_queued = target * 8;
+
+        let queues = channels
+            .iter()
+            .map(|_| Queue::default())
+            .collect::<Vec<_>>();
+        let capture = queues.clone();
+        let picked = channels.to_vec();
+
+        let mut stream = StreamBuilder::new(device)?;
+
+        stream.read(move |data| {
+            for (queue, &channel) in capture.iter().zip(&picked) {
+                if let Ok(mut queue) = queue.lock() {
+                    queue.extend(data.chunks(available).map(|frame| frame[channel]));
+
+                    let excess = queue.len().saturating_sub(max_queued);
+                    queue.drain(..excess);
+                }
+            }
+        })?;
+
+        stream.play()?;
+
+        let numbers = channels
+            .iter()
+            .map(|channel| (channel + 1).to_string())
+            .collect::<Vec<_>>();
+
+        Ok(Merge {
+            readers: queues
+                .into_iter()
+                .map(|queue| DriftReader::new(queue, sample_rate, sample_rate, target))
+                .collect(),
+            name: format!("{name} ({})", numbers.join("+")),
+            sample_rate,
+            _streams: vec![stream],
+        })
+    }
+
+    /// The merged device, to record from while this is kept.
+    pub fn device(&self) -> DeviceBuilder {
+        crate::mock::merged_device(self.readers.clone(), &self.name, self.sample_rate)
+    }
+}
+
+/// Reads a device's queue at the recording's pace, resampling to follow the
+/// drift between their clocks.
+#[derive(Clone)]
+pub(crate) struct DriftReader {
+    queue: Queue,
+    /// Taken from the queue, oldest first
+    local: VecDeque<f32>,
+    /// Where the next sample falls between `local[0]` and `local[1]`
+    position: f64,
+    /// Device samples per recorded one, by their nominal rates
+    nominal: f64,
+    ratio: f64,
+    target: usize,
+    fill: f64,
+    since_adjust: usize,
+}
+
+impl DriftReader {
+    /// Reading a queue filled at `from` Hz as `to` Hz, keeping about `target`
+    /// samples in it.
+    pub(crate) fn new(queue: Queue, from: u32, to: u32, target: usize) -> DriftReader {
+        let nominal = f64::from(from) / f64::from(to.max(1));
+
+        DriftReader {
+            queue,
+            local: VecDeque::new(),
+            position: 0.0,
+            nominal,
+            ratio: nominal,
+            target: target.max(1),
+            fill: target as f64,
+            since_adjust: 0,
+        }
+    }
+
+    pub(crate) fn next(&mut self) -> f32 {
+        self.since_adjust += 1;
+
+        if self.since_adjust >= ADJUST_EVERY {
+            self.since_adjust = 0;
+            self.adjust();
+        }
+
+        if self.local.len() < 2 {
+            if let Ok(mut queue) = self.queue.lock() {
+                self.local.extend(queue.drain(..));
+            }
+
+            // Not here yet; silence until it is
+            if self.local.len() < 2 {
+                return 0.0;
+            }
+        }
+
+        let t = self.position as f32;
+        let value = self.local[0] + (self.local[1] - self.local[0]) * t;
+
+        self.position += self.ratio;
+
+        while self.position >= 1.0 && self.local.len() > 1 {
+            self.local.pop_front();
+            self.position -= 1.0;
+        }
+
+        value
+    }
+
+    /// Speed up as the queue fills past its target, and slow down as it
+    /// empties.
+    fn adjust(&mut self) {
+        let Ok(mut queue) = self.queue.lock() else {
+            return;
+        };
+
+        let fill = queue.len() + self.local.len();
+
+        // Far behind, e.g. queued since before recording started: catch up
+        if fill > self.target * 4 {
+            let excess = fill - self.target;
+            let local = excess.min(self.local.len().saturating_sub(2));
+
+            let n = (excess - local).min(queue.len());
+
+            self.local.drain(..local);
+            queue.drain(..n);
+            self.fill = self.target as f64;
+
+            return;
+        }
+
+        self.fill = self.fill * FILL_SMOOTHING + fill as f64 * (1.0 - FILL_SMOOTHING);
+
+        let error = self.fill / self.target as f64 - 1.0;
+        self.ratio = self.nominal * (1.0 + (error * DRIFT_GAIN).clamp(-MAX_DRIFT, MAX_DRIFT));
+    }
+}
@@ -0,0 +1,695 @@
+use crate::ltc;
+#[cfg(not(target_arch = "wasm32"))]
+use crate::Error;
+use crate::Stats;
+use cpal::SupportedStreamConfig;
+use serde_json::json;
+#[cfg(not(target_arch = "wasm32"))]
+use std::fs::File;
+#[cfg(not(target_arch = "wasm32"))]
+use std::fs::OpenOptions;
+#[cfg(not(target_arch = "wasm32"))]
+use std::io::Read;
+#[cfg(not(target_arch = "wasm32"))]
+use std::io::Seek;
+#[cfg(not(target_arch = "wasm32"))]
+use std::io::SeekFrom;
+#[cfg(not(target_arch = "wasm32"))]
+use std::io::Write;
+use std::path::Path;
+use std::path::PathBuf;
+use std::str::FromStr;
+use std::time::SystemTime;
+use std::time::UNIX_EPOCH;
+
+/// Field-recording metadata written as an `iXML` chunk.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Ixml {
+    pub project: Option<String>,
+    pub scene: Option<String>,
+    pub take: Option<String>,
+    pub tracks: Vec<String>,
+    pub speed: Option<Speed>,
+}
+
+/// Where a file starts by timecode, e.g. from LTC recorded with it.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Speed {
+    pub timecode_rate: ltc::Rate,
+    pub sample_rate: u32,
+    /// At the file's first frame
+    pub samples_since_midnight: u64,
+}
+
+impl Ixml {
+    pub fn is_empty(&self) -> bool {
+        self.project.is_none()
+            && self.scene.is_none()
+            && self.take.is_none()
+            && self.tracks.is_empty()
+            && self.speed.is_none()
+    }
+
+    pub fn to_xml(&self) -> String {
+        let mut xml = String::from("<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n<BWFXML>\n");
+        xml.push_str("  <IXML_VERSION>1.61</IXML_VERSION>\n");
+
+        for (tag, value) in [
+            ("PROJECT", &self.project),
+            ("SCENE", &self.scene),
+            ("TAKE", &self.take),
+        ] {
+            if let Some(value) = value {
+                xml.push_str(&format!("  <{tag}>{}</{tag}>\n", escape_xml(value)));
+            }
+        }
+
+        if !self.tracks.is_empty() {
+            xml.push_str("  <TRACK_LIST>\n");
+            xml.push_str(&format!(
+                "    <TRACK_COUNT>{}</TRACK_COUNT>\n",
+                self.tracks.len()
+            ));
+
+            for (i, name) in self.tracks.iter().enumerate() {
+                xml.push_str("    <TRACK>\n");
+                xml.push_str(&format!("      <CHANNEL_INDEX>{}</CHANNEL_INDEX>\n", i + 1));
+                xml.push_str(&format!(
+                    "      <INTERLEAVE_INDEX>{}</INTERLEAVE_INDEX>\n",
+                    i + 1
+                ));
+                xml.push_str(&format!("      <NAME>{}</NAME>\n", escape_xml(name)));
+                xml.push_str("    </TRACK>\n");
+            }
+
+            xml.push_str("  </TRACK_LIST>\n");
+        }
+
+        if let Some(speed) = &self.speed {
+            let flag = if speed.timecode_rate.is_drop() {
+                "DF"
+            } else {
+                "NDF"
+            };
+            let samples = speed.samples_since_midnight;
+
+            xml.push_str("  <SPEED>\n");
+            xml.push_str(&format!(
+                "    <TIMECODE_RATE>{}</TIMECODE_RATE>\n",
+                speed.timecode_rate.ratio()
+            ));
+            xml.push_str(&format!("    <TIMECODE_FLAG>{flag}</TIMECODE_FLAG>\n"));
+            xml.push_str(&format!(
+                "    <TIMESTAMP_SAMPLE_RATE>{}</TIMESTAMP_SAMPLE_RATE>\n",
+                speed.sample_rate
+            ));
+            xml.push_str(&format!(
+                "    <TIMESTAMP_SAMPLES_SINCE_MIDNIGHT_HI>{}</TIMESTAMP_SAMPLES_SINCE_MIDNIGHT_HI>\n",
+                samples >> 32
+            ));
+            xml.push_str(&format!(
+                "    <TIMESTAMP_SAMPLES_SINCE_MIDNIGHT_LO>{}</TIMESTAMP_SAMPLES_SINCE_MIDNIGHT_LO>\n",
+                samples & 0xFFFF_FFFF
+            ));
+            xml.push_str("  </SPEED>\n");
+        }
+
+        xml.push_str("</BWFXML>\n");
+        xml
+    }
+
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fn write<P>(&self, path: P) -> Result<(), Error>
+    where
+        P: AsRef<Path>,
+    {
+        append_chunk(path, b"iXML", self.to_xml().as_bytes())
+    }
+}
+
+/// Free-form tags written as a RIFF `LIST`/`INFO` chunk, and carried over
+/// to compressed formats as their own kind of tags.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Tags(Vec<(String, String)>);
+
+impl Tags {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn insert<K, V>(&mut self, key: K, value: V) -> &mut Self
+    where
+        K: Into<String>,
+        V: Into<String>,
+    {
+        let key = key.into().to_lowercase();
+        let value = value.into();
+
+        match self.0.iter_mut().find(|(k, _)| *k == key) {
+            Some((_, v)) => *v = value,
+            None => self.0.push((key, value)),
+        }
+
+        self
+    }
+
+    pub fn get(&self, key: &str) -> Option<&str> {
+        let key = key.to_lowercase();
+
+        self.0
+            .iter()
+            .find(|(k, _)| *k == key)
+            .map(|(_, v)| v.as_str())
+    }
+
+    pub fn contains(&self, key: &str) -> bool {
+        self.get(key).is_some()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = (&str, &str)> {
+        self.0.iter().map(|(k, v)| (k.as_str(), v.as_str()))
+    }
+
+    pub fn to_info_chunk(&self) -> Vec<u8> {
+        let mut data = b"INFO".to_vec();
+
+        for (key, value) in self.iter() {
+            let Some(id) = info_id(key) else {
+                continue;
+            };
+
+            let mut text = value.as_bytes().to_vec();
+            text.push(0);
+
+            data.extend_from_slice(&id);
+            data.extend_from_slice(&(text.len() as u32).to_le_bytes());
+            data.extend_from_slice(&text);
+
+            if text.len() % 2 == 1 {
+                data.push(0);
+            }
+        }
+
+        data
+    }
+
+    /// Tags back from the contents of a `LIST`/`INFO` chunk, under the names
+    /// `insert` takes.
+    pub fn from_info_chunk(data: &[u8]) -> Tags {
+        let mut tags = Tags::new();
+
+        let Some(mut rest) = data.strip_prefix(b"INFO") else {
+            return tags;
+        };
+
+        while rest.len() >= 8 {
+            let id = [rest[0], rest[1], rest[2], rest[3]];
+            let len = u32::from_le_bytes([rest[4], rest[5], rest[6], rest[7]]) as usize;
+
+            let Some(text) = rest.get(8..8 + len) else {
+                break;
+            };
+
+            let text = text.split(|&byte| byte == 0).next().unwrap_or_default();
+            tags.insert(info_key(&id), String::from_utf8_lossy(text));
+
+            // Word aligned, like chunks
+            rest = rest.get(8 + len + len % 2..).unwrap_or_default();
+        }
+
+        tags
+    }
+
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fn write<P>(&self, path: P) -> Result<(), Error>
+    where
+        P: AsRef<Path>,
+    {
+        append_chunk(path, b"LIST", &self.to_info_chunk())
+    }
+
+    /// The tags of the WAV file at `path`, for carrying over to formats that
+    /// keep them their own way.
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fn read<P>(path: P) -> Result<Tags, Error>
+    where
+        P: AsRef<Path>,
+    {
+        Ok(read_chunk(path, b"LIST")?
+            .map(|data| Tags::from_info_chunk(&data))
+            .unwrap_or_default())
+    }
+}
+
+/// Tag names and the INFO ids they're written as.
+const INFO_IDS: [(&str, &[u8; 4]); 10] = [
+    ("title", b"INAM"),
+    ("artist", b"IART"),
+    ("album", b"IPRD"),
+    ("comment", b"ICMT"),
+    ("genre", b"IGNR"),
+    ("date", b"ICRD"),
+    ("copyright", b"ICOP"),
+    ("track", b"ITRK"),
+    ("software", b"ISFT"),
+    ("device", b"ISRF"),
+];
+
+fn info_id(key: &str) -> Option<[u8; 4]> {
+    if let Some((_, id)) = INFO_IDS.iter().find(|(name, _)| *name == key) {
+        return Some(**id);
+    }
+
+    // Allow raw INFO ids such as `ieng=...`
+    if key.len() == 4 && key.is_ascii() {
+        let mut id = [0u8; 4];
+        id.copy_from_slice(key.to_ascii_uppercase().as_bytes());
+        return Some(id);
+    }
+
+    None
+}
+
+/// The name of an INFO id, or the id itself for ones without a name.
+fn info_key(id: &[u8; 4]) -> String {
+    match INFO_IDS.iter().find(|(_, known)| *known == id) {
+        Some((name, _)) => (*name).to_owned(),
+        None => String::from_utf8_lossy(id).to_lowercase(),
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Marker {
+    pub frame: u64,
+    pub label: String,
+}
+
+impl Marker {
+    pub fn seconds(&self, sample_rate: u32) -> f64 {
+        self.frame as f64 / f64::from(sample_rate)
+    }
+}
+
+/// A file format for a recording's markers, to load into other tools.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum MarkerFormat {
+    /// Audacity label track, imported with File > Import > Labels
+    Audacity,
+    /// CUE sheet with a track starting at each marker
+    Cue,
+    /// Podcasting 2.0 chapters JSON
+    Chapters,
+}
+
+impl MarkerFormat {
+    pub fn name(&self) -> &'static str {
+        match self {
+            MarkerFormat::Audacity => "audacity",
+            MarkerFormat::Cue => "cue",
+            MarkerFormat::Chapters => "chapters",
+        }
+    }
+
+    /// Where the markers of `output` are written in this format, e.g.
+    /// `out.wav.cue`.
+    pub fn path<P>(&self, output: P) -> PathBuf
+    where
+        P: AsRef<Path>,
+    {
+        let mut path = output.as_ref().as_os_str().to_owned();
+        path.push(match self {
+            MarkerFormat::Audacity => ".txt",
+            MarkerFormat::Cue => ".cue",
+            MarkerFormat::Chapters => ".chapters.json",
+        });
+        path.into()
+    }
+
+    /// The markers of the recording named `file`, in this format.
+    pub fn export(&self, markers: &[Marker], sample_rate: u32, file: &str) -> String {
+        match self {
+            MarkerFormat::Audacity => markers
+                .iter()
+                .map(|marker| {
+                    let seconds = marker.seconds(sample_rate);
+                    format!("{seconds:.6}\t{seconds:.6}\t{}\n", one_line(&marker.label))
+                })
+                .collect(),
+            MarkerFormat::Cue => to_cue(markers, sample_rate, file),
+            MarkerFormat::Chapters => {
+                let chapters: Vec<_> = markers
+                    .iter()
+                    .map(|marker| {
+                        json!({
+                            "startTime": marker.seconds(sample_rate),
+                            "title": marker.label,
+                        })
+                    })
+                    .collect();
+
+                let json = json!({ "version": "1.2.0", "chapters": chapters });
+
+                // A `Value` always serializes
+                serde_json::to_string_pretty(&json).unwrap_or_default() + "\n"
+            }
+        }
+    }
+}
+
+impl FromStr for MarkerFormat {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "audacity" | "txt" => Ok(MarkerFormat::Audacity),
+            "cue" => Ok(MarkerFormat::Cue),
+            "chapters" | "json" => Ok(MarkerFormat::Chapters),
+            _ => Err(format!(
+                "unknown marker format `{s}`, expected audacity, cue or chapters"
+            )),
+        }
+    }
+}
+
+/// A CUE sheet with a track from each marker to the next. CUE times are in
+/// 1/75s frames, and the first track has to start at the top of the file,
+/// so one is added there, untitled, if no marker is.
+fn to_cue(markers: &[Marker], sample_rate: u32, file: &str) -> String {
+    let mut cue = format!("FILE \"{}\" WAVE\n", cue_quote(file));
+
+    let untitled = Marker {
+        frame: 0,
+        label: String::new(),
+    };
+    let lead = (markers.first().map(|marker| marker.frame) != Some(0)).then_some(&untitled);
+
+    for (index, marker) in lead.into_iter().chain(markers).enumerate() {
+        let cd_frames = marker.frame * 75 / u64::from(sample_rate);
+
+        cue.push_str(&format!("  TRACK {:02} AUDIO\n", index + 1));
+
+        if !marker.label.is_empty() {
+            cue.push_str(&format!("    TITLE \"{}\"\n", cue_quote(&marker.label)));
+        }
+
+        cue.push_str(&format!(
+            "    INDEX 01 {:02}:{:02}:{:02}\n",
+            cd_frames / 75 / 60,
+            cd_frames / 75 % 60,
+            cd_frames % 75
+        ));
+    }
+
+    cue
+}
+
+// CUE sheets have no escapes
+fn cue_quote(value: &str) -> String {
+    one_line(value).replace('"', "'")
+}
+
+fn one_line(value: &str) -> String {
+    value.replace(['\r', '\n', '\t'], " ")
+}
+
+/// Session provenance written as `<output>.json` next to a recording.
+#[derive(Debug, Clone)]
+pub struct Sidecar {
+    pub device: Option<String>,
+    /// How the device was held, as obtained rather than asked for
+    pub share_mode: Option<crate::ShareMode>,
+    pub config: SupportedStreamConfig,
+    pub started: SystemTime,
+    pub stopped: SystemTime,
+    pub stats: Stats,
+    pub markers: Vec<Marker>,
+}
+
+impl Sidecar {
+    pub fn path<P>(output: P) -> PathBuf
+    where
+        P: AsRef<Path>,
+    {
+        let mut path = output.as_ref().as_os_str().to_owned();
+        path.push(".json");
+        path.into()
+    }
+
+    pub fn to_json(&self) -> serde_json::Value {
+        let sample_rate = self.config.sample_rate().0;
+        let frames = self.stats.frames(self.config.channels());
+
+        let buffer_size = match self.config.buffer_size() {
+            cpal::SupportedBufferSize::Range { min, max } => json!({ "min": min, "max": max }),
+            cpal::SupportedBufferSize::Unknown => serde_json::Value::Null,
+        };
+
+        let markers: Vec<_> = self
+            .markers
+            .iter()
+            .map(|marker| {
+                json!({
+                    "frame": marker.frame,
+                    "seconds": marker.seconds(sample_rate),
+                    "label": marker.label,
+                })
+            })
+            .collect();
+
+        let checksum = self.stats.checksum.map(|checksum| {
+            json!({
+                "algorithm": checksum.algorithm.name(),
+                "value": checksum.hex(),
+            })
+        });
+
+        json!({
+            "device": self.device,
+            "share_mode": self.share_mode.map(|mode| mode.name()),
+            "config": {
+                "sample_rate": sample_rate,
+                "channels": self.config.channels(),
+                "sample_format": self.config.sample_format().to_string(),
+                "buffer_size": buffer_size,
+            },
+            "started": format_timestamp(self.started),
+            "stopped": format_timestamp(self.stopped),
+            "frames": frames,
+            "duration": frames as f64 / f64::from(sample_rate),
+            "peak_dbfs": crate::to_dbfs(self.stats.peak),
+            "rms_dbfs": crate::to_dbfs(self.stats.rms()),
+            "dropouts": self.stats.dropouts,
+            "markers": markers,
+            "checksum": checksum,
+        })
+    }
+
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fn write<P>(&self, output: P) -> Result<(), Error>
+    where
+        P: AsRef<Path>,
+    {
+        let json = serde_json::to_string_pretty(&self.to_json()).or(Err(Error::MetadataError))?;
+        std::fs::write(Self::path(output), json).or(Err(Error::MetadataError))
+    }
+}
+
+/// Format a time as an RFC 3339 UTC timestamp, e.g. `2023-09-01T12:30:00Z`.
+pub fn format_timestamp(time: SystemTime) -> String {
+    let secs = time
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or(0);
+
+    let (year, month, day) = civil_from_days((secs / 86_400) as i64);
+    let rem = secs % 86_400;
+
+    format!(
+        "{year:04}-{month:02}-{day:02}T{:02}:{:02}:{:02}Z",
+        rem / 3600,
+        rem % 3600 / 60,
+        rem % 60
+    )
+}
+
+// Howard Hinnant's days-to-civil algorithm
+fn civil_from_days(days: i64) -> (i64, u32, u32) {
+    let z = days + 719_468;
+    let era = z.div_euclid(146_097);
+    let doe = z.rem_euclid(146_097);
+    let yoe = (doe - doe / 1460 + doe / 36_524 - doe / 146_096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = (doy - (153 * mp + 2) / 5 + 1) as u32;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 } as u32;
+    let year = yoe + era * 400 + i64::from(month <= 2);
+
+    (year, month, day)
+}
+
+/// Append a chunk to a finalized RIFF/WAVE file and fix up the RIFF size.
+#[cfg(not(target_arch = "wasm32"))]
+pub fn append_chunk<P>(path: P, id: &[u8; 4], data: &[u8]) -> Result<(), Error>
+where
+    P: AsRef<Path>,
+{
+    let len = u32::try_from(data.len()).or(Err(Error::MetadataError))?;
+
+    let mut file = OpenOptions::new()
+        .read(true)
+        .write(true)
+        .open(path)
+        .or(Err(Error::MetadataError))?;
+
+    let mut header = [0u8; 12];
+    file.read_exact(&mut header).or(Err(Error::MetadataError))?;
+
+    if &header[0..4] != b"RIFF" || &header[8..12] != b"WAVE" {
+        return Err(Error::MetadataError);
+    }
+
+    let mut chunk = Vec::with_capacity(data.len() + 9);
+    chunk.extend_from_slice(id);
+    chunk.extend_from_slice(&len.to_le_bytes());
+    chunk.extend_from_slice(data);
+
+    // RIFF chunks are word aligned
+    if data.len() % 2 == 1 {
+        chunk.push(0);
+    }
+
+    let end = file.seek(SeekFrom::End(0)).or(Err(Error::MetadataError))?;
+    file.write_all(&chunk).or(Err(Error::MetadataError))?;
+
+    let riff_size = u32::try_from(end + chunk.len() as u64 - 8).or(Err(Error::MetadataError))?;
+    file.seek(SeekFrom::Start(4))
+        .or(Err(Error::MetadataError))?;
+    file.write_all(&riff_size.to_le_bytes())
+        .or(Err(Error::MetadataError))?;
+
+    Ok(())
+}
+
+/// Cut a RIFF/WAVE file off after its `data` chunk, dropping what follows
+/// it (such as tags) so more audio can be appended. The RIFF size is left
+/// for the writer to fix up.
+#[cfg(not(target_arch = "wasm32"))]
+pub fn strip_after_data<P>(path: P) -> Result<(), Error>
+where
+    P: AsRef<Path>,
+{
+    let mut file = OpenOptions::new()
+        .read(true)
+        .write(true)
+        .open(path)
+        .or(Err(Error::MetadataError))?;
+
+    let (offset, len) = find_data(&mut file)?;
+
+    file.set_len(offset + len).or(Err(Error::MetadataError))
+}
+
+/// The contents of the first `id` chunk of a RIFF/WAVE file, if it has one.
+#[cfg(not(target_arch = "wasm32"))]
+pub fn read_chunk<P>(path: P, id: &[u8; 4]) -> Result<Option<Vec<u8>>, Error>
+where
+    P: AsRef<Path>,
+{
+    let mut file = File::open(path).or(Err(Error::MetadataError))?;
+
+    let Some((offset, len)) = find_chunk(&mut file, id)? else {
+        return Ok(None);
+    };
+
+    let mut data = Vec::new();
+    file.seek(SeekFrom::Start(offset))
+        .or(Err(Error::MetadataError))?;
+    file.take(len)
+        .read_to_end(&mut data)
+        .or(Err(Error::MetadataError))?;
+
+    Ok(Some(data))
+}
+
+/// Where the audio data of a RIFF/WAVE file starts, and its length.
+#[cfg(not(target_arch = "wasm32"))]
+pub(crate) fn find_data(file: &mut File) -> Result<(u64, u64), Error> {
+    find_chunk(file, b"data")?.ok_or(Error::MetadataError)
+}
+
+/// Where the contents of the first `id` chunk start, and their length.
+#[cfg(not(target_arch = "wasm32"))]
+fn find_chunk(file: &mut File, id: &[u8; 4]) -> Result<Option<(u64, u64)>, Error> {
+    let mut header = [0u8; 12];
+    file.seek(SeekFrom::Start(0))
+        .or(Err(Error::MetadataError))?;
+    file.read_exact(&mut header).or(Err(Error::MetadataError))?;
+
+    if &header[0..4] != b"RIFF" || &header[8..12] != b"WAVE" {
+        return Err(Error::MetadataError);
+    }
+
+    let mut position = 12u64;
+
+    loop {
+        let mut chunk = [0u8; 8];
+
+        if file.read_exact(&mut chunk).is_err() {
+            return Ok(None);
+        }
+
+        let len = u64::from(u32::from_le_bytes([chunk[4], chunk[5], chunk[6], chunk[7]]));
+
+        if &chunk[0..4] == id {
+            return Ok(Some((position + 8, len)));
+        }
+
+        // RIFF chunks are word aligned
+        position += 8 + len + len % 2;
+        file.seek(SeekFrom::Start(position))
+            .or(Err(Error::MetadataError))?;
+    }
+}
+
+fn escape_xml(value: &str) -> String {
+    let mut escaped = String::with_capacity(value.len());
+
+    for c in value.chars() {
+        match c {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' => escaped.push_str("&quot;"),
+            '\'' => escaped.push_str("&apos;"),
+            c => escaped.push(c),
+        }
+    }
+
+    escaped
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn tags_round_trip_through_info() {
+        let mut tags = Tags::new();
+        tags.insert("title", "Take 3")
+            .insert("artist", "Someone")
+            .insert("date", "2026-10-16")
+            .insert("ieng", "Engineer");
+
+        assert_eq!(Tags::from_info_chunk(&tags.to_info_chunk()), tags);
+    }
+
+    #[test]
+    fn info_without_tags() {
+        assert!(Tags::from_info_chunk(b"adtl").is_empty());
+        assert!(Tags::from_info_chunk(b"INFO").is_empty());
+    }
+}
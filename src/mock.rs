@@ -0,0 +1,332 @@
+//! Virtual input devices: a WAV file played back as if it were being
+//! captured, devices merged by `crate::merge`, and with the `mock-host`
+//! feature, generated test signals.
+
+// Virtual devices run on a worker thread, which wasm32 can't spawn
+#![cfg_attr(target_arch = "wasm32", allow(dead_code, unused_imports))]
+
+#[cfg(feature = "mock-host")]
+use crate::generator::Generator;
+#[cfg(feature = "mock-host")]
+use crate::generator::Wave;
+#[cfg(not(target_arch = "wasm32"))]
+use crate::merge::DriftReader;
+use crate::Backend;
+use crate::Device;
+use crate::DeviceBuilder;
+use crate::Error;
+use std::path::Path;
+#[cfg(feature = "mock-host")]
+use std::path::PathBuf;
+use std::sync::atomic::AtomicBool;
+use std::sync::atomic::Ordering;
+use std::sync::Arc;
+use std::thread::JoinHandle;
+use std::time::Duration;
+use std::time::Instant;
+
+/// Frames delivered per callback.
+const BUFFER_FRAMES: usize = 512;
+
+#[cfg(feature = "mock-host")]
+#[derive(Debug, Clone, PartialEq)]
+pub enum Signal {
+    Sine {
+        freq: f64,
+        level: f32,
+    },
+    /// A sawtooth from -1 to 1 once per second
+    Ramp,
+    /// Samples of a WAV file, which also sets the rate and channel count
+    File(PathBuf),
+}
+
+#[derive(Clone)]
+pub(crate) struct MockDevice {
+    source: Source,
+    realtime: bool,
+}
+
+#[derive(Clone)]
+enum Source {
+    #[cfg(feature = "mock-host")]
+    Sine(Generator),
+    #[cfg(feature = "mock-host")]
+    Ramp {
+        position: u64,
+        rate: u64,
+    },
+    Data(Arc<[f32]>, usize),
+    /// A channel from each device, by `crate::merge`
+    #[cfg(not(target_arch = "wasm32"))]
+    Merged(Vec<DriftReader>, Arc<str>),
+}
+
+impl DeviceBuilder {
+    /// An input device that plays back a WAV file in real time, at the
+    /// file's own sample rate and channel count. The stream ends with the
+    /// file; see [`crate::StreamBuilder::on_end`].
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fn from_file<P>(path: P) -> Result<DeviceBuilder, Error>
+    where
+        P: AsRef<Path>,
+    {
+        let (spec, samples) = read_wav(path.as_ref())?;
+
+        Ok(virtual_device(
+            Source::Data(samples.into(), 0),
+            spec.sample_rate,
+            spec.channels,
+        ))
+    }
+
+    /// A fake input device producing `signal` in real time.
+    #[cfg(all(feature = "mock-host", not(target_arch = "wasm32")))]
+    pub fn new_mock(
+        signal: Signal,
+        sample_rate: u32,
+        channels: u16,
+    ) -> Result<DeviceBuilder, Error> {
+        let (source, sample_rate, channels) = match signal {
+            Signal::Sine { freq, level } => {
+                let mut generator = Generator::new(Wave::Sine, sample_rate);
+                generator.freq(freq).level(level);
+                (Source::Sine(generator), sample_rate, channels)
+            }
+            Signal::Ramp => {
+                let rate = u64::from(sample_rate.max(1));
+                (Source::Ramp { position: 0, rate }, sample_rate, channels)
+            }
+            Signal::File(path) => return DeviceBuilder::from_file(path),
+        };
+
+        Ok(virtual_device(source, sample_rate, channels))
+    }
+
+    /// Whether a virtual device paces itself in real time or delivers samples
+    /// as fast as they are consumed. Has no effect on real devices.
+    pub fn realtime(&mut self, realtime: bool) -> &mut Self {
+        if let Backend::Mock(mock) = &mut self.inner {
+            mock.realtime = realtime;
+        }
+
+        self
+    }
+}
+
+/// A device with a channel from each reader, named `name`.
+#[cfg(not(target_arch = "wasm32"))]
+pub(crate) fn merged_device(
+    readers: Vec<DriftReader>,
+    name: &str,
+    sample_rate: u32,
+) -> DeviceBuilder {
+    let channels = u16::try_from(readers.len()).unwrap_or(u16::MAX);
+
+    virtual_device(Source::Merged(readers, name.into()), sample_rate, channels)
+}
+
+fn virtual_device(source: Source, sample_rate: u32, channels: u16) -> DeviceBuilder {
+    let config = cpal::SupportedStreamConfig::new(
+        channels,
+        cpal::SampleRate(sample_rate),
+        cpal::SupportedBufferSize::Unknown,
+        cpal::SampleFormat::F32,
+    );
+
+    DeviceBuilder {
+        kind: Device::Input,
+        inner: Backend::Mock(MockDevice {
+            source,
+            realtime: true,
+        }),
+        config,
+    }
+}
+
+/// The mock device selected by `AUDIORT_MOCK`, e.g. `sine:440`, `ramp` or
+/// `file:take.wav`, which then stands in for the default input.
+#[cfg(all(feature = "mock-host", not(target_arch = "wasm32")))]
+pub(crate) fn from_env() -> Result<Option<DeviceBuilder>, Error> {
+    let Ok(spec) = std::env::var("AUDIORT_MOCK") else {
+        return Ok(None);
+    };
+
+    let (kind, arg) = spec.split_once(':').unwrap_or((&spec, ""));
+
+    let signal = match kind {
+        "sine" => Signal::Sine {
+            freq: arg.parse().unwrap_or(1000.0),
+            level: -6.0,
+        },
+        "ramp" => Signal::Ramp,
+        "file" => Signal::File(arg.into()),
+        _ => return Err(Error::DeviceNotFoundError),
+    };
+
+    DeviceBuilder::new_mock(signal, 48_000, 2).map(Some)
+}
+
+impl Source {
+    /// The next frame's value, or `None` once a file runs out.
+    fn next(&mut self, channels: usize, frame: &mut [f32]) -> Option<()> {
+        match self {
+            #[cfg(feature = "mock-host")]
+            Source::Sine(generator) => frame.fill(generator.next_sample()),
+            #[cfg(feature = "mock-host")]
+            Source::Ramp { position, rate } => {
+                frame.fill((*position % *rate) as f32 / *rate as f32 * 2.0 - 1.0);
+                *position += 1;
+            }
+            Source::Data(data, position) => {
+                let samples = data.get(*position..*position + channels)?;
+                frame.copy_from_slice(samples);
+                *position += channels;
+            }
+            #[cfg(not(target_arch = "wasm32"))]
+            Source::Merged(readers, _) => {
+                for (value, reader) in frame.iter_mut().zip(readers) {
+                    *value = reader.next();
+                }
+            }
+        }
+
+        Some(())
+    }
+}
+
+impl MockDevice {
+    pub(crate) fn is_realtime(&self) -> bool {
+        self.realtime
+    }
+
+    pub(crate) fn name(&self) -> String {
+        match self.source {
+            #[cfg(feature = "mock-host")]
+            Source::Sine(_) => "Mock sine".into(),
+            #[cfg(feature = "mock-host")]
+            Source::Ramp { .. } => "Mock ramp".into(),
+            Source::Data(..) => "File".into(),
+            #[cfg(not(target_arch = "wasm32"))]
+            Source::Merged(_, ref name) => name.to_string(),
+        }
+    }
+
+    pub(crate) fn build<T, D>(
+        &self,
+        cfg: &cpal::StreamConfig,
+        mut on_data: D,
+        mut on_end: Option<crate::EndCallback>,
+    ) -> MockStream
+    where
+        T: cpal::SizedSample + cpal::FromSample<f32>,
+        D: FnMut(&[T], bool) + Send + 'static,
+    {
+        let playing = Arc::new(AtomicBool::new(false));
+        let stopped = Arc::new(AtomicBool::new(false));
+
+        let mut source = self.source.clone();
+        let realtime = self.realtime;
+        let sample_rate = cfg.sample_rate.0;
+        let channels = usize::from(cfg.channels.max(1));
+        let is_playing = Arc::clone(&playing);
+        let is_stopped = Arc::clone(&stopped);
+
+        let worker = std::thread::spawn(move || {
+            let mut frame = vec![0.0; channels];
+            let mut buffer = Vec::with_capacity(BUFFER_FRAMES * channels);
+            let mut started = Instant::now();
+            let mut delivered = 0u64;
+
+            while !is_stopped.load(Ordering::Relaxed) {
+                if !is_playing.load(Ordering::Relaxed) {
+                    std::thread::sleep(Duration::from_millis(5));
+                    started = Instant::now();
+                    delivered = 0;
+                    continue;
+                }
+
+                buffer.clear();
+
+                for _ in 0..BUFFER_FRAMES {
+                    if source.next(channels, &mut frame).is_none() {
+                        break;
+                    }
+
+                    buffer.extend(frame.iter().map(|&value| T::from_sample(value)));
+                }
+
+                // A finished file leaves the stream idle
+                if buffer.is_empty() {
+                    if let Some(callback) = on_end.take() {
+                        callback();
+                    }
+
+                    std::thread::sleep(Duration::from_millis(5));
+                    continue;
+                }
+
+                on_data(&buffer, false);
+                delivered += (buffer.len() / channels) as u64;
+
+                if realtime {
+                    let due = started
+                        + Duration::from_secs_f64(delivered as f64 / f64::from(sample_rate));
+                    std::thread::sleep(due.saturating_duration_since(Instant::now()));
+                }
+            }
+        });
+
+        MockStream {
+            playing,
+            stopped,
+            worker: Some(worker),
+        }
+    }
+}
+
+pub(crate) struct MockStream {
+    playing: Arc<AtomicBool>,
+    stopped: Arc<AtomicBool>,
+    worker: Option<JoinHandle<()>>,
+}
+
+impl MockStream {
+    pub(crate) fn play(&self) {
+        self.playing.store(true, Ordering::Relaxed);
+    }
+
+    pub(crate) fn pause(&self) {
+        self.playing.store(false, Ordering::Relaxed);
+    }
+}
+
+impl Drop for MockStream {
+    fn drop(&mut self) {
+        self.stopped.store(true, Ordering::Relaxed);
+
+        if let Some(worker) = self.worker.take() {
+            let _ = worker.join();
+        }
+    }
+}
+
+fn read_wav(path: &Path) -> Result<(hound::WavSpec, Vec<f32>), Error> {
+    let mut reader = hound::WavReader::open(path).or(Err(Error::ReadError))?;
+    let spec = reader.spec();
+
+    let samples = match spec.sample_format {
+        hound::SampleFormat::Float => reader.samples::<f32>().collect::<Result<_, _>>(),
+        hound::SampleFormat::Int => {
+            let scale = (1u64 << (spec.bits_per_sample - 1)) as f32;
+
+            reader
+                .samples::<i32>()
+                .map(|sample| sample.map(|value| value as f32 / scale))
+                .collect()
+        }
+    }
+    .or(Err(Error::ReadError))?;
+
+    Ok((spec, samples))
+}
@@ -0,0 +1,184 @@
+//! The Node.js addon loaded by `node/index.js`, which adds async iteration
+//! over `Recorder` chunks on top of `nextChunk()`.
+
+use crate::Device;
+use crate::DeviceBuilder;
+use crate::Error;
+use crate::Stats;
+use crate::StreamBuilder;
+use napi::bindgen_prelude::AsyncTask;
+use napi::bindgen_prelude::Float32Array;
+use napi::Env;
+use napi::Task;
+use napi_derive::napi;
+use std::sync::mpsc;
+use std::sync::Arc;
+use std::sync::Mutex;
+
+/// Chunks waiting for JavaScript before new ones are dropped.
+const CHUNK_BUFFER: usize = 64;
+
+impl From<Error> for napi::Error {
+    fn from(err: Error) -> napi::Error {
+        napi::Error::from_reason(err.to_string())
+    }
+}
+
+type Chunks = Arc<Mutex<mpsc::Receiver<Vec<f32>>>>;
+
+#[napi(object)]
+pub struct Levels {
+    pub frames: i64,
+    pub peak_dbfs: f64,
+    pub rms_dbfs: f64,
+    pub clipped: i64,
+    pub dropouts: i64,
+    pub load: f64,
+}
+
+/// Records an input device to a WAV file, or only to chunks when no path is
+/// given. Chunks are interleaved `Float32Array`s; once JavaScript falls
+/// behind by 64 of them, newer ones are dropped.
+#[napi]
+pub struct Recorder {
+    stream: StreamBuilder,
+    chunks: Chunks,
+    file: bool,
+}
+
+#[napi]
+impl Recorder {
+    /// `device` is as for `DeviceBuilder::open`; the default input otherwise.
+    #[napi(constructor)]
+    pub fn new(path: Option<String>, device: Option<String>) -> napi::Result<Recorder> {
+        let device = match device.as_deref() {
+            Some(spec) => DeviceBuilder::open(Device::Input, spec)?,
+            None => DeviceBuilder::new_default_input()?,
+        };
+
+        let mut stream = StreamBuilder::new(device)?;
+        let (tx, rx) = mpsc::sync_channel(CHUNK_BUFFER);
+
+        let send = move |data: &[f32]| {
+            let _ = tx.try_send(data.to_vec());
+        };
+
+        match &path {
+            Some(path) => {
+                stream.tap(send);
+                stream.write_wav(path)?;
+            }
+            None => stream.read(send)?,
+        }
+
+        Ok(Recorder {
+            stream,
+            chunks: Arc::new(Mutex::new(rx)),
+            file: path.is_some(),
+        })
+    }
+
+    #[napi(getter)]
+    pub fn sample_rate(&self) -> u32 {
+        self.stream.config().sample_rate().0
+    }
+
+    #[napi(getter)]
+    pub fn channels(&self) -> u32 {
+        self.stream.config().channels().into()
+    }
+
+    /// Frames per buffer the device settled on, or `null` before it starts.
+    #[napi(getter)]
+    pub fn buffer_frames(&self) -> Option<u32> {
+        self.stream.info().map(|info| info.buffer_frames)
+    }
+
+    /// `"playing"`, `"paused"`, `"stopped"` or `"errored"`.
+    #[napi(getter)]
+    pub fn state(&self) -> &'static str {
+        self.stream.state().name()
+    }
+
+    #[napi]
+    pub fn start(&self) -> napi::Result<()> {
+        Ok(self.stream.play()?)
+    }
+
+    /// Pause recording; `start()` resumes into the same file.
+    #[napi]
+    pub fn stop(&self) -> napi::Result<()> {
+        Ok(self.stream.pause()?)
+    }
+
+    #[napi]
+    pub fn levels(&self) -> Levels {
+        self.levels_of(self.stream.stats())
+    }
+
+    /// Stop recording, finalize the file and end the chunk iterator.
+    #[napi]
+    pub fn finish(&mut self) -> napi::Result<Levels> {
+        let stats = match self.file {
+            true => self.stream.finish()?,
+            false => {
+                self.stream.stop();
+                self.stream.stats()
+            }
+        };
+
+        self.file = false;
+
+        Ok(self.levels_of(stats))
+    }
+
+    /// The next chunk, or `null` once the recorder is finished.
+    #[napi(ts_return_type = "Promise<Float32Array | null>")]
+    pub fn next_chunk(&self) -> AsyncTask<NextChunk> {
+        AsyncTask::new(NextChunk {
+            chunks: Arc::clone(&self.chunks),
+        })
+    }
+}
+
+impl Recorder {
+    fn levels_of(&self, stats: Stats) -> Levels {
+        Levels {
+            frames: stats.frames(self.stream.config().channels()) as i64,
+            peak_dbfs: crate::to_dbfs(stats.peak).into(),
+            rms_dbfs: crate::to_dbfs(stats.rms()).into(),
+            clipped: stats.clipped as i64,
+            dropouts: stats.dropouts as i64,
+            load: stats.load.into(),
+        }
+    }
+}
+
+pub struct NextChunk {
+    chunks: Chunks,
+}
+
+impl Task for NextChunk {
+    type Output = Option<Vec<f32>>;
+    type JsValue = Option<Float32Array>;
+
+    // Runs on the libuv thread pool, so waiting here doesn't block JavaScript
+    fn compute(&mut self) -> napi::Result<Option<Vec<f32>>> {
+        let chunks = self.chunks.lock().or(Err(Error::OutputLockError))?;
+        Ok(chunks.recv().ok())
+    }
+
+    fn resolve(&mut self, _: Env, chunk: Option<Vec<f32>>) -> napi::Result<Option<Float32Array>> {
+        Ok(chunk.map(Float32Array::new))
+    }
+}
+
+#[napi]
+pub fn input_devices() -> napi::Result<Vec<String>> {
+    Ok(DeviceBuilder::names(Device::Input)?)
+}
+
+#[napi]
+pub fn output_devices() -> napi::Result<Vec<String>> {
+    Ok(DeviceBuilder::names(Device::Output)?)
+}
@@ -0,0 +1,373 @@
+use crate::effects::Effect;
+use crate::effects::SharedChain;
+use crate::fail;
+use crate::priority::Priority;
+use crate::DeviceBuilder;
+use crate::Error;
+use cpal::traits::DeviceTrait;
+use cpal::traits::StreamTrait;
+use std::collections::VecDeque;
+use std::sync::atomic::AtomicU32;
+use std::sync::atomic::Ordering;
+use std::sync::Arc;
+use std::sync::Mutex;
+use std::time::Duration;
+
+type Queue = Arc<Mutex<VecDeque<f32>>>;
+
+/// Level above which louder samples are softly limited, so volume above 1
+/// can't clip.
+const KNEE: f32 = 0.9;
+
+/// Plays interleaved `f32` samples pushed from another thread on an output
+/// device, filling underruns with silence.
+pub struct Player {
+    stream: cpal::Stream,
+    queue: Queue,
+    effects: SharedChain,
+    volume: Arc<AtomicU32>,
+    sample_rate: u32,
+    channels: u16,
+    max_queued: usize,
+    buffer_size: cpal::BufferSize,
+}
+
+impl Player {
+    pub fn new(device: &DeviceBuilder, sample_rate: u32, channels: u16) -> Result<Player, Error> {
+        Player::build(
+            device,
+            sample_rate,
+            channels,
+            cpal::BufferSize::Default,
+            None,
+        )
+    }
+
+    /// With a given buffer size, and real-time priority for the callback
+    /// when `priority` is given.
+    pub(crate) fn build(
+        device: &DeviceBuilder,
+        sample_rate: u32,
+        channels: u16,
+        buffer_size: cpal::BufferSize,
+        priority: Option<Priority>,
+    ) -> Result<Player, Error> {
+        let cfg = cpal::StreamConfig {
+            channels,
+            sample_rate: cpal::SampleRate(sample_rate),
+            buffer_size,
+        };
+
+        let queue = Queue::default();
+        let effects = SharedChain::default();
+        let volume = Arc::new(AtomicU32::new(1.0f32.to_bits()));
+
+        let inner = device.inner.cpal()?;
+        let output = Output {
+            queue: Arc::clone(&queue),
+            effects: Arc::clone(&effects),
+            volume: Arc::clone(&volume),
+            current_volume: 1.0,
+            channels: usize::from(channels.max(1)),
+            priority,
+        };
+
+        let stream = match device.config.sample_format() {
+            cpal::SampleFormat::F32 => build::<f32>(inner, &cfg, output),
+            cpal::SampleFormat::I32 => build::<i32>(inner, &cfg, output),
+            cpal::SampleFormat::I16 => build::<i16>(inner, &cfg, output),
+            cpal::SampleFormat::I8 => build::<i8>(inner, &cfg, output),
+            _ => return Err(Error::StreamConfigFormatError),
+        }?;
+
+        let mut player = Player {
+            stream,
+            queue,
+            effects,
+            volume,
+            sample_rate,
+            channels,
+            max_queued: 0,
+            buffer_size,
+        };
+
+        player.set_max_latency(Duration::from_secs(1));
+
+        Ok(player)
+    }
+
+    pub fn sample_rate(&self) -> u32 {
+        self.sample_rate
+    }
+
+    pub fn channels(&self) -> u16 {
+        self.channels
+    }
+
+    /// Frames per callback, if fixed rather than left to the device.
+    pub fn buffer_frames(&self) -> Option<u32> {
+        match self.buffer_size {
+            cpal::BufferSize::Fixed(frames) => Some(frames),
+            cpal::BufferSize::Default => None,
+        }
+    }
+
+    pub fn volume(&self) -> f32 {
+        self.volume_control().get()
+    }
+
+    /// Linear gain applied last, after effects, with louder samples softly
+    /// limited rather than clipped. Can be changed while playing; the
+    /// change is ramped over a buffer so it doesn't click.
+    pub fn set_volume(&self, volume: f32) {
+        self.volume_control().set(volume);
+    }
+
+    /// A handle that changes the volume from another thread, such as one
+    /// reading keys.
+    pub fn volume_control(&self) -> Volume {
+        Volume(Arc::clone(&self.volume))
+    }
+
+    /// Oldest samples are dropped once more than this much audio is queued.
+    pub fn set_max_latency(&mut self, latency: Duration) -> &mut Self {
+        let frames = latency.as_secs_f64() * f64::from(self.sample_rate);
+        self.max_queued = frames as usize * usize::from(self.channels);
+        self
+    }
+
+    /// Add an effect run on samples as they're played. Can be added while
+    /// playing.
+    pub fn effect<E>(&self, effect: E) -> &Self
+    where
+        E: Effect + 'static,
+    {
+        if let Ok(mut effects) = self.effects.lock() {
+            effects.push(effect);
+        }
+
+        self
+    }
+
+    pub fn push(&self, samples: &[f32]) {
+        push(&self.queue, self.channels, self.max_queued, samples);
+    }
+
+    /// A handle that pushes samples from another thread, such as a capture
+    /// callback. It keeps the latency limit set at the time it's made.
+    pub fn feeder(&self) -> Feeder {
+        Feeder {
+            queue: Arc::clone(&self.queue),
+            channels: self.channels,
+            max_queued: self.max_queued,
+        }
+    }
+
+    /// Frames waiting to be played.
+    pub fn queued(&self) -> usize {
+        let samples = self.queue.lock().map(|queue| queue.len()).unwrap_or(0);
+        samples / usize::from(self.channels.max(1))
+    }
+
+    pub fn play(&self) -> Result<(), Error> {
+        self.stream.play().or(Err(Error::PlayError))
+    }
+
+    pub fn pause(&self) -> Result<(), Error> {
+        self.stream.pause().or(Err(Error::PauseError))
+    }
+}
+
+#[derive(Clone)]
+pub struct Feeder {
+    queue: Queue,
+    channels: u16,
+    max_queued: usize,
+}
+
+impl Feeder {
+    pub fn push(&self, samples: &[f32]) {
+        push(&self.queue, self.channels, self.max_queued, samples);
+    }
+}
+
+#[derive(Clone)]
+pub struct Volume(Arc<AtomicU32>);
+
+impl Volume {
+    pub fn get(&self) -> f32 {
+        f32::from_bits(self.0.load(Ordering::Relaxed))
+    }
+
+    pub fn set(&self, volume: f32) {
+        self.0.store(volume.max(0.0).to_bits(), Ordering::Relaxed);
+    }
+}
+
+fn push(queue: &Queue, channels: u16, max_queued: usize, samples: &[f32]) {
+    if let Ok(mut queue) = queue.lock() {
+        queue.extend(samples);
+
+        let excess = queue.len().saturating_sub(max_queued);
+        // Keep whole frames so channels stay aligned
+        let excess = excess
+            .next_multiple_of(usize::from(channels.max(1)))
+            .min(queue.len());
+
+        queue.drain(..excess);
+    }
+}
+
+/// Decoded interleaved audio, played from any frame of it.
+pub struct Clip {
+    samples: Vec<f32>,
+    sample_rate: u32,
+    channels: usize,
+}
+
+impl Clip {
+    pub fn new(samples: Vec<f32>, sample_rate: u32, channels: u16) -> Clip {
+        Clip {
+            samples,
+            sample_rate,
+            channels: usize::from(channels.max(1)),
+        }
+    }
+
+    pub fn frames(&self) -> usize {
+        self.samples.len() / self.channels
+    }
+
+    /// The frame `time` into the clip, or its end.
+    pub fn frame_at(&self, time: Duration) -> usize {
+        let frame = (time.as_secs_f64() * f64::from(self.sample_rate)).round() as usize;
+        frame.min(self.frames())
+    }
+
+    /// The samples from frame `start` up to `end`, within the clip.
+    pub fn section(&self, start: usize, end: usize) -> &[f32] {
+        let end = end.min(self.frames());
+        let start = start.min(end);
+
+        &self.samples[start * self.channels..end * self.channels]
+    }
+}
+
+/// Crossfade the last `frames` of `tail` into `lead`, the audio just before
+/// where a loop starts again, so the jump back is seamless. Missing lead is
+/// silence, making it a fade out.
+pub fn crossfade_loop(tail: &mut [f32], lead: &[f32], channels: u16, frames: usize) {
+    let channels = usize::from(channels.max(1));
+    let frames = frames.min(tail.len() / channels);
+    let lead_frames = lead.len() / channels;
+    let tail_start = tail.len() / channels - frames;
+
+    for frame in 0..frames {
+        // Equal power, as the two sides needn't be alike
+        let t = (frame as f32 + 0.5) / frames as f32 * std::f32::consts::FRAC_PI_2;
+        let (fade_in, fade_out) = t.sin_cos();
+
+        // Lead lines up to end where the fade does
+        let lead_frame = (lead_frames + frame).checked_sub(frames);
+
+        for channel in 0..channels {
+            let incoming = lead_frame.map_or(0.0, |at| lead[at * channels + channel]);
+            let sample = &mut tail[(tail_start + frame) * channels + channel];
+
+            *sample = *sample * fade_out + incoming * fade_in;
+        }
+    }
+}
+
+/// What the output callback reads from
+struct Output {
+    queue: Queue,
+    effects: SharedChain,
+    volume: Arc<AtomicU32>,
+    /// Where the last buffer's volume ended
+    current_volume: f32,
+    channels: usize,
+    priority: Option<Priority>,
+}
+
+impl Output {
+    /// Ramp from the last volume to the one set, then limit.
+    fn apply_volume(&mut self, frames: &mut [f32]) {
+        let target = f32::from_bits(self.volume.load(Ordering::Relaxed));
+        let start = self.current_volume;
+        self.current_volume = target;
+
+        // Left alone at unity, so playback stays bit-perfect
+        if start == 1.0 && target == 1.0 {
+            return;
+        }
+
+        let count = (frames.len() / self.channels).max(1) as f32;
+
+        for (frame, samples) in frames.chunks_mut(self.channels).enumerate() {
+            let gain = start + (target - start) * (frame as f32 + 1.0) / count;
+
+            for value in samples.iter_mut() {
+                *value = limit(*value * gain);
+            }
+        }
+    }
+}
+
+/// Soft limiting above `KNEE`, approaching full scale without reaching it.
+fn limit(value: f32) -> f32 {
+    let magnitude = value.abs();
+
+    if magnitude <= KNEE {
+        return value;
+    }
+
+    let headroom = 1.0 - KNEE;
+    let limited = KNEE + headroom * ((magnitude - KNEE) / headroom).tanh();
+
+    limited.copysign(value)
+}
+
+fn build<T>(
+    device: &cpal::Device,
+    cfg: &cpal::StreamConfig,
+    mut output: Output,
+) -> Result<cpal::Stream, Error>
+where
+    T: cpal::SizedSample + cpal::FromSample<f32>,
+{
+    let mut buffer = Vec::new();
+
+    device
+        .build_output_stream(
+            cfg,
+            move |data: &mut [T], _: &cpal::OutputCallbackInfo| {
+                if let Some(priority) = output.priority.take() {
+                    priority.promote();
+                }
+
+                buffer.clear();
+
+                if let Ok(mut queue) = output.queue.lock() {
+                    let available = data.len().min(queue.len());
+                    buffer.extend(queue.drain(..available));
+                }
+
+                // Underruns are filled with silence
+                buffer.resize(data.len(), 0.0);
+
+                if let Ok(mut effects) = output.effects.lock() {
+                    effects.process(&mut buffer, output.channels);
+                }
+
+                output.apply_volume(&mut buffer);
+
+                for (sample, &value) in data.iter_mut().zip(buffer.iter()) {
+                    *sample = T::from_sample(value);
+                }
+            },
+            |err| fail!("playing data failed", err),
+            None,
+        )
+        .or(Err(Error::StreamCreationError))
+}
@@ -0,0 +1,138 @@
+//! Reserving disk space ahead of a recording, so that a long session's file
+//! isn't scattered across the disk and writes don't stall while the
+//! filesystem finds room. The file's length is left alone; only the blocks
+//! behind it are claimed early.
+
+use std::fs::File;
+use std::path::Path;
+use std::path::PathBuf;
+
+/// Steps of reservation after the first grow with the file, between these
+const MIN_STEP: u64 = 16 << 20;
+const MAX_STEP: u64 = 1 << 30;
+
+/// Keeps the file being written ahead of its reservation.
+pub(crate) struct Reserve {
+    /// Up front for each file, e.g. its expected size
+    expected: u64,
+    path: Option<PathBuf>,
+    file: Option<File>,
+    reserved: u64,
+}
+
+impl Reserve {
+    pub(crate) fn new(expected: u64) -> Reserve {
+        Reserve {
+            expected,
+            path: None,
+            file: None,
+            reserved: 0,
+        }
+    }
+
+    /// Move on to a newly started file, returning the previous one to
+    /// `release` once it's finalized.
+    pub(crate) fn open(&mut self, path: &Path) -> Option<PathBuf> {
+        self.file = File::options().write(true).open(path).ok();
+        self.reserved = 0;
+
+        if let Some(file) = &self.file {
+            let len = file.metadata().map_or(0, |metadata| metadata.len());
+            self.extend(len, self.expected.max(MIN_STEP));
+        }
+
+        self.path.replace(path.to_owned())
+    }
+
+    /// Done with the current file, returning it to `release` once it's
+    /// finalized.
+    pub(crate) fn close(&mut self) -> Option<PathBuf> {
+        self.file = None;
+        self.path.take()
+    }
+
+    /// Reserve more once most of what's reserved is used: half as much
+    /// again each time, so long sessions take few, large extents.
+    pub(crate) fn grow(&mut self) {
+        let Some(file) = &self.file else {
+            return;
+        };
+
+        let Ok(len) = file.metadata().map(|metadata| metadata.len()) else {
+            return;
+        };
+
+        if len >= self.reserved - self.reserved / 4 {
+            let step = (self.reserved / 2).clamp(MIN_STEP, MAX_STEP);
+            self.extend(self.reserved.max(len), step);
+        }
+    }
+
+    fn extend(&mut self, offset: u64, len: u64) {
+        let Some(file) = &self.file else {
+            return;
+        };
+
+        if allocate(file, offset, len) {
+            self.reserved = offset + len;
+        } else {
+            // Left to the filesystem, as without a reservation
+            self.file = None;
+        }
+    }
+}
+
+/// Give back what a finished file didn't use.
+pub(crate) fn release(path: &Path) {
+    if let Ok(file) = File::options().write(true).open(path) {
+        if let Ok(metadata) = file.metadata() {
+            // Filesystems skip truncating to the same length, so grow by a
+            // byte first to make it a real one
+            let _ = file.set_len(metadata.len() + 1);
+            let _ = file.set_len(metadata.len());
+        }
+    }
+}
+
+#[cfg(target_os = "linux")]
+fn allocate(file: &File, offset: u64, len: u64) -> bool {
+    use std::os::fd::AsRawFd;
+
+    let (Ok(offset), Ok(len)) = (libc::off_t::try_from(offset), libc::off_t::try_from(len)) else {
+        return false;
+    };
+
+    // SAFETY: the descriptor is open for writing for the whole call
+    let result =
+        unsafe { libc::fallocate(file.as_raw_fd(), libc::FALLOC_FL_KEEP_SIZE, offset, len) };
+
+    result == 0
+}
+
+#[cfg(target_os = "macos")]
+fn allocate(file: &File, _offset: u64, len: u64) -> bool {
+    use std::os::fd::AsRawFd;
+
+    let Ok(len) = libc::off_t::try_from(len) else {
+        return false;
+    };
+
+    // From the end of what's allocated, which is where `offset` falls
+    let mut store = libc::fstore_t {
+        fst_flags: libc::F_ALLOCATEALL,
+        fst_posmode: libc::F_PEOFPOSMODE,
+        fst_offset: 0,
+        fst_length: len,
+        fst_bytesalloc: 0,
+    };
+
+    // SAFETY: `store` is a valid fstore_t for the call to fill in
+    let result = unsafe { libc::fcntl(file.as_raw_fd(), libc::F_PREALLOCATE, &mut store) };
+
+    result != -1
+}
+
+#[cfg(not(any(target_os = "linux", target_os = "macos")))]
+fn allocate(_file: &File, _offset: u64, _len: u64) -> bool {
+    false
+}
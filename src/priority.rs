@@ -0,0 +1,54 @@
+//! Real-time scheduling for the threads running audio callbacks, so other
+//! work can't hold them up. It takes privileges, e.g. `rtprio` in
+//! `/etc/security/limits.conf`; without them audio runs as it would anyway.
+
+use std::sync::atomic::AtomicU8;
+use std::sync::atomic::Ordering;
+use std::sync::Arc;
+
+const ASKED: u8 = 1;
+const REFUSED: u8 = 2;
+
+/// Asks for real-time priority from callbacks, keeping how that went for
+/// whoever reports on it.
+#[derive(Clone, Default)]
+pub struct Priority(Arc<AtomicU8>);
+
+impl Priority {
+    /// Raise the calling thread to real-time priority.
+    pub fn promote(&self) -> bool {
+        let granted = promote_thread();
+
+        let flags = if granted { ASKED } else { ASKED | REFUSED };
+        self.0.fetch_or(flags, Ordering::Relaxed);
+
+        granted
+    }
+
+    /// Whether every thread that asked got it; `None` until one has.
+    pub fn granted(&self) -> Option<bool> {
+        let flags = self.0.load(Ordering::Relaxed);
+        (flags & ASKED != 0).then_some(flags & REFUSED == 0)
+    }
+}
+
+#[cfg(unix)]
+fn promote_thread() -> bool {
+    let policy = libc::SCHED_FIFO;
+
+    // SAFETY: `param` is zeroed then filled in, and only the calling thread
+    // is rescheduled
+    unsafe {
+        let mut param: libc::sched_param = std::mem::zeroed();
+        // Midway, leaving room above for the kernel and sound servers
+        param.sched_priority =
+            (libc::sched_get_priority_min(policy) + libc::sched_get_priority_max(policy)) / 2;
+
+        libc::pthread_setschedparam(libc::pthread_self(), policy, &param) == 0
+    }
+}
+
+#[cfg(not(unix))]
+fn promote_thread() -> bool {
+    false
+}
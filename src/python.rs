@@ -0,0 +1,272 @@
+//! The `audiort` Python module: capture into numpy arrays, play them back and
+//! list devices. Build it with `maturin build`, which picks up the feature
+//! from `pyproject.toml`.
+
+// pyo3's generated wrappers convert `PyErr` into itself
+#![allow(clippy::useless_conversion)]
+
+use crate::playback;
+use crate::Device;
+use crate::DeviceBuilder;
+use crate::Error;
+use crate::StreamBuilder;
+use numpy::PyArray1;
+use numpy::PyArray2;
+use numpy::PyArrayMethods;
+use numpy::PyReadonlyArrayDyn;
+use pyo3::exceptions::PyRuntimeError;
+use pyo3::prelude::*;
+use pyo3::types::PyDict;
+use std::sync::Arc;
+use std::sync::Mutex;
+use std::time::Duration;
+
+impl From<Error> for PyErr {
+    fn from(err: Error) -> PyErr {
+        PyRuntimeError::new_err(err.to_string())
+    }
+}
+
+/// How far `Player.play` keeps ahead of the device before waiting.
+const PLAY_AHEAD: Duration = Duration::from_millis(500);
+
+/// The device `spec` opens, or the default device of `kind`.
+fn device(kind: Device, spec: Option<&str>) -> Result<DeviceBuilder, Error> {
+    match spec {
+        Some(spec) => DeviceBuilder::open(kind, spec),
+        None if kind == Device::Input => DeviceBuilder::new_default_input(),
+        None => DeviceBuilder::new_default_output(),
+    }
+}
+
+/// Captures an input device into memory. `take()` returns what was captured
+/// since the last call as a `(frames, channels)` float32 array, and `mark()`
+/// labels positions within it.
+#[pyclass(unsendable)]
+pub struct Recorder {
+    stream: StreamBuilder,
+    buffer: Arc<Mutex<Vec<f32>>>,
+    markers: Vec<(u64, String)>,
+}
+
+#[pymethods]
+impl Recorder {
+    #[new]
+    #[pyo3(signature = (device = None))]
+    fn new(device: Option<&str>) -> PyResult<Recorder> {
+        let mut stream = StreamBuilder::new(self::device(Device::Input, device)?)?;
+        let buffer = Arc::new(Mutex::new(Vec::new()));
+        let sink = Arc::clone(&buffer);
+
+        stream.read(move |data| {
+            if let Ok(mut buffer) = sink.lock() {
+                buffer.extend_from_slice(data);
+            }
+        })?;
+
+        Ok(Recorder {
+            stream,
+            buffer,
+            markers: Vec::new(),
+        })
+    }
+
+    #[getter]
+    fn sample_rate(&self) -> u32 {
+        self.stream.config().sample_rate().0
+    }
+
+    #[getter]
+    fn channels(&self) -> u16 {
+        self.stream.config().channels()
+    }
+
+    /// Frames per buffer the device settled on, or `None` before it starts.
+    #[getter]
+    fn buffer_frames(&self) -> Option<u32> {
+        self.stream.info().map(|info| info.buffer_frames)
+    }
+
+    /// `"playing"`, `"paused"`, `"stopped"` or `"errored"`.
+    #[getter]
+    fn state(&self) -> &'static str {
+        self.stream.state().name()
+    }
+
+    fn start(&self) -> PyResult<()> {
+        Ok(self.stream.play()?)
+    }
+
+    fn stop(&self) -> PyResult<()> {
+        Ok(self.stream.pause()?)
+    }
+
+    /// Label the current position of the next `take()`, returning its frame.
+    fn mark(&mut self, label: String) -> u64 {
+        let frame = self.frames();
+        self.markers.push((frame, label));
+        frame
+    }
+
+    /// `(frame, label)` pairs for the next `take()`.
+    #[getter]
+    fn markers(&self) -> Vec<(u64, String)> {
+        self.markers.clone()
+    }
+
+    /// The audio captured since the last call, clearing markers.
+    fn take<'py>(&mut self, py: Python<'py>) -> PyResult<Bound<'py, PyArray2<f32>>> {
+        let samples = match self.buffer.lock() {
+            Ok(mut buffer) => std::mem::take(&mut *buffer),
+            Err(_) => return Err(Error::OutputLockError.into()),
+        };
+
+        self.markers.clear();
+
+        let channels = usize::from(self.channels().max(1));
+        let frames = samples.len() / channels;
+
+        PyArray1::from_vec_bound(py, samples).reshape([frames, channels])
+    }
+
+    /// Capture `seconds` of audio and return it, as `start()`, wait, `stop()`,
+    /// `take()`. Anything captured earlier is discarded.
+    fn record<'py>(
+        &mut self,
+        py: Python<'py>,
+        seconds: f64,
+    ) -> PyResult<Bound<'py, PyArray2<f32>>> {
+        self.take(py)?;
+        self.start()?;
+
+        let wanted = (seconds.max(0.0) * f64::from(self.sample_rate())) as u64;
+
+        while self.frames() < wanted {
+            py.allow_threads(|| std::thread::sleep(Duration::from_millis(10)));
+
+            // Let Ctrl+C interrupt long recordings
+            if let Err(err) = py.check_signals() {
+                self.stop()?;
+                return Err(err);
+            }
+        }
+
+        self.stop()?;
+
+        // The last buffer usually overshoots
+        let channels = usize::from(self.channels().max(1));
+
+        if let Ok(mut buffer) = self.buffer.lock() {
+            buffer.truncate(wanted as usize * channels);
+        }
+
+        self.take(py)
+    }
+
+    /// Levels since the recorder was created.
+    fn levels<'py>(&self, py: Python<'py>) -> PyResult<Bound<'py, PyDict>> {
+        let stats = self.stream.stats();
+        let levels = PyDict::new_bound(py);
+
+        levels.set_item("peak_dbfs", crate::to_dbfs(stats.peak))?;
+        levels.set_item("rms_dbfs", crate::to_dbfs(stats.rms()))?;
+        levels.set_item("clipped", stats.clipped)?;
+        levels.set_item("dropouts", stats.dropouts)?;
+        levels.set_item("load", stats.load)?;
+
+        Ok(levels)
+    }
+}
+
+impl Recorder {
+    fn frames(&self) -> u64 {
+        let samples = self.buffer.lock().map(|buffer| buffer.len()).unwrap_or(0);
+        (samples / usize::from(self.channels().max(1))) as u64
+    }
+}
+
+/// Plays float32 arrays, shaped `(frames, channels)` or flat and interleaved,
+/// on an output device.
+#[pyclass(unsendable)]
+pub struct Player {
+    player: playback::Player,
+}
+
+#[pymethods]
+impl Player {
+    #[new]
+    #[pyo3(signature = (sample_rate, channels, device = None))]
+    fn new(sample_rate: u32, channels: u16, device: Option<&str>) -> PyResult<Player> {
+        let device = self::device(Device::Output, device)?;
+        let player = playback::Player::new(&device, sample_rate, channels)?;
+
+        player.play()?;
+
+        Ok(Player { player })
+    }
+
+    #[getter]
+    fn sample_rate(&self) -> u32 {
+        self.player.sample_rate()
+    }
+
+    #[getter]
+    fn channels(&self) -> u16 {
+        self.player.channels()
+    }
+
+    /// Frames waiting to be played.
+    #[getter]
+    fn queued(&self) -> usize {
+        self.player.queued()
+    }
+
+    /// Queue `samples`, returning once all but the last half second of them
+    /// are playing.
+    fn play(&self, py: Python<'_>, samples: PyReadonlyArrayDyn<'_, f32>) -> PyResult<()> {
+        let samples = samples.as_array().iter().copied().collect::<Vec<_>>();
+        let ahead = PLAY_AHEAD.as_secs_f64() * f64::from(self.sample_rate());
+        // Small enough that the player's own limit never drops anything
+        let chunk = (ahead as usize / 5 * usize::from(self.channels().max(1))).max(1);
+
+        for chunk in samples.chunks(chunk) {
+            while self.player.queued() as f64 > ahead {
+                py.allow_threads(|| std::thread::sleep(Duration::from_millis(10)));
+                py.check_signals()?;
+            }
+
+            self.player.push(chunk);
+        }
+
+        Ok(())
+    }
+
+    /// Block until everything queued has played.
+    fn wait(&self, py: Python<'_>) -> PyResult<()> {
+        while self.player.queued() > 0 {
+            py.allow_threads(|| std::thread::sleep(Duration::from_millis(10)));
+            py.check_signals()?;
+        }
+
+        Ok(())
+    }
+}
+
+#[pyfunction]
+fn input_devices() -> PyResult<Vec<String>> {
+    Ok(DeviceBuilder::names(Device::Input)?)
+}
+
+#[pyfunction]
+fn output_devices() -> PyResult<Vec<String>> {
+    Ok(DeviceBuilder::names(Device::Output)?)
+}
+
+#[pymodule]
+fn audiort(m: &Bound<'_, PyModule>) -> PyResult<()> {
+    m.add_class::<Recorder>()?;
+    m.add_class::<Player>()?;
+    m.add_function(wrap_pyfunction!(input_devices, m)?)?;
+    m.add_function(wrap_pyfunction!(output_devices, m)?)?;
+    Ok(())
+}
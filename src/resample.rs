@@ -0,0 +1,68 @@
+//! Converting audio between sample rates and channel counts, e.g. to join
+//! files recorded differently.
+
+/// Linear interpolation from one sample rate to another, a buffer at a time.
+pub struct Resampler {
+    channels: usize,
+    /// Input frames per output frame
+    step: f64,
+    /// Where the next output frame falls after `last`, in input frames
+    position: f64,
+    last: Option<Vec<f32>>,
+}
+
+impl Resampler {
+    pub fn new(from: u32, to: u32, channels: usize) -> Resampler {
+        Resampler {
+            channels: channels.max(1),
+            step: f64::from(from) / f64::from(to.max(1)),
+            position: 0.0,
+            last: None,
+        }
+    }
+
+    pub fn process(&mut self, data: &[f32], output: &mut Vec<f32>) {
+        for frame in data.chunks_exact(self.channels) {
+            let Some(last) = self.last.as_mut() else {
+                self.last = Some(frame.to_vec());
+                continue;
+            };
+
+            while self.position < 1.0 {
+                let t = self.position as f32;
+
+                output.extend(last.iter().zip(frame).map(|(a, b)| a + (b - a) * t));
+                self.position += self.step;
+            }
+
+            self.position -= 1.0;
+            last.copy_from_slice(frame);
+        }
+    }
+
+    /// Output what is left of the last frame, ready to start afresh.
+    pub fn finish(&mut self, output: &mut Vec<f32>) {
+        if let Some(last) = self.last.take() {
+            while self.position < 1.0 {
+                output.extend_from_slice(&last);
+                self.position += self.step;
+            }
+        }
+
+        self.position = 0.0;
+    }
+}
+
+/// Convert interleaved samples from `from` channels to `to`: mixing down
+/// to mono, or else taking channels in turn, so mono goes to all of them.
+pub fn remap(data: &[f32], from: usize, to: usize, output: &mut Vec<f32>) {
+    let from = from.max(1);
+
+    for frame in data.chunks_exact(from) {
+        if to == 1 {
+            output.push(frame.iter().sum::<f32>() / from as f32);
+        } else {
+            output.extend((0..to).map(|channel| frame[channel % from]));
+        }
+    }
+}
@@ -0,0 +1,234 @@
+//! Changing the speed of audio without changing its pitch, by WSOLA: short
+//! windows of the input, each shifted a little to line up with the one
+//! before, overlapped at a steady rate.
+
+use std::f64::consts::PI;
+
+/// Window length in seconds; long enough to hold a cycle of a low voice.
+const WINDOW: f64 = 0.04;
+
+/// How far each window may shift to line up, in seconds.
+const TOLERANCE: f64 = 0.012;
+
+/// Time-stretches interleaved audio a buffer at a time, like `Resampler`.
+pub struct Stretcher {
+    channels: usize,
+    /// Output frames between windows, half a window
+    hop: usize,
+    /// Input frames between windows
+    step: f64,
+    tolerance: usize,
+    /// Hann, over two hops, so overlapping halves add up to 1
+    window: Vec<f32>,
+    /// Input still to be looked at, from frame `start` of all of it
+    input: Vec<f32>,
+    start: usize,
+    /// Where the next window falls before it's shifted, in input frames
+    position: f64,
+    /// Where the last window was taken from
+    last: Option<usize>,
+    /// The second half of the last window, to overlap the next one with
+    tail: Vec<f32>,
+}
+
+impl Stretcher {
+    /// `speed` above 1 is faster, and shorter.
+    pub fn new(speed: f64, sample_rate: u32, channels: usize) -> Stretcher {
+        let channels = channels.max(1);
+        let hop = ((WINDOW * f64::from(sample_rate)) as usize / 2).max(1);
+
+        Stretcher {
+            channels,
+            hop,
+            step: hop as f64 * speed.max(0.01),
+            tolerance: (TOLERANCE * f64::from(sample_rate)) as usize,
+            window: (0..hop * 2)
+                .map(|i| (0.5 - 0.5 * (PI * i as f64 / hop as f64).cos()) as f32)
+                .collect(),
+            input: Vec::new(),
+            start: 0,
+            position: 0.0,
+            last: None,
+            tail: vec![0.0; hop * channels],
+        }
+    }
+
+    pub fn process(&mut self, data: &[f32], output: &mut Vec<f32>) {
+        self.input.extend_from_slice(data);
+        self.run(usize::MAX, output);
+    }
+
+    /// Output the rest, ready to start afresh.
+    pub fn finish(&mut self, output: &mut Vec<f32>) {
+        let end = self.start + self.input.len() / self.channels;
+
+        // Silence after the end carries the last of the input through
+        let padding = (self.tolerance + self.hop * 2) * self.channels;
+        self.input.resize(self.input.len() + padding, 0.0);
+        self.run(end, output);
+
+        if self.last.is_some() {
+            output.extend_from_slice(&self.tail);
+        }
+
+        self.input.clear();
+        self.start = 0;
+        self.position = 0.0;
+        self.last = None;
+        self.tail.fill(0.0);
+    }
+
+    /// Overlap windows while there's input for them, up to frame `end`.
+    fn run(&mut self, end: usize, output: &mut Vec<f32>) {
+        let channels = self.channels;
+        let hop = self.hop;
+
+        loop {
+            let nominal = self.position.round() as usize;
+            let available = self.start + self.input.len() / channels;
+
+            // The furthest a window can shift to has to be here in full
+            if nominal >= end || nominal + self.tolerance + hop * 2 > available {
+                break;
+            }
+
+            let best = match self.last {
+                Some(last) => self.best_match(last + hop, nominal),
+                None => nominal,
+            };
+
+            let at = (best - self.start) * channels;
+
+            for i in 0..hop {
+                for channel in 0..channels {
+                    let j = i * channels + channel;
+
+                    output.push(self.tail[j] + self.window[i] * self.input[at + j]);
+                    self.tail[j] = self.window[hop + i] * self.input[at + hop * channels + j];
+                }
+            }
+
+            self.last = Some(best);
+            self.position += self.step;
+
+            // Nothing before the next window's earliest shift or the
+            // continuation of this one is looked at again
+            let keep = (self.position.round() as usize)
+                .saturating_sub(self.tolerance)
+                .min(best + hop);
+
+            if keep > self.start + hop * 4 {
+                self.input.drain(..(keep - self.start) * channels);
+                self.start = keep;
+            }
+        }
+    }
+
+    /// Where near `nominal` a window looks most like the audio at `target`,
+    /// which would have followed on from the last one.
+    fn best_match(&self, target: usize, nominal: usize) -> usize {
+        let first = nominal.saturating_sub(self.tolerance).max(self.start);
+        let last = nominal + self.tolerance;
+
+        // Channels mixed down, which is plenty to line up on
+        let mono = |from: usize, frames: usize| {
+            self.input[(from - self.start) * self.channels..][..frames * self.channels]
+                .chunks_exact(self.channels)
+                .map(|frame| frame.iter().sum::<f32>())
+                .collect::<Vec<_>>()
+        };
+
+        let reference = mono(target, self.hop);
+        let candidates = mono(first, last - first + self.hop);
+
+        (0..=last - first)
+            .map(|offset| {
+                let window = &candidates[offset..offset + self.hop];
+                let (correlation, energy) = window
+                    .iter()
+                    .zip(&reference)
+                    .fold((0.0, 0.0), |(correlation, energy), (x, r)| {
+                        (correlation + x * r, energy + x * x)
+                    });
+
+                (first + offset, correlation / (energy.sqrt() + 1e-9))
+            })
+            .max_by(|a, b| a.1.total_cmp(&b.1))
+            .map_or(nominal, |(candidate, _)| candidate)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SAMPLE_RATE: u32 = 48_000;
+
+    fn stretch(input: &[f32], speed: f64, channels: usize) -> Vec<f32> {
+        let mut stretcher = Stretcher::new(speed, SAMPLE_RATE, channels);
+        let mut output = Vec::new();
+
+        for buffer in input.chunks(1000 * channels) {
+            stretcher.process(buffer, &mut output);
+        }
+
+        stretcher.finish(&mut output);
+        output
+    }
+
+    fn sine(freq: f64, frames: u32) -> Vec<f32> {
+        (0..frames)
+            .map(|frame| (f64::from(frame) * freq / f64::from(SAMPLE_RATE) * 2.0 * PI).sin() as f32)
+            .collect()
+    }
+
+    #[test]
+    fn unity_speed_passes_through() {
+        // Noise, so only one shift lines up
+        let mut seed = 1u32;
+        let input: Vec<f32> = (0..SAMPLE_RATE / 4)
+            .map(|_| {
+                seed = seed.wrapping_mul(1_664_525).wrapping_add(1_013_904_223);
+                (seed >> 8) as f32 / (1 << 23) as f32 - 1.0
+            })
+            .collect();
+
+        let output = stretch(&input, 1.0, 1);
+        let hop = (WINDOW * f64::from(SAMPLE_RATE)) as usize / 2;
+
+        // After the first window fades in
+        for (index, (a, b)) in input.iter().zip(&output).enumerate().skip(hop) {
+            assert!((a - b).abs() < 1e-4, "{index}: {a} {b}");
+        }
+    }
+
+    #[test]
+    fn length_follows_speed() {
+        let input: Vec<f32> = sine(440.0, SAMPLE_RATE)
+            .into_iter()
+            .flat_map(|value| [value, -value])
+            .collect();
+
+        for speed in [0.5, 0.75, 1.5, 2.0] {
+            let frames = stretch(&input, speed, 2).len() / 2;
+            let expected = f64::from(SAMPLE_RATE) / speed;
+
+            // Within a window of where it should end
+            assert!((frames as f64 - expected).abs() < WINDOW * f64::from(SAMPLE_RATE));
+        }
+    }
+
+    #[test]
+    fn pitch_is_kept() {
+        let output = stretch(&sine(440.0, SAMPLE_RATE), 1.5, 1);
+
+        // Half a second from the middle, clear of the ends
+        let middle = &output[SAMPLE_RATE as usize / 10..][..SAMPLE_RATE as usize / 2];
+        let crossings = middle
+            .windows(2)
+            .filter(|pair| pair[0] < 0.0 && pair[1] >= 0.0)
+            .count();
+
+        assert!((crossings as f64 * 2.0 - 440.0).abs() <= 4.0, "{crossings}");
+    }
+}
@@ -0,0 +1,377 @@
+//! Live transcription. Captured audio is resampled to 16 kHz mono and handed
+//! to a `Transcriber` in chunks on its own thread, and the text that comes
+//! back is written to a WebVTT file as it arrives.
+
+use crate::Error;
+use std::fs::File;
+use std::io::BufWriter;
+use std::io::Write;
+use std::path::Path;
+use std::sync::mpsc;
+use std::sync::Arc;
+use std::sync::Mutex;
+use std::thread::JoinHandle;
+use std::time::Duration;
+
+/// The rate transcribers get audio at.
+pub const SAMPLE_RATE: u32 = 16_000;
+
+/// Audio handed to the transcriber at once. Long enough for context, short
+/// enough for the transcript to keep up.
+const CHUNK_SECONDS: usize = 10;
+
+/// A stretch of the recording and what was said in it.
+#[derive(Debug, Clone)]
+pub struct Cue {
+    pub start: Duration,
+    pub end: Duration,
+    pub text: String,
+}
+
+pub trait Transcriber: Send {
+    /// Transcribe 16 kHz mono `samples` found `start` into the recording.
+    fn transcribe(&mut self, samples: &[f32], start: Duration) -> Result<Vec<Cue>, Error>;
+}
+
+/// Runs a program on each chunk, saved as a 16 kHz mono WAV file whose path
+/// replaces `{path}` in the arguments, and takes its output as the text.
+pub struct Command {
+    args: Vec<String>,
+    path: std::path::PathBuf,
+}
+
+impl Command {
+    /// `command` is split on whitespace, e.g. `whisper-cli -nt -f {path}`.
+    pub fn new(command: &str) -> Command {
+        let path = std::env::temp_dir().join(format!("audiort-{}.wav", std::process::id()));
+
+        Command {
+            args: command.split_whitespace().map(str::to_owned).collect(),
+            path,
+        }
+    }
+}
+
+impl Transcriber for Command {
+    fn transcribe(&mut self, samples: &[f32], start: Duration) -> Result<Vec<Cue>, Error> {
+        let spec = hound::WavSpec {
+            channels: 1,
+            sample_rate: SAMPLE_RATE,
+            bits_per_sample: 16,
+            sample_format: hound::SampleFormat::Int,
+        };
+
+        let mut writer = hound::WavWriter::create(&self.path, spec).or(Err(Error::WriteError))?;
+
+        for &value in samples {
+            let value = (value.clamp(-1.0, 1.0) * f32::from(i16::MAX)) as i16;
+            writer.write_sample(value).or(Err(Error::WriteError))?;
+        }
+
+        writer.finalize().or(Err(Error::WriteError))?;
+
+        let path = self.path.to_string_lossy();
+        let args: Vec<String> = self
+            .args
+            .iter()
+            .map(|arg| arg.replace("{path}", &path))
+            .collect();
+
+        let (program, args) = args.split_first().ok_or(Error::TranscribeError)?;
+
+        let output = std::process::Command::new(program)
+            .args(args)
+            .stderr(std::process::Stdio::null())
+            .output()
+            .or(Err(Error::TranscribeError))?;
+
+        let _ = std::fs::remove_file(&self.path);
+
+        if !output.status.success() {
+            return Err(Error::TranscribeError);
+        }
+
+        let text = String::from_utf8_lossy(&output.stdout);
+        let text = text.split_whitespace().collect::<Vec<_>>().join(" ");
+
+        Ok(Vec::from_iter((!text.is_empty()).then(|| Cue {
+            start,
+            end: start + duration(samples.len()),
+            text,
+        })))
+    }
+}
+
+/// Transcribes in-process with whisper.cpp and a ggml model file.
+#[cfg(feature = "whisper")]
+pub struct Whisper {
+    state: whisper_rs::WhisperState,
+    language: Option<String>,
+}
+
+#[cfg(feature = "whisper")]
+impl Whisper {
+    /// `language` is a code such as `en`, or `None` to detect it.
+    pub fn new<P>(model: P, language: Option<&str>) -> Result<Whisper, Error>
+    where
+        P: AsRef<Path>,
+    {
+        let model = model.as_ref().to_str().ok_or(Error::TranscribeError)?;
+
+        let context = whisper_rs::WhisperContext::new_with_params(model, Default::default())
+            .or(Err(Error::TranscribeError))?;
+
+        Ok(Whisper {
+            state: context.create_state().or(Err(Error::TranscribeError))?,
+            language: language.map(str::to_owned),
+        })
+    }
+}
+
+#[cfg(feature = "whisper")]
+impl Transcriber for Whisper {
+    fn transcribe(&mut self, samples: &[f32], start: Duration) -> Result<Vec<Cue>, Error> {
+        let mut params =
+            whisper_rs::FullParams::new(whisper_rs::SamplingStrategy::Greedy { best_of: 1 });
+        params.set_language(Some(self.language.as_deref().unwrap_or("auto")));
+        params.set_print_progress(false);
+        params.set_print_realtime(false);
+        params.set_print_special(false);
+        params.set_print_timestamps(false);
+
+        self.state
+            .full(params, samples)
+            .or(Err(Error::TranscribeError))?;
+
+        let segments = self
+            .state
+            .full_n_segments()
+            .or(Err(Error::TranscribeError))?;
+
+        // Segment times are in hundredths of a second
+        let time =
+            |centiseconds: i64| start + Duration::from_millis(centiseconds.max(0) as u64 * 10);
+
+        let mut cues = Vec::new();
+
+        for segment in 0..segments {
+            let text = self
+                .state
+                .full_get_segment_text(segment)
+                .or(Err(Error::TranscribeError))?;
+
+            let text = text.trim();
+
+            if text.is_empty() {
+                continue;
+            }
+
+            cues.push(Cue {
+                start: time(
+                    self.state
+                        .full_get_segment_t0(segment)
+                        .or(Err(Error::TranscribeError))?,
+                ),
+                end: time(
+                    self.state
+                        .full_get_segment_t1(segment)
+                        .or(Err(Error::TranscribeError))?,
+                ),
+                text: text.to_owned(),
+            });
+        }
+
+        Ok(cues)
+    }
+}
+
+enum Message {
+    Audio(Vec<f32>),
+    End,
+}
+
+/// A transcription running alongside a stream; see
+/// `StreamBuilder::transcribe`.
+pub struct Transcription {
+    sender: mpsc::Sender<Message>,
+    worker: Option<JoinHandle<()>>,
+    error: Arc<Mutex<Option<Error>>>,
+}
+
+impl Transcription {
+    pub(crate) fn new<T, P>(
+        mut transcriber: T,
+        path: P,
+        sample_rate: u32,
+        channels: u16,
+    ) -> Result<Transcription, Error>
+    where
+        T: Transcriber + 'static,
+        P: AsRef<Path>,
+    {
+        let mut transcript = BufWriter::new(File::create(path).or(Err(Error::WriteError))?);
+        transcript
+            .write_all(b"WEBVTT\n\n")
+            .or(Err(Error::WriteError))?;
+        transcript.flush().or(Err(Error::WriteError))?;
+
+        let (sender, receiver) = mpsc::channel();
+        let error = Arc::new(Mutex::new(None));
+        let failed = Arc::clone(&error);
+
+        let mut resampler = Resampler::new(sample_rate, usize::from(channels.max(1)));
+        let chunk = CHUNK_SECONDS * SAMPLE_RATE as usize;
+
+        let worker = std::thread::spawn(move || {
+            let mut samples = Vec::with_capacity(chunk);
+            let mut start = Duration::ZERO;
+
+            for message in receiver {
+                let end = match message {
+                    Message::Audio(data) => {
+                        resampler.process(&data, &mut samples);
+                        false
+                    }
+                    Message::End => true,
+                };
+
+                // The last chunk goes out short
+                if samples.len() < chunk && !end {
+                    continue;
+                }
+
+                if !samples.is_empty() {
+                    let result = transcriber
+                        .transcribe(&samples, start)
+                        .and_then(|cues| write_cues(&mut transcript, &cues));
+
+                    // Keep going; a later chunk may work
+                    if let (Err(err), Ok(mut error)) = (result, failed.lock()) {
+                        error.get_or_insert(err);
+                    }
+
+                    start += duration(samples.len());
+                    samples.clear();
+                }
+
+                if end {
+                    break;
+                }
+            }
+        });
+
+        Ok(Transcription {
+            sender,
+            worker: Some(worker),
+            error,
+        })
+    }
+
+    pub(crate) fn feeder(&self) -> impl FnMut(&[f32]) + Send + 'static {
+        let sender = self.sender.clone();
+
+        move |data| {
+            let _ = sender.send(Message::Audio(data.to_vec()));
+        }
+    }
+
+    /// Transcribe what's left and wait for it. Returns the first error any
+    /// chunk had.
+    pub fn finish(mut self) -> Result<(), Error> {
+        self.stop();
+
+        match self.error.lock() {
+            Ok(mut error) => error.take().map_or(Ok(()), Err),
+            Err(_) => Err(Error::OutputLockError),
+        }
+    }
+
+    fn stop(&mut self) {
+        let _ = self.sender.send(Message::End);
+
+        if let Some(worker) = self.worker.take() {
+            let _ = worker.join();
+        }
+    }
+}
+
+impl Drop for Transcription {
+    fn drop(&mut self) {
+        self.stop();
+    }
+}
+
+/// Mixes to mono and averages down (or holds up) to `SAMPLE_RATE`.
+struct Resampler {
+    channels: usize,
+    /// Input samples per output sample
+    step: f64,
+    position: f64,
+    sum: f32,
+    count: f32,
+    last: f32,
+}
+
+impl Resampler {
+    fn new(sample_rate: u32, channels: usize) -> Resampler {
+        Resampler {
+            channels,
+            step: f64::from(sample_rate) / f64::from(SAMPLE_RATE),
+            position: 0.0,
+            sum: 0.0,
+            count: 0.0,
+            last: 0.0,
+        }
+    }
+
+    fn process(&mut self, data: &[f32], output: &mut Vec<f32>) {
+        for frame in data.chunks(self.channels) {
+            self.sum += frame.iter().sum::<f32>() / frame.len() as f32;
+            self.count += 1.0;
+            self.position += 1.0;
+
+            if self.position >= self.step {
+                self.last = self.sum / self.count;
+                self.sum = 0.0;
+                self.count = 0.0;
+            }
+
+            while self.position >= self.step {
+                output.push(self.last);
+                self.position -= self.step;
+            }
+        }
+    }
+}
+
+fn duration(samples: usize) -> Duration {
+    Duration::from_secs_f64(samples as f64 / f64::from(SAMPLE_RATE))
+}
+
+fn write_cues(transcript: &mut BufWriter<File>, cues: &[Cue]) -> Result<(), Error> {
+    for cue in cues {
+        writeln!(
+            transcript,
+            "{} --> {}\n{}\n",
+            timestamp(cue.start),
+            timestamp(cue.end),
+            cue.text
+        )
+        .or(Err(Error::WriteError))?;
+    }
+
+    transcript.flush().or(Err(Error::WriteError))
+}
+
+/// `HH:MM:SS.mmm`, as WebVTT wants.
+fn timestamp(time: Duration) -> String {
+    let millis = time.as_millis();
+
+    format!(
+        "{:02}:{:02}:{:02}.{:03}",
+        millis / 3_600_000,
+        millis / 60_000 % 60,
+        millis / 1000 % 60,
+        millis % 1000
+    )
+}
@@ -0,0 +1,185 @@
+//! 64-bit float WAV files, which hound neither reads nor writes, to keep
+//! full precision through long processing chains.
+
+use crate::metadata;
+use crate::Error;
+use hound::WavSpec;
+use std::fs::File;
+use std::io::BufReader;
+use std::io::BufWriter;
+use std::io::Read;
+use std::io::Seek;
+use std::io::SeekFrom;
+use std::io::Write;
+use std::path::Path;
+
+const WAVE_FORMAT_IEEE_FLOAT: u16 = 3;
+const WAVE_FORMAT_EXTENSIBLE: u16 = 0xfffe;
+
+/// Bytes before the samples in files `Writer` makes.
+const HEADER_LEN: u64 = 56;
+
+/// The spec of the WAV file at `path`, if it holds 64-bit floats.
+pub fn spec<P>(path: P) -> Result<Option<WavSpec>, Error>
+where
+    P: AsRef<Path>,
+{
+    let fmt = metadata::read_chunk(path, b"fmt ")
+        .ok()
+        .flatten()
+        .filter(|fmt| fmt.len() >= 16)
+        .ok_or(Error::ReadError)?;
+
+    let u16_at = |at: usize| u16::from_le_bytes([fmt[at], fmt[at + 1]]);
+
+    // Extensible formats give the real one at the start of their GUID
+    let format = match u16_at(0) {
+        WAVE_FORMAT_EXTENSIBLE if fmt.len() >= 26 => u16_at(24),
+        format => format,
+    };
+
+    if format != WAVE_FORMAT_IEEE_FLOAT || u16_at(14) != 64 {
+        return Ok(None);
+    }
+
+    Ok(Some(WavSpec {
+        channels: u16_at(2),
+        sample_rate: u32::from_le_bytes([fmt[4], fmt[5], fmt[6], fmt[7]]),
+        bits_per_sample: 64,
+        sample_format: hound::SampleFormat::Float,
+    }))
+}
+
+/// The interleaved samples of a 64-bit float WAV file, read from `file`
+/// for as long as they're wanted.
+pub fn samples(mut file: File) -> Result<impl Iterator<Item = Result<f64, Error>>, Error> {
+    let (offset, len) = metadata::find_data(&mut file).or(Err(Error::ReadError))?;
+
+    file.seek(SeekFrom::Start(offset))
+        .or(Err(Error::ReadError))?;
+
+    let mut reader = BufReader::new(file.take(len));
+
+    Ok(std::iter::from_fn(move || {
+        let mut bytes = [0u8; 8];
+
+        match reader.read_exact(&mut bytes) {
+            Ok(()) => Some(Ok(f64::from_le_bytes(bytes))),
+            Err(err) if err.kind() == std::io::ErrorKind::UnexpectedEof => None,
+            Err(_) => Some(Err(Error::ReadError)),
+        }
+    }))
+}
+
+/// Writes a 64-bit float WAV file; its sizes are filled in by `finalize`.
+pub struct Writer {
+    file: BufWriter<File>,
+    channels: u16,
+    sample_rate: u32,
+    samples: u64,
+}
+
+impl Writer {
+    pub fn create<P>(path: P, channels: u16, sample_rate: u32) -> Result<Writer, Error>
+    where
+        P: AsRef<Path>,
+    {
+        let file = File::create(path).or(Err(Error::WriteError))?;
+
+        let mut writer = Writer {
+            file: BufWriter::new(file),
+            channels: channels.max(1),
+            sample_rate,
+            samples: 0,
+        };
+
+        let header = writer.header()?;
+        writer.file.write_all(&header).or(Err(Error::WriteError))?;
+
+        Ok(writer)
+    }
+
+    pub fn write(&mut self, samples: &[f64]) -> Result<(), Error> {
+        for sample in samples {
+            self.file
+                .write_all(&sample.to_le_bytes())
+                .or(Err(Error::WriteError))?;
+        }
+
+        self.samples += samples.len() as u64;
+
+        Ok(())
+    }
+
+    pub fn finalize(mut self) -> Result<(), Error> {
+        let header = self.header()?;
+
+        self.file.flush().or(Err(Error::WriteError))?;
+
+        let file = self.file.get_mut();
+        file.seek(SeekFrom::Start(0)).or(Err(Error::WriteError))?;
+        file.write_all(&header).or(Err(Error::WriteError))?;
+        file.flush().or(Err(Error::WriteError))
+    }
+
+    /// RIFF, `fmt `, `fact` and `data` headers for what's written so far.
+    fn header(&self) -> Result<Vec<u8>, Error> {
+        let data_len = self.samples * 8;
+        let riff_len = u32::try_from(HEADER_LEN - 8 + data_len).or(Err(Error::WriteError))?;
+        let frames = self.samples / u64::from(self.channels);
+        let block_align = self.channels * 8;
+
+        let mut header = Vec::with_capacity(HEADER_LEN as usize);
+        header.extend_from_slice(b"RIFF");
+        header.extend_from_slice(&riff_len.to_le_bytes());
+        header.extend_from_slice(b"WAVE");
+
+        header.extend_from_slice(b"fmt ");
+        header.extend_from_slice(&16u32.to_le_bytes());
+        header.extend_from_slice(&WAVE_FORMAT_IEEE_FLOAT.to_le_bytes());
+        header.extend_from_slice(&self.channels.to_le_bytes());
+        header.extend_from_slice(&self.sample_rate.to_le_bytes());
+        header.extend_from_slice(&(self.sample_rate * u32::from(block_align)).to_le_bytes());
+        header.extend_from_slice(&block_align.to_le_bytes());
+        header.extend_from_slice(&64u16.to_le_bytes());
+
+        // Required for formats other than PCM
+        header.extend_from_slice(b"fact");
+        header.extend_from_slice(&4u32.to_le_bytes());
+        header.extend_from_slice(&(frames as u32).to_le_bytes());
+
+        header.extend_from_slice(b"data");
+        header.extend_from_slice(&(data_len as u32).to_le_bytes());
+
+        Ok(header)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn round_trip() {
+        let path = std::env::temp_dir().join(format!("audiort-wav64-{}.wav", std::process::id()));
+        let written = [0.0, 1.0, -1.0, 0.1, 1e-300, -0.333_333_333_333_333_3];
+
+        let mut writer = Writer::create(&path, 2, 96_000).unwrap();
+        writer.write(&written).unwrap();
+        writer.finalize().unwrap();
+
+        let spec = spec(&path).unwrap().unwrap();
+        let file = File::open(&path).unwrap();
+        let read = samples(file)
+            .unwrap()
+            .collect::<Result<Vec<_>, _>>()
+            .unwrap();
+
+        std::fs::remove_file(&path).unwrap();
+
+        assert_eq!(spec.channels, 2);
+        assert_eq!(spec.sample_rate, 96_000);
+        assert_eq!(spec.bits_per_sample, 64);
+        assert_eq!(read, written);
+    }
+}
@@ -0,0 +1,70 @@
+//! WavPack, lossless or hybrid, by the `wavpack` program. The WAV header
+//! and its chunks are kept in the file, so unpacking gives back the original,
+//! and the tags are written as APEv2 too.
+
+use crate::metadata::Tags;
+use crate::Error;
+use std::path::Path;
+use std::process::Command;
+use std::process::Stdio;
+
+/// Whether the `wavpack` program can be run.
+pub fn available() -> bool {
+    Command::new("wavpack")
+        .arg("--version")
+        .stdout(Stdio::null())
+        .stderr(Stdio::null())
+        .status()
+        .is_ok()
+}
+
+/// Convert the WAV file at `from` to a WavPack file at `to`. With a
+/// `bitrate` in kbps it's hybrid: `to` is lossy at that rate, and a `.wvc`
+/// correction file beside it restores the rest. Needs `wavpack` installed;
+/// see `available`.
+pub fn encode_wv<P, Q>(from: P, to: Q, bitrate: Option<u32>) -> Result<(), Error>
+where
+    P: AsRef<Path>,
+    Q: AsRef<Path>,
+{
+    let mut wavpack = Command::new("wavpack");
+
+    // Quietly, overwriting, with an MD5 of the audio to verify against
+    wavpack.args(["-q", "-y", "-m"]);
+
+    if let Some(bitrate) = bitrate {
+        wavpack.arg(format!("-b{bitrate}")).arg("-c");
+    }
+
+    // The WAV's own chunks go along too, but players read APEv2 tags
+    for (key, value) in Tags::read(&from)?.iter() {
+        wavpack.arg("-w").arg(format!("{}={value}", ape_key(key)));
+    }
+
+    let status = wavpack
+        .arg(from.as_ref())
+        .arg(to.as_ref())
+        .stdout(Stdio::null())
+        .status()
+        .or(Err(Error::EncodeError))?;
+
+    match status.success() {
+        true => Ok(()),
+        false => Err(Error::EncodeError),
+    }
+}
+
+/// The APEv2 item a tag goes in.
+fn ape_key(key: &str) -> String {
+    match key {
+        "date" => "Year".to_owned(),
+        "software" => "Encoder".to_owned(),
+        key => {
+            let mut chars = key.chars();
+            chars
+                .next()
+                .map(|first| first.to_uppercase().chain(chars).collect())
+                .unwrap_or_default()
+        }
+    }
+}
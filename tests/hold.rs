@@ -0,0 +1,45 @@
+//! `hold` keeps audio out of the file until it's let through, and
+//! `frames_captured` counts only what was. Run with `cargo test --features
+//! mock-host`.
+
+#![cfg(all(feature = "mock-host", not(target_arch = "wasm32")))]
+
+use audiort::mock::Signal;
+use audiort::DeviceBuilder;
+use audiort::StreamBuilder;
+use std::sync::atomic::AtomicBool;
+use std::sync::atomic::Ordering;
+use std::sync::Arc;
+use std::time::Duration;
+
+const SAMPLE_RATE: u32 = 48_000;
+const CHANNELS: u16 = 2;
+
+#[test]
+fn held_audio_isnt_counted() {
+    let path = std::env::temp_dir().join(format!("audiort-hold-{}.wav", std::process::id()));
+
+    let device = DeviceBuilder::new_mock(Signal::Ramp, SAMPLE_RATE, CHANNELS).unwrap();
+    let held = Arc::new(AtomicBool::new(false));
+
+    let mut stream = StreamBuilder::new(device).unwrap();
+    stream.hold(Arc::clone(&held), Duration::ZERO, Duration::ZERO);
+    stream.write_wav(&path).unwrap();
+    stream.play().unwrap();
+
+    std::thread::sleep(Duration::from_millis(100));
+    assert_eq!(stream.frames_captured(), 0);
+
+    held.store(true, Ordering::Relaxed);
+    std::thread::sleep(Duration::from_millis(100));
+
+    stream.stop();
+
+    let frames = stream.finish().unwrap().frames(CHANNELS);
+    let written = hound::WavReader::open(&path).unwrap().duration();
+    std::fs::remove_file(&path).unwrap();
+
+    assert!(frames > 0);
+    assert_eq!(stream.frames_captured(), frames);
+    assert_eq!(u64::from(written), frames);
+}
@@ -0,0 +1,174 @@
+//! `segment_wav` splits on exact frames, and `rotate_wav` between buffers,
+//! with nothing lost or repeated between files. Run with `cargo test --features mock-host`.
+
+#![cfg(all(feature = "mock-host", not(target_arch = "wasm32")))]
+
+use audiort::effects::Gain;
+use audiort::mock::Signal;
+use audiort::DeviceBuilder;
+use audiort::StreamBuilder;
+use std::path::Path;
+use std::path::PathBuf;
+use std::time::Duration;
+use std::time::Instant;
+
+const SAMPLE_RATE: u32 = 48_000;
+const CHANNELS: u16 = 2;
+
+/// Record a ramp in segments of `frames` until `count` are finished, and
+/// return every file, the unfinished last one included.
+fn record(name: &str, frames: u64, count: usize, effects: bool) -> Vec<(PathBuf, u64)> {
+    let dir = std::env::temp_dir().join(format!("audiort-{name}-{}", std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+
+    let mut device = DeviceBuilder::new_mock(Signal::Ramp, SAMPLE_RATE, CHANNELS).unwrap();
+    device.realtime(false);
+
+    let mut stream = StreamBuilder::new(device).unwrap();
+
+    if effects {
+        stream.effect(Gain(1.0));
+    }
+
+    let segments = dir.clone();
+    stream.segment_wav(frames, move |index| segments.join(format!("{index}.wav")));
+    stream.write_wav(dir.join("0.wav")).unwrap();
+    stream.play().unwrap();
+
+    let mut files = Vec::new();
+    let deadline = Instant::now() + Duration::from_secs(30);
+
+    while files.len() < count {
+        assert!(Instant::now() < deadline, "segments never finished");
+
+        for (path, stats) in stream.finished_segments().unwrap() {
+            files.push((path, stats.frames(CHANNELS)));
+        }
+
+        std::thread::sleep(Duration::from_millis(5));
+    }
+
+    stream.stop();
+
+    for (path, stats) in stream.finished_segments().unwrap() {
+        files.push((path, stats.frames(CHANNELS)));
+    }
+
+    let last = dir.join(format!("{}.wav", files.len()));
+    files.push((last, stream.finish().unwrap().frames(CHANNELS)));
+
+    files
+}
+
+/// The ramp position of each frame, checking the channels agree.
+fn positions(path: &Path) -> Vec<u32> {
+    let mut reader = hound::WavReader::open(path).unwrap();
+    let samples: Vec<f32> = reader.samples().map(Result::unwrap).collect();
+
+    samples
+        .chunks(usize::from(CHANNELS))
+        .map(|frame| {
+            assert_eq!(frame[0], frame[1]);
+            ((frame[0] + 1.0) / 2.0 * SAMPLE_RATE as f32).round() as u32 % SAMPLE_RATE
+        })
+        .collect()
+}
+
+fn check(files: &[(PathBuf, u64)], frames: u64) {
+    let (_, finished) = files.split_last().unwrap();
+
+    for (path, written) in finished {
+        assert_eq!(*written, frames, "{}", path.display());
+    }
+
+    check_continuous(files);
+}
+
+/// Each file holds the frames its stats say, and together they carry on
+/// from one to the next.
+fn check_continuous(files: &[(PathBuf, u64)]) {
+    let mut all = Vec::new();
+
+    for (path, written) in files {
+        let positions = positions(path);
+
+        assert_eq!(positions.len() as u64, *written, "{}", path.display());
+
+        all.extend(positions);
+    }
+
+    assert_eq!(all[0], 0);
+
+    for (index, pair) in all.windows(2).enumerate() {
+        assert_eq!(
+            (pair[0] + 1) % SAMPLE_RATE,
+            pair[1],
+            "discontinuity at frame {index}"
+        );
+    }
+
+    for (path, _) in files {
+        let _ = std::fs::remove_file(path);
+    }
+}
+
+#[test]
+fn splits_inside_buffers() {
+    // Not a multiple of the mock's 512 frame buffers
+    let frames = 1_001;
+
+    check(&record("inside", frames, 5, false), frames);
+}
+
+#[test]
+fn splits_on_buffer_edges() {
+    let frames = 1_024;
+
+    check(&record("edges", frames, 5, false), frames);
+}
+
+#[test]
+fn splits_processed_audio() {
+    let frames = 777;
+
+    check(&record("processed", frames, 5, true), frames);
+}
+
+#[test]
+fn rotates_between_files() {
+    let dir = std::env::temp_dir().join(format!("audiort-rotate-{}", std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+
+    let mut device = DeviceBuilder::new_mock(Signal::Ramp, SAMPLE_RATE, CHANNELS).unwrap();
+    device.realtime(false);
+
+    let mut stream = StreamBuilder::new(device).unwrap();
+    stream.write_wav(dir.join("0.wav")).unwrap();
+    stream.play().unwrap();
+
+    let mut files = Vec::new();
+    let mut written = 0;
+
+    for index in 1..4 {
+        std::thread::sleep(Duration::from_millis(20));
+
+        // Everything captured before the rotation goes in the earlier files
+        let captured = stream.frames_captured();
+        let stats = stream.rotate_wav(dir.join(format!("{index}.wav"))).unwrap();
+
+        written += stats.frames(CHANNELS);
+        assert!(written >= captured, "{written} written of {captured}");
+
+        files.push((
+            dir.join(format!("{}.wav", index - 1)),
+            stats.frames(CHANNELS),
+        ));
+    }
+
+    stream.stop();
+
+    let stats = stream.finish().unwrap();
+    files.push((dir.join("3.wav"), stats.frames(CHANNELS)));
+
+    check_continuous(&files);
+}